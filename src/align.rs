@@ -0,0 +1,140 @@
+use crate::ocr_element::{OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::{Rect, Vec2};
+
+// tidying up a column of boxes at once. Edges are moved rather than whole boxes, since a box
+// should keep covering the same ink; only distributing moves boxes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    // every left edge to the leftmost one, and so on
+    Left,
+    Right,
+    Top,
+    Bottom,
+    // every box as tall as the median, around its own centre
+    EqualHeights,
+    // the same space between each box and the next, the top and bottom ones staying put
+    DistributeVertically,
+}
+
+impl Alignment {
+    pub const ALL: [Alignment; 6] = [
+        Alignment::Left,
+        Alignment::Right,
+        Alignment::Top,
+        Alignment::Bottom,
+        Alignment::EqualHeights,
+        Alignment::DistributeVertically,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Alignment::Left => "Align left edges",
+            Alignment::Right => "Align right edges",
+            Alignment::Top => "Align top edges",
+            Alignment::Bottom => "Align bottom edges",
+            Alignment::EqualHeights => "Equalize heights",
+            Alignment::DistributeVertically => "Distribute vertically",
+        }
+    }
+
+    // distributing two boxes wouldn't change anything
+    pub fn min_count(&self) -> usize {
+        match self {
+            Alignment::DistributeVertically => 3,
+            _ => 2,
+        }
+    }
+}
+
+fn aligned(boxes: &[Rect], alignment: Alignment) -> Vec<Rect> {
+    let fold = |f: fn(f32, f32) -> f32, edge: fn(&Rect) -> f32| {
+        boxes.iter().map(edge).reduce(f).unwrap_or_default()
+    };
+    match alignment {
+        Alignment::Left => {
+            let x = fold(f32::min, |b| b.min.x);
+            boxes
+                .iter()
+                .map(|b| Rect::from_x_y_ranges(x..=b.max.x, b.y_range()))
+                .collect()
+        }
+        Alignment::Right => {
+            let x = fold(f32::max, |b| b.max.x);
+            boxes
+                .iter()
+                .map(|b| Rect::from_x_y_ranges(b.min.x..=x, b.y_range()))
+                .collect()
+        }
+        Alignment::Top => {
+            let y = fold(f32::min, |b| b.min.y);
+            boxes
+                .iter()
+                .map(|b| Rect::from_x_y_ranges(b.x_range(), y..=b.max.y))
+                .collect()
+        }
+        Alignment::Bottom => {
+            let y = fold(f32::max, |b| b.max.y);
+            boxes
+                .iter()
+                .map(|b| Rect::from_x_y_ranges(b.x_range(), b.min.y..=y))
+                .collect()
+        }
+        Alignment::EqualHeights => {
+            let mut heights: Vec<f32> = boxes.iter().map(|b| b.height()).collect();
+            heights.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let height = heights[heights.len() / 2];
+            boxes
+                .iter()
+                .map(|b| Rect::from_center_size(b.center(), Vec2::new(b.width(), height)))
+                .collect()
+        }
+        Alignment::DistributeVertically => {
+            let mut order: Vec<usize> = (0..boxes.len()).collect();
+            order.sort_by(|a, b| {
+                boxes[*a]
+                    .center()
+                    .y
+                    .partial_cmp(&boxes[*b].center().y)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let (first, last) = (boxes[order[0]], boxes[order[order.len() - 1]]);
+            let heights: f32 = boxes.iter().map(|b| b.height()).sum();
+            let gap = (last.max.y - first.min.y - heights) / (boxes.len() - 1) as f32;
+            let mut result = boxes.to_vec();
+            let mut y = first.min.y;
+            for i in order {
+                let b = boxes[i];
+                result[i] = b.translate(Vec2::new(0.0, y - b.min.y));
+                y += b.height() + gap;
+            }
+            result
+        }
+    }
+}
+
+// line up the bboxes of ids; returns how many changed
+pub fn align(tree: &mut Tree<OCRElement>, ids: &[InternalID], alignment: Alignment) -> usize {
+    let with_boxes: Vec<(InternalID, Rect)> = ids
+        .iter()
+        .filter_map(|id| Some((*id, *tree.get_node(id)?.bbox()?)))
+        .collect();
+    if with_boxes.len() < alignment.min_count() {
+        return 0;
+    }
+    let boxes: Vec<Rect> = with_boxes.iter().map(|(_, bbox)| *bbox).collect();
+    let mut changed = 0;
+    for ((id, old), new) in with_boxes.iter().zip(aligned(&boxes, alignment)) {
+        // boxes are written as whole pixels
+        let new = Rect::from_min_max(new.min.round(), new.max.round());
+        if new != *old {
+            if let Some(elt) = tree.get_mut_node(id) {
+                elt.ocr_properties
+                    .insert("bbox".to_string(), OCRProperty::BBox(new));
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,37 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// a hidden sibling of path, so the final rename stays on the same filesystem
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+}
+
+// move a fully written temp file over the target
+pub fn commit(temp: &Path, path: &Path) -> Result<(), String> {
+    fs::rename(temp, path).map_err(|e| {
+        let _ = fs::remove_file(temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+// write to a temp file and rename it over path, so a crash or full disk mid-write
+// leaves the old file intact instead of a truncated one
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    let temp = temp_path_for(path);
+    let res = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .map_err(|e| format!("Failed to write {}: {}", temp.display(), e));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    commit(&temp, path)
+}
@@ -0,0 +1,114 @@
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::page_xml;
+use crate::tree::Tree;
+use crate::InternalID;
+use html5ever::interface::tree_builder::TreeSink;
+use html5ever::interface::AppendNode;
+use html5ever::{local_name, namespace_url, ns, Attribute, QualName};
+use scraper::{Html, Selector};
+
+// one <meta name="x-wconf-calibration"> in the head per recalibration, so whoever reads
+// the confidences later knows what they mean
+pub const META_NAME: &str = "x-wconf-calibration";
+
+// a change to every word's x_wconf after correcting by hand
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recalibration {
+    // x_wconf' = scale * x_wconf + offset, rounded and kept in 0..=99, since 100 means a
+    // person checked the word. Words a person has checked keep their 100.
+    Linear { scale: f32, offset: f32 },
+    // the given words, e.g. the ones let stand in the review queue
+    CheckedTo100,
+}
+
+impl Recalibration {
+    pub fn map(&self, wconf: u32) -> u32 {
+        match self {
+            Self::Linear { scale, offset } => {
+                (scale * wconf as f32 + offset).round().clamp(0.0, 99.0) as u32
+            }
+            Self::CheckedTo100 => 100,
+        }
+    }
+
+    pub fn describe(&self, count: usize) -> String {
+        match self {
+            Self::Linear { scale, offset } => format!(
+                "x_wconf = round({} * x_wconf + {}), clamped to 0-99, on {} unverified words",
+                scale, offset, count
+            ),
+            Self::CheckedTo100 => format!("x_wconf = 100 on {} words checked by hand", count),
+        }
+    }
+}
+
+// recalibrate the words of tree (only checked for CheckedTo100); returns how many changed
+pub fn apply(
+    tree: &mut Tree<OCRElement>,
+    recalibration: Recalibration,
+    checked: &[InternalID],
+) -> usize {
+    let ids = match recalibration {
+        Recalibration::Linear { .. } => tree.preorder(),
+        Recalibration::CheckedTo100 => checked.to_vec(),
+    };
+    let mut changed = 0;
+    for id in ids {
+        let elt = match tree.get_mut_node(&id) {
+            Some(elt) if elt.ocr_element_type == OCRClass::Word => elt,
+            _ => continue,
+        };
+        let wconf = match elt.wconf() {
+            Some(wconf) => wconf,
+            None if recalibration == Recalibration::CheckedTo100 => 0,
+            None => continue,
+        };
+        if matches!(recalibration, Recalibration::Linear { .. }) && elt.is_verified() {
+            continue;
+        }
+        let new = recalibration.map(wconf);
+        if new != wconf || elt.wconf().is_none() {
+            elt.ocr_properties
+                .insert(String::from("x_wconf"), OCRProperty::UInt(new));
+            changed += 1;
+        }
+    }
+    changed
+}
+
+// what goes in the <meta>: when and what was done
+pub fn note(description: &str) -> String {
+    format!("{} {}", page_xml::timestamp(), description)
+}
+
+// the <meta> for a note as html, for heads kept as text
+pub fn meta_tag(note: &str) -> String {
+    format!(
+        "<meta name=\"{}\" content=\"{}\">",
+        META_NAME,
+        page_xml::escape(note)
+    )
+}
+
+// add the <meta> for a note to the head of the document, which it must have
+pub fn document(head: &mut Html, note: &str) {
+    let head_id = match head.select(&Selector::parse("head").unwrap()).next() {
+        Some(elt) => elt.id(),
+        None => return,
+    };
+    let meta = head.create_element(
+        QualName::new(None, ns!(html), local_name!("meta")),
+        vec![
+            Attribute {
+                name: QualName::new(None, ns!(), local_name!("name")),
+                value: META_NAME.into(),
+            },
+            Attribute {
+                name: QualName::new(None, ns!(), local_name!("content")),
+                value: note.into(),
+            },
+        ],
+        Default::default(),
+    );
+    head.append(&head_id, AppendNode(meta));
+}
@@ -0,0 +1,285 @@
+use crate::ocr_element::{self, OCRClass, OCRElement};
+use crate::tree::Tree;
+use crate::InternalID;
+use std::collections::HashSet;
+
+// what to turn the words of a selection into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseMode {
+    // a capital at the start of each sentence, shouted and garbled words lowered
+    Sentence,
+    Title,
+    Lower,
+    Upper,
+}
+
+impl CaseMode {
+    pub const ALL: [CaseMode; 4] = [
+        CaseMode::Sentence,
+        CaseMode::Title,
+        CaseMode::Lower,
+        CaseMode::Upper,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaseMode::Sentence => "Sentence case",
+            CaseMode::Title => "Title Case",
+            CaseMode::Lower => "lowercase",
+            CaseMode::Upper => "UPPERCASE",
+        }
+    }
+}
+
+// i and I don't pair up the same way everywhere
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Locale {
+    // dotted İ/i and dotless I/ı
+    Turkic,
+    // ij at the start of a word is capitalised as one letter: IJsselmeer
+    Dutch,
+    Other,
+}
+
+impl Locale {
+    fn from_lang(lang: Option<&str>) -> Self {
+        // both tesseract's codes and ISO 639-1
+        match lang.map(|lang| lang.split(['-', '_']).next().unwrap_or(lang)) {
+            Some("tur" | "tr" | "aze" | "az" | "crh" | "tat" | "tt") => Locale::Turkic,
+            Some("nld" | "nl") => Locale::Dutch,
+            _ => Locale::Other,
+        }
+    }
+
+    fn lower(&self, c: char) -> String {
+        match (self, c) {
+            (Locale::Turkic, 'I') => "ı".to_string(),
+            (Locale::Turkic, 'İ') => "i".to_string(),
+            _ => c.to_lowercase().collect(),
+        }
+    }
+
+    fn upper(&self, c: char) -> String {
+        match (self, c) {
+            (Locale::Turkic, 'i') => "İ".to_string(),
+            _ => c.to_uppercase().collect(),
+        }
+    }
+
+    fn lowercase(&self, word: &str) -> String {
+        word.chars().map(|c| self.lower(c)).collect()
+    }
+
+    fn uppercase(&self, word: &str) -> String {
+        word.chars().map(|c| self.upper(c)).collect()
+    }
+
+    // the first letter up and the rest as it is; punctuation in front ("(word", "„Wort")
+    // is skipped
+    fn capitalize_first(&self, word: &str) -> String {
+        let start = match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+            Some((i, _)) => i,
+            None => return word.to_string(),
+        };
+        let (before, rest) = word.split_at(start);
+        let mut chars = rest.chars();
+        let first = chars.next().unwrap_or_default();
+        let rest = chars.as_str();
+        let mut out = format!("{}{}", before, self.upper(first));
+        if *self == Locale::Dutch && (first == 'i' || first == 'I') && rest.starts_with('j') {
+            out.push('J');
+            out.push_str(&rest[1..]);
+        } else {
+            out.push_str(rest);
+        }
+        out
+    }
+
+    fn capitalize(&self, word: &str) -> String {
+        self.capitalize_first(&self.lowercase(word))
+    }
+}
+
+fn letters(word: &str) -> impl Iterator<Item = char> + '_ {
+    word.chars().filter(|c| c.is_alphabetic())
+}
+
+fn is_all_caps(word: &str) -> bool {
+    letters(word).next().is_some() && letters(word).all(|c| !c.is_lowercase())
+}
+
+// a capital after a small letter, like "tHe" or "thE"
+fn is_garbled(word: &str) -> bool {
+    let letters: Vec<char> = letters(word).collect();
+    letters
+        .windows(2)
+        .any(|pair| pair[0].is_lowercase() && pair[1].is_uppercase())
+}
+
+fn roman_value(c: char) -> Option<u32> {
+    match c {
+        'I' => Some(1),
+        'V' => Some(5),
+        'X' => Some(10),
+        'L' => Some(50),
+        'C' => Some(100),
+        'D' => Some(500),
+        'M' => Some(1000),
+        _ => None,
+    }
+}
+
+// a well formed upper case numeral like XIV or MCMLXXX, possibly with punctuation around it
+// ("Chapter IV.", "(XII)"); VX, IIII and the like aren't numerals
+fn is_roman_numeral(word: &str) -> bool {
+    let numeral = word.trim_matches(|c: char| c.is_ascii_punctuation());
+    let values: Option<Vec<u32>> = numeral.chars().map(roman_value).collect();
+    let values = match values {
+        Some(values) if !values.is_empty() => values,
+        _ => return false,
+    };
+    let mut total = 0;
+    for (i, value) in values.iter().enumerate() {
+        match values.get(i + 1) {
+            Some(next) if next > value => {
+                // only I, X and C subtract, and only from the next two numerals up
+                if ![1, 10, 100].contains(value) || *next > value * 10 {
+                    return false;
+                }
+                total -= *value as i64;
+            }
+            _ => total += *value as i64,
+        }
+    }
+    to_roman(total) == numeral
+}
+
+fn to_roman(mut n: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '»', '”', '’'])
+        .ends_with(['.', '!', '?', '…'])
+}
+
+// every word of the line is in capitals, so it's a heading and left as it is
+fn is_heading(tree: &Tree<OCRElement>, line: &InternalID) -> bool {
+    let words: Vec<&OCRElement> = tree
+        .descendants(line)
+        .into_iter()
+        .filter_map(|id| tree.get_node(&id))
+        .filter(|elt| elt.ocr_element_type == OCRClass::Word)
+        .filter(|elt| letters(&elt.ocr_text).next().is_some())
+        .collect();
+    let letter_count: usize = words.iter().map(|elt| letters(&elt.ocr_text).count()).sum();
+    letter_count > 1 && words.iter().all(|elt| is_all_caps(&elt.ocr_text))
+}
+
+fn recase(word: &str, mode: CaseMode, starts_sentence: bool, locale: Locale) -> String {
+    if is_roman_numeral(word) && mode != CaseMode::Lower {
+        return word.to_string();
+    }
+    match mode {
+        CaseMode::Lower => locale.lowercase(word),
+        CaseMode::Upper => locale.uppercase(word),
+        CaseMode::Title => locale.capitalize(word),
+        CaseMode::Sentence => {
+            // capitalised words may be names, so only shouted or garbled ones are lowered
+            let word = if is_all_caps(word) || is_garbled(word) {
+                locale.lowercase(word)
+            } else {
+                word.to_string()
+            };
+            if starts_sentence {
+                locale.capitalize_first(&word)
+            } else {
+                word
+            }
+        }
+    }
+}
+
+// change the case of every word under root, in reading order. Sentence and title case
+// leave lines that are all capitals alone, every mode but lowercase leaves roman numerals
+// alone, and i/I follow the word's language (or default_lang). Returns how many words
+// changed.
+pub fn change_case(
+    tree: &mut Tree<OCRElement>,
+    root: &InternalID,
+    mode: CaseMode,
+    default_lang: Option<&str>,
+) -> usize {
+    let selected: HashSet<InternalID> = tree.descendants(root).into_iter().collect();
+    let words: Vec<InternalID> = tree
+        .preorder()
+        .into_iter()
+        .filter(|id| {
+            tree.get_node(id)
+                .is_some_and(|elt| elt.ocr_element_type == OCRClass::Word)
+        })
+        .collect();
+    let mut headings = HashSet::new();
+    let mut starts_sentence = true;
+    let mut changed = 0;
+    for id in words {
+        let text = match tree.get_node(&id) {
+            Some(elt) => elt.ocr_text.clone(),
+            None => continue,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let next_starts_sentence = ends_sentence(&text);
+        if selected.contains(&id) {
+            let line = tree.parent(&id).filter(|parent| {
+                tree.get_node(parent)
+                    .is_some_and(|elt| elt.ocr_element_type == OCRClass::Line)
+            });
+            let heading = match (mode, line) {
+                (CaseMode::Sentence | CaseMode::Title, Some(line)) => {
+                    if !headings.contains(&line) && is_heading(tree, &line) {
+                        headings.insert(line);
+                    }
+                    headings.contains(&line)
+                }
+                _ => false,
+            };
+            if !heading {
+                let lang = ocr_element::inherited_lang(tree, &id);
+                let locale = Locale::from_lang(lang.as_deref().or(default_lang));
+                let new_text = recase(&text, mode, starts_sentence, locale);
+                if new_text != text {
+                    if let Some(elt) = tree.get_mut_node(&id) {
+                        elt.ocr_text = new_text;
+                        changed += 1;
+                    }
+                }
+            }
+        }
+        starts_sentence = next_starts_sentence;
+    }
+    changed
+}
@@ -0,0 +1,580 @@
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::search;
+use crate::tree::{Position, Tree};
+use crate::InternalID;
+use egui::{Rect, Vec2};
+use image::GrayImage;
+use std::collections::HashMap;
+
+// thresholds for deciding a word is probably a speck of dirt on the scan
+#[derive(Debug, Clone)]
+pub struct NoiseSettings {
+    pub max_area: f32,
+    pub max_wconf: u32,
+    pub flag_lone_punctuation: bool,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        NoiseSettings {
+            max_area: 25.0,
+            max_wconf: 20,
+            flag_lone_punctuation: true,
+        }
+    }
+}
+
+// a word we think is noise and why
+#[derive(Debug, Clone)]
+pub struct NoiseCandidate {
+    pub id: InternalID,
+    pub reason: String,
+    // whether the user still wants it deleted
+    pub checked: bool,
+}
+
+fn is_lone_punctuation(text: &str) -> bool {
+    let mut chars = text.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => !c.is_alphanumeric(),
+        _ => false,
+    }
+}
+
+fn noise_reason(elt: &OCRElement, settings: &NoiseSettings) -> Option<String> {
+    if let Some(bbox) = elt.bbox() {
+        let area = bbox.width() * bbox.height();
+        if area < settings.max_area {
+            return Some(format!("tiny ({} px²)", area as u32));
+        }
+    }
+    if settings.flag_lone_punctuation && is_lone_punctuation(&elt.ocr_text) {
+        return Some(format!("lone \"{}\"", elt.ocr_text.trim()));
+    }
+    if let Some(wconf) = elt.wconf() {
+        if wconf < settings.max_wconf {
+            return Some(format!("confidence {}", wconf));
+        }
+    }
+    None
+}
+
+// collect every word that looks like scanner noise in document order
+pub fn find_noise(tree: &Tree<OCRElement>, settings: &NoiseSettings) -> Vec<NoiseCandidate> {
+    tree.preorder()
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            noise_reason(elt, settings).map(|reason| NoiseCandidate {
+                id,
+                reason,
+                checked: true,
+            })
+        })
+        .collect()
+}
+
+// what to do with every low confidence word at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchAction {
+    MarkForReview,
+    ClearText,
+    QueueReocr,
+}
+
+impl BatchAction {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [Self::MarkForReview, Self::ClearText, Self::QueueReocr].iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::MarkForReview => "Mark for review",
+            Self::ClearText => "Clear text",
+            Self::QueueReocr => "Queue for re-OCR",
+        }
+    }
+}
+
+// words under roots with a confidence below threshold, in document order
+pub fn low_confidence_words(
+    tree: &Tree<OCRElement>,
+    roots: &[InternalID],
+    threshold: u32,
+) -> Vec<InternalID> {
+    roots
+        .iter()
+        .flat_map(|root| tree.descendants(root))
+        .filter(|id| {
+            tree.get_node(id).is_some_and(|elt| {
+                elt.ocr_element_type == OCRClass::Word
+                    && elt.wconf().is_some_and(|wconf| wconf < threshold)
+            })
+        })
+        .collect()
+}
+
+// copies of the same word stacked on (nearly) the same spot by a bad OCR merge
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    // the copy we keep: the most confident one
+    pub keep: InternalID,
+    pub duplicates: Vec<InternalID>,
+}
+
+// intersection over union: 1.0 for identical boxes
+fn overlap(a: &Rect, b: &Rect) -> f32 {
+    let inter = a.intersect(*b);
+    if !inter.is_positive() {
+        return 0.0;
+    }
+    let inter_area = inter.area();
+    inter_area / (a.area() + b.area() - inter_area)
+}
+
+// group words on the same page with the same text whose bboxes overlap by at least
+// min_overlap; running headers repeated on every page aren't duplicates
+pub fn find_duplicates(tree: &Tree<OCRElement>, min_overlap: f32) -> Vec<DuplicateGroup> {
+    let mut by_text: HashMap<(InternalID, String), Vec<InternalID>> = HashMap::new();
+    for id in tree.preorder() {
+        if let Some(elt) = tree.get_node(&id) {
+            let text = elt.ocr_text.trim();
+            if elt.ocr_element_type == OCRClass::Word && !text.is_empty() && elt.bbox().is_some() {
+                by_text
+                    .entry((search::page_of(tree, id), text.to_string()))
+                    .or_default()
+                    .push(id);
+            }
+        }
+    }
+    let bbox = |id: &InternalID| *tree.get_node(id).and_then(|elt| elt.bbox()).unwrap();
+    let mut groups = Vec::new();
+    for ids in by_text.values() {
+        let mut grouped = vec![false; ids.len()];
+        for i in 0..ids.len() {
+            if grouped[i] {
+                continue;
+            }
+            let mut members = vec![ids[i]];
+            for j in i + 1..ids.len() {
+                if !grouped[j] && overlap(&bbox(&ids[i]), &bbox(&ids[j])) >= min_overlap {
+                    grouped[j] = true;
+                    members.push(ids[j]);
+                }
+            }
+            if members.len() > 1 {
+                let keep = *members
+                    .iter()
+                    .max_by_key(|id| tree.get_node(id).and_then(|elt| elt.wconf()))
+                    .unwrap();
+                members.retain(|id| *id != keep);
+                groups.push(DuplicateGroup {
+                    keep,
+                    duplicates: members,
+                });
+            }
+        }
+    }
+    // ids are handed out while parsing, so this is close to document order
+    groups.sort_by_key(|group| group.keep);
+    groups
+}
+
+// change every element of class from into class to, e.g. ocr_caption -> ocr_line
+// height_range limits it to boxes of that height (in image pixels)
+pub fn convert_class(
+    tree: &mut Tree<OCRElement>,
+    from: &OCRClass,
+    to: &OCRClass,
+    height_range: Option<(f32, f32)>,
+) -> usize {
+    let targets: Vec<InternalID> = tree
+        .preorder()
+        .into_iter()
+        .filter(|id| {
+            tree.get_node(id).is_some_and(|elt| {
+                elt.ocr_element_type == *from
+                    && height_range.is_none_or(|(min, max)| {
+                        elt.bbox()
+                            .is_some_and(|bbox| (min..=max).contains(&bbox.height()))
+                    })
+            })
+        })
+        .collect();
+    for id in &targets {
+        if let Some(elt) = tree.get_mut_node(id) {
+            elt.ocr_element_type = to.clone();
+        }
+    }
+    targets.len()
+}
+
+fn median(values: &mut [f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Some(values[values.len() / 2])
+}
+
+// words more than ratio times taller or shorter than the median word of their line,
+// which usually means segmentation glued in a neighbouring line or a speck
+pub fn find_height_outliers(tree: &Tree<OCRElement>, ratio: f32) -> Vec<(InternalID, String)> {
+    let mut outliers = Vec::new();
+    for line in tree.preorder() {
+        let is_line = tree
+            .get_node(&line)
+            .is_some_and(|elt| matches!(elt.ocr_element_type, OCRClass::Line | OCRClass::Caption));
+        if !is_line {
+            continue;
+        }
+        let words: Vec<(InternalID, f32)> = tree
+            .children(&line)
+            .filter_map(|id| {
+                let elt = tree.get_node(id)?;
+                if elt.ocr_element_type != OCRClass::Word {
+                    return None;
+                }
+                Some((*id, elt.bbox()?.height()))
+            })
+            .collect();
+        // a median of two doesn't say much
+        if words.len() < 3 {
+            continue;
+        }
+        let mut heights: Vec<f32> = words.iter().map(|(_, height)| *height).collect();
+        let median = match median(&mut heights) {
+            Some(median) if median > 0.0 => median,
+            _ => continue,
+        };
+        for (id, height) in words {
+            if height > median * ratio || height * ratio < median {
+                outliers.push((
+                    id,
+                    format!(
+                        "{} px tall, line median {} px",
+                        height as u32, median as u32
+                    ),
+                ));
+            }
+        }
+    }
+    outliers
+}
+
+// neighbouring words of a line so close together they're probably one word the engine cut
+// into pieces
+#[derive(Debug, Clone)]
+pub struct Oversegmented {
+    // in reading order, at least two
+    pub words: Vec<InternalID>,
+    // the widest gap between them, and the usual gap between words on their line
+    pub gap: f32,
+    pub line_gap: f32,
+    pub checked: bool,
+}
+
+// runs of words whose gaps are less than ratio times the median gap between words on their
+// line. Lines need three words or more so the median means something.
+pub fn find_oversegmented(tree: &Tree<OCRElement>, ratio: f32) -> Vec<Oversegmented> {
+    let mut found = Vec::new();
+    for line in tree.preorder() {
+        let is_line = tree
+            .get_node(&line)
+            .is_some_and(|elt| matches!(elt.ocr_element_type, OCRClass::Line | OCRClass::Caption));
+        if !is_line {
+            continue;
+        }
+        let words: Vec<(InternalID, Rect)> = tree
+            .children(&line)
+            .filter_map(|id| {
+                let elt = tree.get_node(id)?;
+                if elt.ocr_element_type != OCRClass::Word {
+                    return None;
+                }
+                Some((*id, *elt.bbox()?))
+            })
+            .collect();
+        if words.len() < 3 {
+            continue;
+        }
+        let gaps: Vec<f32> = words
+            .windows(2)
+            .map(|pair| pair[1].1.min.x - pair[0].1.max.x)
+            .collect();
+        let line_gap = match median(&mut gaps.clone()) {
+            Some(median) if median > 0.0 => median,
+            _ => continue,
+        };
+        let mut run: Option<Oversegmented> = None;
+        for (i, gap) in gaps.iter().enumerate() {
+            if *gap < line_gap * ratio {
+                let run = run.get_or_insert_with(|| Oversegmented {
+                    words: vec![words[i].0],
+                    gap: *gap,
+                    line_gap,
+                    checked: true,
+                });
+                run.words.push(words[i + 1].0);
+                run.gap = run.gap.max(*gap);
+            } else if let Some(done) = run.take() {
+                found.push(done);
+            }
+        }
+        found.extend(run);
+    }
+    found
+}
+
+// a word whose box spans a stretch of blank paper, so it's probably two words
+#[derive(Debug, Clone)]
+pub struct GappedWord {
+    pub word: InternalID,
+    // left and right edge of the blank stretch, in image coords
+    pub gap: (f32, f32),
+    // the text on either side; a guess from where the gap is, editable before splitting
+    pub left: String,
+    pub right: String,
+    pub checked: bool,
+}
+
+// the widest run of blank columns between the first and last inked ones, in pixels of the
+// crop. A column is blank with at most a couple of specks in it.
+fn widest_blank_run(
+    image: &GrayImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Option<(u32, u32)> {
+    let pixels = || (0..height).flat_map(move |dy| (0..width).map(move |dx| (dx, dy)));
+    let luma = |(dx, dy): (u32, u32)| image.get_pixel(x + dx, y + dy).0[0];
+    let (darkest, lightest) = pixels()
+        .map(luma)
+        .fold((u8::MAX, u8::MIN), |(lo, hi), l| (lo.min(l), hi.max(l)));
+    // an empty or washed out box has nothing to go by
+    if lightest.saturating_sub(darkest) < 40 {
+        return None;
+    }
+    let threshold = darkest / 2 + lightest / 2;
+    let specks = (height / 50).max(1);
+    let blank: Vec<bool> = (0..width)
+        .map(|dx| (0..height).filter(|dy| luma((dx, *dy)) < threshold).count() as u32 <= specks)
+        .collect();
+    let first = blank.iter().position(|b| !b)? as u32;
+    let last = blank.iter().rposition(|b| !b)? as u32;
+    let mut widest: Option<(u32, u32)> = None;
+    let mut start = None;
+    for dx in first..=last {
+        match (blank[dx as usize], start) {
+            (true, None) => start = Some(dx),
+            (false, Some(s)) => {
+                if widest.is_none_or(|(a, b)| dx - s > b - a) {
+                    widest = Some((s, dx));
+                }
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    widest
+}
+
+// words on page with a blank stretch wider than ratio times their height inside them.
+// image is the page's scan; scale takes page coords to its pixels when the two differ.
+pub fn find_gapped_words(
+    tree: &Tree<OCRElement>,
+    page: &InternalID,
+    image: &GrayImage,
+    scale: Vec2,
+    ratio: f32,
+) -> Vec<GappedWord> {
+    let mut found = Vec::new();
+    for id in tree.descendants(page) {
+        let elt = match tree.get_node(&id) {
+            Some(elt) if elt.ocr_element_type == OCRClass::Word => elt,
+            _ => continue,
+        };
+        let bbox = match elt.bbox() {
+            Some(bbox) => *bbox,
+            None => continue,
+        };
+        let x = (bbox.min.x * scale.x).max(0.0) as u32;
+        let y = (bbox.min.y * scale.y).max(0.0) as u32;
+        let width = ((bbox.width() * scale.x) as u32).min(image.width().saturating_sub(x));
+        let height = ((bbox.height() * scale.y) as u32).min(image.height().saturating_sub(y));
+        if width < 3 || height < 3 {
+            continue;
+        }
+        let (start, end) = match widest_blank_run(image, x, y, width, height) {
+            Some(run) if (run.1 - run.0) as f32 >= height as f32 * ratio => run,
+            _ => continue,
+        };
+        let gap = (
+            bbox.min.x + start as f32 / scale.x,
+            bbox.min.x + end as f32 / scale.x,
+        );
+        // split the text where the gap falls along the box
+        let chars: Vec<char> = elt.ocr_text.chars().collect();
+        let fraction = ((gap.0 + gap.1) / 2.0 - bbox.min.x) / bbox.width();
+        let at = ((chars.len() as f32 * fraction).round() as usize).min(chars.len());
+        found.push(GappedWord {
+            word: id,
+            gap,
+            left: chars[..at].iter().collect(),
+            right: chars[at..].iter().collect(),
+            checked: true,
+        });
+    }
+    found
+}
+
+// cut a word in two at its gap: it keeps the left part and a new word after it gets the
+// right part. Returns the new word.
+pub fn split_at_gap(tree: &mut Tree<OCRElement>, gapped: &GappedWord) -> Option<InternalID> {
+    let elt = tree.get_mut_node(&gapped.word)?;
+    let bbox = *elt.bbox()?;
+    let mut right = elt.clone();
+    right.source_id = None;
+    right.source_title = None;
+    let left_bbox = Rect::from_x_y_ranges(bbox.min.x..=gapped.gap.0, bbox.y_range());
+    let right_bbox = Rect::from_x_y_ranges(gapped.gap.1..=bbox.max.x, bbox.y_range());
+    elt.ocr_properties
+        .insert("bbox".to_string(), OCRProperty::BBox(left_bbox));
+    right
+        .ocr_properties
+        .insert("bbox".to_string(), OCRProperty::BBox(right_bbox));
+    // per character confidences go with their characters
+    if let Some(OCRProperty::Floats(confs)) = elt.ocr_properties.get("x_confs").cloned() {
+        let at = gapped.left.chars().count();
+        if elt.ocr_text == format!("{}{}", gapped.left, gapped.right) && at <= confs.len() {
+            elt.ocr_properties.insert(
+                "x_confs".to_string(),
+                OCRProperty::Floats(confs[..at].to_vec()),
+            );
+            right.ocr_properties.insert(
+                "x_confs".to_string(),
+                OCRProperty::Floats(confs[at..].to_vec()),
+            );
+        } else {
+            elt.ocr_properties.remove("x_confs");
+            right.ocr_properties.remove("x_confs");
+        }
+    }
+    elt.ocr_text = gapped.left.clone();
+    right.ocr_text = gapped.right.clone();
+    tree.add_sibling(&gapped.word, right, &Position::After).ok()
+}
+
+// cut a word in two before its at'th character, dividing the bbox in proportion to how much
+// of the text falls on either side. Returns the new word.
+pub fn split_word_at(
+    tree: &mut Tree<OCRElement>,
+    word: &InternalID,
+    at: usize,
+) -> Option<InternalID> {
+    let elt = tree.get_node(word)?;
+    let bbox = *elt.bbox()?;
+    let chars: Vec<char> = elt.ocr_text.chars().collect();
+    if at == 0 || at >= chars.len() {
+        return None;
+    }
+    let x = bbox.min.x + bbox.width() * at as f32 / chars.len() as f32;
+    let left: String = chars[..at].iter().collect();
+    let right: String = chars[at..].iter().collect();
+    // a cursor next to a space doesn't mean the space is part of either word
+    let (left, right) = (left.trim_end(), right.trim_start());
+    if left.is_empty() || right.is_empty() {
+        return None;
+    }
+    let gapped = GappedWord {
+        word: *word,
+        gap: (x, x),
+        left: left.to_string(),
+        right: right.to_string(),
+        checked: true,
+    };
+    split_at_gap(tree, &gapped)
+}
+
+// the words of a line left to right, each pair of neighbours with the x between them
+pub fn word_boundaries(
+    tree: &Tree<OCRElement>,
+    line: &InternalID,
+) -> Vec<(InternalID, InternalID, f32)> {
+    let mut words: Vec<(InternalID, Rect)> = tree
+        .children(line)
+        .filter_map(|id| {
+            let elt = tree.get_node(id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            Some((*id, *elt.bbox()?))
+        })
+        .collect();
+    words.sort_by(|a, b| a.1.min.x.total_cmp(&b.1.min.x));
+    words
+        .windows(2)
+        .map(|pair| {
+            let ((left, left_bbox), (right, right_bbox)) = (pair[0], pair[1]);
+            (left, right, (left_bbox.max.x + right_bbox.min.x) / 2.0)
+        })
+        .collect()
+}
+
+// shift the right edge of left and the left edge of right together, keeping whatever gap
+// there is between them; neither word gets narrower than a pixel
+pub fn move_word_boundary(
+    tree: &mut Tree<OCRElement>,
+    left: &InternalID,
+    right: &InternalID,
+    dx: f32,
+) {
+    let (left_bbox, right_bbox) = match (
+        tree.get_node(left).and_then(|elt| elt.bbox()),
+        tree.get_node(right).and_then(|elt| elt.bbox()),
+    ) {
+        (Some(left_bbox), Some(right_bbox)) => (*left_bbox, *right_bbox),
+        _ => return,
+    };
+    let lowest = left_bbox.min.x + 1.0 - left_bbox.max.x;
+    let highest = right_bbox.max.x - 1.0 - right_bbox.min.x;
+    if lowest > highest {
+        return;
+    }
+    let dx = dx.clamp(lowest, highest);
+    if let Some(OCRProperty::BBox(bbox)) = tree
+        .get_mut_node(left)
+        .and_then(|elt| elt.ocr_properties.get_mut("bbox"))
+    {
+        bbox.max.x += dx;
+    }
+    if let Some(OCRProperty::BBox(bbox)) = tree
+        .get_mut_node(right)
+        .and_then(|elt| elt.ocr_properties.get_mut("bbox"))
+    {
+        bbox.min.x += dx;
+    }
+}
+
+// squeeze a word's bbox vertically into its line's bbox
+pub fn clamp_to_line(tree: &mut Tree<OCRElement>, word: &InternalID) {
+    let line_bbox = match tree
+        .parent(word)
+        .and_then(|line| tree.get_node(&line))
+        .and_then(|line| line.bbox())
+    {
+        Some(bbox) => *bbox,
+        None => return,
+    };
+    if let Some(OCRProperty::BBox(bbox)) = tree
+        .get_mut_node(word)
+        .and_then(|elt| elt.ocr_properties.get_mut("bbox"))
+    {
+        bbox.min.y = bbox.min.y.clamp(line_bbox.min.y, line_bbox.max.y);
+        bbox.max.y = bbox.max.y.clamp(line_bbox.min.y, line_bbox.max.y);
+    }
+}
@@ -0,0 +1,764 @@
+use crate::atomic;
+use crate::export::{self, ExportFormat, ExportOptions, PageSeparator, ReadingOrder, Unverified};
+use crate::jobs::Progress;
+use crate::json_tree;
+use crate::ocr_element::{self, OCRElement, OCRProperty};
+use crate::page_xml;
+use crate::pdf;
+use crate::project::ProjectSettings;
+use crate::proof;
+use crate::redact;
+use crate::search::SearchOptions;
+use crate::stats;
+use crate::template::{self, Template};
+use crate::tree::Tree;
+use scraper::Html;
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage:
+    hocr_editor                     start the editor
+    hocr_editor convert IN --to hocr|txt|tsv|alto|json|pagexml [-o OUT]
+    hocr_editor validate PATTERN...
+    hocr_editor merge -o OUT.hocr IN...
+    hocr_editor extract [--format txt|tsv|alto] [--reading-order document|geometric]
+                        [--dehyphenate] [--page-separator blank|formfeed|numbered]
+                        [--unverified keep|blank|flag] [--list-unverified]
+                        -o OUT_DIR PATTERN...
+    hocr_editor stats [--json] PATTERN...
+    hocr_editor proof [--font FONT.ttf] -o OUT_DIR PATTERN...
+    hocr_editor pagexml -o OUT_DIR PATTERN...
+    hocr_editor json -o OUT_DIR PATTERN...
+    hocr_editor frequencies [--case-sensitive] [--no-normalize] [--ignore-diacritics]
+                        -o OUT.tsv PATTERN...
+    hocr_editor fields -t TEMPLATE -o OUT.csv|OUT.json PATTERN...";
+
+pub fn load_tree(path: &Path) -> Result<Tree<OCRElement>, String> {
+    let html_buffer =
+        read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if json_tree::file_is_json(path) {
+        return json_tree::parse(&html_buffer).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    if page_xml::is_page_xml(&html_buffer) {
+        return page_xml::parse(&html_buffer).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    Ok(OCRElement::html_to_ocr_tree(Html::parse_document(
+        &html_buffer,
+    )))
+}
+
+// the head to write a tree under: the input's own for hOCR, a bare one for everything else
+fn load_head(path: &Path) -> Html {
+    let source = read_to_string(path).unwrap_or_default();
+    if json_tree::file_is_json(path) || page_xml::is_page_xml(&source) {
+        pdf::without_body(Html::parse_document(page_xml::HOCR_SKELETON))
+    } else {
+        pdf::without_body(Html::parse_document(&source))
+    }
+}
+
+// the project settings the editor would use for the document; unlike the editor, a project
+// file that can't be read is an error
+fn load_project(document: &Path) -> Result<ProjectSettings, String> {
+    match ProjectSettings::find(document) {
+        Some(path) => ProjectSettings::load(&path),
+        None => Ok(ProjectSettings::default()),
+    }
+}
+
+// expand each glob pattern (e.g. "scans/**/*.hocr") into the files it matches
+fn expand_patterns(patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    Ok(expand_patterns_relative(patterns)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+// the directories of a pattern before the first one with a wildcard: "scans/**/*.hocr" ->
+// "scans", and a plain file's own directory
+fn glob_base(pattern: &str) -> PathBuf {
+    let path = Path::new(pattern);
+    let mut base = PathBuf::new();
+    for component in path.components() {
+        if component
+            .as_os_str()
+            .to_string_lossy()
+            .contains(['*', '?', '['])
+        {
+            return base;
+        }
+        base.push(component);
+    }
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+// like expand_patterns, with each match's path under its pattern's base as well, so
+// outputs can mirror the input folders
+fn expand_patterns_relative(patterns: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let matches = glob::glob(pattern).map_err(|e| format!("Bad pattern {}: {}", pattern, e))?;
+        let base = glob_base(pattern);
+        let before = paths.len();
+        paths.extend(
+            matches
+                .filter_map(|entry| entry.ok())
+                .filter(|p| p.is_file())
+                .map(|p| {
+                    let relative = p.strip_prefix(&base).map_or_else(
+                        |_| PathBuf::from(p.file_name().unwrap_or_default()),
+                        Path::to_path_buf,
+                    );
+                    (p, relative)
+                }),
+        );
+        if paths.len() == before {
+            println!("warning: {} didn't match any files", pattern);
+        }
+    }
+    Ok(paths)
+}
+
+// where the output for an input at relative (see expand_patterns_relative) goes: the same
+// folders under out_dir, and the whole stem kept so "book.v2.hocr" becomes "book.v2.txt"
+fn output_path(out_dir: &Path, relative: &Path, extension: &str) -> Result<PathBuf, String> {
+    let dir = out_dir.join(relative.parent().unwrap_or(Path::new("")));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(dir.join(format!("{}.{}", stem, extension)))
+}
+
+fn extract(args: &[String]) -> Result<(), String> {
+    let mut format = ExportFormat::Text;
+    // what's given here wins over each document's project
+    let mut reading_order = None;
+    let mut dehyphenate = false;
+    let mut page_separator = None;
+    let mut unverified = None;
+    let mut list_unverified = false;
+    let mut out_dir: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" | "-f" => {
+                let name = args.next().ok_or("--format needs a value")?;
+                format = ExportFormat::from_name(name)
+                    .ok_or(format!("Unknown export format {}", name))?;
+            }
+            "--reading-order" => {
+                let name = args.next().ok_or("--reading-order needs a value")?;
+                reading_order = Some(
+                    ReadingOrder::from_name(name)
+                        .ok_or(format!("Unknown reading order {}", name))?,
+                );
+            }
+            "--dehyphenate" => dehyphenate = true,
+            "--page-separator" => {
+                let name = args.next().ok_or("--page-separator needs a value")?;
+                page_separator = Some(
+                    PageSeparator::from_name(name)
+                        .ok_or(format!("Unknown page separator {}", name))?,
+                );
+            }
+            "--unverified" => {
+                let name = args.next().ok_or("--unverified needs a value")?;
+                unverified = Some(
+                    Unverified::from_name(name)
+                        .ok_or(format!("Unknown --unverified value {}", name))?,
+                );
+            }
+            "--list-unverified" => list_unverified = true,
+            "-o" | "--output" => {
+                out_dir = Some(PathBuf::from(args.next().ok_or("-o needs a directory")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let out_dir = out_dir.ok_or("extract needs an output directory (-o)")?;
+    if patterns.is_empty() {
+        return Err("extract needs at least one input pattern".to_string());
+    }
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+    let mut failed = 0;
+    let mut written = HashSet::new();
+    for (path, relative) in expand_patterns_relative(&patterns)? {
+        let res = output_path(&out_dir, &relative, format.extension()).and_then(|out_path| {
+            if !written.insert(out_path.clone()) {
+                return Err(format!("{} was already written", out_path.display()));
+            }
+            let mut options = load_project(&path)?.export_options(&ExportOptions::default());
+            options.reading_order = reading_order.unwrap_or(options.reading_order);
+            options.dehyphenate |= dehyphenate;
+            options.page_separator = page_separator.unwrap_or(options.page_separator);
+            options.unverified = unverified.unwrap_or(options.unverified);
+            options.list_unverified |= list_unverified;
+            let tree = load_tree(&path)?;
+            export::write_pages_atomic(&tree, format, &options, &out_path, &Progress::default())?;
+            if options.list_unverified {
+                atomic::write_atomic(
+                    &export::unverified_list_path(&out_path),
+                    export::unverified_regions(&tree, options.reading_order),
+                )?;
+            }
+            Ok(out_path)
+        });
+        match res {
+            Ok(out_path) => println!("{} -> {}", path.display(), out_path.display()),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// one file to another format; the output defaults to the input with the format's extension
+fn convert(args: &[String]) -> Result<(), String> {
+    let mut input: Option<PathBuf> = None;
+    let mut to: Option<String> = None;
+    let mut out_path: Option<PathBuf> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" | "-t" => to = Some(args.next().ok_or("--to needs a format")?.clone()),
+            "-o" | "--output" => {
+                out_path = Some(PathBuf::from(args.next().ok_or("-o needs a file")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if input.is_some() => return Err("convert takes one input file".to_string()),
+            _ => input = Some(PathBuf::from(arg)),
+        }
+    }
+    let input = input.ok_or("convert needs an input file")?;
+    let to = to.ok_or("convert needs a format (--to)")?;
+    let extension = match to.as_str() {
+        "pagexml" => "",
+        "hocr" | "json" => to.as_str(),
+        other => ExportFormat::from_name(other)
+            .ok_or(format!("Unknown format {}", other))?
+            .extension(),
+    };
+    let out_path = out_path.unwrap_or_else(|| match to.as_str() {
+        // one PAGE file per page, so they get a folder
+        "pagexml" => input.with_extension("page"),
+        _ => input.with_extension(extension),
+    });
+    if out_path == input {
+        return Err(format!(
+            "{} would be overwritten; pick another -o",
+            input.display()
+        ));
+    }
+    let project = load_project(&input)?;
+    let tree = load_tree(&input)?;
+    match to.as_str() {
+        "hocr" => atomic::write_atomic(
+            &out_path,
+            ocr_element::add_as_body_with(&tree, &load_head(&input), &project.write_options())
+                .html(),
+        )?,
+        "json" => json_tree::write(&tree, &out_path)?,
+        "pagexml" => {
+            std::fs::create_dir_all(&out_path)
+                .map_err(|e| format!("Failed to create {}: {}", out_path.display(), e))?;
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            page_xml::write_pages(&tree, &stem, &out_path)?;
+        }
+        name => {
+            let format = ExportFormat::from_name(name).ok_or(format!("Unknown format {}", name))?;
+            export::write_pages_atomic(
+                &tree,
+                format,
+                &project.export_options(&ExportOptions::default()),
+                &out_path,
+                &Progress::default(),
+            )?
+        }
+    }
+    println!("{} -> {}", input.display(), out_path.display());
+    Ok(())
+}
+
+// the stats warnings and repeated ids of every file; fails if any file has any
+fn validate(args: &[String]) -> Result<(), String> {
+    let mut patterns = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    if patterns.is_empty() {
+        return Err("validate needs at least one input file".to_string());
+    }
+    let mut bad_files = 0;
+    for path in expand_patterns(&patterns)? {
+        let tree = match load_tree(&path) {
+            Ok(tree) => tree,
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                bad_files += 1;
+                continue;
+            }
+        };
+        let mut problems: Vec<String> = stats::validate(&tree)
+            .into_iter()
+            .map(|(id, warning)| {
+                // the id in the file is what other tools will call it
+                match tree.get_node(&id).and_then(|elt| elt.source_id.as_ref()) {
+                    Some(source_id) => format!("{} ({})", warning, source_id),
+                    None => warning,
+                }
+            })
+            .collect();
+        let mut seen = HashSet::new();
+        for id in tree.preorder() {
+            if let Some(source_id) = tree.get_node(&id).and_then(|elt| elt.source_id.as_ref()) {
+                if !seen.insert(source_id) {
+                    problems.push(format!("id {} is used more than once", source_id));
+                }
+            }
+        }
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+        } else {
+            println!("{}: {} problems", path.display(), problems.len());
+            for problem in &problems {
+                println!("  {}", problem);
+            }
+            bad_files += 1;
+        }
+    }
+    if bad_files > 0 {
+        Err(format!("{} files have problems", bad_files))
+    } else {
+        Ok(())
+    }
+}
+
+// image paths relative to the file they came from, made to work from out_dir instead
+fn rebase_images(tree: &mut Tree<OCRElement>, source: &Path, out_dir: &Path) {
+    let out_dir = out_dir.canonicalize().unwrap_or(out_dir.to_path_buf());
+    let pages: Vec<_> = tree.roots().copied().collect();
+    for page in pages {
+        if let Some(OCRProperty::Image(image)) = tree
+            .get_mut_node(&page)
+            .and_then(|elt| elt.ocr_properties.get_mut("image"))
+        {
+            let resolved = redact::resolve_image(image, Some(source));
+            let resolved = resolved.canonicalize().unwrap_or(resolved);
+            let rebased = resolved
+                .strip_prefix(&out_dir)
+                .map(|relative| relative.to_path_buf())
+                .unwrap_or(resolved);
+            *image = rebased.display().to_string();
+        }
+    }
+}
+
+// the pages of every input, in order, in one hOCR file with the first input's head
+fn merge(args: &[String]) -> Result<(), String> {
+    let mut out_path: Option<PathBuf> = None;
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                out_path = Some(PathBuf::from(args.next().ok_or("-o needs a file")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => inputs.push(arg.clone()),
+        }
+    }
+    let out_path = out_path.ok_or("merge needs an output file (-o)")?;
+    let inputs = expand_patterns(&inputs)?;
+    if inputs.len() < 2 {
+        return Err("merge needs at least two input files".to_string());
+    }
+    let out_dir = match out_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    // written the way the first input's project wants, like its head
+    let project = load_project(&inputs[0])?;
+    let mut merged: Tree<OCRElement> = Tree::new();
+    for input in &inputs {
+        let mut tree = load_tree(input)?;
+        rebase_images(&mut tree, input, &out_dir);
+        for page in tree.roots() {
+            let value = tree.get_node(page).cloned().unwrap_or_default();
+            let new_page = merged.add_root(value);
+            for child in tree.children(page) {
+                merged.graft(&new_page, &tree, child)?;
+            }
+        }
+        println!("{}: {} pages", input.display(), tree.roots().len());
+    }
+    // page numbers from separate files are bound to clash
+    if !ocr_element::ppageno_problems(&merged).is_empty() {
+        println!("Renumbering pages from 0");
+        ocr_element::renumber_pages(&mut merged, 0, 1);
+    }
+    atomic::write_atomic(
+        &out_path,
+        ocr_element::add_as_body_with(&merged, &load_head(&inputs[0]), &project.write_options())
+            .html(),
+    )?;
+    println!("{} pages -> {}", merged.roots().len(), out_path.display());
+    Ok(())
+}
+
+// one JSON object per line with --json, so a whole corpus can be piped through jq
+fn print_stats(args: &[String]) -> Result<(), String> {
+    let mut json = false;
+    let mut patterns = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    if patterns.is_empty() {
+        return Err("stats needs at least one input file".to_string());
+    }
+    let mut failed = 0;
+    for path in expand_patterns(&patterns)? {
+        let tree = match load_tree(&path) {
+            Ok(tree) => tree,
+            Err(e) => {
+                // stdout stays one JSON object per line
+                eprintln!("{}: {}", path.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        let stats = stats::compute(&tree, crate::BAD_WCONF_THRESHOLD);
+        let warnings: Vec<String> = stats::validate(&tree)
+            .into_iter()
+            .map(|(_, warning)| warning)
+            .collect();
+        if json {
+            let report = serde_json::json!({
+                "file": path.display().to_string(),
+                "stats": stats,
+                "warnings": warnings,
+            });
+            println!("{}", report);
+        } else {
+            println!("{}", path.display());
+            println!(
+                "  {} pages, {} blocks, {} paragraphs, {} lines, {} words",
+                stats.pages, stats.blocks, stats.pars, stats.lines, stats.words
+            );
+            match stats.mean_wconf {
+                Some(mean) => println!(
+                    "  mean confidence {:.1}, {} words below {}",
+                    mean,
+                    stats.low_conf_words,
+                    crate::BAD_WCONF_THRESHOLD
+                ),
+                None => println!("  no confidence values"),
+            }
+            for warning in &warnings {
+                println!("  warning: {}", warning);
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// a pdf per file with every word boxed in its confidence color, for review away from the editor
+fn proof_sheets(args: &[String]) -> Result<(), String> {
+    let mut out_dir: Option<PathBuf> = None;
+    let mut font: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                out_dir = Some(PathBuf::from(args.next().ok_or("-o needs a directory")?));
+            }
+            "--font" => {
+                font = Some(PathBuf::from(
+                    args.next().ok_or("--font needs a font file")?,
+                ));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let out_dir = out_dir.ok_or("proof needs an output directory (-o)")?;
+    if patterns.is_empty() {
+        return Err("proof needs at least one input pattern".to_string());
+    }
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+    let mut failed = 0;
+    let mut written = HashSet::new();
+    for (path, relative) in expand_patterns_relative(&patterns)? {
+        let res = output_path(&out_dir, &relative, "pdf").and_then(|out_path| {
+            let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+            let out_path = out_path.with_file_name(format!("{}_proof.pdf", stem));
+            if !written.insert(out_path.clone()) {
+                return Err(format!("{} was already written", out_path.display()));
+            }
+            proof::write_proof(
+                &load_tree(&path)?,
+                &path,
+                &out_path,
+                crate::BAD_WCONF_THRESHOLD,
+                font.as_deref(),
+            )?;
+            Ok(out_path)
+        });
+        match res {
+            Ok(out_path) => println!("{} -> {}", path.display(), out_path.display()),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// how often each word occurs across all inputs
+fn word_frequencies(args: &[String]) -> Result<(), String> {
+    let mut options = SearchOptions::default();
+    let mut out_path: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--case-sensitive" => options.ignore_case = false,
+            "--no-normalize" => options.normalize = false,
+            "--ignore-diacritics" => options.ignore_diacritics = true,
+            "-o" | "--output" => {
+                out_path = Some(PathBuf::from(args.next().ok_or("-o needs a file")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let out_path = out_path.ok_or("frequencies needs an output file (-o)")?;
+    if patterns.is_empty() {
+        return Err("frequencies needs at least one input pattern".to_string());
+    }
+    let mut counts = HashMap::new();
+    let mut failed = 0;
+    for path in expand_patterns(&patterns)? {
+        match load_tree(&path) {
+            Ok(tree) => stats::count_words(&tree, &options, &mut counts),
+            Err(e) => {
+                println!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    let frequencies = stats::sorted_frequencies(counts);
+    atomic::write_atomic(&out_path, stats::frequencies_to_tsv(&frequencies))?;
+    println!(
+        "{} distinct words -> {}",
+        frequencies.len(),
+        out_path.display()
+    );
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// one PAGE XML file per page of every input
+fn page_xml_files(args: &[String]) -> Result<(), String> {
+    let mut out_dir: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                out_dir = Some(PathBuf::from(args.next().ok_or("-o needs a directory")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let out_dir = out_dir.ok_or("pagexml needs an output directory (-o)")?;
+    if patterns.is_empty() {
+        return Err("pagexml needs at least one input pattern".to_string());
+    }
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+    let mut failed = 0;
+    let mut written = HashSet::new();
+    for (path, relative) in expand_patterns_relative(&patterns)? {
+        let res = load_tree(&path).and_then(|tree| {
+            // the pages go into the input's folder mirrored under out_dir
+            let dir = out_dir.join(relative.parent().unwrap_or(Path::new("")));
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+            let paths = page_xml::page_paths(&tree, &stem, &dir);
+            let mut own = HashSet::new();
+            if let Some(taken) = paths
+                .iter()
+                .find(|path| written.contains(*path) || !own.insert(*path))
+            {
+                return Err(format!("{} was already written", taken.display()));
+            }
+            written.extend(paths);
+            page_xml::write_pages(&tree, &stem, &dir)
+        });
+        match res {
+            Ok(pages) => println!("{} -> {} pages", path.display(), pages.len()),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// the whole tree of every input as <stem>.json
+fn json_files(args: &[String]) -> Result<(), String> {
+    let mut out_dir: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                out_dir = Some(PathBuf::from(args.next().ok_or("-o needs a directory")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let out_dir = out_dir.ok_or("json needs an output directory (-o)")?;
+    if patterns.is_empty() {
+        return Err("json needs at least one input pattern".to_string());
+    }
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+    let mut failed = 0;
+    let mut written = HashSet::new();
+    for (path, relative) in expand_patterns_relative(&patterns)? {
+        let res = output_path(&out_dir, &relative, "json").and_then(|out_path| {
+            if !written.insert(out_path.clone()) {
+                return Err(format!("{} was already written", out_path.display()));
+            }
+            json_tree::write(&load_tree(&path)?, &out_path)?;
+            Ok(out_path)
+        });
+        match res {
+            Ok(out_path) => println!("{} -> {}", path.display(), out_path.display()),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// the text in each template region of every page, one record per page
+fn extract_fields(args: &[String]) -> Result<(), String> {
+    let mut template_path: Option<PathBuf> = None;
+    let mut out_path: Option<PathBuf> = None;
+    let mut patterns = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-t" | "--template" => {
+                template_path = Some(PathBuf::from(args.next().ok_or("-t needs a file")?));
+            }
+            "-o" | "--output" => {
+                out_path = Some(PathBuf::from(args.next().ok_or("-o needs a file")?));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => patterns.push(arg.clone()),
+        }
+    }
+    let template = Template::load(&template_path.ok_or("fields needs a template (-t)")?)?;
+    let out_path = out_path.ok_or("fields needs an output file (-o)")?;
+    if patterns.is_empty() {
+        return Err("fields needs at least one input pattern".to_string());
+    }
+    let mut records = Vec::new();
+    let mut failed = 0;
+    for path in expand_patterns(&patterns)? {
+        match load_tree(&path) {
+            Ok(tree) => records.extend(template::extract_records(
+                &tree,
+                &path.display().to_string(),
+                &template,
+            )),
+            Err(e) => {
+                println!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    template::write_records(&records, &template, &out_path)?;
+    println!("{} pages -> {}", records.len(), out_path.display());
+    if failed > 0 {
+        Err(format!("{} files failed", failed))
+    } else {
+        Ok(())
+    }
+}
+
+// run a subcommand, returning the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let res = match args[0].as_str() {
+        "convert" => convert(&args[1..]),
+        "validate" => validate(&args[1..]),
+        "merge" => merge(&args[1..]),
+        "extract" => extract(&args[1..]),
+        "stats" => print_stats(&args[1..]),
+        "proof" => proof_sheets(&args[1..]),
+        "fields" => extract_fields(&args[1..]),
+        "pagexml" => page_xml_files(&args[1..]),
+        "json" => json_files(&args[1..]),
+        "frequencies" => word_frequencies(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("Unknown command {}\n{}", other, USAGE)),
+    };
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::ocr_element::{OCRClass, OCRElement};
+use crate::tree::Tree;
+use crate::InternalID;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+// split one csv line into fields, handling "quoted, fields" and "" escapes
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// spreadsheets saving as csv often put a byte order mark before the header
+fn is_header(fields: &[String]) -> bool {
+    match fields {
+        [wrong, right] => {
+            let wrong = wrong.trim_start_matches('\u{feff}');
+            wrong.trim().eq_ignore_ascii_case("wrong") && right.trim().eq_ignore_ascii_case("right")
+        }
+        _ => false,
+    }
+}
+
+// wrong -> right word replacements, e.g. collected while proofreading one volume
+#[derive(Debug, Clone, Default)]
+pub struct CorrectionList {
+    pub pairs: HashMap<String, String>,
+}
+
+impl CorrectionList {
+    // two columns per line, wrong then right, under an optional wrong,right header; lines
+    // with other shapes are skipped
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let pairs: HashMap<String, String> = contents
+            .lines()
+            .map(parse_csv_line)
+            .enumerate()
+            .filter(|(i, fields)| *i > 0 || !is_header(fields))
+            .map(|(_, fields)| fields)
+            .filter_map(|fields| match fields.as_slice() {
+                [wrong, right] if !wrong.trim().is_empty() && wrong != right => {
+                    Some((wrong.trim().to_string(), right.trim().to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        if pairs.is_empty() {
+            return Err(format!("No corrections found in {}", path.display()));
+        }
+        Ok(CorrectionList { pairs })
+    }
+}
+
+// one word that a correction applies to
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub id: InternalID,
+    pub old: String,
+    pub new: String,
+    // the preview lets each one be unticked before applying
+    pub checked: bool,
+}
+
+// every word whose text, not counting surrounding punctuation, is one of the wrong forms;
+// the punctuation is kept, so "tlie," becomes "the,"
+pub fn find_corrections(tree: &Tree<OCRElement>, list: &CorrectionList) -> Vec<Correction> {
+    tree.preorder()
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            let text = elt.ocr_text.as_str();
+            let core = text.trim_matches(|c: char| !c.is_alphanumeric());
+            let right = list
+                .pairs
+                .get(text.trim())
+                .map(|right| right.to_string())
+                .or_else(|| {
+                    let right = list.pairs.get(core)?;
+                    let start = text.find(core)?;
+                    Some(format!(
+                        "{}{}{}",
+                        &text[..start],
+                        right,
+                        &text[start + core.len()..]
+                    ))
+                })?;
+            Some(Correction {
+                id,
+                old: text.to_string(),
+                new: right,
+                checked: true,
+            })
+        })
+        .collect()
+}
+
+// returns how many words changed
+pub fn apply(tree: &mut Tree<OCRElement>, corrections: &[Correction]) -> usize {
+    let mut changed = 0;
+    for correction in corrections.iter().filter(|c| c.checked) {
+        if let Some(elt) = tree.get_mut_node(&correction.id) {
+            // skip words edited since the preview was made
+            if elt.ocr_text == correction.old {
+                elt.ocr_text = correction.new.clone();
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,97 @@
+use crate::ocr_element::{OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::{Rect, Vec2};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// sizes this close are the same scan, give or take rounding
+const TOLERANCE: f32 = 0.01;
+
+// what to do about a page whose bbox doesn't match its scan's size. Scaling the coordinates
+// is done on the spot, so only the other two are ever remembered.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ImageMapping {
+    // resize every box on the page to fit the scan
+    ScaleCoordinates,
+    // stretch the scan to the page bbox when drawing it
+    ScaleImage,
+    // draw both as they are
+    Ignore,
+}
+
+// width and height in pixels, read from the file's header only
+pub fn image_size(path: &Path) -> Option<Vec2> {
+    match image::image_dimensions(path) {
+        Ok((width, height)) => Some(Vec2::new(width as f32, height as f32)),
+        Err(e) => {
+            println!("Couldn't read the size of {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn mismatch(page_size: Vec2, image_size: Vec2) -> bool {
+    let differs = |a: f32, b: f32| (a - b).abs() > TOLERANCE * a.max(b);
+    differs(page_size.x, image_size.x) || differs(page_size.y, image_size.y)
+}
+
+// "the scan is 2.00 × the page", for telling a 150 dpi scan from a 300 dpi one
+pub fn describe(page_size: Vec2, image_size: Vec2) -> String {
+    let scale = image_size / page_size;
+    if (scale.x - scale.y).abs() <= TOLERANCE * scale.x.max(scale.y) {
+        format!("the scan is {:.2} × the page", scale.x)
+    } else {
+        format!(
+            "the scan is {:.2} × as wide and {:.2} × as tall as the page",
+            scale.x, scale.y
+        )
+    }
+}
+
+// what the session remembers a page's mapping under: its id in the file, so the mapping
+// stays with the page when pages before it are added or removed. Pages that were never
+// saved get the id a save would give them.
+pub fn page_key(tree: &Tree<OCRElement>, page: &InternalID) -> String {
+    tree.get_node(page)
+        .and_then(|elt| elt.source_id.clone())
+        .unwrap_or_else(|| {
+            let index = tree.roots().position(|root| root == page).unwrap_or(0);
+            format!("page_{}", index + 1)
+        })
+}
+
+// scale every bbox under page so the page's bbox matches size, along with the baseline
+// offsets and font metrics measured in the same pixels
+pub fn fit_page(tree: &mut Tree<OCRElement>, page: &InternalID, size: Vec2) {
+    let old = match tree.get_node(page).and_then(|elt| elt.bbox()) {
+        Some(bbox) if bbox.width() > 0.0 && bbox.height() > 0.0 => *bbox,
+        _ => return,
+    };
+    let scale = size / old.size();
+    if (scale - Vec2::splat(1.0)).length() < 0.001 {
+        return;
+    }
+    for id in tree.descendants(page) {
+        let elt = match tree.get_mut_node(&id) {
+            Some(elt) => elt,
+            None => continue,
+        };
+        for (name, prop) in elt.ocr_properties.iter_mut() {
+            match (name.as_str(), prop) {
+                ("bbox", OCRProperty::BBox(bbox)) => {
+                    *bbox = Rect::from_min_max(
+                        (bbox.min.to_vec2() * scale).to_pos2(),
+                        (bbox.max.to_vec2() * scale).to_pos2(),
+                    );
+                }
+                // the slope is a ratio, so only the offset changes
+                ("baseline", OCRProperty::Baseline(_, offset)) => *offset *= scale.y,
+                ("x_size" | "x_ascenders" | "x_descenders", OCRProperty::Float(value)) => {
+                    *value *= scale.y
+                }
+                _ => {}
+            }
+        }
+    }
+}
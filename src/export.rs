@@ -0,0 +1,802 @@
+use crate::atomic;
+use crate::hyphenation;
+use crate::ignore;
+use crate::jobs::{Job, Progress};
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::page_xml;
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::Rect;
+use rayon::prelude::*;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// how many pages get converted at once before being written out
+const PAGES_PER_CHUNK: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Text,
+    // the table `tesseract ... tsv` writes
+    Tsv,
+    // ALTO v4 XML, what libraries and newspaper archives read
+    Alto,
+}
+
+impl ExportFormat {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [Self::Text, Self::Tsv, Self::Alto].iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::Text => "Text",
+            Self::Tsv => "Tesseract TSV",
+            Self::Alto => "ALTO",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Tsv => "tsv",
+            Self::Alto => "xml",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::variants()
+            .find(|format| {
+                format.extension() == name || format.to_user_str().eq_ignore_ascii_case(name)
+            })
+            .copied()
+    }
+
+    // what goes before page number index (0-based) of the output
+    fn page_separator(&self, index: usize, options: &ExportOptions) -> String {
+        match self {
+            Self::Text => options.page_separator.text(index),
+            Self::Tsv | Self::Alto => String::new(),
+        }
+    }
+
+    // what goes before the first page, even when there are none
+    fn header(&self) -> String {
+        match self {
+            Self::Tsv => format!("{}\n", TSV_HEADER),
+            Self::Alto => ALTO_HEADER.to_string(),
+            Self::Text => String::new(),
+        }
+    }
+
+    // what goes after the last page, so a document without pages is still a valid file
+    fn trailer(&self) -> &'static str {
+        match self {
+            Self::Alto => ALTO_FOOTER,
+            Self::Text | Self::Tsv => "",
+        }
+    }
+
+    // convert page number index (0-based); pages are independent so this can run on any
+    // thread
+    fn export_page(
+        &self,
+        tree: &Tree<OCRElement>,
+        page: InternalID,
+        index: usize,
+        options: &ExportOptions,
+    ) -> String {
+        match self {
+            Self::Text => page_to_text(tree, page, options),
+            Self::Tsv => page_to_tsv(tree, page, index + 1, options),
+            Self::Alto => page_to_alto(tree, page, index + 1, options),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReadingOrder {
+    // the order elements appear in the file
+    #[default]
+    Document,
+    // blocks and paragraphs sorted top to bottom, then left to right
+    Geometric,
+}
+
+impl ReadingOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "document" => Some(Self::Document),
+            "geometric" => Some(Self::Geometric),
+            _ => None,
+        }
+    }
+}
+
+// how pages are told apart in a text export
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PageSeparator {
+    // a blank line, same as between paragraphs
+    #[default]
+    BlankLine,
+    // \f, which pagers and printers understand as a page break
+    FormFeed,
+    // a "--- page 3 ---" line before every page
+    Numbered,
+}
+
+impl PageSeparator {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [Self::BlankLine, Self::FormFeed, Self::Numbered].iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::BlankLine => "Blank line",
+            Self::FormFeed => "Form feed",
+            Self::Numbered => "Page number line",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blank" => Some(Self::BlankLine),
+            "formfeed" => Some(Self::FormFeed),
+            "numbered" => Some(Self::Numbered),
+            _ => None,
+        }
+    }
+
+    fn text(&self, index: usize) -> String {
+        match (self, index) {
+            (Self::Numbered, _) => format!("--- page {} ---\n", index + 1),
+            (_, 0) => String::new(),
+            (Self::BlankLine, _) => "\n".to_string(),
+            (Self::FormFeed, _) => "\x0c".to_string(),
+        }
+    }
+}
+
+// what happens to words nobody has checked yet, for publishing the checked parts first
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Unverified {
+    #[default]
+    Keep,
+    // left out, as if the word weren't there
+    Blank,
+    // kept, but in ⟦brackets⟧ so readers know not to trust it
+    Flag,
+}
+
+impl Unverified {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [Self::Keep, Self::Blank, Self::Flag].iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::Keep => "Keep",
+            Self::Blank => "Leave out",
+            Self::Flag => "Flag ⟦like this⟧",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(Self::Keep),
+            "blank" => Some(Self::Blank),
+            "flag" => Some(Self::Flag),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, elt: &OCRElement, text: &str) -> String {
+        match self {
+            Self::Blank if !elt.is_verified() => String::new(),
+            Self::Flag if !elt.is_verified() && !text.is_empty() => format!("⟦{}⟧", text),
+            _ => text.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub reading_order: ReadingOrder,
+    // rejoin words split with a hyphen at the end of a line
+    pub dehyphenate: bool,
+    pub page_separator: PageSeparator,
+    pub unverified: Unverified,
+    // also write <name>.unverified.tsv, the low confidence words nobody has checked
+    pub list_unverified: bool,
+}
+
+// children of id in the order they should be read
+fn ordered_children(
+    tree: &Tree<OCRElement>,
+    id: &InternalID,
+    order: ReadingOrder,
+) -> Vec<InternalID> {
+    let mut children: Vec<InternalID> = tree.children(id).copied().collect();
+    let is_container = tree
+        .get_node(id)
+        .is_some_and(|node| matches!(node.ocr_element_type, OCRClass::Page | OCRClass::CArea));
+    if order == ReadingOrder::Geometric && is_container {
+        let key = |child: &InternalID| {
+            tree.get_node(child)
+                .and_then(|node| node.bbox())
+                .map_or((0.0, 0.0), |bbox| (bbox.top(), bbox.left()))
+        };
+        children.sort_by(|a, b| {
+            key(a)
+                .partial_cmp(&key(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+    children
+}
+
+// paired split words are written once, in their joined form, where the first half is
+fn word_text(elt: &OCRElement) -> Option<&str> {
+    if elt
+        .ocr_properties
+        .contains_key(hyphenation::CONTINUED_PROPERTY)
+    {
+        return None;
+    }
+    match elt.ocr_properties.get(hyphenation::JOINED_PROPERTY) {
+        Some(OCRProperty::Text(joined)) => Some(joined.as_str()),
+        _ => Some(elt.ocr_text.trim()),
+    }
+}
+
+fn line_text(tree: &Tree<OCRElement>, line: InternalID, unverified: Unverified) -> String {
+    tree.descendants(&line)
+        .iter()
+        .filter_map(|id| tree.get_node(id))
+        .filter(|node| node.ocr_element_type == OCRClass::Word)
+        .filter_map(|node| Some(unverified.apply(node, word_text(node)?)))
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// every line under id in reading order, grouped by paragraph
+fn collect_pars(
+    tree: &Tree<OCRElement>,
+    id: &InternalID,
+    order: ReadingOrder,
+    pars: &mut Vec<Vec<InternalID>>,
+) {
+    if let Some(node) = tree.get_node(id) {
+        // separators and photos never have text worth exporting
+        if !node.ocr_element_type.is_textual() {
+            return;
+        }
+        match node.ocr_element_type {
+            OCRClass::Line | OCRClass::Caption => {
+                if pars.is_empty() {
+                    pars.push(Vec::new());
+                }
+                pars.last_mut().unwrap().push(*id);
+            }
+            _ => {
+                if node.ocr_element_type == OCRClass::Par {
+                    pars.push(Vec::new());
+                }
+                for child in ordered_children(tree, id, order) {
+                    collect_pars(tree, &child, order, pars);
+                }
+            }
+        }
+    }
+}
+
+// line ids grouped by paragraph, in the order the text export writes them
+pub fn reading_order_lines(
+    tree: &Tree<OCRElement>,
+    id: InternalID,
+    order: ReadingOrder,
+) -> Vec<Vec<InternalID>> {
+    let mut pars = Vec::new();
+    collect_pars(tree, &id, order, &mut pars);
+    pars
+}
+
+// every word under id in reading order
+pub fn reading_order_words(
+    tree: &Tree<OCRElement>,
+    id: InternalID,
+    order: ReadingOrder,
+) -> Vec<InternalID> {
+    reading_order_lines(tree, id, order)
+        .into_iter()
+        .flatten()
+        .flat_map(|line| tree.descendants(&line))
+        .filter(|id| {
+            tree.get_node(id)
+                .is_some_and(|elt| elt.ocr_element_type == OCRClass::Word)
+        })
+        .collect()
+}
+
+// "exam-" + "ple text" -> "example" + "text"
+fn dehyphenate(lines: &mut [String]) {
+    for i in 0..lines.len().saturating_sub(1) {
+        let ends_hyphenated = {
+            let mut chars = lines[i].chars().rev();
+            chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_alphabetic())
+        };
+        if !ends_hyphenated {
+            continue;
+        }
+        let next = lines[i + 1].clone();
+        let (first_word, rest) = match next.split_once(' ') {
+            Some((first, rest)) => (first.to_string(), rest.to_string()),
+            None => (next.clone(), String::new()),
+        };
+        lines[i].pop();
+        lines[i].push_str(&first_word);
+        lines[i + 1] = rest;
+    }
+}
+
+// the text of each line under id, grouped by paragraph like reading_order_lines; lines
+// left empty (e.g. by dehyphenation) are dropped, and so are paragraphs without any text
+pub fn text_lines(
+    tree: &Tree<OCRElement>,
+    id: InternalID,
+    options: &ExportOptions,
+) -> Vec<Vec<(InternalID, String)>> {
+    reading_order_lines(tree, id, options.reading_order)
+        .into_iter()
+        .map(|par| {
+            let mut lines: Vec<String> = par
+                .iter()
+                .map(|line| line_text(tree, *line, options.unverified))
+                .collect();
+            if options.dehyphenate {
+                dehyphenate(&mut lines);
+            }
+            par.into_iter()
+                .zip(lines)
+                .filter(|(_, line)| !line.is_empty())
+                .collect::<Vec<(InternalID, String)>>()
+        })
+        .filter(|par| !par.is_empty())
+        .collect()
+}
+
+// one output line per ocr_line, with a blank line between paragraphs
+pub fn page_to_text(tree: &Tree<OCRElement>, page: InternalID, options: &ExportOptions) -> String {
+    let mut s = String::new();
+    for par in text_lines(tree, page, options) {
+        if !s.is_empty() {
+            s.push('\n');
+        }
+        for (_, line) in par {
+            s.push_str(&line);
+            s.push('\n');
+        }
+    }
+    s
+}
+
+const TSV_HEADER: &str =
+    "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext";
+
+// one row for id and then its children's. counters holds the page, block, par, line and
+// word numbers of the row being written; starting a row zeroes the numbers below it like
+// tesseract does.
+fn tsv_rows(
+    tree: &Tree<OCRElement>,
+    id: InternalID,
+    options: &ExportOptions,
+    counters: &mut [usize; 5],
+    s: &mut String,
+) {
+    let elt = match tree.get_node(&id) {
+        Some(elt) => elt,
+        None => return,
+    };
+    let level = elt.ocr_element_type.level() as usize;
+    counters[level] += 1;
+    for counter in counters[level + 1..].iter_mut() {
+        *counter = 0;
+    }
+    let (left, top, width, height) = elt.bbox().map_or((0, 0, 0, 0), |bbox| {
+        (
+            bbox.left() as i64,
+            bbox.top() as i64,
+            bbox.width() as i64,
+            bbox.height() as i64,
+        )
+    });
+    let (conf, text) = if elt.ocr_element_type == OCRClass::Word {
+        let conf = elt.wconf().map_or(-1, |wconf| wconf as i64);
+        // a tab or newline in a word would break the table
+        let text = options
+            .unverified
+            .apply(elt, elt.ocr_text.trim())
+            .replace(['\t', '\n', '\r'], " ");
+        (conf, text)
+    } else {
+        (-1, String::new())
+    };
+    s.push_str(&format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        level + 1,
+        counters[0],
+        counters[1],
+        counters[2],
+        counters[3],
+        counters[4],
+        left,
+        top,
+        width,
+        height,
+        conf,
+        text
+    ));
+    for child in ordered_children(tree, &id, options.reading_order) {
+        tsv_rows(tree, child, options, counters, s);
+    }
+}
+
+// page_num is 1-based like tesseract's
+pub fn page_to_tsv(
+    tree: &Tree<OCRElement>,
+    page: InternalID,
+    page_num: usize,
+    options: &ExportOptions,
+) -> String {
+    let mut s = String::new();
+    let mut counters = [page_num - 1, 0, 0, 0, 0];
+    tsv_rows(tree, page, options, &mut counters, &mut s);
+    s
+}
+
+const ALTO_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\">
+  <Description>
+    <MeasurementUnit>pixel</MeasurementUnit>
+  </Description>
+  <Layout>
+";
+
+const ALTO_FOOTER: &str = "  </Layout>
+</alto>
+";
+
+// ALTO's HPOS, VPOS, WIDTH and HEIGHT attributes for a bbox
+fn alto_position(bbox: Option<&Rect>) -> String {
+    bbox.map_or(String::new(), |bbox| {
+        format!(
+            " HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+            bbox.left() as i64,
+            bbox.top() as i64,
+            bbox.width() as i64,
+            bbox.height() as i64
+        )
+    })
+}
+
+// the String for a word, if it isn't left out, with the joined form of a word broken over a line end as
+// SUBS_CONTENT on both halves and the first half's hyphen as a HYP
+fn alto_string(
+    tree: &Tree<OCRElement>,
+    word: &InternalID,
+    id: &str,
+    options: &ExportOptions,
+) -> Option<String> {
+    let elt = tree.get_node(word)?;
+    let joined = |elt: &OCRElement| match elt.ocr_properties.get(hyphenation::JOINED_PROPERTY) {
+        Some(OCRProperty::Text(joined)) => Some(joined.clone()),
+        _ => None,
+    };
+    let (subs, text) = if let Some(joined) = joined(elt) {
+        let text = elt.ocr_text.trim();
+        (
+            Some(("HypPart1", joined)),
+            text.strip_suffix('-').unwrap_or(text),
+        )
+    } else {
+        let first_half = hyphenation::partner(tree, word).and_then(|first| tree.get_node(&first));
+        (
+            first_half
+                .and_then(joined)
+                .map(|joined| ("HypPart2", joined)),
+            elt.ocr_text.trim(),
+        )
+    };
+    let content = options.unverified.apply(elt, text);
+    if content.is_empty() {
+        return None;
+    }
+    let wc = elt.wconf().map_or(String::new(), |wconf| {
+        format!(" WC=\"{:.2}\"", wconf as f32 / 100.0)
+    });
+    let subs_attrs = subs.as_ref().map_or(String::new(), |(kind, joined)| {
+        format!(
+            " SUBS_TYPE=\"{}\" SUBS_CONTENT=\"{}\"",
+            kind,
+            page_xml::escape(joined)
+        )
+    });
+    let mut s = String::new();
+    let _ = writeln!(
+        s,
+        "            <String ID=\"{}\" CONTENT=\"{}\"{}{}{}/>",
+        id,
+        page_xml::escape(&content),
+        wc,
+        subs_attrs,
+        alto_position(elt.bbox())
+    );
+    if matches!(subs, Some(("HypPart1", _))) {
+        let _ = writeln!(s, "            <HYP CONTENT=\"-\"/>");
+    }
+    Some(s)
+}
+
+// one Page; ALTO has no paragraph level, so every paragraph becomes a TextBlock.
+// page_num is 1-based.
+pub fn page_to_alto(
+    tree: &Tree<OCRElement>,
+    page: InternalID,
+    page_num: usize,
+    options: &ExportOptions,
+) -> String {
+    let mut s = String::new();
+    let bbox = tree.get_node(&page).and_then(|elt| elt.bbox()).copied();
+    let (width, height) = bbox.map_or((0, 0), |bbox| (bbox.width() as i64, bbox.height() as i64));
+    let _ = writeln!(
+        s,
+        "    <Page ID=\"page_{}\" PHYSICAL_IMG_NR=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\">",
+        page_num, page_num, width, height
+    );
+    let _ = writeln!(s, "      <PrintSpace{}>", alto_position(bbox.as_ref()));
+    let line_bbox = |line: &InternalID| tree.get_node(line).and_then(|elt| elt.bbox()).copied();
+    for (b, par) in reading_order_lines(tree, page, options.reading_order)
+        .iter()
+        .enumerate()
+    {
+        let block_id = format!("block_{}_{}", page_num, b + 1);
+        let block_bbox = par.iter().filter_map(line_bbox).reduce(|x, y| x.union(y));
+        let _ = writeln!(
+            s,
+            "        <TextBlock ID=\"{}\"{}>",
+            block_id,
+            alto_position(block_bbox.as_ref())
+        );
+        for (l, line) in par.iter().enumerate() {
+            let line_id = format!("line_{}_{}_{}", page_num, b + 1, l + 1);
+            let _ = writeln!(
+                s,
+                "          <TextLine ID=\"{}\"{}>",
+                line_id,
+                alto_position(line_bbox(line).as_ref())
+            );
+            let words = tree.descendants(line).into_iter().filter(|id| {
+                tree.get_node(id)
+                    .is_some_and(|elt| elt.ocr_element_type == OCRClass::Word)
+            });
+            let mut written = 0;
+            for word in words {
+                let id = format!("{}_{}", line_id.replacen("line", "word", 1), written + 1);
+                if let Some(string) = alto_string(tree, &word, &id, options) {
+                    if written > 0 {
+                        let _ = writeln!(s, "            <SP/>");
+                    }
+                    s.push_str(&string);
+                    written += 1;
+                }
+            }
+            let _ = writeln!(s, "          </TextLine>");
+        }
+        let _ = writeln!(s, "        </TextBlock>");
+    }
+    let _ = writeln!(s, "      </PrintSpace>");
+    let _ = writeln!(s, "    </Page>");
+    s
+}
+
+const UNVERIFIED_HEADER: &str = "page\tleft\ttop\twidth\theight\tconf\ttext";
+
+// where the list of unverified regions goes for an export to path: out.txt -> out.unverified.tsv
+pub fn unverified_list_path(path: &Path) -> PathBuf {
+    path.with_extension("unverified.tsv")
+}
+
+// one row per run of low confidence words on a line that nobody has checked, with the box
+// around the run and its lowest confidence; pages are numbered from 1
+pub fn unverified_regions(tree: &Tree<OCRElement>, order: ReadingOrder) -> String {
+    let stripped = ignore::without_ignored(tree);
+    let tree = &*stripped;
+    let mut s = format!("{}\n", UNVERIFIED_HEADER);
+    for (page_index, page) in tree.roots().enumerate() {
+        for line in reading_order_lines(tree, *page, order)
+            .into_iter()
+            .flatten()
+        {
+            let mut runs: Vec<Vec<&OCRElement>> = Vec::new();
+            let mut in_run = false;
+            for id in tree.descendants(&line) {
+                let elt = match tree.get_node(&id) {
+                    Some(elt) if elt.ocr_element_type == OCRClass::Word => elt,
+                    _ => continue,
+                };
+                let doubtful = !elt.is_verified()
+                    && elt
+                        .wconf()
+                        .is_some_and(|wconf| wconf < crate::BAD_WCONF_THRESHOLD);
+                if doubtful {
+                    if !in_run {
+                        runs.push(Vec::new());
+                    }
+                    runs.last_mut().unwrap().push(elt);
+                }
+                in_run = doubtful;
+            }
+            for run in runs {
+                let bbox = match run
+                    .iter()
+                    .filter_map(|elt| elt.bbox().copied())
+                    .reduce(|a, b| a.union(b))
+                {
+                    Some(bbox) => bbox,
+                    None => continue,
+                };
+                let conf = run.iter().filter_map(|elt| elt.wconf()).min().unwrap_or(0);
+                let text = run
+                    .iter()
+                    .map(|elt| elt.ocr_text.trim())
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+                    .replace(['\t', '\n', '\r'], " ");
+                s.push_str(&format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    page_index + 1,
+                    bbox.left() as i64,
+                    bbox.top() as i64,
+                    bbox.width() as i64,
+                    bbox.height() as i64,
+                    conf,
+                    text
+                ));
+            }
+        }
+    }
+    s
+}
+
+pub fn write_pages(
+    tree: &Tree<OCRElement>,
+    format: ExportFormat,
+    options: &ExportOptions,
+    path: &Path,
+    progress: &Progress,
+) -> Result<(), String> {
+    // words in a page's ignore regions are gutter garbage, not text
+    let stripped = ignore::without_ignored(tree);
+    let tree = &*stripped;
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(format.header().as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    // convert a chunk of pages in parallel, then write them in order so memory stays bounded
+    for (chunk_index, chunk) in pages.chunks(PAGES_PER_CHUNK).enumerate() {
+        progress.check()?;
+        let converted: Vec<String> = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(i, page)| {
+                format.export_page(tree, *page, chunk_index * PAGES_PER_CHUNK + i, options)
+            })
+            .collect();
+        for (i, page_str) in converted.into_iter().enumerate() {
+            let separator = format.page_separator(chunk_index * PAGES_PER_CHUNK + i, options);
+            writer
+                .write_all(separator.as_bytes())
+                .and_then(|()| writer.write_all(page_str.as_bytes()))
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        progress.advance(chunk.len());
+    }
+    writer
+        .write_all(format.trailer().as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    writer
+        .flush()
+        .and_then(|()| writer.get_ref().sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// write_pages into a temp file and only replace path once everything is written, so an
+// interrupted export leaves the old file rather than a truncated one
+pub fn write_pages_atomic(
+    tree: &Tree<OCRElement>,
+    format: ExportFormat,
+    options: &ExportOptions,
+    path: &Path,
+    progress: &Progress,
+) -> Result<(), String> {
+    let temp = atomic::temp_path_for(path);
+    let res = write_pages(tree, format, options, &temp, progress)
+        .and_then(|()| atomic::commit(&temp, path));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    res
+}
+
+// export a snapshot of the tree on a background thread; the result is how many pages
+// were written
+pub fn spawn_export(
+    tree: Tree<OCRElement>,
+    format: ExportFormat,
+    options: ExportOptions,
+    path: PathBuf,
+) -> Job<usize> {
+    let name = format!(
+        "Export to {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let total = tree.roots().len();
+    Job::spawn(name, total, move |progress| {
+        write_pages_atomic(&tree, format, &options, &path, progress)?;
+        if options.list_unverified {
+            atomic::write_atomic(
+                &unverified_list_path(&path),
+                unverified_regions(&tree, options.reading_order),
+            )?;
+        }
+        Ok(total)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Tree<OCRElement> {
+        OCRElement::html_to_ocr_tree(scraper::Html::parse_document(
+            r#"<div class="ocr_page" title="bbox 0 0 100 100">
+<div class="ocr_carea" title="bbox 0 0 100 10"><p class="ocr_par" title="bbox 0 0 100 10">
+<span class="ocr_line" title="bbox 0 0 100 10">
+<span class="ocrx_word" title="bbox 0 0 40 10; x_wconf 90">a&amp;b</span>
+<span class="ocrx_word" title="bbox 50 0 100 10">two</span>
+</span></p></div></div>"#,
+        ))
+    }
+
+    #[test]
+    fn tsv_has_a_row_per_element() {
+        let tree = sample();
+        let page = *tree.roots().next().unwrap();
+        let tsv = page_to_tsv(&tree, page, 1, &ExportOptions::default());
+        let rows: Vec<&str> = tsv.lines().collect();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], "1\t1\t0\t0\t0\t0\t0\t0\t100\t100\t-1\t");
+        assert_eq!(rows[4], "5\t1\t1\t1\t1\t1\t0\t0\t40\t10\t90\ta&b");
+        // no confidence is -1, like tesseract's
+        assert_eq!(rows[5], "5\t1\t1\t1\t1\t2\t50\t0\t50\t10\t-1\ttwo");
+    }
+
+    #[test]
+    fn alto_escapes_and_spaces_words() {
+        let tree = sample();
+        let page = *tree.roots().next().unwrap();
+        let alto = page_to_alto(&tree, page, 1, &ExportOptions::default());
+        assert!(alto.contains(r#"<Page ID="page_1" PHYSICAL_IMG_NR="1" WIDTH="100" HEIGHT="100">"#));
+        assert!(alto.contains(
+            r#"<String ID="word_1_1_1_1" CONTENT="a&amp;b" WC="0.90" HPOS="0" VPOS="0" WIDTH="40" HEIGHT="10"/>"#
+        ));
+        assert_eq!(alto.matches("<SP/>").count(), 1);
+        assert_eq!(alto.matches("<TextBlock ").count(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::ocr_element::{OCRClass, OCRElement};
+use crate::tree::Tree;
+use crate::InternalID;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// a kind of entity to look for, e.g. dates; words matching pattern get tinted in color
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Highlighter {
+    pub name: String,
+    pub pattern: String,
+    pub color: [u8; 3],
+    pub enabled: bool,
+}
+
+impl Highlighter {
+    fn new(name: &str, pattern: &str, color: [u8; 3]) -> Self {
+        Highlighter {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            color,
+            enabled: true,
+        }
+    }
+}
+
+pub fn default_highlighters() -> Vec<Highlighter> {
+    vec![
+        // 1901, 12.3.1901, 12/03/01, 1901-03-12
+        Highlighter::new(
+            "Dates",
+            r"^(1[5-9]|20)\d\d$|^\d{1,2}[./-]\d{1,2}[./-]\d{2,4}$|^\d{4}-\d\d-\d\d$",
+            [255, 160, 0],
+        ),
+        // 12.50, 1,000, $3, £4.10
+        Highlighter::new(
+            "Amounts",
+            r"^[$£€]?\d{1,3}([,.]\d{3})*([.,]\d{1,2})?[$£€]?$",
+            [0, 180, 80],
+        ),
+        // MS 123, Add.4567, A-12/3: letters and digits run together
+        Highlighter::new(
+            "Catalogue numbers",
+            r"^[A-Z]{1,4}[.\-]?\d+([./-]\d+)*$",
+            [160, 80, 255],
+        ),
+    ]
+}
+
+// (highlighter index, what's wrong with its pattern)
+pub type PatternError = (usize, String);
+
+// the enabled highlighters with their patterns compiled; bad patterns come back as errors
+// (by highlighter index) so the settings window can point at them
+pub fn compile(highlighters: &[Highlighter]) -> (Vec<(usize, Regex)>, Vec<PatternError>) {
+    let mut compiled = Vec::new();
+    let mut errors = Vec::new();
+    for (i, highlighter) in highlighters.iter().enumerate() {
+        if !highlighter.enabled || highlighter.pattern.is_empty() {
+            continue;
+        }
+        match Regex::new(&highlighter.pattern) {
+            Ok(regex) => compiled.push((i, regex)),
+            Err(e) => errors.push((i, e.to_string())),
+        }
+    }
+    (compiled, errors)
+}
+
+// one word a highlighter matched
+#[derive(Debug, Clone, Copy)]
+pub struct EntityMatch {
+    pub id: InternalID,
+    pub highlighter: usize,
+}
+
+// every word matching one of the patterns, in document order; a word only counts for the
+// first highlighter it matches
+pub fn find_entities(tree: &Tree<OCRElement>, compiled: &[(usize, Regex)]) -> Vec<EntityMatch> {
+    tree.preorder()
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            let text = elt.ocr_text.trim();
+            compiled
+                .iter()
+                .find(|(_, regex)| regex.is_match(text))
+                .map(|(highlighter, _)| EntityMatch {
+                    id,
+                    highlighter: *highlighter,
+                })
+        })
+        .collect()
+}
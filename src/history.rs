@@ -0,0 +1,498 @@
+use crate::ocr_element::{self, OCRElement, WriteOptions};
+use crate::stats;
+use crate::tree::Tree;
+use crate::InternalID;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// how many steps back we keep; every step is a whole copy of the tree
+const MAX_UNDO: usize = 100;
+// and how much memory those copies may take, since a lazily loaded document grows with every
+// page looked at; the last step is kept regardless
+const MAX_UNDO_BYTES: usize = 1 << 30;
+
+// undo/redo as whole-tree copies. Edits call touch() as they happen and the editor commits
+// once the edit is over (the drag is released, the text box loses focus), so a drag or a
+// word typed letter by letter is a single step.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Tree<OCRElement>>,
+    redo: Vec<Tree<OCRElement>>,
+    // what each step did, for the history panel: done[i] took undo[i] to the next state,
+    // undone[i] takes the state before redo[i] to redo[i]
+    done: Vec<String>,
+    undone: Vec<String>,
+    // notes for the document head that each step added (e.g. how confidences were
+    // recalibrated), kept alongside done and undone so they come and go with their step
+    done_notes: Vec<Vec<String>>,
+    undone_notes: Vec<Vec<String>>,
+    // notes of the pending step, and of steps too old to undo any more
+    pending_notes: Vec<String>,
+    dropped_notes: Vec<String>,
+    // the tree as of the last commit
+    committed: Tree<OCRElement>,
+    // something changed since the last commit
+    pending: bool,
+    // the oldest steps have been forgotten, so undo can't go all the way back
+    dropped: bool,
+    // the tree as it was opened, kept even when the oldest steps are dropped
+    opened: Tree<OCRElement>,
+    // stats::tree_bytes of each tree kept, alongside undo, redo, committed and opened
+    undo_bytes: Vec<usize>,
+    redo_bytes: Vec<usize>,
+    committed_bytes: usize,
+    opened_bytes: usize,
+    // goes up with every commit, undo and redo, so views of the tree know to refresh
+    version: u64,
+}
+
+impl History {
+    // forget everything, e.g. when a new file is opened
+    pub fn reset(&mut self, tree: &Tree<OCRElement>) {
+        self.undo.clear();
+        self.redo.clear();
+        self.done.clear();
+        self.undone.clear();
+        self.done_notes.clear();
+        self.undone_notes.clear();
+        self.pending_notes.clear();
+        self.dropped_notes.clear();
+        self.undo_bytes.clear();
+        self.redo_bytes.clear();
+        self.committed = tree.clone();
+        self.opened = tree.clone();
+        self.committed_bytes = stats::tree_bytes(tree);
+        self.opened_bytes = self.committed_bytes;
+        self.pending = false;
+        self.dropped = false;
+        self.version += 1;
+    }
+
+    // every tree kept with its size, current state first
+    fn trees_mut(&mut self) -> impl Iterator<Item = (&mut Tree<OCRElement>, &mut usize)> {
+        std::iter::once((&mut self.committed, &mut self.committed_bytes))
+            .chain(std::iter::once((&mut self.opened, &mut self.opened_bytes)))
+            .chain(self.undo.iter_mut().zip(self.undo_bytes.iter_mut()))
+            .chain(self.redo.iter_mut().zip(self.redo_bytes.iter_mut()))
+    }
+
+    // roughly how much memory the kept trees take, on top of the one being edited
+    pub fn memory_bytes(&self) -> usize {
+        self.committed_bytes
+            + self.opened_bytes
+            + self.undo_bytes.iter().sum::<usize>()
+            + self.redo_bytes.iter().sum::<usize>()
+    }
+
+    // the lowest id that none of the kept trees have handed out, so nodes added now can't
+    // collide with ones that only exist in a state that can be redone
+    pub fn next_id(&self) -> InternalID {
+        std::iter::once(&self.committed)
+            .chain(self.undo.iter())
+            .chain(self.redo.iter())
+            .map(|tree| tree.next_id())
+            .fold(self.opened.next_id(), InternalID::max)
+    }
+
+    // a page of a lazily loaded document was just parsed into tree: give every kept state the
+    // same subtree, so undoing and redoing neither throw it away nor unload it
+    pub fn page_loaded(&mut self, tree: &Tree<OCRElement>, page: &InternalID) {
+        let page_bytes = stats::subtree_bytes(tree, page);
+        for (kept, bytes) in self.trees_mut() {
+            if kept.get_node(page).is_some() && !kept.has_children(page) {
+                match kept.copy_children_from(page, tree) {
+                    Ok(()) => *bytes += page_bytes,
+                    Err(e) => println!("{}", e),
+                }
+            }
+        }
+        self.version += 1;
+    }
+
+    pub fn touch(&mut self) {
+        self.pending = true;
+    }
+
+    // touch, and have the step add note to the document head
+    pub fn note(&mut self, note: String) {
+        self.pending = true;
+        self.pending_notes.push(note);
+    }
+
+    // the notes of every step in effect, oldest first
+    pub fn notes(&self) -> impl Iterator<Item = &String> {
+        self.dropped_notes
+            .iter()
+            .chain(self.done_notes.iter().flatten())
+            .chain(self.pending_notes.iter())
+    }
+
+    pub fn commit(&mut self, tree: &Tree<OCRElement>) {
+        if !self.pending {
+            return;
+        }
+        self.done.push(describe_change(&self.committed, tree));
+        self.done_notes.push(mem::take(&mut self.pending_notes));
+        self.undo
+            .push(mem::replace(&mut self.committed, tree.clone()));
+        self.undo_bytes.push(mem::replace(
+            &mut self.committed_bytes,
+            stats::tree_bytes(tree),
+        ));
+        while self.undo.len() > MAX_UNDO
+            || (self.undo.len() > 1 && self.undo_bytes.iter().sum::<usize>() > MAX_UNDO_BYTES)
+        {
+            self.undo.remove(0);
+            self.undo_bytes.remove(0);
+            self.done.remove(0);
+            let notes = self.done_notes.remove(0);
+            self.dropped_notes.extend(notes);
+            self.dropped = true;
+        }
+        self.redo.clear();
+        self.redo_bytes.clear();
+        self.undone.clear();
+        self.undone_notes.clear();
+        self.pending = false;
+        self.version += 1;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    // the tree as of the last commit, to compare the pending step against
+    pub fn committed(&self) -> &Tree<OCRElement> {
+        &self.committed
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.pending || !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // the tree to go back to, if there is one
+    pub fn undo(&mut self, tree: &Tree<OCRElement>) -> Option<Tree<OCRElement>> {
+        self.commit(tree);
+        let prev = self.undo.pop()?;
+        let prev_bytes = self.undo_bytes.pop().unwrap_or_default();
+        self.undone.extend(self.done.pop());
+        self.undone_notes.extend(self.done_notes.pop());
+        self.redo
+            .push(mem::replace(&mut self.committed, prev.clone()));
+        self.redo_bytes
+            .push(mem::replace(&mut self.committed_bytes, prev_bytes));
+        self.version += 1;
+        Some(prev)
+    }
+
+    pub fn redo(&mut self) -> Option<Tree<OCRElement>> {
+        let next = self.redo.pop()?;
+        let next_bytes = self.redo_bytes.pop().unwrap_or_default();
+        self.done.extend(self.undone.pop());
+        self.done_notes.extend(self.undone_notes.pop());
+        self.undo
+            .push(mem::replace(&mut self.committed, next.clone()));
+        self.undo_bytes
+            .push(mem::replace(&mut self.committed_bytes, next_bytes));
+        self.version += 1;
+        Some(next)
+    }
+
+    // every step that can be undone, oldest first, and every one that can be redone, next
+    // first
+    pub fn steps(&self) -> (&[String], impl Iterator<Item = &String>) {
+        (&self.done, self.undone.iter().rev())
+    }
+
+    // how many steps back the current state is from the oldest one kept
+    pub fn position(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn dropped(&self) -> bool {
+        self.dropped
+    }
+
+    pub fn opened(&self) -> &Tree<OCRElement> {
+        &self.opened
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+// what to call an element in the panel: its id in the file, or the one it'd be saved with
+fn name(tree: &Tree<OCRElement>, id: &InternalID, written: &HashMap<InternalID, String>) -> String {
+    tree.get_node(id)
+        .and_then(|elt| elt.source_id.clone())
+        .or_else(|| written.get(id).cloned())
+        .unwrap_or_else(|| format!("element {}", id))
+}
+
+fn written_names(tree: &Tree<OCRElement>) -> HashMap<InternalID, String> {
+    ocr_element::written_ids(tree, &WriteOptions::default())
+        .into_iter()
+        .map(|(name, id)| (id, name))
+        .collect()
+}
+
+fn one_or_many(ids: &[InternalID], one: impl FnOnce(&InternalID) -> String, many: &str) -> String {
+    match ids {
+        [id] => one(id),
+        _ => format!("{} {} elements", many, ids.len()),
+    }
+}
+
+// everything but the bbox, in a stable order
+fn other_properties(elt: &OCRElement) -> Vec<String> {
+    let mut props: Vec<String> = elt
+        .ocr_properties
+        .iter()
+        .filter(|(name, _)| name.as_str() != "bbox")
+        .map(|(name, prop)| format!("{} {}", name, prop.to_str()))
+        .collect();
+    props.sort();
+    props
+}
+
+// every element of after that's new or different from before, in document order, with what
+// changed ("text, bbox"), and how many of before's elements are gone
+pub fn edited_elements(
+    before: &Tree<OCRElement>,
+    after: &Tree<OCRElement>,
+) -> (Vec<(InternalID, String)>, usize) {
+    let mut edited = Vec::new();
+    for id in after.preorder() {
+        let new = match after.get_node(&id) {
+            Some(new) => new,
+            None => continue,
+        };
+        let old = match before.get_node(&id) {
+            Some(old) => old,
+            None => {
+                edited.push((id, "added".to_string()));
+                continue;
+            }
+        };
+        let mut what = Vec::new();
+        if before.parent(&id) != after.parent(&id) {
+            what.push("moved");
+        }
+        if old.ocr_element_type != new.ocr_element_type {
+            what.push("type");
+        }
+        if old.ocr_text != new.ocr_text {
+            what.push("text");
+        }
+        if old.bbox() != new.bbox() {
+            what.push("bbox");
+        }
+        if other_properties(old) != other_properties(new) || old.ocr_lang != new.ocr_lang {
+            what.push("properties");
+        }
+        if !what.is_empty() {
+            edited.push((id, what.join(", ")));
+        }
+    }
+    let deleted = before
+        .preorder()
+        .into_iter()
+        .filter(|id| after.get_node(id).is_none())
+        .count();
+    (edited, deleted)
+}
+
+// a short description of the edit that turned before into after, worked out by comparing
+// the two so edits don't have to describe themselves
+pub fn describe_change(before: &Tree<OCRElement>, after: &Tree<OCRElement>) -> String {
+    let before_ids: HashSet<InternalID> = before.preorder().into_iter().collect();
+    let after_ids: HashSet<InternalID> = after.preorder().into_iter().collect();
+    // only the top of a removed or added subtree counts
+    let removed: Vec<InternalID> = before
+        .preorder()
+        .into_iter()
+        .filter(|id| !after_ids.contains(id))
+        .filter(|id| {
+            before
+                .parent(id)
+                .is_none_or(|parent| after_ids.contains(&parent))
+        })
+        .collect();
+    let added: Vec<InternalID> = after
+        .preorder()
+        .into_iter()
+        .filter(|id| !before_ids.contains(id))
+        .filter(|id| {
+            after
+                .parent(id)
+                .is_none_or(|parent| before_ids.contains(&parent))
+        })
+        .collect();
+    let (mut moved, mut retyped, mut retexted, mut resized, mut other) =
+        (vec![], vec![], vec![], vec![], vec![]);
+    for id in after.preorder() {
+        let (old, new) = match (before.get_node(&id), after.get_node(&id)) {
+            (Some(old), Some(new)) => (old, new),
+            _ => continue,
+        };
+        if before.parent(&id) != after.parent(&id) {
+            moved.push(id);
+        }
+        if old.ocr_element_type != new.ocr_element_type {
+            retyped.push(id);
+        }
+        if old.ocr_text != new.ocr_text {
+            retexted.push(id);
+        }
+        if old.bbox() != new.bbox() {
+            resized.push(id);
+        }
+        if other_properties(old) != other_properties(new) || old.ocr_lang != new.ocr_lang {
+            other.push(id);
+        }
+    }
+    let before_names = written_names(before);
+    let after_names = written_names(after);
+    let old_name = |id: &InternalID| name(before, id, &before_names);
+    let new_name = |id: &InternalID| name(after, id, &after_names);
+    if let ([gone], [first, ..]) = (&removed[..], &moved[..]) {
+        if let Some(target) = after.parent(first) {
+            return format!("Merged {} into {}", old_name(gone), new_name(&target));
+        }
+    }
+    if let ([wrapper], false) = (&added[..], moved.is_empty()) {
+        return format!(
+            "Grouped {} elements into {}",
+            moved.len(),
+            new_name(wrapper)
+        );
+    }
+    if !removed.is_empty() {
+        return one_or_many(
+            &removed,
+            |id| format!("Deleted {}", old_name(id)),
+            "Deleted",
+        );
+    }
+    if !added.is_empty() {
+        return one_or_many(&added, |id| format!("Added {}", new_name(id)), "Added");
+    }
+    if !moved.is_empty() {
+        return one_or_many(&moved, |id| format!("Moved {}", new_name(id)), "Moved");
+    }
+    if !retyped.is_empty() {
+        return one_or_many(
+            &retyped,
+            |id| {
+                let class = after
+                    .get_node(id)
+                    .map_or(String::new(), |elt| elt.ocr_element_type.to_user_str());
+                format!("Made {} a {}", old_name(id), class.to_lowercase())
+            },
+            "Changed the type of",
+        );
+    }
+    if !retexted.is_empty() {
+        return one_or_many(
+            &retexted,
+            |id| {
+                let text: String = after
+                    .get_node(id)
+                    .map_or(String::new(), |elt| elt.ocr_text.chars().take(30).collect());
+                format!("Changed text of {} to \"{}\"", new_name(id), text)
+            },
+            "Changed text of",
+        );
+    }
+    if !resized.is_empty() {
+        return one_or_many(
+            &resized,
+            |id| format!("Resized {} bbox", new_name(id)),
+            "Resized",
+        );
+    }
+    if !other.is_empty() {
+        return one_or_many(
+            &other,
+            |id| format!("Changed properties of {}", new_name(id)),
+            "Changed properties of",
+        );
+    }
+    "Edit".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(text: &str) -> Tree<OCRElement> {
+        let mut tree = Tree::new();
+        tree.add_root(OCRElement {
+            ocr_text: text.to_string(),
+            ..Default::default()
+        });
+        tree
+    }
+
+    fn text(tree: &Tree<OCRElement>) -> &str {
+        &tree.get_node(&0).unwrap().ocr_text
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_steps() {
+        let mut history = History::default();
+        history.reset(&page("a"));
+        // nothing touched, nothing to commit
+        history.commit(&page("b"));
+        assert!(!history.can_undo());
+        history.touch();
+        history.commit(&page("b"));
+        history.touch();
+        let undone = history.undo(&page("c")).unwrap();
+        assert_eq!(text(&undone), "b");
+        assert_eq!(text(&history.undo(&undone).unwrap()), "a");
+        assert!(history.undo(&page("a")).is_none());
+        assert_eq!(text(&history.redo().unwrap()), "b");
+        assert_eq!(history.steps().1.count(), 1);
+        // a new step drops what could be redone
+        history.touch();
+        history.commit(&page("d"));
+        assert!(!history.can_redo());
+        assert_eq!(history.position(), 2);
+    }
+
+    #[test]
+    fn notes_outlive_dropped_steps() {
+        let mut history = History::default();
+        history.reset(&page("0"));
+        history.note("first".to_string());
+        history.commit(&page("1"));
+        for i in 2..=MAX_UNDO + 5 {
+            history.touch();
+            history.commit(&page(&i.to_string()));
+        }
+        assert!(history.dropped());
+        assert_eq!(history.position(), MAX_UNDO);
+        assert_eq!(history.notes().collect::<Vec<_>>(), ["first"]);
+        // the opened tree is kept all the same
+        assert_eq!(text(history.opened()), "0");
+    }
+
+    #[test]
+    fn notes_come_and_go_with_their_step() {
+        let mut history = History::default();
+        history.reset(&page("a"));
+        history.note("recalibrated".to_string());
+        history.commit(&page("b"));
+        assert_eq!(history.notes().count(), 1);
+        history.undo(&page("b"));
+        assert_eq!(history.notes().count(), 0);
+        history.redo();
+        assert_eq!(history.notes().count(), 1);
+    }
+}
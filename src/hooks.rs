@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+// wrap s in single quotes so the shell sees it as one word
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+// run the user's post-save command with {file} replaced by the saved path
+// this runs on its own thread so a slow upload or PDF build doesn't freeze the editor
+pub fn run_post_save(command: &str, path: &Path) {
+    if command.trim().is_empty() {
+        return;
+    }
+    let path_str = path.display().to_string();
+    let quoted = if cfg!(windows) {
+        format!("\"{}\"", path_str)
+    } else {
+        shell_quote(&path_str)
+    };
+    let command = command.replace("{file}", &quoted);
+    thread::spawn(move || match shell_command(&command).output() {
+        Ok(output) => {
+            if !output.status.success() {
+                println!(
+                    "post-save command `{}` failed with {}: {}",
+                    command,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+        Err(e) => println!("Couldn't run post-save command `{}`: {}", command, e),
+    });
+}
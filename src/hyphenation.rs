@@ -0,0 +1,158 @@
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+
+// the joined form lives on the first half as x_joined "example",
+// the second half is marked with x_continued 1 so exports can skip it
+pub const JOINED_PROPERTY: &str = "x_joined";
+pub const CONTINUED_PROPERTY: &str = "x_continued";
+
+// a word broken over a line end, e.g. "exam-" / "ple"
+#[derive(Debug, Clone)]
+pub struct SplitWord {
+    pub first: InternalID,
+    pub second: InternalID,
+    // what the word should read as; editable before pairing
+    pub joined: String,
+    pub checked: bool,
+}
+
+fn ends_hyphenated(text: &str) -> bool {
+    let mut chars = text.trim().chars().rev();
+    chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_alphabetic())
+}
+
+fn words_of(tree: &Tree<OCRElement>, line: &InternalID) -> Vec<InternalID> {
+    tree.descendants(line)
+        .into_iter()
+        .filter(|id| {
+            tree.get_node(id)
+                .is_some_and(|node| node.ocr_element_type == OCRClass::Word)
+        })
+        .collect()
+}
+
+pub fn is_paired(elt: &OCRElement) -> bool {
+    elt.ocr_properties.contains_key(JOINED_PROPERTY)
+        || elt.ocr_properties.contains_key(CONTINUED_PROPERTY)
+}
+
+fn lines(tree: &Tree<OCRElement>) -> Vec<InternalID> {
+    tree.preorder()
+        .into_iter()
+        .filter(|id| {
+            tree.get_node(id)
+                .is_some_and(|node| node.ocr_element_type == OCRClass::Line)
+        })
+        .collect()
+}
+
+// the other half of a paired word: the first word of the next line for the x_joined half,
+// the last word of the line before for the x_continued half
+pub fn partner(tree: &Tree<OCRElement>, word: &InternalID) -> Option<InternalID> {
+    let elt = tree.get_node(word)?;
+    let joined = elt.ocr_properties.contains_key(JOINED_PROPERTY);
+    if !joined && !elt.ocr_properties.contains_key(CONTINUED_PROPERTY) {
+        return None;
+    }
+    let line = tree.ancestors(word).into_iter().find(|id| {
+        tree.get_node(id)
+            .is_some_and(|node| node.ocr_element_type == OCRClass::Line)
+    })?;
+    let lines = lines(tree);
+    let i = lines.iter().position(|id| *id == line)?;
+    if joined {
+        words_of(tree, lines.get(i + 1)?).first().copied()
+    } else {
+        words_of(tree, &lines[i.checked_sub(1)?]).last().copied()
+    }
+}
+
+// every unpaired "word-" at the end of a line followed by the first word of the next line
+pub fn find_split_words(tree: &Tree<OCRElement>) -> Vec<SplitWord> {
+    let lines = lines(tree);
+    let mut found = Vec::new();
+    for pair in lines.windows(2) {
+        let (first, second) = match (
+            words_of(tree, &pair[0]).last(),
+            words_of(tree, &pair[1]).first(),
+        ) {
+            (Some(first), Some(second)) => (*first, *second),
+            _ => continue,
+        };
+        let (first_elt, second_elt) = match (tree.get_node(&first), tree.get_node(&second)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        if !ends_hyphenated(&first_elt.ocr_text) || is_paired(first_elt) || is_paired(second_elt) {
+            continue;
+        }
+        let head = first_elt.ocr_text.trim().trim_end_matches('-');
+        found.push(SplitWord {
+            first,
+            second,
+            joined: format!("{}{}", head, second_elt.ocr_text.trim()),
+            checked: true,
+        });
+    }
+    found
+}
+
+// store the logical word; both physical words stay where they are
+pub fn pair(tree: &mut Tree<OCRElement>, split: &SplitWord) {
+    if let Some(first) = tree.get_mut_node(&split.first) {
+        first.ocr_properties.insert(
+            JOINED_PROPERTY.to_string(),
+            OCRProperty::Text(split.joined.clone()),
+        );
+    }
+    if let Some(second) = tree.get_mut_node(&split.second) {
+        second
+            .ocr_properties
+            .insert(CONTINUED_PROPERTY.to_string(), OCRProperty::UInt(1));
+    }
+}
+
+// the other half, if it is paired back with word
+fn paired_partner(tree: &Tree<OCRElement>, word: &InternalID) -> Option<InternalID> {
+    let other = partner(tree, word)?;
+    (partner(tree, &other) == Some(*word)).then_some(other)
+}
+
+fn strip_pairing(tree: &mut Tree<OCRElement>, word: &InternalID) {
+    if let Some(elt) = tree.get_mut_node(word) {
+        elt.ocr_properties.remove(JOINED_PROPERTY);
+        elt.ocr_properties.remove(CONTINUED_PROPERTY);
+    }
+}
+
+// the opposite of pair: both halves go back to being words of their own
+pub fn unpair(tree: &mut Tree<OCRElement>, word: &InternalID) {
+    if let Some(other) = paired_partner(tree, word) {
+        strip_pairing(tree, &other);
+    }
+    strip_pairing(tree, word);
+}
+
+// unpair every pair whose other half is gone or no longer at the end of the neighbouring
+// line, or where either half was moved or retyped since before, so the joined form never
+// goes stale and a continued half is never left out of exports on its own. Returns how many
+// words were unpaired
+pub fn unpair_edited(before: &Tree<OCRElement>, after: &mut Tree<OCRElement>) -> usize {
+    let edited = |half: &InternalID| match (before.get_node(half), after.get_node(half)) {
+        (Some(old), Some(new)) => {
+            old.ocr_text != new.ocr_text || before.parent(half) != after.parent(half)
+        }
+        _ => true,
+    };
+    let broken: Vec<InternalID> = after
+        .preorder()
+        .into_iter()
+        .filter(|id| after.get_node(id).is_some_and(is_paired))
+        .filter(|id| paired_partner(after, id).is_none_or(|other| edited(id) || edited(&other)))
+        .collect();
+    for id in &broken {
+        strip_pairing(after, id);
+    }
+    broken.len()
+}
@@ -0,0 +1,87 @@
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::search;
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::{Pos2, Rect};
+use std::borrow::Cow;
+
+// "x_ignore x0 y0 x1 y1 ..." on a page: gutters, edges and binding shadows whose words are
+// OCR garbage. Those words are left out of exports and listed for deletion.
+pub const IGNORE_PROPERTY: &str = "x_ignore";
+
+pub fn ignore_regions(page: &OCRElement) -> Vec<Rect> {
+    match page.ocr_properties.get(IGNORE_PROPERTY) {
+        Some(OCRProperty::Floats(values)) => values
+            .chunks_exact(4)
+            .map(|v| Rect::from_min_max(Pos2::new(v[0], v[1]), Pos2::new(v[2], v[3])))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn set_ignore_regions(page: &mut OCRElement, regions: &[Rect]) {
+    if regions.is_empty() {
+        page.ocr_properties.remove(IGNORE_PROPERTY);
+        return;
+    }
+    let values = regions
+        .iter()
+        .flat_map(|rect| [rect.min.x, rect.min.y, rect.max.x, rect.max.y])
+        .map(f32::round)
+        .collect();
+    page.ocr_properties
+        .insert(IGNORE_PROPERTY.to_string(), OCRProperty::Floats(values));
+}
+
+// words lying entirely inside one of their page's ignore regions, in document order
+pub fn ignored_words(tree: &Tree<OCRElement>) -> Vec<InternalID> {
+    let mut ignored = Vec::new();
+    for page in tree.roots() {
+        let regions = match tree.get_node(page).map(ignore_regions) {
+            Some(regions) if !regions.is_empty() => regions,
+            _ => continue,
+        };
+        for id in tree.descendants(page) {
+            let inside = tree.get_node(&id).is_some_and(|elt| {
+                elt.ocr_element_type == OCRClass::Word
+                    && elt.bbox().is_some_and(|bbox| {
+                        regions.iter().any(|region| region.contains_rect(*bbox))
+                    })
+            });
+            if inside {
+                ignored.push(id);
+            }
+        }
+    }
+    ignored
+}
+
+pub fn is_ignored(tree: &Tree<OCRElement>, id: InternalID) -> bool {
+    let bbox = match tree.get_node(&id) {
+        Some(elt) if elt.ocr_element_type == OCRClass::Word => match elt.bbox() {
+            Some(bbox) => *bbox,
+            None => return false,
+        },
+        _ => return false,
+    };
+    tree.get_node(&search::page_of(tree, id))
+        .is_some_and(|page| {
+            ignore_regions(page)
+                .iter()
+                .any(|region| region.contains_rect(bbox))
+        })
+}
+
+// the tree as exports should see it: without the ignored words. Only copied when there are
+// some.
+pub fn without_ignored(tree: &Tree<OCRElement>) -> Cow<'_, Tree<OCRElement>> {
+    let ignored = ignored_words(tree);
+    if ignored.is_empty() {
+        return Cow::Borrowed(tree);
+    }
+    let mut stripped = tree.clone();
+    for id in ignored {
+        stripped.delete_node(&id);
+    }
+    Cow::Owned(stripped)
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// a file waiting in the watched folder
+#[derive(Debug, Clone)]
+pub struct InboxEntry {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+// polls a folder for hOCR files as an OCR batch writes them
+#[derive(Debug, Default)]
+pub struct Inbox {
+    pub entries: Vec<InboxEntry>,
+    pub opened: HashSet<PathBuf>,
+    last_scan: Option<Instant>,
+}
+
+fn is_hocr(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ["html", "xml", "hocr"].contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+impl Inbox {
+    // rescan the folder if it's been long enough, returns whether anything new showed up
+    pub fn poll(&mut self, folder: &Path) -> bool {
+        if let Some(last) = self.last_scan {
+            if last.elapsed() < POLL_INTERVAL {
+                return false;
+            }
+        }
+        self.last_scan = Some(Instant::now());
+        let mut entries: Vec<InboxEntry> = match read_dir(folder) {
+            Ok(dir) => dir
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_hocr(&entry.path()))
+                .filter_map(|entry| {
+                    let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                    Some(InboxEntry {
+                        path: entry.path(),
+                        modified,
+                    })
+                })
+                .collect(),
+            Err(e) => {
+                println!("Couldn't read watch folder {}: {}", folder.display(), e);
+                Vec::new()
+            }
+        };
+        // newest first
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.modified));
+        let changed = entries.len() != self.entries.len()
+            || entries
+                .iter()
+                .zip(self.entries.iter())
+                .any(|(a, b)| a.path != b.path);
+        self.entries = entries;
+        changed
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.clear();
+        self.last_scan = None;
+    }
+
+    pub fn unopened_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| !self.opened.contains(&entry.path))
+            .count()
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// what a cancelled job's result says
+pub const CANCELLED: &str = "Cancelled";
+
+// how far a background job has got, shared with the thread doing it. The work checks
+// is_cancelled between steps and gives up with CANCELLED.
+#[derive(Debug, Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    // 0 if there's no telling how long it'll take
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Progress {
+            total: AtomicUsize::new(total),
+            ..Default::default()
+        }
+    }
+
+    pub fn advance(&self, steps: usize) {
+        self.done.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    // for work that only finds out how big it is once it has started
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    // None for work that can't say
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total() {
+            0 => None,
+            total => Some(self.done() as f32 / total as f32),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // for the work itself: Err(CANCELLED) once the user has asked it to stop
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// work running on another thread so the frame loop never waits on it; the UI polls
+// take_result every frame
+#[derive(Debug)]
+pub struct Job<T> {
+    // what the jobs panel calls it, e.g. "Export to book.txt"
+    pub name: String,
+    pub progress: Arc<Progress>,
+    result: Arc<Mutex<Option<Result<T, String>>>>,
+}
+
+impl<T: Send + 'static> Job<T> {
+    pub fn spawn(
+        name: impl Into<String>,
+        total: usize,
+        work: impl FnOnce(&Progress) -> Result<T, String> + Send + 'static,
+    ) -> Self {
+        let job = Job {
+            name: name.into(),
+            progress: Arc::new(Progress::new(total)),
+            result: Arc::new(Mutex::new(None)),
+        };
+        let progress = job.progress.clone();
+        let result = job.result.clone();
+        thread::spawn(move || {
+            let res = work(&progress);
+            *result.lock().unwrap() = Some(res);
+        });
+        job
+    }
+
+    // the result once, when it's ready
+    pub fn take_result(&self) -> Option<Result<T, String>> {
+        self.result.lock().unwrap().take()
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+}
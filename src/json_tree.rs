@@ -0,0 +1,167 @@
+use crate::atomic;
+use crate::ocr_element::{self, OCRClass, OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+// the whole tree as plain json, for scripts that would rather not parse hOCR:
+//
+//     {"pages": [{"class": "ocr_page", "tag": "div", "lang": null,
+//                 "properties": {"bbox": [0, 0, 2480, 3508], "ppageno": 0},
+//                 "text": "", "children": [...]}]}
+//
+// bboxes, baselines and scan_res are arrays of numbers, counts and sizes are numbers and
+// everything else is a string, the way it would be written in the title.
+
+fn property_to_json(prop: &OCRProperty) -> Value {
+    match prop {
+        OCRProperty::BBox(bbox) => json!([bbox.min.x, bbox.min.y, bbox.max.x, bbox.max.y]),
+        OCRProperty::Image(path) => json!(path),
+        OCRProperty::Float(f) => json!(f),
+        OCRProperty::UInt(i) => json!(i),
+        OCRProperty::Baseline(slope, offset) => json!([slope, offset]),
+        OCRProperty::ScanRes(x, y) => json!([x, y]),
+        OCRProperty::Text(text) => json!(text),
+        OCRProperty::Floats(values) => json!(values),
+    }
+}
+
+// back to the text after the name in a title, so hOCR's own parsing can be reused
+fn json_to_suffix(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(json_to_suffix)
+            .collect::<Vec<String>>()
+            .join(" "),
+        other => other.to_string(),
+    }
+}
+
+fn node_to_json(tree: &Tree<OCRElement>, id: &InternalID) -> Value {
+    let elt = match tree.get_node(id) {
+        Some(elt) => elt,
+        None => return Value::Null,
+    };
+    // sorted so the same tree always gives the same file
+    let mut names: Vec<&String> = elt.ocr_properties.keys().collect();
+    names.sort();
+    let properties: Map<String, Value> = names
+        .into_iter()
+        .map(|name| (name.clone(), property_to_json(&elt.ocr_properties[name])))
+        .collect();
+    let children: Vec<Value> = tree
+        .children(id)
+        .map(|child| node_to_json(tree, child))
+        .collect();
+    json!({
+        "class": elt.class_name(),
+        "tag": elt.html_element_type,
+        "lang": elt.ocr_lang,
+        "properties": properties,
+        "text": elt.ocr_text,
+        "children": children,
+    })
+}
+
+pub fn to_json(tree: &Tree<OCRElement>) -> String {
+    let pages: Vec<Value> = tree.roots().map(|page| node_to_json(tree, page)).collect();
+    serde_json::to_string_pretty(&json!({ "pages": pages })).unwrap_or_default()
+}
+
+pub fn write(tree: &Tree<OCRElement>, path: &Path) -> Result<(), String> {
+    atomic::write_atomic(path, to_json(tree))
+}
+
+fn json_to_element(value: &Value) -> Result<OCRElement, String> {
+    let class_name = value
+        .get("class")
+        .and_then(|class| class.as_str())
+        .ok_or("element without a class")?;
+    let class: OCRClass = class_name
+        .parse()
+        .map_err(|_| format!("unknown class {}", class_name))?;
+    let mut properties = HashMap::new();
+    if let Some(Value::Object(props)) = value.get("properties") {
+        for (name, prop) in props {
+            // free text is the plain string here, but quoted and escaped in a title
+            let suffix = match prop {
+                Value::String(text) if ocr_element::is_text_property(name) => {
+                    ocr_element::quote(text)
+                }
+                _ => json_to_suffix(prop),
+            };
+            match OCRProperty::parse_property(name, &suffix) {
+                Some(prop) => {
+                    properties.insert(name.clone(), prop);
+                }
+                // a short or mangled array would leave the element without its geometry
+                None if matches!(name.as_str(), "bbox" | "baseline" | "scan_res") => {
+                    return Err(format!("bad {} {} on a {}", name, prop, class_name))
+                }
+                None => println!("Skipping property {} {} on a {}", name, prop, class_name),
+            }
+        }
+    }
+    if !properties.contains_key("bbox") {
+        return Err(format!("{} without a bbox", class_name));
+    }
+    let tag = match value.get("tag").and_then(|tag| tag.as_str()) {
+        Some(tag) => tag.to_string(),
+        None if class.level() < 3 => "div".to_string(),
+        None => "span".to_string(),
+    };
+    Ok(OCRElement {
+        html_element_type: tag,
+        ocr_element_type: class,
+        ocr_properties: properties,
+        ocr_text: value
+            .get("text")
+            .and_then(|text| text.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        ocr_lang: value
+            .get("lang")
+            .and_then(|lang| lang.as_str())
+            .map(|lang| lang.to_string()),
+        legacy_class: OCRClass::is_legacy(class_name).then(|| class_name.to_string()),
+        ..Default::default()
+    })
+}
+
+fn add_children(
+    tree: &mut Tree<OCRElement>,
+    parent: &InternalID,
+    value: &Value,
+) -> Result<(), String> {
+    if let Some(Value::Array(children)) = value.get("children") {
+        for child in children {
+            let id = tree.push_child(parent, json_to_element(child)?)?;
+            add_children(tree, &id, child)?;
+        }
+    }
+    Ok(())
+}
+
+// the tree to_json wrote, or one a script made up in the same shape
+pub fn parse(source: &str) -> Result<Tree<OCRElement>, String> {
+    let value: Value = serde_json::from_str(source).map_err(|e| e.to_string())?;
+    let pages = match value.get("pages") {
+        Some(Value::Array(pages)) => pages,
+        _ => return Err("expected an object with a \"pages\" list".to_string()),
+    };
+    let mut tree = Tree::new();
+    for page in pages {
+        let id = tree.add_root(json_to_element(page)?);
+        add_children(&mut tree, &id, page)?;
+    }
+    Ok(tree)
+}
+
+pub fn file_is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
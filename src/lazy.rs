@@ -0,0 +1,327 @@
+use crate::atomic;
+use crate::ocr_element::{self, OCRElement, WriteOptions, OCR_PAGE_SELECTOR};
+use crate::tree::Tree;
+use crate::InternalID;
+use scraper::Html;
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::ops::Range;
+use std::path::Path;
+
+// a document whose pages only get parsed when they're first looked at
+// we scan the file once for the byte range of each ocr_page, keep the page roots in the tree,
+// and fill in a page's subtree from its range on first access. The source stays in memory so
+// saving over the opened file doesn't pull the bytes out from under the ranges.
+#[derive(Debug)]
+pub struct LazyDocument {
+    source: String,
+    // everything before the first page (doctype, head, ...)
+    prefix: String,
+    ranges: HashMap<InternalID, Range<usize>>,
+    // whatever sits between the previous page and this one in the file
+    gaps: HashMap<InternalID, Range<usize>>,
+    suffix_start: usize,
+    loaded: HashSet<InternalID>,
+    // each page as opened (just the root) or right after loading, to tell which were edited
+    snapshots: HashMap<InternalID, PageContents>,
+}
+
+// a page's elements in document order, each with its children; compared as values, since
+// serializing them writes the properties in whatever order the HashMap has them
+type PageContents = Vec<(InternalID, OCRElement, Vec<InternalID>)>;
+
+fn page_contents(tree: &Tree<OCRElement>, page: &InternalID) -> PageContents {
+    tree.descendants(page)
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?.clone();
+            Some((id, elt, tree.children(&id).copied().collect()))
+        })
+        .collect()
+}
+
+// the (unquoted) value of the class attribute in a start tag contains class
+fn has_class(tag: &str, class: &str) -> bool {
+    tag.find("class=")
+        .map(|pos| {
+            let rest = &tag[pos + "class=".len()..];
+            let value = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or(""),
+                _ => rest
+                    .split(|c: char| c.is_whitespace() || c == '>')
+                    .next()
+                    .unwrap_or(""),
+            };
+            value.split_whitespace().any(|c| c == class)
+        })
+        .unwrap_or(false)
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+// byte ranges of every ocr_page element, from its start tag through its end tag
+fn scan_pages(source: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    // (start, tag name, nesting depth of that tag name) of the page we're inside
+    let mut current: Option<(usize, String, u32)> = None;
+    let mut i = 0;
+    while let Some(offset) = source[i..].find('<') {
+        let pos = i + offset;
+        if source[pos..].starts_with("<!--") {
+            match source[pos..].find("-->") {
+                Some(end) => {
+                    i = pos + end + "-->".len();
+                    continue;
+                }
+                None => break,
+            }
+        }
+        let end = match source[pos..].find('>') {
+            Some(end) => pos + end + 1,
+            None => break,
+        };
+        let tag = &source[pos..end];
+        let closing = tag.starts_with("</");
+        let name = tag_name(tag);
+        match &mut current {
+            None => {
+                if !closing && has_class(tag, "ocr_page") {
+                    current = Some((pos, name, 1));
+                }
+            }
+            Some((start, page_tag, depth)) => {
+                if name == *page_tag {
+                    if closing {
+                        *depth -= 1;
+                        if *depth == 0 {
+                            ranges.push(*start..end);
+                            current = None;
+                        }
+                    } else if !tag.ends_with("/>") {
+                        *depth += 1;
+                    }
+                }
+            }
+        }
+        i = end;
+    }
+    ranges
+}
+
+// the start tag an element's html begins with
+fn start_tag(html: &str) -> &str {
+    html.find('>').map_or(html, |end| &html[..end + 1])
+}
+
+impl LazyDocument {
+    // index the file and build a tree with just the page roots
+    pub fn open(path: &Path) -> Result<(Self, Tree<OCRElement>), String> {
+        let source = read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let page_ranges = scan_pages(&source);
+        let mut tree = Tree::new();
+        let mut ranges = HashMap::new();
+        let mut gaps = HashMap::new();
+        let mut snapshots = HashMap::new();
+        let mut prev_end = page_ranges.first().map_or(source.len(), |r| r.start);
+        for range in page_ranges {
+            // only the start tag is needed for the page's own properties
+            let page_tag = start_tag(&source[range.clone()]);
+            let fragment = Html::parse_fragment(&format!("{}</{}>", page_tag, tag_name(page_tag)));
+            let page = fragment
+                .select(&OCR_PAGE_SELECTOR)
+                .next()
+                .ok_or(format!("Couldn't parse page start tag {}", page_tag))
+                .and_then(OCRElement::html_elt_to_ocr_elt);
+            match page {
+                Ok(elt) => {
+                    let id = tree.add_root(elt);
+                    snapshots.insert(id, page_contents(&tree, &id));
+                    gaps.insert(id, prev_end..range.start);
+                    prev_end = range.end;
+                    ranges.insert(id, range);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+        let first_start = ranges
+            .values()
+            .map(|r| r.start)
+            .min()
+            .unwrap_or(source.len());
+        Ok((
+            LazyDocument {
+                prefix: source[..first_start].to_string(),
+                source,
+                ranges,
+                gaps,
+                suffix_start: prev_end,
+                loaded: HashSet::new(),
+                snapshots,
+            },
+            tree,
+        ))
+    }
+
+    // the doctype and head, for copying into the written document
+    pub fn head_source(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn is_loaded(&self, page: &InternalID) -> bool {
+        !self.ranges.contains_key(page) || self.loaded.contains(page)
+    }
+
+    // parse the page's subtree from its byte range if we haven't yet
+    pub fn load_page(&mut self, tree: &mut Tree<OCRElement>, page: &InternalID) {
+        if self.is_loaded(page) {
+            return;
+        }
+        // the page's own attributes may have been edited before it got loaded
+        let root_edited = self.is_dirty(tree, page);
+        self.loaded.insert(*page);
+        let fragment = Html::parse_fragment(&self.source[self.ranges[page].clone()]);
+        if let Some(page_elt) = fragment.select(&OCR_PAGE_SELECTOR).next() {
+            OCRElement::add_children_to_ocr_tree(page_elt, *page, tree);
+        }
+        // an empty snapshot never matches, so the page stays edited
+        let snapshot = if root_edited {
+            Vec::new()
+        } else {
+            page_contents(tree, page)
+        };
+        self.snapshots.insert(*page, snapshot);
+    }
+
+    fn is_dirty(&self, tree: &Tree<OCRElement>, page: &InternalID) -> bool {
+        self.snapshots
+            .get(page)
+            .is_some_and(|snapshot| *snapshot != page_contents(tree, page))
+    }
+
+    // write the document: untouched pages are copied byte for byte from the original file,
+    // edited and new pages are serialized from the tree with options. head_extra (e.g. a
+    // <meta>) goes at the end of the head.
+    pub fn save(
+        &self,
+        tree: &Tree<OCRElement>,
+        path: &Path,
+        options: &WriteOptions,
+        head_extra: &str,
+    ) -> Result<(), String> {
+        let source = &self.source;
+        let mut out = self.prefix.clone();
+        if let Some(end) = out.to_ascii_lowercase().find("</head>") {
+            out.insert_str(end, head_extra);
+        }
+        for (i, page) in tree.roots().enumerate() {
+            match self.ranges.get(page) {
+                Some(range) if !self.is_dirty(tree, page) => {
+                    out.push_str(&source[self.gaps[page].clone()]);
+                    out.push_str(&source[range.clone()]);
+                }
+                // a page that was never loaded only needs its start tag rewritten
+                Some(range) if !self.loaded.contains(page) => {
+                    let html = ocr_element::page_to_html_with(tree, page, i as u32 + 1, options);
+                    let old_tag = start_tag(&source[range.clone()]);
+                    out.push_str(&source[self.gaps[page].clone()]);
+                    out.push_str(start_tag(&html));
+                    out.push_str(&source[range.start + old_tag.len()..range.end]);
+                }
+                Some(_) => {
+                    out.push_str(&source[self.gaps[page].clone()]);
+                    out.push_str(&ocr_element::page_to_html_with(
+                        tree,
+                        page,
+                        i as u32 + 1,
+                        options,
+                    ));
+                }
+                None => {
+                    out.push('\n');
+                    out.push_str(&ocr_element::page_to_html_with(
+                        tree,
+                        page,
+                        i as u32 + 1,
+                        options,
+                    ));
+                }
+            }
+        }
+        out.push_str(&source[self.suffix_start..]);
+        atomic::write_atomic(path, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // odd spacing and attribute order on purpose: untouched pages must come back as they were
+    const SOURCE: &str = r#"<!DOCTYPE html>
+<html><head><title>t</title></head><body>
+<!-- <div class="ocr_page"> in a comment isn't a page -->
+<div   title='bbox 0 0 100 100' class="ocr_page" id="page_1">
+ <div class="ocr_carea" title="bbox 0 0 100 50"><div class="ocr_par" title="bbox 0 0 100 10"><span class="ocr_line" title="bbox 0 0 100 10"><span class="ocrx_word" title="bbox 0 0 40 10">one</span></span></div></div>
+</div>
+<div class="ocr_page" id="page_2" title="bbox 0 0 100 100"><span class="ocr_line" title="bbox 0 0 100 10"><span class="ocrx_word" title="bbox 0 0 40 10">two</span></span></div>
+</body></html>
+"#;
+
+    fn open_sample(name: &str) -> (std::path::PathBuf, LazyDocument, Tree<OCRElement>) {
+        let path = std::env::temp_dir().join(format!(
+            "hocr_editor_lazy_{}_{}.hocr",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, SOURCE).unwrap();
+        let (doc, tree) = LazyDocument::open(&path).unwrap();
+        (path, doc, tree)
+    }
+
+    #[test]
+    fn scan_pages_finds_whole_pages() {
+        let ranges = scan_pages(SOURCE);
+        assert_eq!(ranges.len(), 2);
+        assert!(SOURCE[ranges[0].clone()].starts_with("<div   title="));
+        assert!(SOURCE[ranges[0].clone()].ends_with("</div>\n</div>"));
+        assert!(SOURCE[ranges[1].clone()].contains("two"));
+    }
+
+    #[test]
+    fn untouched_document_saves_byte_for_byte() {
+        let (path, mut doc, mut tree) = open_sample("untouched");
+        // loading a page isn't editing it
+        let first = *tree.roots().next().unwrap();
+        doc.load_page(&mut tree, &first);
+        assert_eq!(tree.descendants(&first).len(), 5);
+        doc.save(&tree, &path, &WriteOptions::default(), "")
+            .unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), SOURCE);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn only_edited_pages_are_rewritten() {
+        let (path, mut doc, mut tree) = open_sample("edited");
+        let second = *tree.roots().nth(1).unwrap();
+        doc.load_page(&mut tree, &second);
+        let word = *tree.descendants(&second).last().unwrap();
+        tree.get_mut_node(&word).unwrap().ocr_text = "zwei".to_string();
+        doc.save(&tree, &path, &WriteOptions::default(), "")
+            .unwrap();
+        let saved = read_to_string(&path).unwrap();
+        let first_page = &SOURCE[scan_pages(SOURCE)[0].clone()];
+        assert!(saved.contains(first_page));
+        assert!(saved.contains("zwei"));
+        assert!(!saved.contains(">two<"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
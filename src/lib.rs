@@ -0,0 +1,101 @@
+//! Reading, editing and writing hOCR without the editor.
+//!
+//! [`ocr_element::OCRElement::html_to_ocr_tree`] turns a parsed hOCR document into a
+//! [`tree::Tree`] of [`ocr_element::OCRElement`]s, the tree's methods restructure it and
+//! [`ocr_element::add_as_body`] writes it back out:
+//!
+//! ```no_run
+//! use hocr_editor::ocr_element::{self, OCRElement};
+//!
+//! let source = std::fs::read_to_string("page.hocr").unwrap();
+//! let document = scraper::Html::parse_document(&source);
+//! let tree = OCRElement::html_to_ocr_tree(document);
+//! for page in tree.roots() {
+//!     println!("{}", ocr_element::get_root_preview_text(&tree, *page));
+//! }
+//! let head = scraper::Html::parse_document("<html><head></head></html>");
+//! let html = ocr_element::add_as_body(&tree, &head).html();
+//! ```
+
+/// lining up and spacing out a selection of boxes
+pub mod align;
+/// writing files through a temp file so a failed write never truncates the old one
+pub mod atomic;
+/// rescaling word confidences after proofreading, recorded in the document head
+pub mod calibrate;
+/// fixing the capitalization of words
+pub mod casing;
+/// finding scanner noise, duplicate words and words that were split or glued wrongly
+pub mod cleanup;
+/// applying a list of wrong → right word replacements
+pub mod corrections;
+/// telling when a page's bbox doesn't match its scan and scaling the page to fit
+pub mod dpi;
+/// plain text, TSV and ALTO exports
+pub mod export;
+/// regexes for entities such as dates and amounts
+pub mod highlight;
+/// undo and redo as whole-tree copies, plus what changed since opening
+pub mod history;
+/// running a shell command after saving
+pub mod hooks;
+/// words broken over a line end
+pub mod hyphenation;
+/// page regions (gutters, edges) whose words are left out of exports
+pub mod ignore;
+/// watching a folder for new OCR output
+pub mod inbox;
+/// background jobs with progress and cancelling
+pub mod jobs;
+/// the tree as plain JSON
+pub mod json_tree;
+/// documents whose pages are only parsed when first looked at
+pub mod lazy;
+/// running tesseract on a scan or a part of one
+pub mod ocr;
+/// hOCR elements, their properties, and reading and writing them
+pub mod ocr_element;
+/// reading and writing PAGE XML
+pub mod page_xml;
+/// importing PDFs as rendered pages
+pub mod pdf;
+/// settings shared by everyone working on a folder of documents
+pub mod project;
+/// proofreading PDFs with the text over the scan, colored by confidence
+pub mod proof;
+/// blacking out regions of the scans and their text
+pub mod redact;
+/// the queue of things that need a look
+pub mod review;
+/// searching words with case, diacritics and Unicode folding
+pub mod search;
+/// named copies of a document and the per-document session file
+pub mod snapshots;
+/// saving with ids, titles and layout kept as they were, for files in git
+pub mod stable_save;
+/// document statistics, warnings and word frequencies
+pub mod stats;
+/// filling in missing levels and wrapping elements in new ones
+pub mod structure;
+/// words that are rare in a reference corpus
+pub mod suspicious;
+/// regions shared by a batch of similar forms
+pub mod template;
+/// turning typed romaji and the like into another script
+pub mod transliterate;
+/// the tree the elements live in
+pub mod tree;
+/// file URIs for scans, so paths with spaces and non-ASCII names load
+pub mod uri;
+/// reading order from the layout of the boxes
+pub mod xycut;
+
+/// words whose x_wconf is below this count as badly recognized
+pub const BAD_WCONF_THRESHOLD: u32 = 80;
+
+/// the font text is shown and proofed in; unlike the pdf builtins it has Japanese as well as
+/// Latin
+pub static DEFAULT_FONT: &[u8] = include_bytes!("resources/NotoSansJP-Regular.ttf");
+
+/// how nodes are referred to in a [`tree::Tree`]; ids aren't reused within a tree
+pub type InternalID = u32;
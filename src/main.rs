@@ -1,6 +1,34 @@
-use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::align::Alignment;
+use crate::calibrate::Recalibration;
+use crate::casing::CaseMode;
+use crate::cleanup::{
+    BatchAction, DuplicateGroup, GappedWord, NoiseCandidate, NoiseSettings, Oversegmented,
+};
+use crate::corrections::{Correction, CorrectionList};
+use crate::dpi::ImageMapping;
+use crate::export::{ExportFormat, ExportOptions, PageSeparator, Unverified};
+use crate::highlight::EntityMatch;
+use crate::history::History;
+use crate::hyphenation::SplitWord;
+use crate::inbox::Inbox;
+use crate::jobs::Job;
+use crate::lazy::LazyDocument;
+use crate::notices::Notices;
+use crate::ocr::{HocrJob, OcrJob, ReocrJob};
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty, USER_PROPERTY_PREFIX};
+use crate::project::ProjectSettings;
+use crate::review::{ReviewItem, ReviewKind, ReviewSort};
+use crate::search::{SearchOptions, SearchScope};
+use crate::settings::Settings;
+use crate::snapshots::Session;
+use crate::speech::Speech;
+use crate::suspicious::FrequencyList;
+use crate::template::{Template, TemplateRegion};
+use crate::transliterate::MappingTable;
 use crate::tree::{Position, Tree};
 use eframe::egui;
+use egui::emath::RectTransform;
+use egui::text::{CCursor, CCursorRange};
 use egui::CursorIcon::{ResizeHorizontal, ResizeNeSw, ResizeNwSe, ResizeVertical};
 use egui::{FontData, FontDefinitions, FontFamily, Pos2, Rect, Sense, Shape, Vec2};
 use html5ever::interface::tree_builder::TreeSink;
@@ -12,13 +40,23 @@ use rfd::FileDialog;
 use scraper::Node::*;
 use scraper::Selector;
 use scraper::{ElementRef, Html};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-mod ocr_element;
-mod tree;
+// everything but the ui lives in the library so other programs (and cli) can use it too
+use hocr_editor::{
+    align, atomic, calibrate, casing, cleanup, corrections, dpi, export, highlight, history, hooks,
+    hyphenation, ignore, inbox, jobs, json_tree, lazy, ocr, ocr_element, page_xml, pdf, project,
+    proof, redact, review, search, snapshots, stable_save, stats, structure, suspicious, template,
+    transliterate, tree, uri, xycut, InternalID, BAD_WCONF_THRESHOLD, DEFAULT_FONT,
+};
+
+mod cli;
+mod notices;
+mod settings;
+mod speech;
 
 // global "constants" for egui stuff
 lazy_static! {
@@ -33,6 +71,11 @@ lazy_static! {
 }
 
 fn main() {
+    // any arguments mean we're running headless
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
     let options = eframe::NativeOptions::default();
     let _ = eframe::run_native(
         "HOCR Editor",
@@ -41,8 +84,6 @@ fn main() {
     );
 }
 
-type InternalID = u32;
-
 // TODO: do I need this?
 #[derive(Default, Debug, PartialEq)]
 enum Mode {
@@ -51,15 +92,87 @@ enum Mode {
     SingleSelect,
 }
 
+// what dragging on the page image does
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+enum Tool {
+    #[default]
+    Select,
+    Separator,
+    Photo,
+    // read the drawn rectangle with tesseract without touching the tree
+    PreviewOcr,
+    // move everything inside the drawn rectangle under the selected element
+    Assign,
+    // black out the words under the drawn rectangle on redacted export
+    Redact,
+    // mark the drawn rectangle as gutter or edge whose words are left out of exports
+    Ignore,
+    // drag a line to see how long it is
+    Measure,
+    // drag along a row or column to place a guide there
+    Guide,
+    // drag out the bbox of a new element inside whatever contains it
+    Draw,
+}
+
+// context menu entries that can't run while the tree is being drawn
+#[derive(Debug, Clone)]
+enum ContextAction {
+    Align(Alignment),
+    ChangeCase(CaseMode),
+    Delete,
+    MergeSelected,
+    // one place earlier or later among its siblings
+    Move(Position),
+    // dropped on a row of the tree panel: into that element, or before or after it
+    Drop(InternalID, Option<Position>),
+    // a deep copy right after the original
+    Duplicate,
+    Cut,
+    Copy,
+    // the clipboard into the element (None) or next to it
+    Paste(Option<Position>),
+    ExportCrop,
+    QueueReocr,
+    Reocr,
+    // its children top to bottom and left to right by their bboxes
+    SortChildren,
+    // which way the text runs, counterclockwise in degrees
+    SetTextAngle(u32),
+    // a word in two before this character of its text
+    SplitWord(usize),
+    ToggleRedact,
+    // a word broken over a line end back into two words of their own
+    Unpair,
+    Unwrap,
+    Wrap(OCRClass),
+}
+
 // main struct: the state of our app
 #[derive(Debug)]
 struct HOCREditor {
     file_path: Option<PathBuf>,
     html_write_head: Html,
+    // uri egui shows the current page's scan by, the file it is and, when the path had to be
+    // percent-encoded, the file's bytes (egui's file loader can't decode uris)
     image_path: Option<String>,
+    image_file: Option<PathBuf>,
+    image_bytes: Option<std::sync::Arc<[u8]>>,
+    // image_file's size in pixels, to catch scans that don't match their page bbox
+    image_size: Option<Vec2>,
+    // image_size again when what's shown is a scaled down copy that fits in a texture
+    image_full_size: Option<Vec2>,
+    // the biggest texture the graphics card takes, 0 until the first frame says
+    max_texture_side: usize,
+    // the page the size mismatch wizard is asking about
+    dpi_wizard_page: Option<usize>,
+    dpi_wizard_all: bool,
     file_path_changed: bool,
     internal_ocr_tree: RefCell<Tree<OCRElement>>,
     mode: Mode,
+    tool: Tool,
+    // image coords of where the current draw started
+    draw_start: Option<Pos2>,
     // to allow the rendered tree to interact with state
     // we update these first
     // then when we detect updates we update the tree
@@ -69,6 +182,184 @@ struct HOCREditor {
     parent_id: RefCell<Option<InternalID>>,
     sibling_id: RefCell<Option<InternalID>>,
     sibling_position: RefCell<Position>,
+    context_action: RefCell<Option<(InternalID, ContextAction)>>,
+    // the row being dragged in the tree panel
+    tree_drag: Cell<Option<InternalID>>,
+    // the tree panel was clicked last, so ctrl+x/c/v act on elements
+    tree_focused: bool,
+    // copies of the elements last cut or copied, as the roots of a tree of their own
+    clipboard: Option<Tree<OCRElement>>,
+    // elements selected along with selected_id, by ctrl-click or by dragging a box around them
+    also_selected: RefCell<HashSet<InternalID>>,
+    // selected_id as of the last frame, to tell when a plain click replaced the selection
+    last_selected: Cell<Option<InternalID>>,
+    // where a drag to select everything inside a box started, in image coords
+    band_start: Option<Pos2>,
+    // noise cleanup window
+    noise_window_open: bool,
+    noise_settings: NoiseSettings,
+    noise_candidates: Vec<NoiseCandidate>,
+    // transliteration helper for the word text editor
+    translit_tables: Vec<MappingTable>,
+    translit_table: Option<usize>,
+    translit_auto: bool,
+    // letters typed into a word that may still become part of a longer match
+    translit_pending: Option<(InternalID, transliterate::Pending)>,
+    // on-screen keyboard: whitespace separated keys, "label=text" if the label should differ
+    keyboard_open: bool,
+    // the text box the keyboard types into (the last one with focus) and the selection then
+    keyboard_target: Option<(egui::Id, Option<InternalID>)>,
+    // clicked keys, typed into their text box at the start of the next frame
+    keyboard_insert: Option<(egui::Id, String)>,
+    inline_edit: bool,
+    // everything that survives a restart
+    settings: Settings,
+    preferences_open: bool,
+    // new files showing up in settings.watch_folder
+    inbox: Inbox,
+    export_job: Option<Job<usize>>,
+    export_options: ExportOptions,
+    // the .hocr-editor.toml the open file's folder shares, if any
+    project: ProjectSettings,
+    // how words are folded together when counting them for the frequency list
+    frequency_options: SearchOptions,
+    // set when the file was opened with lazy page loading
+    lazy_doc: Option<LazyDocument>,
+    stats_open: bool,
+    // the statistics and the history version they were computed at; the whole tree is walked
+    // for them, so not every frame
+    stats: Option<(u64, stats::DocumentStats)>,
+    search_open: bool,
+    search_query: String,
+    search_options: SearchOptions,
+    search_scope: SearchScope,
+    search_results: Vec<InternalID>,
+    // low confidence worklist
+    low_conf_open: bool,
+    low_conf_threshold: u32,
+    low_conf_page_only: bool,
+    low_conf_words: Vec<InternalID>,
+    // rescaling every x_wconf after correcting by hand
+    recalibrate_open: bool,
+    recalibration: Recalibration,
+    // words waiting to be run through OCR again
+    reocr_queue: Vec<InternalID>,
+    // elements tesseract is reading again in the background
+    reocr_jobs: Vec<ReocrJob>,
+    // File > New from image / Open PDF: the hOCR file to open once it's been written
+    import_job: Option<HocrJob>,
+    // everything running in the background, with a cancel button each
+    jobs_open: bool,
+    // a line for each job that finishes
+    notices: Notices,
+    // the unified list of things to look at during a correction pass
+    review_open: bool,
+    // every undoable step, listed so any state can be gone back to
+    history_open: bool,
+    review_items: Vec<ReviewItem>,
+    // settings.wordlists as last loaded, by lang
+    reference_lists: HashMap<String, FrequencyList>,
+    loaded_wordlists: Vec<(String, PathBuf)>,
+    new_wordlist_lang: String,
+    review_sort: ReviewSort,
+    review_resolved: HashSet<(InternalID, ReviewKind)>,
+    ocr_preview: Option<OcrJob>,
+    // hyphenated words broken over line ends
+    split_words_open: bool,
+    // words the engine cut into pieces, found by how small the gaps between them are
+    oversegmented_open: bool,
+    oversegmented: Vec<Oversegmented>,
+    oversegmented_ratio: f32,
+    // and words that should have been cut, found by blank paper inside their box
+    gapped_open: bool,
+    gapped_words: Vec<GappedWord>,
+    gapped_ratio: f32,
+    gap_scan: Option<Job<Vec<GappedWord>>>,
+    // everything changed since the file was opened, to look over before saving
+    edited_open: bool,
+    edited_filter: String,
+    edited: Vec<(InternalID, String)>,
+    edited_deleted: usize,
+    // the history version edited was worked out at
+    edited_version: Option<u64>,
+    // wrong -> right replacements loaded from a csv, and the words they'd change
+    corrections_open: bool,
+    correction_list: Option<CorrectionList>,
+    corrections: Vec<Correction>,
+    split_words: Vec<SplitWord>,
+    renumber_open: bool,
+    text_preview_open: bool,
+    // langs of settings.lang_fonts whose font files loaded, and the ones that will once fonts
+    // set this frame are in use (egui only switches fonts at the start of a frame)
+    lang_fonts: HashSet<String>,
+    next_lang_fonts: Option<HashSet<String>>,
+    // lang the next font picked in the preferences is for
+    new_font_lang: String,
+    // scan on the left, reading order text on the right
+    two_up: bool,
+    scroll_link: ScrollLink,
+    split: SplitView,
+    duplicates_open: bool,
+    // bulk class conversion
+    convert_open: bool,
+    convert_from: OCRClass,
+    convert_to: OCRClass,
+    convert_filter_height: bool,
+    convert_height: (f32, f32),
+    duplicate_groups: Vec<DuplicateGroup>,
+    renumber_start: u32,
+    renumber_step: u32,
+    // named copies of the document kept in the session file
+    snapshots_open: bool,
+    // named regions of a form, applied to every page of a batch
+    template_open: bool,
+    // words matching the entity highlighters, and patterns that didn't compile
+    entities_open: bool,
+    // number lines on the image; page and line the go-to window jumps to (1-based)
+    show_line_numbers: bool,
+    // handles between the words of the selected line that move both neighbouring edges
+    word_boundaries: bool,
+    go_to_line_open: bool,
+    go_to_line_page: usize,
+    // an element id (word_1_57) or a point (1200,800) on the current page to jump to
+    go_to_element_id: String,
+    go_to_point: String,
+    go_to_line_number: usize,
+    entity_matches: Vec<EntityMatch>,
+    entity_errors: Vec<(usize, String)>,
+    template: Template,
+    new_region_label: String,
+    session: Session,
+    snapshot_name: String,
+    snapshot_diff: Vec<snapshots::Change>,
+    playback: Option<Playback>,
+    // words per second
+    playback_speed: f32,
+    speech: Speech,
+    history: RefCell<History>,
+    // key being typed for a new x_user_ property
+    new_user_key: String,
+    // where Save writes; None until the user has agreed to overwrite or picked a file
+    save_path: Option<PathBuf>,
+    // the open file is PAGE XML or a JSON tree, so it's never saved over
+    imported: bool,
+    confirm_overwrite_open: bool,
+    // image coords under the mouse, for the status bar
+    cursor_pos: Option<Pos2>,
+    // image coords of the last line drawn with the measure tool
+    measurement: Option<(Pos2, Pos2)>,
+    // index into the tree's roots of the page on the canvas
+    current_page: usize,
+    // scroll position of the scan as a fraction of the scrollable range, its visible
+    // size, and whether the next frame should put the new page at the same fraction
+    image_scroll: egui::Vec2,
+    image_view_size: egui::Vec2,
+    keep_image_scroll: bool,
+    show_grid: bool,
+    guides: Vec<Guide>,
+    // the bbox as it was when a move started and how far it's been dragged since, in image
+    // coords
+    moving_bbox: Option<(Rect, Vec2)>,
 }
 
 impl Default for HOCREditor {
@@ -81,35 +372,334 @@ impl Default for HOCREditor {
             file_path_changed: false,
             internal_ocr_tree: RefCell::new(Default::default()),
             mode: Default::default(),
+            tool: Default::default(),
+            draw_start: None,
             parent_id: RefCell::new(None),
             sibling_id: RefCell::new(None),
             sibling_position: RefCell::new(Position::Before),
+            context_action: RefCell::new(None),
+            tree_drag: Cell::new(None),
+            tree_focused: false,
+            clipboard: None,
+            also_selected: RefCell::new(HashSet::new()),
+            last_selected: Cell::new(None),
+            band_start: None,
             image_path: None,
+            image_file: None,
+            image_bytes: None,
+            image_full_size: None,
+            max_texture_side: 0,
+            image_size: None,
+            dpi_wizard_page: None,
+            dpi_wizard_all: false,
             selected_id: RefCell::new(None),
+            noise_window_open: false,
+            noise_settings: Default::default(),
+            noise_candidates: Vec::new(),
+            translit_tables: transliterate::builtin_tables(),
+            translit_table: None,
+            translit_auto: false,
+            translit_pending: None,
+            keyboard_open: false,
+            keyboard_target: None,
+            keyboard_insert: None,
+            inline_edit: false,
+            settings: Default::default(),
+            preferences_open: false,
+            inbox: Default::default(),
+            export_job: None,
+            export_options: ExportOptions::default(),
+            project: Default::default(),
+            frequency_options: Default::default(),
+            lazy_doc: None,
+            stats_open: false,
+            stats: None,
+            search_open: false,
+            search_query: String::new(),
+            search_options: Default::default(),
+            search_scope: Default::default(),
+            search_results: Vec::new(),
+            low_conf_open: false,
+            low_conf_threshold: BAD_WCONF_THRESHOLD,
+            low_conf_page_only: false,
+            low_conf_words: Vec::new(),
+            recalibrate_open: false,
+            recalibration: Recalibration::Linear {
+                scale: 1.0,
+                offset: 0.0,
+            },
+            reocr_queue: Vec::new(),
+            reocr_jobs: Vec::new(),
+            import_job: None,
+            jobs_open: false,
+            notices: Notices::default(),
+            review_open: false,
+            history_open: false,
+            review_items: Vec::new(),
+            reference_lists: HashMap::new(),
+            loaded_wordlists: Vec::new(),
+            new_wordlist_lang: String::new(),
+            review_sort: Default::default(),
+            review_resolved: HashSet::new(),
+            ocr_preview: None,
+            split_words_open: false,
+            oversegmented_open: false,
+            oversegmented: Vec::new(),
+            oversegmented_ratio: 0.3,
+            gapped_open: false,
+            gapped_words: Vec::new(),
+            gapped_ratio: 0.3,
+            gap_scan: None,
+            edited_open: false,
+            edited_filter: String::new(),
+            edited: Vec::new(),
+            edited_deleted: 0,
+            edited_version: None,
+            corrections_open: false,
+            correction_list: None,
+            corrections: Vec::new(),
+            split_words: Vec::new(),
+            renumber_open: false,
+            text_preview_open: false,
+            lang_fonts: HashSet::new(),
+            next_lang_fonts: None,
+            new_font_lang: String::new(),
+            two_up: false,
+            split: SplitView::default(),
+            scroll_link: Default::default(),
+            duplicates_open: false,
+            convert_open: false,
+            convert_from: OCRClass::Caption,
+            convert_to: OCRClass::Line,
+            convert_filter_height: false,
+            convert_height: (0.0, 100.0),
+            snapshots_open: false,
+            template_open: false,
+            entities_open: false,
+            show_line_numbers: false,
+            word_boundaries: false,
+            go_to_line_open: false,
+            go_to_element_id: String::new(),
+            go_to_point: String::new(),
+            go_to_line_page: 1,
+            go_to_line_number: 1,
+            entity_matches: Vec::new(),
+            entity_errors: Vec::new(),
+            template: Template::default(),
+            new_region_label: String::new(),
+            session: Default::default(),
+            snapshot_name: String::new(),
+            snapshot_diff: Vec::new(),
+            playback: None,
+            playback_speed: 3.0,
+            speech: Default::default(),
+            history: Default::default(),
+            new_user_key: String::new(),
+            save_path: None,
+            imported: false,
+            confirm_overwrite_open: false,
+            cursor_pos: None,
+            measurement: None,
+            current_page: 0,
+            image_scroll: egui::Vec2::ZERO,
+            image_view_size: egui::Vec2::ZERO,
+            keep_image_scroll: false,
+            show_grid: false,
+            guides: Vec::new(),
+            moving_bbox: None,
+            duplicate_groups: Vec::new(),
+            renumber_start: 0,
+            renumber_step: 1,
+        }
+    }
+}
+
+const IMAGE_PANE: usize = 0;
+const TEXT_PANE: usize = 1;
+
+// stepping the selection through the words in reading order
+#[derive(Debug)]
+struct Playback {
+    words: Vec<InternalID>,
+    next: usize,
+    // ctx time of the last step
+    last_step: f64,
+}
+
+// keeps the scan and the two-up text scrolled to the same relative position
+#[derive(Debug, Default)]
+struct ScrollLink {
+    // where each pane was last frame, 0 at the top and 1 at the bottom
+    fractions: [f32; 2],
+    max_offsets: [f32; 2],
+    // position to force on a pane next frame
+    pending: [Option<f32>; 2],
+}
+
+impl ScrollLink {
+    fn apply(&mut self, pane: usize, area: egui::ScrollArea) -> egui::ScrollArea {
+        match self.pending[pane].take() {
+            Some(fraction) => area.vertical_scroll_offset(fraction * self.max_offsets[pane]),
+            None => area,
+        }
+    }
+
+    // when one pane moved on its own, move the other one to match
+    fn record<R>(&mut self, pane: usize, output: &egui::scroll_area::ScrollAreaOutput<R>) {
+        let max_offset = (output.content_size.y - output.inner_rect.height()).max(0.0);
+        self.max_offsets[pane] = max_offset;
+        let fraction = if max_offset > 0.0 {
+            output.state.offset.y / max_offset
+        } else {
+            0.0
+        };
+        if (fraction - self.fractions[pane]).abs() > 0.001 {
+            self.fractions = [fraction; 2];
+            self.pending[1 - pane] = Some(fraction);
         }
     }
 }
 
+// a second page next to the one being edited, e.g. the facing page or the same page in a
+// snapshot, with a selection of its own so text can be followed across the page break
+#[derive(Debug, Default)]
+struct SplitView {
+    open: bool,
+    page: usize,
+    // always show the page after the current one
+    facing: bool,
+    // None shows the document itself, Some(i) the i-th snapshot
+    snapshot: Option<usize>,
+    snapshot_tree: Option<Tree<OCRElement>>,
+    selected: Option<InternalID>,
+    // the scan being shown, so it's only reread when the page changes
+    image_file: Option<PathBuf>,
+    image: Option<SplitImage>,
+}
+
+// what image_source found for the split view's scan
+#[derive(Clone, Debug)]
+struct SplitImage {
+    uri: String,
+    bytes: Option<std::sync::Arc<[u8]>>,
+    size: Option<Vec2>,
+}
+
 // when you select the bbox, you change select_id to assoc_id
 struct SelectableRect {
     adj_bbox: Rect,
     selected: bool,
     is_bad: bool,
+    // separators are drawn as a line through the middle of the bbox
+    is_line: bool,
+    stroke_weight: f32,
+    // faded out so the selection stands out among its siblings
+    dimmed: bool,
 }
 
 impl SelectableRect {
-    fn new(adj_bbox: Rect, selected: bool, is_bad: bool) -> Self {
+    fn new(
+        adj_bbox: Rect,
+        selected: bool,
+        is_bad: bool,
+        is_line: bool,
+        stroke_weight: f32,
+        dimmed: bool,
+    ) -> Self {
         Self {
             adj_bbox,
             selected,
             is_bad,
+            is_line,
+            stroke_weight,
+            dimmed,
         }
     }
 }
 
-const STROKE_WEIGHT: f32 = 4.0;
-const UNFOCUS_FILL: egui::Color32 = egui::Color32::TRANSPARENT;
-const BAD_WCONF_THRESHOLD: u32 = 80;
+// closed outline of a rect, for dashing
+fn rect_outline(rect: Rect) -> Vec<Pos2> {
+    vec![
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+        rect.left_top(),
+    ]
+}
+
+// the line along the long axis of a rect
+fn center_line(rect: Rect) -> [Pos2; 2] {
+    if rect.width() >= rect.height() {
+        [
+            Pos2::new(rect.left(), rect.center().y),
+            Pos2::new(rect.right(), rect.center().y),
+        ]
+    } else {
+        [
+            Pos2::new(rect.center().x, rect.top()),
+            Pos2::new(rect.center().x, rect.bottom()),
+        ]
+    }
+}
+
+const STROKE_WEIGHT: f32 = settings::DEFAULT_STROKE_WEIGHT;
+// characters below this are doubtful, below BAD_WCONF_THRESHOLD probably wrong
+const DOUBTFUL_CHAR_CONF: f32 = 95.0;
+const INLINE_EDITOR_ID: &str = "inline word text";
+const PREVIEW_FONT_FAMILY: &str = "Preview";
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 8.0;
+// widest a rotated element's scan is shown in the properties panel
+const STRIP_WIDTH: f32 = 250.0;
+// the pixel grid only shows up once pixels are at least this big on screen
+const GRID_MIN_ZOOM: f32 = 4.0;
+// how close (in screen pixels) the pointer has to get to a guide for an edge to snap to it
+const SNAP_DISTANCE: f32 = 8.0;
+
+// a line across the page, in image coords, that dragged bbox edges snap to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Guide {
+    Horizontal(f32),
+    Vertical(f32),
+}
+
+// the closest guide position within tolerance of pos, if any
+fn snap_to(guides: impl Iterator<Item = f32>, pos: f32, tolerance: f32) -> Option<f32> {
+    guides
+        .filter(|guide| (guide - pos).abs() <= tolerance)
+        .min_by(|a, b| {
+            (a - pos)
+                .abs()
+                .partial_cmp(&(b - pos).abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+// (label, text to insert) for each key of the on-screen keyboard
+fn parse_keyboard_keys(keys: &str) -> Vec<(String, String)> {
+    keys.split_whitespace()
+        .map(|key| match key.split_once('=') {
+            Some((label, text)) if !label.is_empty() && !text.is_empty() => {
+                (label.to_string(), unescape_key(text))
+            }
+            _ => (key.to_string(), key.to_string()),
+        })
+        .collect()
+}
+
+// lets keys insert characters that can't be typed into the layout, like \u{a0}
+fn unescape_key(text: &str) -> String {
+    match text
+        .strip_prefix("\\u{")
+        .and_then(|s| s.strip_suffix('}'))
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(char::from_u32)
+    {
+        Some(c) => c.to_string(),
+        None => text.to_string(),
+    }
+}
 
 // turn red if wconf is low?
 impl egui::Widget for SelectableRect {
@@ -118,26 +708,56 @@ impl egui::Widget for SelectableRect {
             adj_bbox,
             selected,
             is_bad,
+            is_line,
+            stroke_weight,
+            dimmed,
         } = self;
         let response = ui.allocate_rect(adj_bbox, Sense::click());
-        let stroke: egui::Stroke = if selected {
-            *CLICKED_STROKE
+        let hovered = response.hovered();
+        // fade the hover highlight in and out instead of flickering
+        let hover_amount = ui.ctx().animate_bool(response.id, hovered);
+        let color = if selected {
+            CLICKED_STROKE.color
         } else if is_bad {
-            *BAD_STROKE
+            BAD_STROKE.color
+        } else {
+            UNCLICKED_STROKE.color
+        };
+        let color = if dimmed && !hovered {
+            color.gamma_multiply(0.4)
+        } else {
+            color
+        };
+        // selected: solid, thicker when also hovered; only hovered: dashed
+        let width = if selected && hovered {
+            stroke_weight * 1.5
         } else {
-            *UNCLICKED_STROKE
+            stroke_weight
         };
-        let fill: egui::Color32 = if response.hovered() || selected {
+        let stroke = egui::Stroke::new(width, color);
+        let fill: egui::Color32 = if selected {
             *FOCUS_FILL
         } else if is_bad {
             *BAD_FILL
         } else {
-            UNFOCUS_FILL
+            FOCUS_FILL.gamma_multiply(hover_amount)
         };
         // TODO: widgetinfo
         if ui.is_rect_visible(response.rect) {
             ui.painter()
-                .rect(adj_bbox, egui::Rounding::ZERO, fill, stroke);
+                .rect_filled(adj_bbox, egui::Rounding::ZERO, fill);
+            let outline = if is_line {
+                center_line(adj_bbox).to_vec()
+            } else {
+                rect_outline(adj_bbox)
+            };
+            if hovered && !selected {
+                let dash = (stroke_weight * 2.0).max(4.0);
+                ui.painter()
+                    .extend(Shape::dashed_line(&outline, stroke, dash, dash));
+            } else {
+                ui.painter().add(Shape::line(outline, stroke));
+            }
         }
         response.on_hover_and_drag_cursor(egui::CursorIcon::PointingHand)
     }
@@ -150,11 +770,17 @@ fn selectable_rect<Value: PartialEq>(
     current_value: &mut Value,
     selected_value: Value,
     is_bad: bool,
+    is_line: bool,
+    stroke_weight: f32,
+    dimmed: bool,
 ) -> egui::Response {
     let mut response = ui.add(SelectableRect::new(
         rect,
         *current_value == selected_value,
         is_bad,
+        is_line,
+        stroke_weight,
+        dimmed,
     ));
     if response.clicked() && *current_value != selected_value {
         *current_value = selected_value;
@@ -163,12 +789,195 @@ fn selectable_rect<Value: PartialEq>(
     response
 }
 
-fn load_fonts(ctx: &egui::Context) {
+// the scan the page at index points at, made absolute so it can become a file uri
+fn page_image_file(
+    tree: &Tree<OCRElement>,
+    index: usize,
+    document: Option<&Path>,
+) -> Option<PathBuf> {
+    let image = tree
+        .roots()
+        .nth(index)
+        .and_then(|page| tree.get_node(page))
+        .and_then(|page| match page.ocr_properties.get("image") {
+            Some(OCRProperty::Image(path)) => Some(path.clone()),
+            _ => None,
+        })?;
+    let file = redact::resolve_image(&image, document);
+    Some(match std::env::current_dir() {
+        Ok(dir) if file.is_relative() => dir.join(file),
+        _ => file,
+    })
+}
+
+// how scans are shown on screen; the files themselves are never touched
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScanDisplay {
+    // bigger scans are scaled down to this, for the graphics card's sake
+    max_side: usize,
+    // yellowed paper and coloured stains are just noise when reading text
+    grayscale: bool,
+    // above 1 lightens the midtones, below 1 darkens them (for faint ink)
+    gamma: f32,
+}
+
+impl ScanDisplay {
+    fn changes_pixels(&self) -> bool {
+        self.grayscale || (self.gamma - 1.0).abs() > 0.01
+    }
+}
+
+// the scan as a png adjusted for display, scaled down to fit in max_side if downscale
+fn display_copy(
+    file: &Path,
+    display: ScanDisplay,
+    downscale: bool,
+) -> Option<std::sync::Arc<[u8]>> {
+    let mut image = match image::open(file) {
+        Ok(image) => image,
+        Err(e) => {
+            println!("Failed to open {}: {}", file.display(), e);
+            return None;
+        }
+    };
+    if downscale {
+        image = image.resize(
+            display.max_side as u32,
+            display.max_side as u32,
+            image::imageops::FilterType::Triangle,
+        );
+    }
+    if display.grayscale {
+        image = image::DynamicImage::ImageLuma8(image.to_luma8());
+    }
+    if (display.gamma - 1.0).abs() > 0.01 {
+        let curve: Vec<u8> = (0..=255)
+            .map(|v| ((v as f32 / 255.0).powf(1.0 / display.gamma) * 255.0).round() as u8)
+            .collect();
+        image = match image {
+            image::DynamicImage::ImageLuma8(mut gray) => {
+                gray.pixels_mut()
+                    .for_each(|p| p.0[0] = curve[p.0[0] as usize]);
+                image::DynamicImage::ImageLuma8(gray)
+            }
+            other => {
+                let mut rgba = other.to_rgba8();
+                for p in rgba.pixels_mut() {
+                    for c in &mut p.0[..3] {
+                        *c = curve[*c as usize];
+                    }
+                }
+                image::DynamicImage::ImageRgba8(rgba)
+            }
+        };
+    }
+    let mut png = std::io::Cursor::new(Vec::new());
+    match image.write_to(&mut png, image::ImageOutputFormat::Png) {
+        Ok(()) => Some(png.into_inner().into()),
+        Err(e) => {
+            println!("Failed to convert {}: {}", file.display(), e);
+            None
+        }
+    }
+}
+
+// the uri to load a scan from, its bytes if egui can't load the uri itself and, if it had to
+// be scaled down to display.max_side, its real size, which boxes are still measured in
+fn image_source(
+    file: &Path,
+    display: ScanDisplay,
+) -> (String, Option<std::sync::Arc<[u8]>>, Option<Vec2>) {
+    let image_uri = uri::file_uri(file);
+    let size = image::image_dimensions(file)
+        .ok()
+        .map(|(width, height)| Vec2::new(width as f32, height as f32));
+    let too_big = size.is_some_and(|size| size.max_elem() > display.max_side as f32);
+    if too_big || display.changes_pixels() {
+        if let Some(bytes) = display_copy(file, display, too_big) {
+            // its own uri, so egui doesn't hand back a texture it has cached for other settings
+            let copy_uri = format!(
+                "bytes://{}@{}px_{}_{:.2}.png",
+                image_uri,
+                if too_big { display.max_side } else { 0 },
+                display.grayscale,
+                display.gamma
+            );
+            return (copy_uri, Some(bytes), size.filter(|_| too_big));
+        }
+    }
+    // spaces, CJK and the like got encoded, so egui would look for the wrong file
+    if image_uri == format!("file://{}", file.display()) {
+        return (image_uri, None, None);
+    }
+    match std::fs::read(file) {
+        Ok(bytes) => (image_uri, Some(bytes.into()), None),
+        Err(e) => {
+            println!("Failed to read {}: {}", file.display(), e);
+            (image_uri, None, None)
+        }
+    }
+}
+
+// Ctrl+Enter in a word's text box: the character index of the cursor to split the word at.
+// Taken before the text box is added, which would otherwise give up focus on the enter.
+fn split_shortcut(ui: &mut egui::Ui, text_id: egui::Id) -> Option<usize> {
+    if !ui.memory(|m| m.has_focus(text_id))
+        || !ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter))
+    {
+        return None;
+    }
+    egui::TextEdit::load_state(ui.ctx(), text_id)
+        .and_then(|state| state.ccursor_range())
+        .map(|range| range.primary.index)
+}
+
+// the text of a word with each character coloured by how sure the OCR engine was of it, so
+// the doubtful glyphs stand out; characters past the end of confs keep the usual colour
+fn confidence_layout(
+    ui: &egui::Ui,
+    text: &str,
+    confs: &[f32],
+    family: &FontFamily,
+    wrap_width: f32,
+) -> std::sync::Arc<egui::Galley> {
+    let font = egui::FontId::new(
+        egui::TextStyle::Body.resolve(ui.style()).size,
+        family.clone(),
+    );
+    let normal = ui.visuals().text_color();
+    let mut job = egui::text::LayoutJob::default();
+    for (i, c) in text.chars().enumerate() {
+        let color = match confs.get(i) {
+            Some(conf) if *conf < BAD_WCONF_THRESHOLD as f32 => egui::Color32::RED,
+            Some(conf) if *conf < DOUBTFUL_CHAR_CONF => egui::Color32::from_rgb(230, 150, 0),
+            _ => normal,
+        };
+        job.append(
+            c.encode_utf8(&mut [0; 4]),
+            0.0,
+            egui::TextFormat::simple(font.clone(), color),
+        );
+    }
+    job.wrap.max_width = wrap_width;
+    ui.fonts(|f| f.layout_job(job))
+}
+
+// the font family text in lang is shown in, once load_fonts has registered it
+fn lang_font_family(lang: &str) -> FontFamily {
+    FontFamily::Name(format!("lang:{}", lang).into())
+}
+
+// returns the langs whose fonts loaded
+fn load_fonts(
+    ctx: &egui::Context,
+    preview_font: Option<&Path>,
+    lang_fonts: &[(String, PathBuf)],
+) -> HashSet<String> {
     let mut fonts = FontDefinitions::default();
 
     fonts.font_data.insert(
         String::from("Japanese"),
-        FontData::from_static(include_bytes!("resources/NotoSansJP-Regular.ttf")),
+        FontData::from_static(DEFAULT_FONT),
     );
     fonts
         .families
@@ -176,14 +985,62 @@ fn load_fonts(ctx: &egui::Context) {
         .unwrap()
         .push("Japanese".to_owned());
 
+    // the text preview uses the user's font if they picked one, falling back to the ui fonts
+    let mut preview_family = fonts.families[&FontFamily::Proportional].clone();
+    if let Some(path) = preview_font {
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                fonts
+                    .font_data
+                    .insert(String::from("Preview"), FontData::from_owned(bytes));
+                preview_family.insert(0, "Preview".to_owned());
+            }
+            Err(e) => println!("Couldn't load font {}: {}", path.display(), e),
+        }
+    }
+    // each language's font first, then the same fallbacks as the ui
+    let mut loaded = HashSet::new();
+    for (lang, path) in lang_fonts {
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                let name = format!("lang:{}", lang);
+                fonts
+                    .font_data
+                    .insert(name.clone(), FontData::from_owned(bytes));
+                let mut family = fonts.families[&FontFamily::Proportional].clone();
+                family.insert(0, name);
+                fonts.families.insert(lang_font_family(lang), family);
+                loaded.insert(lang.clone());
+            }
+            Err(e) => println!("Couldn't load font {}: {}", path.display(), e),
+        }
+    }
+    fonts
+        .families
+        .insert(FontFamily::Name(PREVIEW_FONT_FAMILY.into()), preview_family);
+
     ctx.set_fonts(fonts);
+    loaded
 }
 
 impl HOCREditor {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        load_fonts(&cc.egui_ctx);
+        // window geometry and panel widths are restored by eframe itself
+        let settings: Settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        let lang_fonts = load_fonts(
+            &cc.egui_ctx,
+            settings.preview_font.as_deref(),
+            &settings.lang_fonts,
+        );
         egui_extras::install_image_loaders(&cc.egui_ctx);
-        Self::default()
+        Self {
+            settings,
+            lang_fonts,
+            ..Self::default()
+        }
     }
     /*
     fn get_selected_elt(&self) -> Option<&OCRElement> {
@@ -211,6 +1068,9 @@ impl HOCREditor {
                 .clone();
             let mut properties = HashMap::new();
             properties.insert("bbox".to_string(), bbox);
+            let lang = ocr_element::inherited_lang(&self.internal_ocr_tree.borrow(), &id)
+                .or_else(|| self.project.language.clone());
+            self.history.borrow_mut().touch();
             let _ = self.internal_ocr_tree.borrow_mut().push_child(
                 &id,
                 OCRElement {
@@ -218,13 +1078,548 @@ impl HOCREditor {
                     ocr_element_type: OCRClass::Word,
                     ocr_properties: properties,
                     ocr_text: "".to_string(),
-                    ocr_lang: None,
+                    ocr_lang: lang,
+                    ..Default::default()
                 },
             );
         }
         *self.parent_id.borrow_mut() = None;
     }
 
+    // separators and photos hang directly off the page containing them
+    fn make_new_region(&self, class: OCRClass, bbox: Rect) {
+        let page = {
+            let tree = self.internal_ocr_tree.borrow();
+            let containing = tree
+                .roots()
+                .find(|id| {
+                    tree.get_node(id)
+                        .and_then(|node| node.ocr_properties.get("bbox"))
+                        .and_then(|prop| prop.as_bbox())
+                        .is_some_and(|page_bbox| page_bbox.contains_rect(bbox))
+                })
+                .or(tree.roots().next())
+                .copied();
+            containing
+        };
+        if let Some(page_id) = page {
+            let mut properties = HashMap::new();
+            properties.insert("bbox".to_string(), OCRProperty::BBox(bbox));
+            self.history.borrow_mut().touch();
+            let res = self.internal_ocr_tree.borrow_mut().push_child(
+                &page_id,
+                OCRElement {
+                    html_element_type: "div".to_string(),
+                    ocr_element_type: class,
+                    ocr_properties: properties,
+                    ocr_text: "".to_string(),
+                    ocr_lang: None,
+                    ..Default::default()
+                },
+            );
+            match res {
+                Ok(id) => *self.selected_id.borrow_mut() = Some(id),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    // a new element with the drawn bbox, one level below the innermost element it fits in:
+    // a line drawn in a paragraph, a word drawn in a line
+    fn make_drawn_element(&self, bbox: Rect) {
+        // a stray click shouldn't leave an empty box behind
+        if bbox.width() < 2.0 || bbox.height() < 2.0 {
+            return;
+        }
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        let (parent, class, lang) = {
+            let tree = self.internal_ocr_tree.borrow();
+            let parent = ocr_element::container_of(&tree, &page, bbox);
+            let class = tree
+                .get_node(&parent)
+                .and_then(|node| node.ocr_element_type.child_class());
+            let lang = ocr_element::inherited_lang(&tree, &parent)
+                .or_else(|| self.project.language.clone());
+            match class {
+                Some(class) => (parent, class, lang),
+                None => return,
+            }
+        };
+        let mut properties = HashMap::new();
+        properties.insert("bbox".to_string(), OCRProperty::BBox(bbox));
+        self.history.borrow_mut().touch();
+        let res = self.internal_ocr_tree.borrow_mut().push_child(
+            &parent,
+            OCRElement {
+                html_element_type: if class.level() < 3 { "div" } else { "span" }.to_string(),
+                // only words are checked against a language
+                ocr_lang: if class == OCRClass::Word { lang } else { None },
+                ocr_element_type: class,
+                ocr_properties: properties,
+                ocr_text: "".to_string(),
+                ..Default::default()
+            },
+        );
+        match res {
+            Ok(id) => *self.selected_id.borrow_mut() = Some(id),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    // outlines grow with the page so they stay readable zoomed in and out of the way zoomed out
+    fn stroke_weight(&self) -> f32 {
+        (self.settings.stroke_weight * self.settings.zoom)
+            .clamp(1.0, self.settings.stroke_weight * 4.0)
+    }
+
+    // drag handles scale the same way but never get too small to grab
+    fn handle_size(&self) -> f32 {
+        (self.settings.handle_size * self.settings.zoom).clamp(6.0, self.settings.handle_size * 4.0)
+    }
+
+    // the font for text in lang: its own if one is set up for it or for its base language
+    // ("ja" for "ja-JP"), the ui font otherwise
+    fn lang_family(&self, lang: Option<&str>) -> FontFamily {
+        let lang = match lang.map(str::trim) {
+            Some(lang) => lang,
+            None => return FontFamily::Proportional,
+        };
+        let base = lang.split(['-', '_']).next().unwrap_or(lang);
+        [lang, base]
+            .into_iter()
+            .find(|lang| self.lang_fonts.contains(*lang))
+            .map_or(FontFamily::Proportional, lang_font_family)
+    }
+
+    // the font for an element's text, by the lang it has or inherits
+    fn element_family(&self, id: InternalID) -> FontFamily {
+        let lang = ocr_element::inherited_lang(&self.internal_ocr_tree.borrow(), &id);
+        self.lang_family(lang.as_deref())
+    }
+
+    // drag out a rect on the image with the separator/photo tools
+    fn draw_region(
+        &mut self,
+        to_screen: &RectTransform,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+    ) {
+        if self.tool == Tool::Select {
+            return;
+        }
+        let draw_response = ui
+            .interact(response.rect, response.id.with("draw"), Sense::drag())
+            .on_hover_cursor(egui::CursorIcon::Crosshair);
+        if draw_response.drag_started() {
+            self.draw_start = draw_response
+                .interact_pointer_pos()
+                .map(|pos| to_screen.inverse().transform_pos(pos));
+        }
+        if let (Some(start), Some(curr)) = (self.draw_start, draw_response.interact_pointer_pos()) {
+            let end = to_screen.inverse().transform_pos(curr);
+            let rect = Rect::from_two_pos(start, end);
+            if draw_response.dragged() {
+                let screen_rect = to_screen.transform_rect(rect);
+                let stroke = egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color);
+                if self.tool == Tool::Measure {
+                    self.measurement = Some((start, end));
+                } else if self.tool == Tool::Guide {
+                    let guide = if rect.width() >= rect.height() {
+                        Guide::Horizontal(start.y)
+                    } else {
+                        Guide::Vertical(start.x)
+                    };
+                    self.draw_guide(guide, to_screen, &response.rect, ui);
+                } else if self.tool == Tool::Separator {
+                    ui.painter().line_segment(center_line(screen_rect), stroke);
+                } else {
+                    ui.painter()
+                        .rect_stroke(screen_rect, egui::Rounding::ZERO, stroke);
+                }
+            }
+            if draw_response.drag_released() {
+                match self.tool {
+                    Tool::Separator => self.make_new_region(OCRClass::Separator, rect),
+                    Tool::Photo => self.make_new_region(OCRClass::Photo, rect),
+                    Tool::Draw => self.make_drawn_element(rect),
+                    Tool::PreviewOcr => self.preview_ocr(rect),
+                    Tool::Assign => self.assign_to_selected(rect),
+                    Tool::Redact => self.redact_region(rect),
+                    Tool::Ignore => self.add_ignore_region(rect),
+                    Tool::Measure => self.measurement = Some((start, end)),
+                    Tool::Guide => self.guides.push(if rect.width() >= rect.height() {
+                        Guide::Horizontal(start.y.round())
+                    } else {
+                        Guide::Vertical(start.x.round())
+                    }),
+                    Tool::Select => (),
+                }
+                self.draw_start = None;
+            }
+        }
+    }
+
+    // "120 px" or "120 px (10.2 mm)" if the page under a has a scan_res
+    fn measurement_text(&self, a: Pos2, b: Pos2) -> String {
+        let px = a.distance(b);
+        let tree = self.internal_ocr_tree.borrow();
+        let dpi = tree
+            .roots()
+            .filter_map(|page| tree.get_node(page))
+            .find(|page| page.bbox().is_some_and(|bbox| bbox.contains(a)))
+            .and_then(|page| page.dpi());
+        match dpi {
+            Some(dpi) => format!("{:.0} px ({:.1} mm)", px, px / dpi * 25.4),
+            None => format!("{:.0} px", px),
+        }
+    }
+
+    fn draw_measurement(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        if let Some((a, b)) = self.measurement {
+            let (screen_a, screen_b) = (to_screen.transform_pos(a), to_screen.transform_pos(b));
+            ui.painter().line_segment(
+                [screen_a, screen_b],
+                egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color),
+            );
+            ui.painter().text(
+                screen_b,
+                egui::Align2::LEFT_BOTTOM,
+                self.measurement_text(a, b),
+                egui::FontId::proportional(14.0),
+                CLICKED_STROKE.color,
+            );
+        }
+    }
+
+    // reparent every element inside the marquee that could be a child of the selection
+    // only the outermost match is moved, so a line keeps its words
+    fn assign_to_selected(&self, marquee: Rect) {
+        let target = match *self.selected_id.borrow() {
+            Some(target) => target,
+            None => return,
+        };
+        self.history.borrow_mut().touch();
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        let (target_level, target_bbox) = match tree.get_node(&target) {
+            Some(node) => (node.ocr_element_type.level(), node.bbox().copied()),
+            None => return,
+        };
+        // only elements on the target's page that would be its direct children
+        let page = search::page_of(&tree, target);
+        let mut moved: Vec<InternalID> = Vec::new();
+        for id in tree.descendants(&page) {
+            let fits = tree.get_node(&id).is_some_and(|node| {
+                node.ocr_element_type.level() == target_level + 1
+                    && node.bbox().is_some_and(|bbox| marquee.contains_rect(*bbox))
+            });
+            if fits && tree.parent(&id) != Some(target) {
+                moved.push(id);
+            }
+        }
+        let mut new_bbox = target_bbox;
+        for id in &moved {
+            match tree.move_node(id, &target) {
+                Ok(()) => {
+                    if let (Some(bbox), Some(child_bbox)) =
+                        (new_bbox.as_mut(), tree.get_node(id).and_then(|n| n.bbox()))
+                    {
+                        *bbox = bbox.union(*child_bbox);
+                    }
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+        // grow the target so its new children don't stick out
+        if let (Some(bbox), Some(node)) = (new_bbox, tree.get_mut_node(&target)) {
+            node.ocr_properties
+                .insert("bbox".to_string(), OCRProperty::BBox(bbox));
+        }
+        println!("Assigned {} elements to {}", moved.len(), target);
+    }
+
+    fn current_page_id(&self) -> Option<InternalID> {
+        self.internal_ocr_tree
+            .borrow()
+            .roots()
+            .nth(self.current_page)
+            .copied()
+    }
+
+    // show the scan of the current page
+    fn update_page_image(&mut self) {
+        let file = page_image_file(
+            &self.internal_ocr_tree.borrow(),
+            self.current_page,
+            self.file_path.as_deref(),
+        );
+        let display = self.scan_display();
+        // said once, not for every page of a book of huge scans
+        let was_downscaled = self.image_full_size.is_some();
+        (self.image_path, self.image_bytes, self.image_full_size) =
+            match file.as_deref().map(|file| image_source(file, display)) {
+                Some((image_uri, bytes, full_size)) => (Some(image_uri), bytes, full_size),
+                None => (None, None, None),
+            };
+        self.image_size = file.as_deref().and_then(dpi::image_size);
+        if let Some(size) = self.image_full_size.filter(|_| !was_downscaled) {
+            self.notices.push(
+                format!(
+                    "The scan of page {} is {}×{}, more than the graphics card can show at once; \
+                     it's shown at {} px",
+                    self.current_page + 1,
+                    size.x,
+                    size.y,
+                    display.max_side
+                ),
+                false,
+            );
+        }
+        self.image_file = file;
+        // a scan at another resolution than the OCR ran on would leave every box out of place
+        self.dpi_wizard_page = match (self.current_page_size(), self.image_size) {
+            (Some(page_size), Some(image_size))
+                if dpi::mismatch(page_size, image_size)
+                    && self.image_mapping(self.current_page).is_none() =>
+            {
+                Some(self.current_page)
+            }
+            _ => None,
+        };
+    }
+
+    // how big a scan can be shown as it is; bigger ones are scaled down
+    fn texture_limit(&self) -> usize {
+        let gpu = match self.max_texture_side {
+            0 => usize::MAX,
+            side => side,
+        };
+        match self.settings.max_texture_size {
+            0 => gpu,
+            max => (max as usize).min(gpu),
+        }
+    }
+
+    fn scan_display(&self) -> ScanDisplay {
+        ScanDisplay {
+            max_side: self.texture_limit(),
+            grayscale: self.settings.scan_grayscale,
+            gamma: self.settings.scan_gamma,
+        }
+    }
+
+    // what the session says to do about page's scan not matching its bbox
+    fn image_mapping(&self, page: usize) -> Option<ImageMapping> {
+        let tree = self.internal_ocr_tree.borrow();
+        let page_id = tree.roots().nth(page)?;
+        self.session
+            .image_mappings
+            .get(&dpi::page_key(&tree, page_id))
+            .copied()
+    }
+
+    fn current_page_size(&self) -> Option<Vec2> {
+        let page = self.current_page_id()?;
+        let tree = self.internal_ocr_tree.borrow();
+        tree.get_node(&page)
+            .and_then(|elt| elt.bbox())
+            .map(|bbox| bbox.size())
+    }
+
+    // switch to another page and select it so its blocks are outlined
+    fn go_to_page(&mut self, index: usize) {
+        let page_count = self.internal_ocr_tree.borrow().roots().len();
+        if page_count == 0 {
+            return;
+        }
+        self.current_page = index.min(page_count - 1);
+        self.update_page_image();
+        *self.selected_id.borrow_mut() = self.current_page_id();
+    }
+
+    // paging through a book keeps the zoom and the same relative spot on the page in view
+    fn flip_to_page(&mut self, index: usize) {
+        self.keep_image_scroll = true;
+        self.go_to_page(index);
+    }
+
+    // selecting something on another page (from the tree, search, ...) brings that page up
+    fn follow_selection(&mut self) {
+        let selected = *self.selected_id.borrow();
+        let page_index = selected.and_then(|id| {
+            let tree = self.internal_ocr_tree.borrow();
+            let page = search::page_of(&tree, id);
+            tree.roots().position(|root| *root == page)
+        });
+        if let Some(index) = page_index {
+            if index != self.current_page {
+                self.current_page = index;
+                self.update_page_image();
+            }
+        }
+    }
+
+    // the scan on disk; relative paths are relative to the hOCR file
+    fn image_file(&self) -> Option<PathBuf> {
+        self.image_file.clone()
+    }
+
+    // mark everything that overlaps the drawn rectangle as redacted
+    fn redact_region(&self, region: Rect) {
+        self.history.borrow_mut().touch();
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        let hits: Vec<InternalID> = tree
+            .preorder()
+            .into_iter()
+            .filter(|id| {
+                tree.get_node(id).is_some_and(|elt| {
+                    elt.ocr_element_type == OCRClass::Word
+                        && elt.bbox().is_some_and(|bbox| bbox.intersects(region))
+                })
+            })
+            .collect();
+        for id in &hits {
+            if let Some(elt) = tree.get_mut_node(id) {
+                redact::set_redacted(elt, true);
+            }
+        }
+        println!("Redacted {} words", hits.len());
+    }
+
+    fn export_redacted(&self) {
+        if self.lazy_doc.is_some() {
+            println!("Redacted export needs every page loaded; reopen without lazy loading");
+            return;
+        }
+        if let (Some(hocr_path), Some(out_dir)) = (&self.file_path, FileDialog::new().pick_folder())
+        {
+            match redact::export(
+                &self.internal_ocr_tree.borrow(),
+                &self.write_head(),
+                hocr_path,
+                &out_dir,
+            ) {
+                Ok(path) => println!("Wrote redacted copy to {}", path.display()),
+                Err(e) => println!("Redacted export failed: {}", e),
+            }
+        }
+    }
+
+    fn export_page_xml(&self) {
+        if self.lazy_doc.is_some() {
+            println!("PAGE XML export needs every page loaded; reopen without lazy loading");
+            return;
+        }
+        if let Some(out_dir) = FileDialog::new().pick_folder() {
+            let stem = self
+                .file_path
+                .as_ref()
+                .and_then(|path| path.file_stem())
+                .map_or("page".to_string(), |s| s.to_string_lossy().to_string());
+            match page_xml::write_pages(&self.internal_ocr_tree.borrow(), &stem, &out_dir) {
+                Ok(paths) => println!(
+                    "Wrote {} PAGE XML files to {}",
+                    paths.len(),
+                    out_dir.display()
+                ),
+                Err(e) => println!("PAGE XML export failed: {}", e),
+            }
+        }
+    }
+
+    fn export_json_tree(&self) {
+        if self.lazy_doc.is_some() {
+            println!("JSON export needs every page loaded; reopen without lazy loading");
+            return;
+        }
+        if let Some(path) = FileDialog::new()
+            .add_filter("JSON tree", &["json"])
+            .set_file_name("tree.json")
+            .save_file()
+        {
+            match json_tree::write(&self.internal_ocr_tree.borrow(), &path) {
+                Ok(()) => println!("Wrote {}", path.display()),
+                Err(e) => println!("JSON export failed: {}", e),
+            }
+        }
+    }
+
+    fn export_word_frequencies(&self) {
+        if self.lazy_doc.is_some() {
+            println!("Word frequencies need every page loaded; reopen without lazy loading");
+            return;
+        }
+        if let Some(path) = FileDialog::new()
+            .add_filter("TSV", &["tsv"])
+            .set_file_name("frequencies.tsv")
+            .save_file()
+        {
+            let mut counts = HashMap::new();
+            stats::count_words(
+                &self.internal_ocr_tree.borrow(),
+                &self.frequency_options,
+                &mut counts,
+            );
+            let frequencies = stats::sorted_frequencies(counts);
+            match atomic::write_atomic(&path, stats::frequencies_to_tsv(&frequencies)) {
+                Ok(()) => println!(
+                    "Wrote {} distinct words to {}",
+                    frequencies.len(),
+                    path.display()
+                ),
+                Err(e) => println!("Couldn't export word frequencies: {}", e),
+            }
+        }
+    }
+
+    fn preview_ocr(&mut self, region: Rect) {
+        if let Some(image) = self.image_file() {
+            self.ocr_preview = Some(ocr::spawn_recognize(image, region));
+        }
+    }
+
+    fn show_ocr_preview(&mut self, ctx: &egui::Context) {
+        // cancelled from the jobs window: tesseract runs on, but nobody wants its text
+        let cancelled = self
+            .ocr_preview
+            .as_mut()
+            .is_some_and(|job| job.finished().is_none() && job.job.progress.is_cancelled());
+        if cancelled {
+            self.ocr_preview = None;
+        }
+        let mut open = self.ocr_preview.is_some();
+        if let Some(job) = &mut self.ocr_preview {
+            egui::Window::new("OCR preview")
+                .open(&mut open)
+                .show(ctx, |ui| match job.finished() {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Recognizing...");
+                        });
+                        ctx.request_repaint();
+                    }
+                    Some(Ok(text)) if text.is_empty() => {
+                        ui.label("No text found");
+                    }
+                    Some(Ok(text)) => {
+                        ui.label(text.as_str());
+                        if ui.button("Copy").clicked() {
+                            ui.output_mut(|o| o.copied_text = text.clone());
+                        }
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(egui::Color32::RED, e.as_str());
+                    }
+                });
+        }
+        if !open {
+            self.ocr_preview = None;
+        }
+    }
+
     fn make_new_sibling(&self) {
         if let Some(id) = *self.sibling_id.borrow() {
             let sibling = self
@@ -233,6 +1628,7 @@ impl HOCREditor {
                 .get_node(&id)
                 .expect(format!("sibling id {} doesn't exist in tree", id).as_str())
                 .clone();
+            self.history.borrow_mut().touch();
             let _ = self.internal_ocr_tree.borrow_mut().add_sibling(
                 &id,
                 sibling,
@@ -244,29 +1640,48 @@ impl HOCREditor {
 
     fn merge(&self) {
         if let Some(id) = *self.merge_id.borrow() {
-            // reparent children of old node
-            self.internal_ocr_tree
-                .borrow_mut()
-                .merge_sibling(&id, &*self.merge_position.borrow());
+            // reparent children of old node, and join the text if they're words
+            self.history.borrow_mut().touch();
+            let kept = ocr_element::merge_with_sibling(
+                &mut self.internal_ocr_tree.borrow_mut(),
+                &id,
+                &self.merge_position.borrow(),
+                &self.settings.merge_separator,
+            );
+            // merging into the sibling before leaves that sibling, not id
+            if kept.is_some() && *self.selected_id.borrow() == Some(id) {
+                *self.selected_id.borrow_mut() = kept;
+            }
         }
         *self.merge_id.borrow_mut() = None;
     }
 
     // TODO: rename
     fn render_tree(&self, ui: &mut egui::Ui) {
+        let line_numbers = search::line_numbers(&self.internal_ocr_tree.borrow());
         egui::ScrollArea::vertical().show(ui, |ui| {
             for root in self.internal_ocr_tree.borrow().roots() {
                 // call renderTreeForRoot on each ocr_page
                 // note that the HOCR specification says that ocr_page MUST be present
-                self.render_tree_for_root(*root, ui);
+                self.render_tree_for_root(*root, &line_numbers, ui);
             }
         });
     }
     // TODO: rename
-    fn render_tree_for_root(&self, root: InternalID, ui: &mut egui::Ui) {
+    fn render_tree_for_root(
+        &self,
+        root: InternalID,
+        line_numbers: &HashMap<InternalID, usize>,
+        ui: &mut egui::Ui,
+    ) {
         let ocr_tree = self.internal_ocr_tree.borrow();
         if let Some(elt) = ocr_tree.get_node(&root) {
-            let label_text = format!("{}{}", elt.ocr_element_type.to_user_str(), {
+            // lines show their number on the page, e.g. "Line 12"
+            let type_text = match line_numbers.get(&root) {
+                Some(n) => format!("{} {}", elt.ocr_element_type.to_user_str(), n),
+                None => elt.ocr_element_type.to_user_str(),
+            };
+            let label_text = format!("{}{}", type_text, {
                 let s = ocr_element::get_root_preview_text(&*ocr_tree, root);
                 if !s.is_empty() {
                     format! {": {}", s}
@@ -283,110 +1698,906 @@ impl HOCREditor {
                 )
                 .show_header(ui, |ui| {
                     // ui.label(label_text)
-                    ui.selectable_value(
-                        &mut *self.selected_id.borrow_mut(),
-                        Some(root),
-                        label_text,
-                    )
-                    .context_menu(|ui| {
-                        if ui.button("Merge below").clicked() {
-                            *self.merge_id.borrow_mut() = Some(root);
-                            *self.merge_position.borrow_mut() = Position::After;
-                        }
-                        if ui.button("Merge above").clicked() {
-                            *self.merge_id.borrow_mut() = Some(root);
-                            *self.merge_position.borrow_mut() = Position::Before;
-                        }
-                        if ui.button("Sibling below").clicked() {
-                            *self.sibling_id.borrow_mut() = Some(root);
-                            *self.sibling_position.borrow_mut() = Position::After;
-                        }
-                        if ui.button("Sibling above").clicked() {
-                            *self.sibling_id.borrow_mut() = Some(root);
-                            *self.sibling_position.borrow_mut() = Position::Before;
-                        }
-                        if ui.button("New child").clicked() {
-                            *self.parent_id.borrow_mut() = Some(root);
-                        }
-                    });
+                    self.tree_row(ui, root, label_text)
+                        .context_menu(|ui| self.element_context_menu(root, ui));
                 })
                 // - body created by recursively calling renderTree on the children
                 .body(|ui| {
                     for child in ocr_tree.children(&root) {
-                        self.render_tree_for_root(*child, ui);
+                        self.render_tree_for_root(*child, line_numbers, ui);
                     }
                 });
             } else {
-                let childless_label_text = format!("{}{}", elt.ocr_element_type.to_user_str(), {
+                let not_loaded = self
+                    .lazy_doc
+                    .as_ref()
+                    .is_some_and(|doc| !doc.is_loaded(&root));
+                let childless_label_text = format!("{}{}", type_text, {
                     if !elt.ocr_text.is_empty() {
                         format! {": {}", elt.ocr_text}
                     } else {
                         String::new()
                     }
                 });
-
-                ui.selectable_value(
-                    &mut *self.selected_id.borrow_mut(),
-                    Some(root),
-                    childless_label_text,
+                let childless_label_text = if ocr_element::lang_mismatch(&ocr_tree, &root).is_some()
+                {
+                    format!("⚠ {}", childless_label_text)
+                } else if elt.needs_review() {
+                    format!("⚑ {}", childless_label_text)
+                } else if not_loaded {
+                    format!("{} (not loaded)", childless_label_text)
+                } else {
+                    childless_label_text
+                };
+
+                self.tree_row(ui, root, childless_label_text)
+                    .context_menu(|ui| self.element_context_menu(root, ui));
+            }
+        }
+    }
+
+    // swap id with the sibling before or after it; reading order is document order, so this
+    // is how a word or line read in the wrong place gets fixed
+    fn move_among_siblings(&self, id: InternalID, pos: Position) {
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        let res = match pos {
+            Position::Before => tree
+                .prev_sibling(&id)
+                .map(|prev| tree.move_before(&id, &prev)),
+            Position::After => tree
+                .next_sibling(&id)
+                .map(|next| tree.move_after(&id, &next)),
+        };
+        match res {
+            Some(Ok(())) => self.history.borrow_mut().touch(),
+            Some(Err(e)) => println!("Couldn't move: {}", e),
+            None => {}
+        }
+    }
+
+    // a row of the tree panel, highlighted if it's part of the selection
+    fn tree_row(&self, ui: &mut egui::Ui, id: InternalID, text: String) -> egui::Response {
+        let before = *self.selected_id.borrow();
+        let selected = before == Some(id) || self.also_selected.borrow().contains(&id);
+        let response = ui
+            .selectable_label(selected, text)
+            .interact(egui::Sense::drag());
+        if response.clicked() {
+            *self.selected_id.borrow_mut() = Some(id);
+            self.ctrl_click(&response, id, before);
+        }
+        if response.drag_started() {
+            self.tree_drag.set(Some(id));
+        }
+        self.tree_drop_target(ui, &response, id);
+        response
+    }
+
+    // while a row is dragged over this one: show where it would go, and send it there when
+    // it's let go
+    fn tree_drop_target(&self, ui: &egui::Ui, response: &egui::Response, target: InternalID) {
+        let dragged = match self.tree_drag.get() {
+            Some(dragged) if dragged != target => dragged,
+            _ => return,
+        };
+        ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+        // other widgets don't count as hovered while something is being dragged
+        let pointer = match ui.ctx().pointer_interact_pos() {
+            Some(pointer) if response.rect.contains(pointer) => pointer,
+            _ => return,
+        };
+        let rect = response.rect;
+        let tree = self.internal_ocr_tree.borrow();
+        let holds_children = tree
+            .get_node(&target)
+            .is_some_and(|elt| elt.ocr_element_type.child_class().is_some());
+        // the middle of a row drops into it, the edges next to it
+        let frac = (pointer.y - rect.top()) / rect.height();
+        let pos = match (holds_children, frac) {
+            (true, f) if f < 0.25 => Some(Position::Before),
+            (true, f) if f > 0.75 => Some(Position::After),
+            (true, _) => None,
+            (false, f) if f < 0.5 => Some(Position::Before),
+            (false, _) => Some(Position::After),
+        };
+        let allowed = structure::can_move(&tree, &dragged, &target, pos);
+        let color = if allowed {
+            ui.visuals().selection.stroke.color
+        } else {
+            ui.visuals().error_fg_color
+        };
+        let stroke = egui::Stroke::new(2.0, color);
+        match pos {
+            Some(Position::Before) => ui.painter().hline(rect.x_range(), rect.top(), stroke),
+            Some(Position::After) => ui.painter().hline(rect.x_range(), rect.bottom(), stroke),
+            None => ui.painter().rect_stroke(rect, 2.0, stroke),
+        }
+        if allowed && ui.input(|i| i.pointer.any_released()) {
+            *self.context_action.borrow_mut() = Some((dragged, ContextAction::Drop(target, pos)));
+        }
+    }
+
+    // the selected element and everything selected along with it, in document order
+    fn selection(&self) -> Vec<InternalID> {
+        let selected = *self.selected_id.borrow();
+        let also = self.also_selected.borrow();
+        if also.is_empty() {
+            return selected.into_iter().collect();
+        }
+        self.internal_ocr_tree
+            .borrow()
+            .preorder()
+            .into_iter()
+            .filter(|id| Some(*id) == selected || also.contains(id))
+            .collect()
+    }
+
+    // ctrl-click adds to or takes away from the selection instead of replacing it; before is
+    // what was selected before the click replaced it
+    fn ctrl_click(&self, response: &egui::Response, id: InternalID, before: Option<InternalID>) {
+        if !response.clicked() || !response.ctx.input(|i| i.modifiers.command) {
+            return;
+        }
+        let mut also = self.also_selected.borrow_mut();
+        let selected = match before {
+            None => Some(id),
+            Some(primary) if primary != id => {
+                if !also.remove(&id) {
+                    also.insert(id);
+                }
+                Some(primary)
+            }
+            // unselecting the main one hands over to one of the others
+            Some(_) => {
+                let next = also.iter().next().copied();
+                if let Some(next) = next {
+                    also.remove(&next);
+                }
+                next
+            }
+        };
+        *self.selected_id.borrow_mut() = selected;
+        self.last_selected.set(selected);
+    }
+
+    // select everything on the current page that's wholly inside rect: elements of the same
+    // kind as the selected one, or words if nothing (or a page) is selected
+    fn select_in_rect(&self, rect: Rect, adding: bool) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        let tree = self.internal_ocr_tree.borrow();
+        let class = (*self.selected_id.borrow())
+            .and_then(|id| tree.get_node(&id))
+            .map(|elt| elt.ocr_element_type.clone())
+            .filter(|class| *class != OCRClass::Page)
+            .unwrap_or(OCRClass::Word);
+        let inside: Vec<InternalID> = tree
+            .descendants(&page)
+            .into_iter()
+            .filter(|id| {
+                tree.get_node(id).is_some_and(|elt| {
+                    elt.ocr_element_type == class
+                        && elt.bbox().is_some_and(|bbox| rect.contains_rect(*bbox))
+                })
+            })
+            .collect();
+        if inside.is_empty() {
+            return;
+        }
+        let mut also = self.also_selected.borrow_mut();
+        let mut selected = self.selected_id.borrow_mut();
+        if !adding || selected.is_none() {
+            also.clear();
+            *selected = Some(inside[0]);
+        }
+        also.extend(inside.into_iter().filter(|id| Some(*id) != *selected));
+        self.last_selected.set(*selected);
+    }
+
+    // shared by the tree panel and the boxes on the image
+    fn element_context_menu(&self, id: InternalID, ui: &mut egui::Ui) {
+        if ui.button("Merge below").clicked() {
+            *self.merge_id.borrow_mut() = Some(id);
+            *self.merge_position.borrow_mut() = Position::After;
+        }
+        if ui.button("Merge above").clicked() {
+            *self.merge_id.borrow_mut() = Some(id);
+            *self.merge_position.borrow_mut() = Position::Before;
+        }
+        if ui.button("Sibling below").clicked() {
+            *self.sibling_id.borrow_mut() = Some(id);
+            *self.sibling_position.borrow_mut() = Position::After;
+        }
+        if ui.button("Sibling above").clicked() {
+            *self.sibling_id.borrow_mut() = Some(id);
+            *self.sibling_position.borrow_mut() = Position::Before;
+        }
+        if ui.button("New child").clicked() {
+            *self.parent_id.borrow_mut() = Some(id);
+        }
+        if ui
+            .button("Duplicate (with children)")
+            .on_hover_text("A copy of this element and everything inside it, right below it")
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Duplicate));
+            ui.close_menu();
+        }
+        let (has_prev, has_next) = {
+            let tree = self.internal_ocr_tree.borrow();
+            (
+                tree.prev_sibling(&id).is_some(),
+                tree.next_sibling(&id).is_some(),
+            )
+        };
+        if ui
+            .add_enabled(
+                has_prev,
+                egui::Button::new("Move up").shortcut_text("Alt+Up"),
+            )
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Move(Position::Before)));
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(
+                has_next,
+                egui::Button::new("Move down").shortcut_text("Alt+Down"),
+            )
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Move(Position::After)));
+            ui.close_menu();
+        }
+        let selection = self.selection();
+        if selection.len() > 1 && selection.contains(&id) {
+            let can_join = ocr_element::can_join(&self.internal_ocr_tree.borrow(), &selection);
+            if ui
+                .add_enabled(
+                    can_join,
+                    egui::Button::new(format!("Merge {} selected", selection.len())),
                 )
-                .context_menu(|ui| {
-                    if ui.button("Merge below").clicked() {
-                        *self.merge_id.borrow_mut() = Some(root);
-                        *self.merge_position.borrow_mut() = Position::After;
+                .on_disabled_hover_text("Only siblings of the same kind can be merged")
+                .clicked()
+            {
+                *self.context_action.borrow_mut() = Some((id, ContextAction::MergeSelected));
+                ui.close_menu();
+            }
+            ui.menu_button("Align", |ui| {
+                for alignment in Alignment::ALL {
+                    if ui
+                        .add_enabled(
+                            selection.len() >= alignment.min_count(),
+                            egui::Button::new(alignment.label()),
+                        )
+                        .clicked()
+                    {
+                        *self.context_action.borrow_mut() =
+                            Some((id, ContextAction::Align(alignment)));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+        let wrapped = self.selection_or(id);
+        let classes = structure::wrapper_classes(&self.internal_ocr_tree.borrow(), &wrapped);
+        if !classes.is_empty() {
+            ui.menu_button("Wrap in new parent", |ui| {
+                for class in classes {
+                    if ui.button(class.to_user_str()).clicked() {
+                        *self.context_action.borrow_mut() = Some((id, ContextAction::Wrap(class)));
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+        let is_page = self
+            .internal_ocr_tree
+            .borrow()
+            .get_node(&id)
+            .is_none_or(|elt| elt.ocr_element_type == OCRClass::Page);
+        let has_children = self.internal_ocr_tree.borrow().has_children(&id);
+        if has_children
+            && ui
+                .button("Sort children by position")
+                .on_hover_text("Top to bottom, then left to right within a row")
+                .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::SortChildren));
+            ui.close_menu();
+        }
+        if !is_page
+            && ui
+                .button("Unwrap")
+                .on_hover_text("Remove this element and keep what's inside it")
+                .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Unwrap));
+            ui.close_menu();
+        }
+        ui.menu_button("Change case", |ui| {
+            for mode in CaseMode::ALL {
+                if ui.button(mode.label()).clicked() {
+                    *self.context_action.borrow_mut() = Some((id, ContextAction::ChangeCase(mode)));
+                    ui.close_menu();
+                }
+            }
+        });
+        let angle = ocr_element::quarter_turns(ocr_element::inherited_text_angle(
+            &self.internal_ocr_tree.borrow(),
+            &id,
+        )) * 90;
+        ui.menu_button("Text direction", |ui| {
+            for (degrees, label) in [
+                (0, "Left to right"),
+                (90, "Bottom to top (90°)"),
+                (180, "Upside down (180°)"),
+                (270, "Top to bottom (270°)"),
+            ] {
+                if ui.radio(angle == degrees, label).clicked() {
+                    *self.context_action.borrow_mut() =
+                        Some((id, ContextAction::SetTextAngle(degrees)));
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.separator();
+        if ui
+            .add(egui::Button::new("Cut").shortcut_text("Ctrl+X"))
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Cut));
+            ui.close_menu();
+        }
+        if ui
+            .add(egui::Button::new("Copy").shortcut_text("Ctrl+C"))
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Copy));
+            ui.close_menu();
+        }
+        for (label, pos) in [
+            ("Paste inside", None),
+            ("Paste above", Some(Position::Before)),
+            ("Paste below", Some(Position::After)),
+        ] {
+            let allowed = self.clipboard.as_ref().is_some_and(|clipboard| {
+                structure::can_paste(&self.internal_ocr_tree.borrow(), clipboard, &id, pos)
+            });
+            let button = match pos {
+                None => egui::Button::new(label).shortcut_text("Ctrl+V"),
+                Some(_) => egui::Button::new(label),
+            };
+            if ui.add_enabled(allowed, button).clicked() {
+                *self.context_action.borrow_mut() = Some((id, ContextAction::Paste(pos)));
+                ui.close_menu();
+            }
+        }
+        if ui.button("Delete").clicked() {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Delete));
+            ui.close_menu();
+        }
+        if ui.button("Export crop").clicked() {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::ExportCrop));
+            ui.close_menu();
+        }
+        let redacted = self
+            .internal_ocr_tree
+            .borrow()
+            .get_node(&id)
+            .is_some_and(redact::is_redacted);
+        if ui
+            .button(if redacted { "Unredact" } else { "Redact" })
+            .clicked()
+        {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::ToggleRedact));
+            ui.close_menu();
+        }
+        let paired = self
+            .internal_ocr_tree
+            .borrow()
+            .get_node(&id)
+            .is_some_and(hyphenation::is_paired);
+        if paired && ui.button("Unpair split word").clicked() {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Unpair));
+            ui.close_menu();
+        }
+        if ui.button("Re-OCR this region").clicked() {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::Reocr));
+            ui.close_menu();
+        }
+        if ui.button("Queue for re-OCR").clicked() {
+            *self.context_action.borrow_mut() = Some((id, ContextAction::QueueReocr));
+            ui.close_menu();
+        }
+    }
+
+    fn align_selected(&mut self, alignment: Alignment) {
+        let selection = self.selection();
+        self.history.borrow_mut().touch();
+        let changed = align::align(
+            &mut self.internal_ocr_tree.borrow_mut(),
+            &selection,
+            alignment,
+        );
+        println!("{}: moved {} boxes", alignment.label(), changed);
+    }
+
+    // the selection if id is part of it, otherwise just id
+    fn selection_or(&self, id: InternalID) -> Vec<InternalID> {
+        let selection = self.selection();
+        if selection.contains(&id) {
+            selection
+        } else {
+            vec![id]
+        }
+    }
+
+    // context menu actions that need &mut self run here, after the panels are drawn
+    fn handle_context_action(&mut self) {
+        let action = self.context_action.borrow_mut().take();
+        match action {
+            Some((id, ContextAction::Delete)) => {
+                // the whole selection if the menu was opened on part of it
+                if !self.selection().contains(&id) {
+                    self.also_selected.borrow_mut().clear();
+                    *self.selected_id.borrow_mut() = Some(id);
+                }
+                self.delete_selected();
+            }
+            Some((id, ContextAction::Cut)) => {
+                if !self.selection().contains(&id) {
+                    self.also_selected.borrow_mut().clear();
+                    *self.selected_id.borrow_mut() = Some(id);
+                }
+                self.copy_selected();
+                self.delete_selected();
+            }
+            Some((id, ContextAction::Copy)) => {
+                if !self.selection().contains(&id) {
+                    self.also_selected.borrow_mut().clear();
+                    *self.selected_id.borrow_mut() = Some(id);
+                }
+                self.copy_selected();
+            }
+            Some((id, ContextAction::Paste(pos))) => self.paste(id, pos),
+            Some((id, ContextAction::Duplicate)) => self.duplicate(id),
+            Some((_, ContextAction::MergeSelected)) => {
+                let selection = self.selection();
+                self.history.borrow_mut().touch();
+                let kept = ocr_element::join_elements(
+                    &mut self.internal_ocr_tree.borrow_mut(),
+                    &selection,
+                    &self.settings.merge_separator,
+                );
+                self.also_selected.borrow_mut().clear();
+                *self.selected_id.borrow_mut() = kept;
+                self.last_selected.set(kept);
+            }
+            Some((_, ContextAction::Align(alignment))) => self.align_selected(alignment),
+            Some((id, ContextAction::Wrap(class))) => {
+                let wrapped = self.selection_or(id);
+                self.history.borrow_mut().touch();
+                let res =
+                    structure::wrap_in(&mut self.internal_ocr_tree.borrow_mut(), &wrapped, class);
+                match res {
+                    Ok(parent) => {
+                        self.also_selected.borrow_mut().clear();
+                        *self.selected_id.borrow_mut() = Some(parent);
                     }
-                    if ui.button("Merge above").clicked() {
-                        *self.merge_id.borrow_mut() = Some(root);
-                        *self.merge_position.borrow_mut() = Position::Before;
+                    // e.g. they aren't siblings
+                    Err(e) => println!("Couldn't wrap: {}", e),
+                }
+            }
+            Some((id, ContextAction::Move(pos))) => self.move_among_siblings(id, pos),
+            Some((id, ContextAction::Drop(target, pos))) => {
+                self.history.borrow_mut().touch();
+                let res =
+                    structure::move_to(&mut self.internal_ocr_tree.borrow_mut(), &id, &target, pos);
+                if let Err(e) = res {
+                    println!("Couldn't move: {}", e);
+                }
+            }
+            Some((id, ContextAction::Unwrap)) => {
+                self.history.borrow_mut().touch();
+                let res = self.internal_ocr_tree.borrow_mut().unwrap(&id);
+                match res {
+                    Ok(children) => {
+                        self.also_selected.borrow_mut().remove(&id);
+                        if *self.selected_id.borrow() == Some(id) {
+                            *self.selected_id.borrow_mut() = children.first().copied();
+                        }
                     }
-                    if ui.button("Sibling below").clicked() {
-                        *self.sibling_id.borrow_mut() = Some(root);
-                        *self.sibling_position.borrow_mut() = Position::After;
+                    Err(e) => println!("Couldn't unwrap: {}", e),
+                }
+            }
+            Some((id, ContextAction::ExportCrop)) => self.export_crop(id),
+            Some((id, ContextAction::SortChildren)) => {
+                let ids = self.selection_or(id);
+                self.history.borrow_mut().touch();
+                let mut tree = self.internal_ocr_tree.borrow_mut();
+                let sorted = ids
+                    .iter()
+                    .filter(|id| ocr_element::sort_children_by_position(&mut tree, id))
+                    .count();
+                println!("Reordered the children of {} elements", sorted);
+            }
+            Some((id, ContextAction::SplitWord(at))) => {
+                self.history.borrow_mut().touch();
+                let res = cleanup::split_word_at(&mut self.internal_ocr_tree.borrow_mut(), &id, at);
+                match res {
+                    // carry on typing in the second half
+                    Some(right) => {
+                        self.also_selected.borrow_mut().clear();
+                        *self.selected_id.borrow_mut() = Some(right);
+                        self.last_selected.set(Some(right));
                     }
-                    if ui.button("Sibling above").clicked() {
-                        *self.sibling_id.borrow_mut() = Some(root);
-                        *self.sibling_position.borrow_mut() = Position::Before;
+                    None => println!("Put the cursor between two characters to split a word"),
+                }
+            }
+            Some((id, ContextAction::SetTextAngle(degrees))) => {
+                let ids = self.selection_or(id);
+                self.history.borrow_mut().touch();
+                let mut tree = self.internal_ocr_tree.borrow_mut();
+                for id in ids {
+                    // straight text only needs saying when the element is inside rotated text
+                    let inherited = tree.parent(&id).map_or(0.0, |parent| {
+                        ocr_element::inherited_text_angle(&tree, &parent)
+                    });
+                    if let Some(elt) = tree.get_mut_node(&id) {
+                        if degrees == 0 && ocr_element::quarter_turns(inherited) == 0 {
+                            elt.ocr_properties.remove("textangle");
+                        } else {
+                            elt.ocr_properties.insert(
+                                "textangle".to_string(),
+                                OCRProperty::Float(degrees as f32),
+                            );
+                        }
                     }
-                    if ui.button("New child").clicked() {
-                        *self.parent_id.borrow_mut() = Some(root);
+                }
+            }
+            Some((id, ContextAction::ChangeCase(mode))) => {
+                self.history.borrow_mut().touch();
+                let mut changed = 0;
+                for id in self.selection_or(id) {
+                    changed += casing::change_case(
+                        &mut self.internal_ocr_tree.borrow_mut(),
+                        &id,
+                        mode,
+                        self.project.language.as_deref(),
+                    );
+                }
+                println!("Changed the case of {} words", changed);
+            }
+            Some((id, ContextAction::ToggleRedact)) => {
+                self.history.borrow_mut().touch();
+                // everything follows the element the menu was opened on
+                let redacted = self
+                    .internal_ocr_tree
+                    .borrow()
+                    .get_node(&id)
+                    .is_some_and(redact::is_redacted);
+                for id in self.selection_or(id) {
+                    if let Some(elt) = self.internal_ocr_tree.borrow_mut().get_mut_node(&id) {
+                        redact::set_redacted(elt, !redacted);
                     }
-                });
+                }
+            }
+            Some((id, ContextAction::Unpair)) => {
+                self.history.borrow_mut().touch();
+                hyphenation::unpair(&mut self.internal_ocr_tree.borrow_mut(), &id);
+            }
+            Some((id, ContextAction::QueueReocr)) if !self.reocr_queue.contains(&id) => {
+                self.reocr_queue.push(id);
+            }
+            // already queued
+            Some((_, ContextAction::QueueReocr)) => (),
+            Some((id, ContextAction::Reocr)) => self.start_reocr(id),
+            None => (),
+        }
+    }
+
+    // save the part of the scan under an element as its own image
+    fn export_crop(&self, id: InternalID) {
+        let (bbox, angle) = {
+            let tree = self.internal_ocr_tree.borrow();
+            match tree.get_node(&id).and_then(|n| n.bbox()) {
+                Some(bbox) => (*bbox, ocr_element::inherited_text_angle(&tree, &id)),
+                None => return,
+            }
+        };
+        if let (Some(image), Some(path)) = (
+            self.image_file(),
+            FileDialog::new()
+                .add_filter("image", &["png", "jpg"])
+                .set_file_name(format!("crop_{}.png", id))
+                .save_file(),
+        ) {
+            match ocr::crop_region(&image, bbox)
+                .map(|crop| ocr::upright(crop, angle))
+                .and_then(|crop| crop.save(&path).map_err(|e| e.to_string()))
+            {
+                Ok(()) => println!("Saved crop to {}", path.display()),
+                Err(e) => println!("Couldn't export crop: {}", e),
+            }
+        }
+    }
+
+    // the part of the scan under a rotated element of the current page, with the quarter turns
+    // that make its text read left to right and the size of its bbox
+    fn upright_strip(&self, id: InternalID) -> Option<(egui::Image<'static>, u32, Vec2)> {
+        let (bbox, turns) = {
+            let tree = self.internal_ocr_tree.borrow();
+            if search::page_of(&tree, id) != self.current_page_id()? {
+                return None;
+            }
+            let turns = ocr_element::quarter_turns(ocr_element::inherited_text_angle(&tree, &id));
+            (*tree.get_node(&id)?.bbox()?, turns)
+        };
+        if turns == 0 {
+            return None;
+        }
+        // the scan's size in the coordinates bboxes use
+        let size = match self.image_mapping(self.current_page) {
+            Some(ImageMapping::ScaleImage) => self.current_page_size()?,
+            _ => self.image_size?,
+        };
+        let image_path = self.image_path.clone()?;
+        let image = match &self.image_bytes {
+            Some(bytes) => egui::Image::from_bytes(image_path, bytes.clone()),
+            None => egui::Image::from_uri(image_path),
+        };
+        let uv = Rect::from_min_max(
+            (bbox.min.to_vec2() / size).to_pos2(),
+            (bbox.max.to_vec2() / size).to_pos2(),
+        );
+        Some((image.uv(uv), turns, bbox.size()))
+    }
+
+    // the scan of the page an element is on, which isn't always the one on screen
+    fn element_image_file(&self, id: InternalID) -> Option<PathBuf> {
+        let tree = self.internal_ocr_tree.borrow();
+        match tree
+            .get_node(&search::page_of(&tree, id))?
+            .ocr_properties
+            .get("image")
+        {
+            Some(OCRProperty::Image(path)) => {
+                Some(redact::resolve_image(path, self.file_path.as_deref()))
+            }
+            _ => None,
+        }
+    }
+
+    // run tesseract over an element's bbox in the background; the result replaces its contents
+    fn start_reocr(&mut self, id: InternalID) {
+        if self.reocr_jobs.iter().any(|job| job.target == id) {
+            return;
+        }
+        let (bbox, angle) = {
+            let tree = self.internal_ocr_tree.borrow();
+            let bbox = match tree.get_node(&id) {
+                Some(elt) if elt.ocr_element_type.is_textual() => elt.bbox().copied(),
+                _ => None,
+            };
+            (bbox, ocr_element::inherited_text_angle(&tree, &id))
+        };
+        match (bbox, self.element_image_file(id)) {
+            (Some(bbox), Some(image)) => self
+                .reocr_jobs
+                .push(ocr::spawn_reocr(image, id, bbox, angle)),
+            (None, _) => println!("Can't re-OCR element {}: it has no bbox or text", id),
+            (_, None) => println!("Can't re-OCR element {}: its page has no image", id),
+        }
+    }
+
+    fn poll_reocr_jobs(&mut self, ctx: &egui::Context) {
+        let mut finished = Vec::new();
+        self.reocr_jobs.retain(|job| match job.take_result() {
+            Some(result) => {
+                finished.push((job.target, job.region, result));
+                false
+            }
+            // tesseract can't be stopped halfway, but what it reads can be ignored
+            None => !job.job.progress.is_cancelled(),
+        });
+        for (target, region, result) in finished {
+            match result {
+                Ok(fresh) => self.apply_reocr(target, region, &fresh),
+                Err(e) => self
+                    .notices
+                    .push(format!("Re-OCR of element {} failed: {}", target, e), true),
+            }
+        }
+        if !self.reocr_jobs.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+    }
+
+    // a word just takes the text read in its box; anything bigger gets tesseract's
+    // elements one level down in place of its children
+    fn apply_reocr(&mut self, target: InternalID, region: Rect, fresh: &Tree<OCRElement>) {
+        let class = match self.internal_ocr_tree.borrow().get_node(&target) {
+            // moved, resized or undone while tesseract was running: it read the wrong part
+            Some(elt) if elt.bbox() != Some(&region) => {
+                self.notices.push(
+                    format!("Element {} changed during re-OCR; try again", target),
+                    true,
+                );
+                return;
+            }
+            Some(elt) => elt.ocr_element_type.clone(),
+            // deleted while tesseract was running
+            None => return,
+        };
+        let fresh_words: Vec<&OCRElement> = fresh
+            .preorder()
+            .iter()
+            .filter_map(|id| fresh.get_node(id))
+            .filter(|elt| elt.ocr_element_type == OCRClass::Word)
+            .collect();
+        // a blank result is more likely a bad crop than an empty element
+        if fresh_words.is_empty() {
+            self.notices
+                .push(format!("Re-OCR found no text in element {}", target), true);
+            return;
+        }
+        self.history.borrow_mut().touch();
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        if class == OCRClass::Word {
+            let text = fresh_words
+                .iter()
+                .map(|elt| elt.ocr_text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let wconf = fresh_words.iter().filter_map(|elt| elt.wconf()).min();
+            // per-character confidences only carry over if they're for the same characters
+            let x_confs = match fresh_words[..] {
+                [word] => word.ocr_properties.get("x_confs").cloned(),
+                _ => None,
+            };
+            if let Some(elt) = tree.get_mut_node(&target) {
+                elt.ocr_text = text;
+                match wconf {
+                    Some(wconf) => elt
+                        .ocr_properties
+                        .insert("x_wconf".to_string(), OCRProperty::UInt(wconf)),
+                    None => elt.ocr_properties.remove("x_wconf"),
+                };
+                match x_confs {
+                    Some(x_confs) => elt.ocr_properties.insert("x_confs".to_string(), x_confs),
+                    None => elt.ocr_properties.remove("x_confs"),
+                };
+            }
+            return;
+        }
+        let old_children: Vec<InternalID> = tree.children(&target).copied().collect();
+        for child in old_children {
+            tree.delete_node(&child);
+        }
+        // the topmost elements of the right level; anything under them comes along
+        let level = class.level() + 1;
+        for id in fresh.preorder() {
+            let is_top = fresh.get_node(&id).is_some_and(|elt| {
+                elt.ocr_element_type.level() == level
+                    && fresh.ancestors(&id).iter().all(|a| {
+                        fresh
+                            .get_node(a)
+                            .is_none_or(|elt| elt.ocr_element_type.level() < level)
+                    })
+            });
+            if is_top {
+                if let Err(e) = tree.graft(&target, fresh, &id) {
+                    println!("Couldn't insert re-OCR result: {}", e);
+                }
             }
         }
     }
 
+    // everything that points into the tree by id goes stale when the tree is replaced
+    fn forget_ids(&mut self) {
+        *self.selected_id.borrow_mut() = None;
+        self.search_results.clear();
+        self.low_conf_words.clear();
+        self.reocr_queue.clear();
+        self.reocr_jobs.clear();
+        if let Some(job) = self.gap_scan.take() {
+            job.cancel();
+        }
+        self.review_items.clear();
+        self.review_resolved.clear();
+        self.split_words.clear();
+        self.corrections.clear();
+        self.duplicate_groups.clear();
+        self.snapshot_diff.clear();
+        self.entity_matches.clear();
+        self.playback = None;
+    }
+
     fn reparse_file(&mut self) {
-        if let Some(path) = &self.file_path {
-            let html_buffer = read_to_string(path).expect("Failed to read file");
-            let mut html_tree = Html::parse_document(&html_buffer);
+        if let Some(path) = self.file_path.clone() {
+            let path = &path;
+            // start from a fresh document so opening a second file doesn't stack heads
+            self.html_write_head = Html::new_document();
+            self.forget_ids();
+            self.save_path = None;
+            self.confirm_overwrite_open = false;
+            self.session = Session::load(path);
+            self.inbox.opened.insert(path.clone());
+            let is_page_xml = page_xml::file_is_page_xml(path);
+            self.imported = is_page_xml || json_tree::file_is_json(path);
+            self.project = ProjectSettings::for_document(path);
+            // the last document's project shouldn't carry over
+            self.export_options = self.project.export_options(&ExportOptions::default());
             // read the ocr parts into an internal tree
-            self.internal_ocr_tree = RefCell::new(OCRElement::html_to_ocr_tree(html_tree.clone()));
-            // set the path of the displayed image
-            // TODO: actually make the loop do smth instead of just outputting last image
-            for root_id in self.internal_ocr_tree.borrow().roots() {
-                if let Some(ocr_prop) = self
-                    .internal_ocr_tree
-                    .borrow()
-                    .get_node(root_id)
-                    .expect(
-                        format!(
-                            "{} was marked as root id but doesn't exist in tree",
-                            root_id
-                        )
-                        .as_str(),
-                    )
-                    .ocr_properties
-                    .get("image")
-                {
-                    match ocr_prop {
-                        OCRProperty::Image(path) => {
-                            let mut s = String::from("file://");
-                            s.push_str(path.as_str());
-                            self.image_path = Some(s);
-                        }
-                        _ => (),
+            let mut html_tree = if self.imported {
+                let source = read_to_string(path).unwrap_or_default();
+                let parsed = if is_page_xml {
+                    page_xml::parse(&source)
+                } else {
+                    json_tree::parse(&source)
+                };
+                match parsed {
+                    Ok(tree) => {
+                        self.lazy_doc = None;
+                        self.internal_ocr_tree = RefCell::new(tree);
+                        Html::parse_document(page_xml::HOCR_SKELETON)
+                    }
+                    Err(e) => {
+                        println!("Couldn't read {}: {}", path.display(), e);
+                        self.file_path_changed = false;
+                        return;
+                    }
+                }
+            } else if self.settings.lazy_pages {
+                match LazyDocument::open(path) {
+                    Ok((doc, tree)) => {
+                        // only the part before the first page needs parsing up front
+                        let head_tree = Html::parse_document(doc.head_source());
+                        self.lazy_doc = Some(doc);
+                        self.internal_ocr_tree = RefCell::new(tree);
+                        head_tree
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        self.file_path_changed = false;
+                        return;
                     }
                 }
+            } else {
+                let html_buffer = read_to_string(path).expect("Failed to read file");
+                let html_tree = Html::parse_document(&html_buffer);
+                self.lazy_doc = None;
+                self.internal_ocr_tree =
+                    RefCell::new(OCRElement::html_to_ocr_tree(html_tree.clone()));
+                html_tree
+            };
+            let migrated =
+                ocr_element::migrate_image_uris(&mut self.internal_ocr_tree.borrow_mut());
+            if migrated > 0 {
+                println!(
+                    "warning: {} pages give their image as a file:// uri; it'll be saved as a plain path",
+                    migrated
+                );
+            }
+            // start on the first page
+            self.current_page = 0;
+            self.update_page_image();
+            if self.settings.normalize_legacy_classes {
+                ocr_element::normalize_legacy_classes(&mut self.internal_ocr_tree.borrow_mut());
+            }
+            self.history
+                .borrow_mut()
+                .reset(&self.internal_ocr_tree.borrow());
+            let problems = structure::structure_problems(&self.internal_ocr_tree.borrow()).len();
+            if problems > 0 {
+                println!(
+                    "{} elements are nested out of spec; Tools > Restructure can fix them",
+                    problems
+                );
             }
             self.file_path_changed = false;
             // copy over the xml, doctype, and head into a new html document
@@ -434,7 +2645,7 @@ impl HOCREditor {
         }
     }
 
-    fn draw_baseline(&self, offset: Vec2, elt_id: &InternalID, ui: &mut egui::Ui) {
+    fn draw_baseline(&self, to_screen: &RectTransform, elt_id: &InternalID, ui: &mut egui::Ui) {
         // draw the baseline
         if let Some(node) = self.internal_ocr_tree.borrow().get_node(elt_id) {
             // the bottom left of the bounding box is the origin, which means we also have to grab the bbox
@@ -444,7 +2655,7 @@ impl HOCREditor {
                     .get("bbox")
                     .expect(format!("Node {} doesn't have a bbox", elt_id).as_str())
                 {
-                    let translated = bbox.translate(offset);
+                    let translated = to_screen.transform_rect(*bbox);
                     // println!("screen coord bbox {:?}", translated);
                     /*
                     let (_, painter) = ui.allocate_painter(Vec2::new(translated.width(), translated.height()), Sense {
@@ -453,7 +2664,7 @@ impl HOCREditor {
                         focusable: false,
                     });
                     */
-                    let y_0 = y_int + translated.bottom();
+                    let y_0 = y_int * to_screen.scale().y + translated.bottom();
                     let l_point = Pos2 {
                         x: translated.left(),
                         y: y_0,
@@ -471,7 +2682,13 @@ impl HOCREditor {
         }
     }
     // TODO: return the rect we drew if successful
-    fn draw_bbox(&self, offset: Vec2, elt_id: &InternalID, ui: &mut egui::Ui) {
+    fn draw_bbox(
+        &self,
+        to_screen: &RectTransform,
+        elt_id: &InternalID,
+        ui: &mut egui::Ui,
+        dimmed: bool,
+    ) {
         if let Some(node) = self.internal_ocr_tree.borrow().get_node(elt_id) {
             if let OCRProperty::BBox(bbox) = node
                 .ocr_properties
@@ -485,34 +2702,66 @@ impl HOCREditor {
                     };
                     wconf < BAD_WCONF_THRESHOLD
                 };
-                let egui_rect = bbox.translate(offset);
-                selectable_rect(
+                let egui_rect = to_screen.transform_rect(*bbox);
+                let before = *self.selected_id.borrow();
+                let response = selectable_rect(
                     ui,
                     egui_rect,
                     &mut *self.selected_id.borrow_mut(),
                     Some(*elt_id),
                     not_confident,
-                );
+                    node.ocr_element_type == OCRClass::Separator,
+                    self.stroke_weight(),
+                    dimmed,
+                )
+                .context_menu(|ui| self.element_context_menu(*elt_id, ui));
+                self.ctrl_click(&response, *elt_id, before);
+                // rotated text gets an arrow along the way it reads, on the element that sets
+                // the angle so its words don't each get one
+                if let Some(angle) = node.text_angle().filter(|angle| *angle != 0.0) {
+                    let dir = Vec2::angled(-angle.to_radians());
+                    let length =
+                        0.6 * (dir.x.abs() * egui_rect.width() + dir.y.abs() * egui_rect.height());
+                    ui.painter().arrow(
+                        egui_rect.center() - dir * length / 2.0,
+                        dir * length,
+                        egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color),
+                    );
+                }
+                // double clicking a container enters it, like groups in a vector editor
+                if response.double_clicked() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        let child = ocr_element::child_at(
+                            &self.internal_ocr_tree.borrow(),
+                            elt_id,
+                            to_screen.inverse().transform_pos(pos),
+                        );
+                        if child.is_some() {
+                            *self.selected_id.borrow_mut() = child;
+                        }
+                    }
+                }
             }
         }
     }
 
     fn drag_baseline(
         &mut self,
-        offset: Vec2,
+        to_screen: &RectTransform,
         elt_id: &InternalID,
         ui: &mut egui::Ui,
         response: &egui::Response,
     ) {
+        self.touch_if_dragging(ui, response, 8..10);
         // draw the baseline
         if let Some(node) = self.internal_ocr_tree.borrow_mut().get_mut_node(elt_id) {
-            let translated = node.ocr_properties.get("bbox").unwrap().as_bbox().unwrap().translate(offset);
+            let translated = to_screen.transform_rect(*node.bbox().unwrap());
             // the bottom left of the bounding box is the origin, which means we also have to grab the bbox
             if let Some(OCRProperty::Baseline(slope, y_int)) =
                 node.ocr_properties.get_mut("baseline")
             {
                     // println!("screen coord bbox {:?}", translated);
-                    let y_0 = *y_int + translated.bottom();
+                    let y_0 = *y_int * to_screen.scale().y + translated.bottom();
                     let y_1 = y_0 + translated.width() * *slope;
                     let l_point = Pos2 {
                         x: translated.left(),
@@ -522,7 +2771,7 @@ impl HOCREditor {
                         x: translated.right(),
                         y: y_1,
                     };
-                    let size = Vec2::splat(16.0);
+                    let size = Vec2::splat(self.handle_size());
                     let left_rect = Rect::from_center_size(l_point, size);
                     let right_rect = Rect::from_center_size(r_point, size);
                     let left_rect_id = response.id.with(8);
@@ -534,7 +2783,7 @@ impl HOCREditor {
                         .interact(right_rect, right_rect_id, Sense::drag())
                         .on_hover_and_drag_cursor(ResizeVertical);
                     // if we drag the left coord, change the y-intercept and the slope
-                    *y_int += left_response.drag_delta().y;
+                    *y_int += left_response.drag_delta().y / to_screen.scale().y;
                     // the slope is now (y_1 + right) - (y_0 + left) / rect.width()
                     *slope = ((y_1 + right_response.drag_delta().y)
                         - (y_0 + left_response.drag_delta().y))
@@ -543,17 +2792,180 @@ impl HOCREditor {
         }
     }
 
+    // close the pending undo step, first unpairing split words it deleted, moved or retyped a
+    // half of
+    fn commit_history(&self) {
+        let mut history = self.history.borrow_mut();
+        if history.is_pending() {
+            let unpaired = hyphenation::unpair_edited(
+                history.committed(),
+                &mut self.internal_ocr_tree.borrow_mut(),
+            );
+            if unpaired > 0 {
+                println!("Unpaired {} halves of edited split words", unpaired);
+            }
+        }
+        history.commit(&self.internal_ocr_tree.borrow());
+    }
+
+    fn undo(&mut self) {
+        self.commit_history();
+        let prev = self
+            .history
+            .borrow_mut()
+            .undo(&self.internal_ocr_tree.borrow());
+        if let Some(tree) = prev {
+            self.restore_history(tree);
+        }
+    }
+
+    fn redo(&mut self) {
+        let next = self.history.borrow_mut().redo();
+        if let Some(tree) = next {
+            self.restore_history(tree);
+        }
+    }
+
+    // undo or redo until the document is as it was after step state (0 is before any edit)
+    fn jump_to_history(&mut self, state: usize) {
+        self.commit_history();
+        let current = self.history.borrow().position();
+        for _ in state..current {
+            self.undo();
+        }
+        for _ in current..state {
+            self.redo();
+        }
+    }
+
+    fn show_history(&mut self, ctx: &egui::Context) {
+        let mut jump = None;
+        egui::SidePanel::right("Edit history").show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.heading("Edit history");
+            });
+            let history = self.history.borrow();
+            let current = history.position();
+            let (done, undone) = history.steps();
+            let oldest = if history.dropped() {
+                "Oldest kept state"
+            } else {
+                "Opened file"
+            };
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                if ui.selectable_label(current == 0, oldest).clicked() {
+                    jump = Some(0);
+                }
+                for (i, step) in done.iter().enumerate() {
+                    if ui.selectable_label(current == i + 1, step).clicked() {
+                        jump = Some(i + 1);
+                    }
+                }
+                // steps that were undone, greyed out until they're redone
+                for (i, step) in undone.enumerate() {
+                    let text = egui::RichText::new(step).weak();
+                    if ui.selectable_label(false, text).clicked() {
+                        jump = Some(current + i + 1);
+                    }
+                }
+            });
+        });
+        if let Some(state) = jump {
+            self.jump_to_history(state);
+        }
+    }
+
+    fn restore_history(&mut self, tree: Tree<OCRElement>) {
+        let selected = *self.selected_id.borrow();
+        if selected.is_some_and(|id| tree.get_node(&id).is_none()) {
+            *self.selected_id.borrow_mut() = None;
+        }
+        *self.internal_ocr_tree.borrow_mut() = tree;
+    }
+
+    // a drag handle (response.id.with(i) for i in handles) being held is an edit in progress
+    fn touch_if_dragging(
+        &self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        handles: std::ops::Range<usize>,
+    ) {
+        if handles
+            .into_iter()
+            .any(|i| ui.memory(|m| m.is_being_dragged(response.id.with(i))))
+        {
+            self.history.borrow_mut().touch();
+        }
+    }
+
+    // a handle on each gap between the words of the selected line (or the selected word's
+    // line); dragging one resizes the words on both sides of it at once
+    fn drag_word_boundaries(
+        &self,
+        to_screen: &RectTransform,
+        elt: &InternalID,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+    ) {
+        let (line_bbox, boundaries) = {
+            let tree = self.internal_ocr_tree.borrow();
+            let line = match tree.get_node(elt) {
+                Some(node) if node.ocr_element_type == OCRClass::Word => match tree.parent(elt) {
+                    Some(line) => line,
+                    None => return,
+                },
+                Some(_) => *elt,
+                None => return,
+            };
+            let line_bbox = match tree.get_node(&line).and_then(|node| node.bbox()) {
+                Some(bbox) => to_screen.transform_rect(*bbox),
+                None => return,
+            };
+            (line_bbox, cleanup::word_boundaries(&tree, &line))
+        };
+        // ids after the ones drag_bbox and drag_baseline use
+        let first_handle = 20;
+        self.touch_if_dragging(ui, response, first_handle..first_handle + boundaries.len());
+        let half = self.handle_size() / 2.0;
+        let stroke = egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color);
+        for (i, (left, right, x)) in boundaries.into_iter().enumerate() {
+            let x = to_screen.transform_pos(Pos2::new(x, 0.0)).x;
+            let handle = Rect::from_x_y_ranges(x - half..=x + half, line_bbox.y_range());
+            let handle_response = ui
+                .interact(handle, response.id.with(first_handle + i), Sense::drag())
+                .on_hover_and_drag_cursor(ResizeHorizontal);
+            let stroke = if handle_response.hovered() || handle_response.dragged() {
+                egui::Stroke::new(stroke.width * 2.0, stroke.color)
+            } else {
+                stroke
+            };
+            ui.painter().vline(x, line_bbox.y_range(), stroke);
+            let dx = handle_response.drag_delta().x / to_screen.scale().x;
+            if dx != 0.0 {
+                cleanup::move_word_boundary(
+                    &mut self.internal_ocr_tree.borrow_mut(),
+                    &left,
+                    &right,
+                    dx,
+                );
+            }
+        }
+    }
+
     // sense drags around the bbox
     fn drag_bbox(
         &mut self,
-        offset: Vec2,
+        to_screen: &RectTransform,
         elt: &InternalID,
         ui: &mut egui::Ui,
         response: &egui::Response,
     ) {
+        self.touch_if_dragging(ui, response, 0..8);
+        self.touch_if_dragging(ui, response, 10..11);
         if let Some(node) = self.internal_ocr_tree.borrow_mut().get_mut_node(&elt) {
             if let Some(OCRProperty::BBox(bbox)) = node.ocr_properties.get_mut("bbox") {
-                let egui_rect = bbox.translate(offset);
+                let egui_rect = to_screen.transform_rect(*bbox);
+                let scale = to_screen.scale();
                 // sense drags around the border of the rect
                 // sense drags in any direction around the corners
                 //                 let point_rect = Rect::from_center_size(point_in_screen, size);
@@ -578,8 +2990,8 @@ impl HOCREditor {
                     x: egui_rect.right(),
                     y: egui_rect.bottom(),
                 };
-                // TODO: is this a good size?
-                let size = Vec2::splat(16.0);
+                let size = Vec2::splat(self.handle_size());
+                let half = size.x / 2.0;
                 let top_left_rect = Rect::from_center_size(top_left, size);
                 let top_right_rect = Rect::from_center_size(top_right, size);
                 let bottom_left_rect = Rect::from_center_size(bottom_left, size);
@@ -602,20 +3014,20 @@ impl HOCREditor {
                     .on_hover_and_drag_cursor(ResizeNwSe);
                 // sense drags in only vertical or horiz at the sides
                 let top_rect = Rect::from_min_max(
-                    top_left + Vec2 { x: 8.0, y: -8.0 },
-                    top_right + Vec2 { x: -8.0, y: 8.0 },
+                    top_left + Vec2 { x: half, y: -half },
+                    top_right + Vec2 { x: -half, y: half },
                 );
                 let bottom_rect = Rect::from_min_max(
-                    bottom_left + Vec2 { x: 8.0, y: -8.0 },
-                    bottom_right + Vec2 { x: -8.0, y: 8.0 },
+                    bottom_left + Vec2 { x: half, y: -half },
+                    bottom_right + Vec2 { x: -half, y: half },
                 );
                 let left_rect = Rect::from_min_max(
-                    top_left + Vec2 { x: -8.0, y: 8.0 },
-                    bottom_left + Vec2 { x: 8.0, y: -8.0 },
+                    top_left + Vec2 { x: -half, y: half },
+                    bottom_left + Vec2 { x: half, y: -half },
                 );
                 let right_rect = Rect::from_min_max(
-                    top_right + Vec2 { x: -8.0, y: -8.0 },
-                    bottom_right + Vec2 { x: 8.0, y: 8.0 },
+                    top_right + Vec2 { x: -half, y: -half },
+                    bottom_right + Vec2 { x: half, y: half },
                 );
                 let top_id = response.id.with(4);
                 let bottom_id = response.id.with(5);
@@ -634,44 +3046,240 @@ impl HOCREditor {
                     .interact(bottom_rect, bottom_id, Sense::drag())
                     .on_hover_and_drag_cursor(ResizeVertical);
                 bbox.min.x = (bbox.min.x
-                    + top_left_response.drag_delta().x
-                    + bottom_left_response.drag_delta().x
-                    + left_response.drag_delta().x)
+                    + (top_left_response.drag_delta().x
+                        + bottom_left_response.drag_delta().x
+                        + left_response.drag_delta().x)
+                        / scale.x)
                     .max(0.0);
                 bbox.min.y = (bbox.min.y
-                    + top_left_response.drag_delta().y
-                    + top_right_response.drag_delta().y
-                    + top_response.drag_delta().y)
+                    + (top_left_response.drag_delta().y
+                        + top_right_response.drag_delta().y
+                        + top_response.drag_delta().y)
+                        / scale.y)
                     .max(0.0);
                 bbox.max.x = (bbox.max.x
-                    + top_right_response.drag_delta().x
-                    + bottom_right_response.drag_delta().x
-                    + right_response.drag_delta().x)
+                    + (top_right_response.drag_delta().x
+                        + bottom_right_response.drag_delta().x
+                        + right_response.drag_delta().x)
+                        / scale.x)
                     .max(0.0);
                 bbox.max.y = (bbox.max.y
-                    + bottom_left_response.drag_delta().y
-                    + bottom_right_response.drag_delta().y
-                    + bottom_response.drag_delta().y)
+                    + (bottom_left_response.drag_delta().y
+                        + bottom_right_response.drag_delta().y
+                        + bottom_response.drag_delta().y)
+                        / scale.y)
                     .max(0.0);
-            }
-        }
+                // dragging the inside moves the whole box, shift keeps it on one axis. Not
+                // while typing in it, where dragging selects text.
+                let inside = egui_rect.shrink(half);
+                if !self.inline_edit && inside.is_positive() {
+                    let move_response = ui
+                        .interact(inside, response.id.with(10), Sense::drag())
+                        .on_hover_and_drag_cursor(egui::CursorIcon::Move);
+                    if move_response.drag_started() {
+                        self.moving_bbox = Some((*bbox, Vec2::ZERO));
+                    }
+                    if let (true, Some((start, offset))) =
+                        (move_response.dragged(), &mut self.moving_bbox)
+                    {
+                        *offset += move_response.drag_delta() / scale;
+                        let mut offset = *offset;
+                        if ui.input(|i| i.modifiers.shift) {
+                            if offset.x.abs() > offset.y.abs() {
+                                offset.y = 0.0;
+                            } else {
+                                offset.x = 0.0;
+                            }
+                        }
+                        // not off the top or left of the page
+                        *bbox = start.translate(offset.max(-start.min.to_vec2()));
+                    }
+                    if move_response.drag_released() {
+                        self.moving_bbox = None;
+                    }
+                }
+                // the edges being dragged jump to a guide when the pointer gets close to one
+                if let Some(pointer) = ui.ctx().pointer_interact_pos() {
+                    let pointer = to_screen.inverse().transform_pos(pointer);
+                    let horizontal = || {
+                        self.guides.iter().filter_map(|guide| match guide {
+                            Guide::Horizontal(y) => Some(*y),
+                            _ => None,
+                        })
+                    };
+                    let vertical = || {
+                        self.guides.iter().filter_map(|guide| match guide {
+                            Guide::Vertical(x) => Some(*x),
+                            _ => None,
+                        })
+                    };
+                    let dragging = |responses: [&egui::Response; 3]| {
+                        responses.iter().any(|response| response.dragged())
+                    };
+                    let (x_tol, y_tol) = (SNAP_DISTANCE / scale.x, SNAP_DISTANCE / scale.y);
+                    if dragging([&top_left_response, &bottom_left_response, &left_response]) {
+                        if let Some(x) = snap_to(vertical(), pointer.x, x_tol) {
+                            bbox.min.x = x;
+                        }
+                    }
+                    if dragging([&top_right_response, &bottom_right_response, &right_response]) {
+                        if let Some(x) = snap_to(vertical(), pointer.x, x_tol) {
+                            bbox.max.x = x;
+                        }
+                    }
+                    if dragging([&top_left_response, &top_right_response, &top_response]) {
+                        if let Some(y) = snap_to(horizontal(), pointer.y, y_tol) {
+                            bbox.min.y = y;
+                        }
+                    }
+                    if dragging([
+                        &bottom_left_response,
+                        &bottom_right_response,
+                        &bottom_response,
+                    ]) {
+                        if let Some(y) = snap_to(horizontal(), pointer.y, y_tol) {
+                            bbox.max.y = y;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_guide(&self, guide: Guide, to_screen: &RectTransform, area: &Rect, ui: &egui::Ui) {
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 200, 255));
+        match guide {
+            Guide::Horizontal(y) => {
+                let y = to_screen.transform_pos(Pos2::new(0.0, y)).y;
+                ui.painter().hline(area.x_range(), y, stroke);
+            }
+            Guide::Vertical(x) => {
+                let x = to_screen.transform_pos(Pos2::new(x, 0.0)).x;
+                ui.painter().vline(x, area.y_range(), stroke);
+            }
+        }
+    }
+
+    // one line per image pixel over the visible part of the image
+    fn draw_pixel_grid(&self, to_screen: &RectTransform, area: &Rect, ui: &egui::Ui) {
+        let visible = area.intersect(ui.clip_rect());
+        let image_rect = to_screen.inverse().transform_rect(visible);
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(40));
+        for x in image_rect.left().floor() as i32..=image_rect.right().ceil() as i32 {
+            let screen_x = to_screen.transform_pos(Pos2::new(x as f32, 0.0)).x;
+            ui.painter().vline(screen_x, visible.y_range(), stroke);
+        }
+        for y in image_rect.top().floor() as i32..=image_rect.bottom().ceil() as i32 {
+            let screen_y = to_screen.transform_pos(Pos2::new(0.0, y as f32)).y;
+            ui.painter().hline(visible.x_range(), screen_y, stroke);
+        }
     }
 
     fn draw_img_and_bboxes(&mut self, ui: &mut egui::Ui) {
         // ui.label(format!("Selected ID: {}", self.selected_id.borrow()));
         if self.image_path.is_some() {
             let image_path = self.image_path.clone().unwrap();
-            egui::ScrollArea::both().show(ui, |ui| {
+            let mut area = egui::ScrollArea::both();
+            if self.two_up {
+                area = self.scroll_link.apply(IMAGE_PANE, area);
+            }
+            if self.keep_image_scroll {
+                self.keep_image_scroll = false;
+                // the new image may not be loaded yet, but its page bbox says how big it'll be
+                let page_size = self.current_page_id().and_then(|page| {
+                    self.internal_ocr_tree
+                        .borrow()
+                        .get_node(&page)
+                        .and_then(|elt| elt.bbox())
+                        .map(|bbox| bbox.size())
+                });
+                if let Some(size) = page_size {
+                    let max_offset =
+                        (size * self.settings.zoom - self.image_view_size).max(egui::Vec2::ZERO);
+                    area = area.scroll_offset(self.image_scroll * max_offset);
+                }
+            }
+            let output = area.show(ui, |ui| {
                 // ui.image(image_path);
-                let response = ui.add(egui::Image::from_uri(image_path).fit_to_original_size(1.0));
+                let image = match &self.image_bytes {
+                    Some(bytes) => egui::Image::from_bytes(image_path, bytes.clone()),
+                    None => egui::Image::from_uri(image_path),
+                };
+                let image = match (
+                    self.image_mapping(self.current_page),
+                    self.current_page_size(),
+                ) {
+                    (Some(ImageMapping::ScaleImage), Some(size)) => image
+                        .maintain_aspect_ratio(false)
+                        .fit_to_exact_size(size * self.settings.zoom),
+                    // a scaled down copy still covers the scan's own pixels
+                    _ => match self.image_full_size {
+                        Some(size) => image.fit_to_exact_size(size * self.settings.zoom),
+                        None => image.fit_to_original_size(self.settings.zoom),
+                    },
+                };
+                let response = ui.add(image);
+                // ctrl+scroll zooms
+                if response.hovered() {
+                    let zoom_delta = ui.input(|i| i.zoom_delta());
+                    if zoom_delta != 1.0 {
+                        self.settings.zoom =
+                            (self.settings.zoom * zoom_delta).clamp(MIN_ZOOM, MAX_ZOOM);
+                    }
+                }
+                // image coords -> screen coords
+                let to_screen = RectTransform::from_to(
+                    Rect::from_min_size(Pos2::ZERO, response.rect.size() / self.settings.zoom),
+                    response.rect,
+                );
+                self.draw_region(&to_screen, ui, &response);
+                // keep showing what's being previewed
+                if let Some(job) = &self.ocr_preview {
+                    ui.painter().rect_stroke(
+                        to_screen.transform_rect(job.region),
+                        egui::Rounding::ZERO,
+                        egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color),
+                    );
+                }
+                if self.noise_window_open {
+                    self.draw_noise_preview(&to_screen, ui);
+                }
+                if self.tool == Tool::Redact {
+                    self.draw_redactions(&to_screen, ui);
+                }
+                self.draw_ignore_regions(&to_screen, ui);
+                if self.tool == Tool::Measure {
+                    self.draw_measurement(&to_screen, ui);
+                }
+                if self.template_open {
+                    self.draw_template(&to_screen, ui);
+                }
+                if self.entities_open {
+                    self.draw_entities(&to_screen, ui);
+                }
+                if self.show_line_numbers {
+                    self.draw_line_numbers(&to_screen, ui);
+                }
+                if self.show_grid && self.settings.zoom >= GRID_MIN_ZOOM {
+                    self.draw_pixel_grid(&to_screen, &response.rect, ui);
+                }
+                for guide in &self.guides {
+                    self.draw_guide(*guide, &to_screen, &response.rect, ui);
+                }
+                self.cursor_pos = response
+                    .hover_pos()
+                    .map(|pos| to_screen.inverse().transform_pos(pos));
                 // if we have a selected ID, draw bboxes for it and its siblings
                 if self.selected_id.borrow().is_some() {
                     let elt = self.selected_id.borrow().unwrap();
-                    let offset = response.rect.min.to_vec2();
-                    self.drag_bbox(offset, &elt, ui, &response);
-                    self.drag_baseline(offset, &elt, ui, &response);
-                    self.draw_bbox(offset, &elt, ui);
-                    self.draw_baseline(offset, &elt, ui);
+                    // before the bbox handles, which would otherwise take the drag
+                    if self.word_boundaries {
+                        self.drag_word_boundaries(&to_screen, &elt, ui, &response);
+                    }
+                    self.drag_bbox(&to_screen, &elt, ui, &response);
+                    self.drag_baseline(&to_screen, &elt, ui, &response);
+                    self.draw_bbox(&to_screen, &elt, ui, false);
+                    self.draw_baseline(&to_screen, &elt, ui);
                     // only draw siblings if we are selecting
                     if self.mode == Mode::Select {
                         for sib_elt in self
@@ -680,63 +3288,3130 @@ impl HOCREditor {
                             .prev_siblings(&elt)
                             .chain(self.internal_ocr_tree.borrow().next_siblings(&elt))
                         {
-                            self.draw_bbox(offset, sib_elt, ui);
+                            self.draw_bbox(&to_screen, sib_elt, ui, true);
+                        }
+                        self.draw_sibling_labels(&to_screen, &elt, ui);
+                    }
+                    // if we are editing, allow the bbox to be draggable
+                    if self.inline_edit {
+                        self.show_inline_editor(&to_screen, &elt, ui);
+                    }
+                }
+                self.draw_also_selected(&to_screen, ui);
+                if self.tool == Tool::Select && self.mode == Mode::Select && !self.inline_edit {
+                    self.drag_select_band(&to_screen, ui, &response);
+                }
+            });
+            if self.two_up {
+                self.scroll_link.record(IMAGE_PANE, &output);
+            }
+            // where we are as a fraction of the page, for flip_to_page
+            let max_offset = (output.content_size - output.inner_rect.size()).max(egui::Vec2::ZERO);
+            self.image_view_size = output.inner_rect.size();
+            self.image_scroll = egui::vec2(
+                if max_offset.x > 0.0 {
+                    output.state.offset.x / max_offset.x
+                } else {
+                    0.0
+                },
+                if max_offset.y > 0.0 {
+                    output.state.offset.y / max_offset.y
+                } else {
+                    0.0
+                },
+            );
+        }
+    }
+
+    fn draw_also_selected(&self, to_screen: &RectTransform, ui: &egui::Ui) {
+        let tree = self.internal_ocr_tree.borrow();
+        let stroke = egui::Stroke::new(self.stroke_weight(), CLICKED_STROKE.color);
+        for id in self.also_selected.borrow().iter() {
+            if let Some(bbox) = tree.get_node(id).and_then(|elt| elt.bbox()) {
+                ui.painter().rect_stroke(
+                    to_screen.transform_rect(*bbox),
+                    egui::Rounding::ZERO,
+                    stroke,
+                );
+            }
+        }
+    }
+
+    // dragging across empty page selects everything inside the box, ctrl adds to the
+    // selection. Added after the boxes so clicks still reach them and the resize handles
+    // still win their drags.
+    fn drag_select_band(
+        &mut self,
+        to_screen: &RectTransform,
+        ui: &mut egui::Ui,
+        response: &egui::Response,
+    ) {
+        let band = ui.interact(response.rect, response.id.with("band"), Sense::drag());
+        if band.drag_started() {
+            self.band_start = band
+                .interact_pointer_pos()
+                .map(|pos| to_screen.inverse().transform_pos(pos));
+        }
+        if let (Some(start), Some(pos)) = (self.band_start, band.interact_pointer_pos()) {
+            let rect = Rect::from_two_pos(start, to_screen.inverse().transform_pos(pos));
+            if band.dragged() {
+                ui.painter().rect_stroke(
+                    to_screen.transform_rect(rect),
+                    egui::Rounding::ZERO,
+                    egui::Stroke::new(1.0, CLICKED_STROKE.color),
+                );
+            }
+            if band.drag_released() {
+                self.select_in_rect(rect, ui.input(|i| i.modifiers.command));
+                self.band_start = None;
+            }
+        }
+    }
+
+    // the other page of the split view, read-only: clicking selects within it, and "Edit"
+    // makes it the page being edited
+    fn show_split_page(&mut self, ui: &mut egui::Ui) {
+        let page_count = self.internal_ocr_tree.borrow().roots().len();
+        if self.split.facing {
+            self.split.page = self.current_page + 1;
+        }
+        let mut edit = false;
+        ui.horizontal(|ui| {
+            ui.label("Page");
+            let mut page = self.split.page + 1;
+            let drag = egui::DragValue::new(&mut page).clamp_range(1..=page_count.max(1));
+            if ui.add_enabled(!self.split.facing, drag).changed() {
+                self.split.page = page - 1;
+                self.split.selected = None;
+            }
+            ui.checkbox(&mut self.split.facing, "Facing")
+                .on_hover_text("Always show the page after the one being edited");
+            let source_name = |snapshot: Option<usize>| match snapshot {
+                Some(i) => self
+                    .session
+                    .snapshots
+                    .get(i)
+                    .map_or(String::new(), |snapshot| snapshot.name.clone()),
+                None => "This document".to_string(),
+            };
+            let mut source = self.split.snapshot;
+            egui::ComboBox::from_id_source("split view source")
+                .selected_text(source_name(source))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut source, None, source_name(None));
+                    for i in 0..self.session.snapshots.len() {
+                        ui.selectable_value(&mut source, Some(i), source_name(Some(i)));
+                    }
+                });
+            if source != self.split.snapshot {
+                self.split.snapshot = source;
+                self.split.snapshot_tree =
+                    source.map(|i| snapshots::restore(&self.session.snapshots[i]));
+                self.split.selected = None;
+            }
+            edit = ui
+                .add_enabled(
+                    self.split.snapshot.is_none() && self.split.page < page_count,
+                    egui::Button::new("Edit"),
+                )
+                .on_hover_text("Edit this page instead")
+                .clicked();
+        });
+        if edit {
+            let selected = self.split.selected.take();
+            self.go_to_page(self.split.page);
+            if selected.is_some() {
+                *self.selected_id.borrow_mut() = selected;
+            }
+            return;
+        }
+        let document_tree = self.internal_ocr_tree.borrow();
+        let tree = self.split.snapshot_tree.as_ref().unwrap_or(&document_tree);
+        let page_id = match tree.roots().nth(self.split.page) {
+            Some(page) => *page,
+            None => {
+                ui.label("No such page");
+                return;
+            }
+        };
+        let file = page_image_file(tree, self.split.page, self.file_path.as_deref());
+        if file != self.split.image_file {
+            let display = self.scan_display();
+            self.split.image = file.as_deref().map(|file| {
+                let (uri, bytes, size) = image_source(file, display);
+                SplitImage { uri, bytes, size }
+            });
+            self.split.image_file = file;
+        }
+        if let Some(selected) = self.split.selected.and_then(|id| tree.get_node(&id)) {
+            ui.label(format!(
+                "{}: {}",
+                selected.ocr_element_type.to_user_str(),
+                selected.ocr_text
+            ));
+        }
+        if self
+            .lazy_doc
+            .as_ref()
+            .is_some_and(|doc| self.split.snapshot.is_none() && !doc.is_loaded(&page_id))
+        {
+            ui.label("This page hasn't been loaded yet");
+        }
+        let SplitImage {
+            uri: image_uri,
+            bytes,
+            size: full_size,
+        } = match &self.split.image {
+            Some(image) => image.clone(),
+            None => {
+                ui.label("This page has no image");
+                return;
+            }
+        };
+        let zoom = self.settings.zoom;
+        let stroke_weight = self.stroke_weight();
+        let page_size = tree
+            .get_node(&page_id)
+            .and_then(|elt| elt.bbox())
+            .map(|bbox| bbox.size());
+        egui::ScrollArea::both()
+            .id_source("split view")
+            .show(ui, |ui| {
+                let image = match bytes {
+                    Some(bytes) => egui::Image::from_bytes(image_uri, bytes),
+                    None => egui::Image::from_uri(image_uri),
+                };
+                let mapping = self
+                    .session
+                    .image_mappings
+                    .get(&dpi::page_key(tree, &page_id));
+                let image = match (mapping, page_size) {
+                    (Some(ImageMapping::ScaleImage), Some(size)) => image
+                        .maintain_aspect_ratio(false)
+                        .fit_to_exact_size(size * zoom),
+                    _ => match full_size {
+                        Some(size) => image.fit_to_exact_size(size * zoom),
+                        None => image.fit_to_original_size(zoom),
+                    },
+                };
+                let response = ui.add(image.sense(Sense::click()));
+                let to_screen = RectTransform::from_to(
+                    Rect::from_min_size(Pos2::ZERO, response.rect.size() / zoom),
+                    response.rect,
+                );
+                // the selection and its siblings, or the page's lines when nothing is selected
+                let (shown, selected): (Vec<InternalID>, Option<InternalID>) =
+                    match self.split.selected {
+                        Some(id) => (
+                            tree.prev_siblings(&id)
+                                .chain(tree.next_siblings(&id))
+                                .copied()
+                                .collect(),
+                            Some(id),
+                        ),
+                        None => (
+                            tree.descendants(&page_id)
+                                .into_iter()
+                                .filter(|id| {
+                                    tree.get_node(id)
+                                        .is_some_and(|elt| elt.ocr_element_type == OCRClass::Line)
+                                })
+                                .collect(),
+                            None,
+                        ),
+                    };
+                for id in shown.iter().chain(selected.iter()) {
+                    if let Some((bbox, is_line)) = tree.get_node(id).and_then(|elt| {
+                        elt.bbox()
+                            .map(|bbox| (*bbox, elt.ocr_element_type == OCRClass::Separator))
+                    }) {
+                        selectable_rect(
+                            ui,
+                            to_screen.transform_rect(bbox),
+                            &mut self.split.selected,
+                            Some(*id),
+                            false,
+                            is_line,
+                            stroke_weight,
+                            Some(*id) != selected,
+                        );
+                    }
+                }
+                // clicking on the page picks the smallest element under the pointer
+                if response.clicked() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        self.split.selected = search::element_at(
+                            tree,
+                            page_id,
+                            to_screen.inverse().transform_pos(pos),
+                        );
+                    }
+                }
+            });
+    }
+
+    // the reading order text of the selected page, one clickable word at a time
+    fn show_two_up_text(&mut self, ui: &mut egui::Ui) {
+        let tree = self.internal_ocr_tree.borrow();
+        let page = match *self.selected_id.borrow() {
+            Some(selected) => search::page_of(&tree, selected),
+            None => match tree.roots().next() {
+                Some(page) => *page,
+                None => return,
+            },
+        };
+        let pars = export::reading_order_lines(&tree, page, Default::default());
+        let area = self.scroll_link.apply(
+            TEXT_PANE,
+            egui::ScrollArea::vertical().id_source("two-up text"),
+        );
+        let output = area.show(ui, |ui| {
+            for par in pars {
+                for line in par {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.selectable_value(&mut *self.selected_id.borrow_mut(), Some(line), "▸")
+                            .on_hover_text("Select the line");
+                        for word in tree.descendants(&line) {
+                            let text = match tree.get_node(&word) {
+                                Some(elt) if elt.ocr_element_type == OCRClass::Word => {
+                                    elt.ocr_text.clone()
+                                }
+                                _ => continue,
+                            };
+                            ui.selectable_value(
+                                &mut *self.selected_id.borrow_mut(),
+                                Some(word),
+                                text,
+                            );
+                        }
+                    });
+                }
+                ui.add_space(8.0);
+            }
+        });
+        self.scroll_link.record(TEXT_PANE, &output);
+    }
+
+    // tag each sibling box with its position and the start of its text, matching the tree panel
+    fn draw_sibling_labels(&self, to_screen: &RectTransform, elt: &InternalID, ui: &mut egui::Ui) {
+        let tree = self.internal_ocr_tree.borrow();
+        let siblings = tree
+            .prev_siblings(elt)
+            .chain(std::iter::once(elt))
+            .chain(tree.next_siblings(elt));
+        for (index, sib_id) in siblings.enumerate() {
+            let bbox = match tree.get_node(sib_id).and_then(|node| node.bbox()) {
+                Some(bbox) => to_screen.transform_rect(*bbox),
+                None => continue,
+            };
+            let preview = ocr_element::get_root_preview_text(&tree, *sib_id);
+            let mut text = format!("{}", index + 1);
+            if !preview.is_empty() {
+                let short: String = preview.chars().take(20).collect();
+                text.push_str(&format!(": {}", short));
+                if short.len() < preview.len() {
+                    text.push('…');
+                }
+            }
+            let lang = ocr_element::inherited_lang(&tree, sib_id);
+            let galley = ui.painter().layout_no_wrap(
+                text,
+                egui::FontId::new(11.0, self.lang_family(lang.as_deref())),
+                egui::Color32::WHITE,
+            );
+            // sit just above the box's top left corner
+            let label_rect = Rect::from_min_size(
+                bbox.left_top() - Vec2::new(0.0, galley.size().y),
+                galley.size(),
+            )
+            .expand(1.0);
+            let background = if sib_id == elt {
+                CLICKED_STROKE.color
+            } else {
+                egui::Color32::from_black_alpha(160)
+            };
+            ui.painter()
+                .rect_filled(label_rect, egui::Rounding::same(2.0), background);
+            ui.painter()
+                .galley(label_rect.min + Vec2::splat(1.0), galley);
+        }
+    }
+
+    // edit a word's text in a box right under its bbox on the scan
+    fn show_inline_editor(
+        &mut self,
+        to_screen: &RectTransform,
+        elt_id: &InternalID,
+        ui: &mut egui::Ui,
+    ) {
+        let family = self.element_family(*elt_id);
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        let node = match tree.get_mut_node(elt_id) {
+            Some(node) if node.ocr_element_type == OCRClass::Word => node,
+            _ => {
+                self.inline_edit = false;
+                return;
+            }
+        };
+        let bbox = match node.bbox() {
+            Some(bbox) => to_screen.transform_rect(*bbox),
+            None => return,
+        };
+        let id = egui::Id::new(INLINE_EDITOR_ID);
+        let confs = node.char_confidences().map(|confs| confs.to_vec());
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            confidence_layout(
+                ui,
+                text,
+                confs.as_deref().unwrap_or_default(),
+                &family,
+                wrap_width,
+            )
+        };
+        let response = egui::Area::new(INLINE_EDITOR_ID)
+            .fixed_pos(bbox.left_bottom())
+            .order(egui::Order::Foreground)
+            .show(ui.ctx(), |ui| {
+                if let Some(at) = split_shortcut(ui, id) {
+                    *self.context_action.borrow_mut() =
+                        Some((*elt_id, ContextAction::SplitWord(at)));
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut node.ocr_text)
+                        .id(id)
+                        .desired_width(bbox.width().max(80.0))
+                        .layouter(&mut layouter),
+                )
+            })
+            .inner;
+        if response.changed() {
+            node.mark_corrected();
+            self.history.borrow_mut().touch();
+        }
+        if response.has_focus() {
+            // keep the IME candidate window next to the word we are correcting instead of
+            // wherever the window manager puts it
+            ui.ctx()
+                .output_mut(|o| o.text_cursor_pos = Some(bbox.left_bottom()));
+        }
+        if response.lost_focus() {
+            self.inline_edit = false;
+        }
+    }
+
+    fn open_file(&mut self) {
+        self.file_path = FileDialog::new()
+            .add_filter("hocr", &["html", "xml", "hocr"])
+            .add_filter("JSON tree", &["json"])
+            .pick_file();
+        self.file_path_changed = true;
+    }
+
+    fn new_from_image(&mut self) {
+        if let Some(job) = &self.import_job {
+            println!("Already importing {}", job.source.display());
+            return;
+        }
+        if let Some(image) = FileDialog::new()
+            .add_filter("image", &["png", "jpg", "jpeg", "tif", "tiff", "bmp"])
+            .pick_file()
+        {
+            self.import_job = Some(ocr::spawn_image_to_hocr(image));
+        }
+    }
+
+    fn open_pdf(&mut self) {
+        if let Some(job) = &self.import_job {
+            println!("Already importing {}", job.source.display());
+            return;
+        }
+        if let Some(pdf) = FileDialog::new().add_filter("PDF", &["pdf"]).pick_file() {
+            // importing again would start the transcription over
+            if let Some(path) = pdf::earlier_import(&pdf) {
+                println!("Opening the earlier import {}", path.display());
+                self.file_path = Some(path);
+                self.file_path_changed = true;
+                return;
+            }
+            self.import_job = Some(pdf::spawn_import(pdf, pdf::DEFAULT_DPI));
+        }
+    }
+
+    // open the new hOCR file once it has been written
+    fn poll_import_job(&mut self, ctx: &egui::Context) {
+        let job = match &self.import_job {
+            Some(job) => job,
+            None => return,
+        };
+        match job.take_result() {
+            Some(res) => {
+                self.notices.finished(&job.job.name, &res, |path| {
+                    format!("Wrote {}", path.display())
+                });
+                if let Ok(path) = res {
+                    self.file_path = Some(path);
+                    self.file_path_changed = true;
+                }
+                self.import_job = None;
+            }
+            None => ctx.request_repaint_after(std::time::Duration::from_millis(100)),
+        }
+    }
+
+    fn export(&mut self, format: ExportFormat) {
+        if self.export_job.is_some() {
+            println!("An export is already running");
+            return;
+        }
+        if self.lazy_doc.is_some() {
+            println!("Export needs every page loaded; reopen without lazy loading");
+            return;
+        }
+        if let Some(path) = FileDialog::new()
+            .add_filter(format.to_user_str(), &[format.extension()])
+            .save_file()
+        {
+            // export a copy so the user can keep editing while pages are written out
+            let snapshot = self.internal_ocr_tree.borrow().clone();
+            self.export_job = Some(export::spawn_export(
+                snapshot,
+                format,
+                self.export_options.clone(),
+                path,
+            ));
+        }
+    }
+
+    // pick up whatever background jobs have finished
+    fn poll_jobs(&mut self, ctx: &egui::Context) {
+        if let Some(job) = &self.export_job {
+            if let Some(res) = job.take_result() {
+                self.notices
+                    .finished(&job.name, &res, |pages| format!("Exported {} pages", pages));
+                self.export_job = None;
+            }
+        }
+        if let Some(job) = &self.gap_scan {
+            if let Some(res) = job.take_result() {
+                self.notices.finished(&job.name, &res, |found| {
+                    format!("Found {} words with a gap in them", found.len())
+                });
+                if let Ok(found) = res {
+                    self.gapped_words = found;
+                }
+                self.gap_scan = None;
+            }
+        }
+        if self.export_job.is_some() || self.gap_scan.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+    }
+
+    fn job_count(&self) -> usize {
+        self.export_job.iter().count()
+            + self.gap_scan.iter().count()
+            + self.reocr_jobs.len()
+            + self.import_job.iter().count()
+            + self
+                .ocr_preview
+                .iter()
+                .filter(|preview| preview.is_running())
+                .count()
+    }
+
+    // one row per job: its name, a progress bar (or a spinner if it can't tell) and cancel
+    fn job_row(ui: &mut egui::Ui, name: &str, progress: &jobs::Progress) {
+        ui.horizontal(|ui| {
+            match progress.fraction() {
+                Some(fraction) => {
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .desired_width(120.0)
+                            .show_percentage(),
+                    );
+                }
+                None => {
+                    ui.spinner();
+                }
+            }
+            ui.label(name);
+            if progress.is_cancelled() {
+                ui.label("cancelling…");
+            } else if ui.small_button("Cancel").clicked() {
+                progress.cancel();
+            }
+        });
+    }
+
+    fn show_jobs(&mut self, ctx: &egui::Context) {
+        self.notices.show(ctx);
+        let mut open = self.jobs_open;
+        egui::Window::new("Jobs").open(&mut open).show(ctx, |ui| {
+            if self.job_count() == 0 {
+                ui.label("Nothing running");
+            }
+            if let Some(job) = &self.export_job {
+                Self::job_row(ui, &job.name, &job.progress);
+            }
+            if let Some(job) = &self.gap_scan {
+                Self::job_row(ui, &job.name, &job.progress);
+            }
+            for job in &self.reocr_jobs {
+                Self::job_row(ui, &job.job.name, &job.job.progress);
+            }
+            if let Some(job) = &self.import_job {
+                Self::job_row(ui, &job.job.name, &job.job.progress);
+            }
+            if let Some(preview) = self.ocr_preview.iter().find(|preview| preview.is_running()) {
+                Self::job_row(ui, &preview.job.name, &preview.job.progress);
+            }
+        });
+        self.jobs_open = open;
+    }
+
+    // in lazy mode, parse the page the selection is on if it hasn't been yet
+    fn load_selected_page(&mut self) {
+        if let (Some(doc), Some(selected)) = (&mut self.lazy_doc, *self.selected_id.borrow()) {
+            let mut tree = self.internal_ocr_tree.borrow_mut();
+            let page = tree
+                .ancestors(&selected)
+                .last()
+                .copied()
+                .unwrap_or(selected);
+            if doc.is_loaded(&page) {
+                return;
+            }
+            tree.reserve_ids(self.history.borrow().next_id());
+            doc.load_page(&mut tree, &page);
+            if self.settings.normalize_legacy_classes {
+                ocr_element::normalize_legacy_classes(&mut tree);
+            }
+            // the history's copies don't have this page yet; without it undoing past here
+            // would unload it
+            self.history.borrow_mut().page_loaded(&tree, &page);
+        }
+    }
+
+    fn show_stats(&mut self, ctx: &egui::Context) {
+        if !self.stats_open {
+            return;
+        }
+        let version = self.history.borrow().version();
+        if self.stats.as_ref().map(|(at, _)| *at) != Some(version) {
+            let stats = stats::compute(&self.internal_ocr_tree.borrow(), BAD_WCONF_THRESHOLD);
+            self.stats = Some((version, stats));
+        }
+        let mut open = self.stats_open;
+        egui::Window::new("Statistics")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let stats = self
+                    .stats
+                    .as_ref()
+                    .map_or_else(Default::default, |(_, stats)| stats.clone());
+                egui::Grid::new("stats grid").num_columns(2).show(ui, |ui| {
+                    for (name, value) in [
+                        ("Pages", stats.pages),
+                        ("Blocks", stats.blocks),
+                        ("Paragraphs", stats.pars),
+                        ("Lines", stats.lines),
+                        ("Words", stats.words),
+                        ("Low confidence words", stats.low_conf_words),
+                    ] {
+                        ui.label(name);
+                        ui.label(value.to_string());
+                        ui.end_row();
+                    }
+                    ui.label("Mean confidence");
+                    ui.label(
+                        stats
+                            .mean_wconf
+                            .map_or("-".to_string(), |c| format!("{:.1}", c)),
+                    );
+                    ui.end_row();
+                    // the undo history keeps copies of the tree too
+                    let history_bytes = self.history.borrow().memory_bytes();
+                    ui.label("Estimated memory");
+                    ui.label(stats::format_bytes(stats.memory_bytes + history_bytes));
+                    ui.end_row();
+                    ui.label("of which undo history");
+                    ui.label(stats::format_bytes(history_bytes));
+                    ui.end_row();
+                });
+                ui.separator();
+                ui.checkbox(&mut self.settings.lazy_pages, "Load pages lazily")
+                    .on_hover_text(
+                        "Only parse a page when it's selected. Applies to the next file opened.",
+                    );
+            });
+        self.stats_open = open;
+    }
+
+    fn pick_watch_folder(&mut self) {
+        if let Some(folder) = FileDialog::new().pick_folder() {
+            self.settings.watch_folder = Some(folder);
+            self.inbox.reset();
+        }
+    }
+
+    // list the hOCR files in the watch folder, newest first; click one to open it
+    fn show_inbox(&mut self, ctx: &egui::Context) {
+        let folder = match &self.settings.watch_folder {
+            Some(folder) => folder.clone(),
+            None => return,
+        };
+        self.inbox.poll(&folder);
+        // keep polling even if the user isn't moving the mouse
+        ctx.request_repaint_after(inbox::POLL_INTERVAL);
+        let mut open = true;
+        egui::Window::new(format!("Inbox ({} new)", self.inbox.unopened_count()))
+            .id(egui::Id::new("Inbox"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(folder.display().to_string());
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for entry in &self.inbox.entries {
+                            let name = entry
+                                .path
+                                .file_name()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            let text = if self.inbox.opened.contains(&entry.path) {
+                                egui::RichText::new(name)
+                            } else {
+                                egui::RichText::new(name).strong()
+                            };
+                            let is_current = self.file_path.as_ref() == Some(&entry.path);
+                            if ui.selectable_label(is_current, text).clicked() {
+                                self.file_path = Some(entry.path.clone());
+                                self.file_path_changed = true;
+                            }
                         }
+                    });
+            });
+        if !open {
+            self.settings.watch_folder = None;
+            self.inbox.reset();
+        }
+    }
+
+    // the head to write: the file's, with the notes of the edits that are in effect
+    fn write_head(&self) -> Html {
+        let mut head = self.html_write_head.clone();
+        for note in self.history.borrow().notes() {
+            calibrate::document(&mut head, note);
+        }
+        head
+    }
+
+    // serialize the tree to path, then run the user's post-save hook if there is one
+    fn write_file(&self, path: &Path) {
+        let options = self.project.write_options();
+        let res = match &self.lazy_doc {
+            // pages nobody touched are copied straight from the original file
+            Some(doc) => {
+                let notes: String = self
+                    .history
+                    .borrow()
+                    .notes()
+                    .map(|note| calibrate::meta_tag(note))
+                    .collect();
+                doc.save(&self.internal_ocr_tree.borrow(), path, &options, &notes)
+            }
+            None if self.settings.stable_save => atomic::write_atomic(
+                path,
+                stable_save::to_html(
+                    &self.internal_ocr_tree.borrow(),
+                    &self.write_head(),
+                    &options,
+                ),
+            ),
+            None => atomic::write_atomic(
+                path,
+                ocr_element::add_as_body_with(
+                    &self.internal_ocr_tree.borrow(),
+                    &self.write_head(),
+                    &options,
+                )
+                .html(),
+            ),
+        };
+        match res {
+            Ok(()) => hooks::run_post_save(&self.settings.post_save_command, path),
+            Err(e) => println!("Failed to save {}: {}", path.display(), e),
+        }
+    }
+
+    // writing over the opened file needs a yes first; after that, or after Save as, it doesn't
+    fn save_file(&mut self) {
+        match &self.save_path {
+            Some(path) => self.write_file(path),
+            // saving hOCR over the PAGE or JSON file it came from would lose the original
+            None if self.imported => self.save_file_as(),
+            None if self.file_path.is_some() => self.confirm_overwrite_open = true,
+            None => (),
+        }
+    }
+
+    fn save_file_as(&mut self) {
+        if self.file_path.is_some() {
+            let path = FileDialog::new()
+                .add_filter("hocr", &["html", "xml", "hocr"])
+                .save_file();
+            if let Some(fp) = path {
+                self.write_file(&fp);
+                self.save_path = Some(fp);
+            }
+        }
+    }
+
+    fn show_confirm_overwrite(&mut self, ctx: &egui::Context) {
+        let path = match (&self.file_path, self.confirm_overwrite_open) {
+            (Some(path), true) => path.clone(),
+            _ => return,
+        };
+        egui::Window::new("Overwrite?")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Save over {}? The file as it was opened will be replaced.",
+                    path.display()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Overwrite").clicked() {
+                        self.write_file(&path);
+                        self.save_path = Some(path.clone());
+                        self.confirm_overwrite_open = false;
+                    }
+                    if ui.button("Save as…").clicked() {
+                        self.confirm_overwrite_open = false;
+                        self.save_file_as();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_overwrite_open = false;
+                    }
+                });
+            });
+    }
+
+    // show the scans again, e.g. at a new size limit
+    fn reload_images(&mut self) {
+        self.update_page_image();
+        self.split.image_file = None;
+    }
+
+    fn show_preferences(&mut self, ctx: &egui::Context) {
+        let mut open = self.preferences_open;
+        let mut reload_image = false;
+        let mut reload_fonts = false;
+        egui::Window::new("Preferences")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("Command to run after saving ({file} is replaced by the saved path):");
+                ui.text_edit_singleline(&mut self.settings.post_save_command);
+                ui.checkbox(
+                    &mut self.settings.normalize_legacy_classes,
+                    "Save hOCR 1.1 classes under their modern names",
+                )
+                .on_hover_text(
+                    "ocr_word, ocrx_block and ocrx_line. Applies to the next file opened.",
+                );
+                ui.checkbox(&mut self.settings.stable_save, "Git-friendly saving")
+                    .on_hover_text(
+                        "Keep ids and property order from the file and write one element per line, \
+                         so saving again only changes the lines you edited",
+                    );
+                ui.horizontal(|ui| {
+                    ui.label("Between the text of merged words:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.settings.merge_separator)
+                            .desired_width(40.0),
+                    );
+                })
+                .response
+                .on_hover_text("e.g. a space; leave empty to run them together");
+                ui.separator();
+                ui.label("Sizes at 100% zoom (they scale with zoom):");
+                ui.add(
+                    egui::Slider::new(&mut self.settings.stroke_weight, 0.5..=12.0)
+                        .text("outline width"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.settings.handle_size, 4.0..=40.0)
+                        .text("drag handle size"),
+                );
+                if ui.button("Reset sizes").clicked() {
+                    self.settings.stroke_weight = settings::DEFAULT_STROKE_WEIGHT;
+                    self.settings.handle_size = settings::DEFAULT_HANDLE_SIZE;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Largest scan shown as it is:");
+                    let response = ui.add(
+                        egui::DragValue::new(&mut self.settings.max_texture_size)
+                            .clamp_range(0..=65536)
+                            .suffix(" px"),
+                    );
+                    // not while dragging: every new size means decoding the scan again
+                    if response.drag_released() || response.lost_focus() {
+                        reload_image = true;
+                    }
+                })
+                .response
+                .on_hover_text(format!(
+                    "Bigger scans are scaled down for showing; boxes keep their real \
+                     coordinates. 0 for the graphics card's limit ({} px).",
+                    self.max_texture_side
+                ));
+                ui.separator();
+                ui.label("Wordlists for spotting rare words in the review queue:")
+                    .on_hover_text(
+                        "One word per line, optionally followed by its count, \
+                         e.g. a word frequency export of text you trust",
+                    );
+                let mut remove = None;
+                for (i, (lang, path)) in self.settings.wordlists.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(if lang.is_empty() {
+                            "any"
+                        } else {
+                            lang.as_str()
+                        });
+                        ui.label(path.display().to_string());
+                        if ui.button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.settings.wordlists.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_wordlist_lang)
+                            .hint_text("lang, e.g. deu")
+                            .desired_width(80.0),
+                    );
+                    if ui.button("Add wordlist…").clicked() {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("wordlist", &["tsv", "txt"])
+                            .pick_file()
+                        {
+                            let lang = self.new_wordlist_lang.trim().to_string();
+                            self.settings.wordlists.retain(|(l, _)| *l != lang);
+                            self.settings.wordlists.push((lang, path));
+                            self.new_wordlist_lang.clear();
+                        }
+                    }
+                });
+                ui.separator();
+                ui.label("Fonts for text in a language, on the scan and in the text preview:")
+                    .on_hover_text(
+                        "By the lang an element has or inherits; \"ja\" also covers \"ja-JP\"",
+                    );
+                let mut remove = None;
+                for (i, (lang, path)) in self.settings.lang_fonts.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(lang);
+                        ui.label(path.display().to_string());
+                        if !self.lang_fonts.contains(lang) {
+                            ui.label(
+                                egui::RichText::new("not loaded")
+                                    .color(ui.visuals().error_fg_color),
+                            );
+                        }
+                        if ui.button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.settings.lang_fonts.remove(i);
+                    reload_fonts = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_font_lang)
+                            .hint_text("lang, e.g. jpn")
+                            .desired_width(80.0),
+                    );
+                    if ui
+                        .add_enabled(
+                            !self.new_font_lang.trim().is_empty(),
+                            egui::Button::new("Add font…"),
+                        )
+                        .clicked()
+                    {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("font", &["ttf", "otf"])
+                            .pick_file()
+                        {
+                            let lang = self.new_font_lang.trim().to_string();
+                            self.settings.lang_fonts.retain(|(l, _)| *l != lang);
+                            self.settings.lang_fonts.push((lang, path));
+                            self.new_font_lang.clear();
+                            reload_fonts = true;
+                        }
+                    }
+                });
+            });
+        self.preferences_open = open;
+        if reload_image {
+            self.reload_images();
+        }
+        if reload_fonts {
+            self.next_lang_fonts = Some(load_fonts(
+                ctx,
+                self.settings.preview_font.as_deref(),
+                &self.settings.lang_fonts,
+            ));
+        }
+    }
+
+    fn load_translit_table(&mut self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("mapping table", &["tsv", "txt"])
+            .pick_file()
+        {
+            match MappingTable::load(&path) {
+                Ok(table) => {
+                    self.translit_tables.push(table);
+                    self.translit_table = Some(self.translit_tables.len() - 1);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    // remember the text box with focus for the on-screen keyboard; clicking a key takes the
+    // focus away, so this keeps the last one until the selection changes
+    fn track_keyboard_target(&mut self, ctx: &egui::Context) {
+        let selected = *self.selected_id.borrow();
+        match ctx.memory(|m| m.focus()) {
+            Some(id) if egui::TextEdit::load_state(ctx, id).is_some() => {
+                self.keyboard_target = Some((id, selected));
+            }
+            _ => {
+                if self
+                    .keyboard_target
+                    .is_some_and(|(_, then)| then != selected)
+                {
+                    self.keyboard_target = None;
+                }
+            }
+        }
+    }
+
+    // clickable keys for characters the physical keyboard can't type
+    fn show_keyboard(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("Keyboard").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (label, text) in parse_keyboard_keys(&self.settings.keyboard_keys) {
+                    // into the text box that last had focus, or the word's text if none has
+                    if ui.button(label).clicked() {
+                        let target = self
+                            .keyboard_target
+                            .map_or(egui::Id::new("word text"), |(id, _)| id);
+                        self.keyboard_insert = Some((target, text));
+                    }
+                }
+            });
+            ui.collapsing("Edit keys", |ui| {
+                ui.label("Keys are separated by spaces; use label=text for a different label.");
+                ui.text_edit_multiline(&mut self.settings.keyboard_keys);
+            });
+        });
+    }
+
+    fn scan_for_noise(&mut self) {
+        if self.lazy_doc.is_some() {
+            self.noise_candidates.clear();
+            return;
+        }
+        self.noise_candidates =
+            cleanup::find_noise(&self.internal_ocr_tree.borrow(), &self.noise_settings);
+    }
+
+    fn scan_for_duplicates(&mut self) {
+        if self.lazy_doc.is_some() {
+            self.duplicate_groups.clear();
+            return;
+        }
+        // 0.8 IoU: the copies come from the same word, so they overlap almost completely
+        self.duplicate_groups = cleanup::find_duplicates(&self.internal_ocr_tree.borrow(), 0.8);
+    }
+
+    fn show_duplicates(&mut self, ctx: &egui::Context) {
+        let mut open = self.duplicates_open;
+        egui::Window::new("Duplicate words")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.lazy_doc.is_some() {
+                    ui.label(
+                        "Finding duplicates needs every page loaded; reopen without lazy loading",
+                    );
+                    return;
+                }
+                if ui.button("Scan").clicked() {
+                    self.scan_for_duplicates();
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for group in &self.duplicate_groups {
+                            let text = match self.internal_ocr_tree.borrow().get_node(&group.keep) {
+                                Some(node) => {
+                                    format!("\"{}\" ×{}", node.ocr_text, group.duplicates.len() + 1)
+                                }
+                                None => continue,
+                            };
+                            ui.selectable_value(
+                                &mut *self.selected_id.borrow_mut(),
+                                Some(group.keep),
+                                text,
+                            );
+                        }
+                    });
+                let count: usize = self
+                    .duplicate_groups
+                    .iter()
+                    .map(|g| g.duplicates.len())
+                    .sum();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Remove {} duplicates", count)),
+                    )
+                    .clicked()
+                {
+                    let selected = *self.selected_id.borrow();
+                    self.history.borrow_mut().touch();
+                    let mut tree = self.internal_ocr_tree.borrow_mut();
+                    for group in self.duplicate_groups.drain(..) {
+                        for id in group.duplicates {
+                            tree.delete_node(&id);
+                            if selected == Some(id) {
+                                *self.selected_id.borrow_mut() = Some(group.keep);
+                            }
+                        }
+                    }
+                }
+            });
+        self.duplicates_open = open;
+    }
+
+    // play from the selected word, or the start of the selected element, to the end of the document
+    fn start_playback(&mut self) {
+        let tree = self.internal_ocr_tree.borrow();
+        let words: Vec<InternalID> = tree
+            .roots()
+            .flat_map(|page| {
+                export::reading_order_words(&tree, *page, export::ReadingOrder::Document)
+            })
+            .collect();
+        let next = match *self.selected_id.borrow() {
+            Some(selected) => {
+                let under_selected = tree.descendants(&selected);
+                words
+                    .iter()
+                    .position(|word| under_selected.contains(word))
+                    .unwrap_or(0)
+            }
+            None => 0,
+        };
+        self.playback = Some(Playback {
+            words,
+            next,
+            last_step: f64::NEG_INFINITY,
+        });
+    }
+
+    // read the selected line or paragraph aloud; a selected word reads its whole line
+    fn read_aloud(&mut self) {
+        let text = match *self.selected_id.borrow() {
+            Some(selected) => {
+                let tree = self.internal_ocr_tree.borrow();
+                let target = match tree.get_node(&selected) {
+                    Some(elt) if elt.ocr_element_type == OCRClass::Word => {
+                        tree.parent(&selected).unwrap_or(selected)
+                    }
+                    _ => selected,
+                };
+                let options = export::ExportOptions {
+                    dehyphenate: true,
+                    ..Default::default()
+                };
+                export::page_to_text(&tree, target, &options)
+            }
+            None => return,
+        };
+        if let Err(e) = self.speech.speak(&text) {
+            println!("Couldn't read aloud: {}", e);
+        }
+    }
+
+    fn step_playback(&mut self, ctx: &egui::Context) {
+        let interval = 1.0 / self.playback_speed.max(0.1) as f64;
+        let now = ctx.input(|i| i.time);
+        let finished = match &mut self.playback {
+            Some(playback) if now - playback.last_step >= interval => {
+                match playback.words.get(playback.next) {
+                    Some(word) => {
+                        *self.selected_id.borrow_mut() = Some(*word);
+                        playback.next += 1;
+                        playback.last_step = now;
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Some(_) => false,
+            None => return,
+        };
+        if finished {
+            self.playback = None;
+        } else {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(interval));
+        }
+    }
+
+    fn save_session(&self) {
+        if let Some(path) = &self.file_path {
+            if let Err(e) = self.session.save(path) {
+                println!("Couldn't save session: {}", e);
+            }
+        }
+    }
+
+    fn show_snapshots(&mut self, ctx: &egui::Context) {
+        let mut open = self.snapshots_open;
+        egui::Window::new("Snapshots")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.lazy_doc.is_some() {
+                    ui.label("Snapshots need every page loaded; reopen without lazy loading");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.snapshot_name);
+                    let name = self.snapshot_name.trim().to_string();
+                    if ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Take snapshot"))
+                        .clicked()
+                    {
+                        let snapshot = snapshots::take(
+                            &name,
+                            &self.internal_ocr_tree.borrow(),
+                            &self.write_head(),
+                        );
+                        self.session.snapshots.push(snapshot);
+                        self.snapshot_name.clear();
+                        self.save_session();
+                    }
+                });
+                ui.separator();
+                let mut revert = None;
+                let mut remove = None;
+                for (i, snapshot) in self.session.snapshots.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(&snapshot.name);
+                        if ui.button("Diff").clicked() {
+                            self.snapshot_diff = snapshots::diff(
+                                &snapshots::restore(snapshot),
+                                &self.internal_ocr_tree.borrow(),
+                            );
+                            if self.snapshot_diff.is_empty() {
+                                println!("No changes since \"{}\"", snapshot.name);
+                            }
+                        }
+                        if ui.button("Revert").clicked() {
+                            revert = Some(i);
+                        }
+                        if ui.button("🗑").on_hover_text("Delete snapshot").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = revert {
+                    let tree = snapshots::restore(&self.session.snapshots[i]);
+                    self.forget_ids();
+                    // reverting is an edit like any other, so it can be undone
+                    self.history.borrow_mut().touch();
+                    *self.internal_ocr_tree.borrow_mut() = tree;
+                }
+                if let Some(i) = remove {
+                    self.session.snapshots.remove(i);
+                    self.save_session();
+                }
+                if !self.snapshot_diff.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for change in &self.snapshot_diff {
+                                match change.id {
+                                    Some(id) => {
+                                        ui.selectable_value(
+                                            &mut *self.selected_id.borrow_mut(),
+                                            Some(id),
+                                            &change.description,
+                                        );
+                                    }
+                                    None => {
+                                        ui.label(&change.description);
+                                    }
+                                }
+                            }
+                        });
+                }
+            });
+        self.snapshots_open = open;
+    }
+
+    fn show_template(&mut self, ctx: &egui::Context) {
+        let mut open = self.template_open;
+        egui::Window::new("Form template")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Load…").clicked() {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("template", &["json"])
+                            .pick_file()
+                        {
+                            match Template::load(&path) {
+                                Ok(template) => self.template = template,
+                                Err(e) => println!("Couldn't load template: {}", e),
+                            }
+                        }
+                    }
+                    if ui.button("Save…").clicked() {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("template", &["json"])
+                            .set_file_name("template.json")
+                            .save_file()
+                        {
+                            if let Err(e) = self.template.save(&path) {
+                                println!("Couldn't save template: {}", e);
+                            }
+                        }
+                    }
+                    if ui
+                        .button("From page")
+                        .on_hover_text("Use the blocks on this page that have a field label")
+                        .clicked()
+                    {
+                        if let Some(page) = self.current_page_id() {
+                            self.template =
+                                Template::from_page(&self.internal_ocr_tree.borrow(), &page);
+                        }
+                    }
+                });
+                ui.separator();
+                let mut remove = None;
+                for (i, region) in self.template.regions.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut region.label);
+                        let [x0, y0, x1, y1] = region.bbox;
+                        ui.label(format!("{:.0} {:.0} {:.0} {:.0}", x0, y0, x1, y1));
+                        if ui.button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.template.regions.remove(i);
+                }
+                // a new region takes the bbox of whatever is selected
+                let selected_bbox = (*self.selected_id.borrow()).and_then(|id| {
+                    self.internal_ocr_tree
+                        .borrow()
+                        .get_node(&id)
+                        .and_then(|elt| elt.bbox())
+                        .copied()
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.new_region_label);
+                    let label = self.new_region_label.trim().to_string();
+                    if ui
+                        .add_enabled(
+                            !label.is_empty() && selected_bbox.is_some(),
+                            egui::Button::new("Add selected"),
+                        )
+                        .clicked()
+                    {
+                        if let Some(bbox) = selected_bbox {
+                            self.template
+                                .regions
+                                .push(TemplateRegion::new(&label, bbox));
+                            self.new_region_label.clear();
+                        }
+                    }
+                });
+                ui.separator();
+                let has_regions = !self.template.regions.is_empty();
+                ui.horizontal(|ui| {
+                    let mut pages = Vec::new();
+                    if ui
+                        .add_enabled(has_regions, egui::Button::new("Apply to page"))
+                        .clicked()
+                    {
+                        pages.extend(self.current_page_id());
+                    }
+                    if ui
+                        .add_enabled(has_regions, egui::Button::new("Apply to all pages"))
+                        .clicked()
+                    {
+                        pages.extend(self.internal_ocr_tree.borrow().roots().copied());
+                    }
+                    if ui
+                        .add_enabled(has_regions, egui::Button::new("Export fields…"))
+                        .on_hover_text("The text in each region of every page, as CSV or JSON")
+                        .clicked()
+                    {
+                        self.export_fields();
+                    }
+                    if !pages.is_empty() {
+                        self.history.borrow_mut().touch();
+                        let mut tree = self.internal_ocr_tree.borrow_mut();
+                        let created: usize = pages
+                            .iter()
+                            .map(|page| template::apply(&mut tree, page, &self.template))
+                            .sum();
+                        println!("Created {} form fields on {} pages", created, pages.len());
+                    }
+                });
+            });
+        self.template_open = open;
+    }
+
+    fn export_fields(&self) {
+        if let Some(path) = FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .add_filter("JSON", &["json"])
+            .set_file_name("fields.csv")
+            .save_file()
+        {
+            let source = self
+                .file_path
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            let records = template::extract_records(
+                &self.internal_ocr_tree.borrow(),
+                &source,
+                &self.template,
+            );
+            match template::write_records(&records, &self.template, &path) {
+                Ok(()) => println!("Exported fields of {} pages", records.len()),
+                Err(e) => println!("Couldn't export fields: {}", e),
+            }
+        }
+    }
+
+    fn find_entities(&mut self) {
+        let (compiled, errors) = highlight::compile(&self.settings.highlighters);
+        self.entity_matches = highlight::find_entities(&self.internal_ocr_tree.borrow(), &compiled);
+        self.entity_errors = errors;
+    }
+
+    fn show_entities(&mut self, ctx: &egui::Context) {
+        let mut open = self.entities_open;
+        egui::Window::new("Entities")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut changed = false;
+                let mut remove = None;
+                for (i, highlighter) in self.settings.highlighters.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut highlighter.enabled, "").changed();
+                        changed |= ui.color_edit_button_srgb(&mut highlighter.color).changed();
+                        ui.add(
+                            egui::TextEdit::singleline(&mut highlighter.name).desired_width(120.0),
+                        );
+                        changed |= ui.text_edit_singleline(&mut highlighter.pattern).changed();
+                        if ui.button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    if let Some((_, e)) = self.entity_errors.iter().find(|(j, _)| *j == i) {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                }
+                if let Some(i) = remove {
+                    self.settings.highlighters.remove(i);
+                    changed = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add").clicked() {
+                        self.settings.highlighters.push(highlight::Highlighter {
+                            name: "New".to_string(),
+                            pattern: String::new(),
+                            color: [255, 255, 0],
+                            enabled: true,
+                        });
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        self.settings.highlighters = highlight::default_highlighters();
+                        changed = true;
+                    }
+                    if ui.button("Find").clicked() {
+                        changed = true;
+                    }
+                });
+                if changed {
+                    self.find_entities();
+                }
+                ui.separator();
+                ui.label(format!("{} matches", self.entity_matches.len()));
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for m in &self.entity_matches {
+                            let (text, name) = match (
+                                self.internal_ocr_tree.borrow().get_node(&m.id),
+                                self.settings.highlighters.get(m.highlighter),
+                            ) {
+                                (Some(elt), Some(highlighter)) => {
+                                    (elt.ocr_text.clone(), highlighter.name.clone())
+                                }
+                                // deleted or edited since the last Find
+                                _ => continue,
+                            };
+                            let selected = *self.selected_id.borrow() == Some(m.id);
+                            if ui
+                                .selectable_label(selected, format!("{}: {}", name, text))
+                                .clicked()
+                            {
+                                *self.selected_id.borrow_mut() = Some(m.id);
+                            }
+                        }
+                    });
+            });
+        self.entities_open = open;
+    }
+
+    // tint the words the highlighters matched on the current page
+    fn draw_entities(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let page = self.current_page_id();
+        let tree = self.internal_ocr_tree.borrow();
+        for m in &self.entity_matches {
+            let (bbox, highlighter) = match (
+                tree.get_node(&m.id).and_then(|elt| elt.bbox()),
+                self.settings.highlighters.get(m.highlighter),
+            ) {
+                (Some(bbox), Some(highlighter)) => (bbox, highlighter),
+                _ => continue,
+            };
+            if Some(search::page_of(&tree, m.id)) != page {
+                continue;
+            }
+            let [r, g, b] = highlighter.color;
+            ui.painter().rect_filled(
+                to_screen.transform_rect(*bbox),
+                egui::Rounding::ZERO,
+                egui::Color32::from_rgba_unmultiplied(r, g, b, 90),
+            );
+        }
+    }
+
+    // outline the template's regions on the current page
+    fn draw_template(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let stroke = egui::Stroke::new(self.stroke_weight(), egui::Color32::from_rgb(200, 0, 200));
+        for region in &self.template.regions {
+            let rect = to_screen.transform_rect(region.rect());
+            ui.painter().rect_stroke(rect, egui::Rounding::ZERO, stroke);
+            ui.painter().text(
+                rect.left_top(),
+                egui::Align2::LEFT_BOTTOM,
+                &region.label,
+                egui::FontId::proportional(12.0),
+                stroke.color,
+            );
+        }
+    }
+
+    fn show_convert_class(&mut self, ctx: &egui::Context) {
+        let mut open = self.convert_open;
+        egui::Window::new("Convert element type")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (label, class) in [
+                        ("from", &mut self.convert_from),
+                        ("to", &mut self.convert_to),
+                    ] {
+                        egui::ComboBox::from_label(label)
+                            .selected_text(class.to_user_str())
+                            .show_ui(ui, |ui| {
+                                for variant in OCRClass::variants() {
+                                    ui.selectable_value(
+                                        class,
+                                        variant.clone(),
+                                        variant.to_user_str(),
+                                    );
+                                }
+                            });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.convert_filter_height, "Only heights from");
+                    ui.add_enabled(
+                        self.convert_filter_height,
+                        egui::DragValue::new(&mut self.convert_height.0).speed(1.0),
+                    );
+                    ui.label("to");
+                    ui.add_enabled(
+                        self.convert_filter_height,
+                        egui::DragValue::new(&mut self.convert_height.1).speed(1.0),
+                    );
+                });
+                if ui.button("Convert all").clicked() {
+                    let height_range = self.convert_filter_height.then_some(self.convert_height);
+                    self.history.borrow_mut().touch();
+                    let count = cleanup::convert_class(
+                        &mut self.internal_ocr_tree.borrow_mut(),
+                        &self.convert_from,
+                        &self.convert_to,
+                        height_range,
+                    );
+                    println!(
+                        "Converted {} {} to {}",
+                        count,
+                        self.convert_from.to_user_str(),
+                        self.convert_to.to_user_str()
+                    );
+                }
+            });
+        self.convert_open = open;
+    }
+
+    fn delete_noise(&mut self) {
+        self.history.borrow_mut().touch();
+        for candidate in self.noise_candidates.iter().filter(|c| c.checked) {
+            self.internal_ocr_tree
+                .borrow_mut()
+                .delete_node(&candidate.id);
+            if *self.selected_id.borrow() == Some(candidate.id) {
+                *self.selected_id.borrow_mut() = None;
+            }
+        }
+        self.noise_candidates.clear();
+    }
+
+    // list everything that looks like scanner noise and let the user delete it in one go
+    fn show_noise_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.noise_window_open;
+        egui::Window::new("Remove noise")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.lazy_doc.is_some() {
+                    ui.label("Removing noise needs every page loaded; reopen without lazy loading");
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.noise_settings.max_area)
+                            .speed(1.0)
+                            .prefix("area below: "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.noise_settings.max_wconf)
+                            .clamp_range(0..=100)
+                            .prefix("wconf below: "),
+                    );
+                });
+                ui.checkbox(
+                    &mut self.noise_settings.flag_lone_punctuation,
+                    "Flag lone punctuation",
+                );
+                if ui.button("Scan").clicked() {
+                    self.scan_for_noise();
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for candidate in self.noise_candidates.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut candidate.checked, "");
+                                let text =
+                                    match self.internal_ocr_tree.borrow().get_node(&candidate.id) {
+                                        Some(node) => {
+                                            format!("\"{}\": {}", node.ocr_text, candidate.reason)
+                                        }
+                                        None => candidate.reason.clone(),
+                                    };
+                                ui.selectable_value(
+                                    &mut *self.selected_id.borrow_mut(),
+                                    Some(candidate.id),
+                                    text,
+                                );
+                            });
+                        }
+                    });
+                let count = self.noise_candidates.iter().filter(|c| c.checked).count();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Delete {} words", count)),
+                    )
+                    .clicked()
+                {
+                    self.delete_noise();
+                }
+            });
+        self.noise_window_open = open;
+    }
+
+    fn run_search(&mut self) {
+        // pages that aren't loaded yet would quietly have no matches
+        if self.lazy_doc.is_some() && self.search_scope == SearchScope::Document {
+            self.search_results.clear();
+            return;
+        }
+        let tree = self.internal_ocr_tree.borrow();
+        let roots = self.search_scope.roots(&tree, *self.selected_id.borrow());
+        self.search_results =
+            search::find_matches(&tree, &roots, &self.search_query, &self.search_options);
+    }
+
+    fn show_search(&mut self, ctx: &egui::Context) {
+        let mut open = self.search_open;
+        egui::Window::new("Search").open(&mut open).show(ctx, |ui| {
+            let mut changed = ui
+                .add(egui::TextEdit::singleline(&mut self.search_query).hint_text("find words"))
+                .changed();
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .checkbox(&mut self.search_options.ignore_case, "Ignore case")
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.search_options.normalize, "Normalize")
+                    .on_hover_text(
+                        "Match ligatures and old letter forms like ſ to their plain letters",
+                    )
+                    .changed();
+                changed |= ui
+                    .checkbox(&mut self.search_options.ignore_diacritics, "Ignore accents")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("search scope")
+                    .selected_text(self.search_scope.to_user_str())
+                    .show_ui(ui, |ui| {
+                        for scope in SearchScope::variants() {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut self.search_scope,
+                                    *scope,
+                                    scope.to_user_str(),
+                                )
+                                .changed();
+                        }
+                    });
+                // the page and selection scopes follow the selection only when asked to
+                changed |= ui.button("Search").clicked();
+            });
+            if changed {
+                self.run_search();
+            }
+            if self.lazy_doc.is_some() && self.search_scope == SearchScope::Document {
+                ui.label("Searching the whole document needs every page loaded; reopen without lazy loading or search the current page");
+            }
+            ui.label(format!("{} matches", self.search_results.len()));
+            let per_page =
+                search::count_per_page(&self.internal_ocr_tree.borrow(), &self.search_results);
+            if per_page.len() > 1 {
+                ui.collapsing("Matches per page", |ui| {
+                    for (page_no, count) in per_page {
+                        ui.label(format!("page {}: {}", page_no + 1, count));
+                    }
+                });
+            }
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for id in &self.search_results {
+                        let text = match self.internal_ocr_tree.borrow().get_node(id) {
+                            Some(node) => node.ocr_text.clone(),
+                            None => continue,
+                        };
+                        ui.selectable_value(&mut *self.selected_id.borrow_mut(), Some(*id), text);
+                    }
+                });
+        });
+        self.search_open = open;
+    }
+
+    fn select_low_confidence(&mut self) {
+        if self.lazy_doc.is_some() && !self.low_conf_page_only {
+            println!("Selecting in the whole document needs every page loaded; reopen without lazy loading or tick Current page only");
+            return;
+        }
+        let tree = self.internal_ocr_tree.borrow();
+        let scope = if self.low_conf_page_only {
+            SearchScope::Page
+        } else {
+            SearchScope::Document
+        };
+        let roots = scope.roots(&tree, *self.selected_id.borrow());
+        self.low_conf_words = cleanup::low_confidence_words(&tree, &roots, self.low_conf_threshold);
+    }
+
+    fn apply_batch_action(&mut self, action: BatchAction) {
+        self.history.borrow_mut().touch();
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        for id in &self.low_conf_words {
+            match action {
+                BatchAction::MarkForReview => {
+                    if let Some(elt) = tree.get_mut_node(id) {
+                        elt.ocr_properties
+                            .insert("x_review".to_string(), OCRProperty::UInt(1));
+                    }
+                }
+                BatchAction::ClearText => {
+                    if let Some(elt) = tree.get_mut_node(id) {
+                        elt.ocr_text.clear();
+                    }
+                }
+                BatchAction::QueueReocr => {
+                    if !self.reocr_queue.contains(id) {
+                        self.reocr_queue.push(*id);
+                    }
+                }
+            }
+        }
+        println!(
+            "{} applied to {} words",
+            action.to_user_str(),
+            self.low_conf_words.len()
+        );
+    }
+
+    // words someone resolved in the review queue, i.e. looked at and let stand
+    fn checked_words(&self) -> Vec<InternalID> {
+        let tree = self.internal_ocr_tree.borrow();
+        let mut words: Vec<InternalID> = self
+            .review_resolved
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| {
+                tree.get_node(id)
+                    .is_some_and(|elt| elt.ocr_element_type == OCRClass::Word)
+            })
+            .collect();
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    fn show_recalibrate(&mut self, ctx: &egui::Context) {
+        let mut open = self.recalibrate_open;
+        let mut apply = false;
+        let checked = self.checked_words().len();
+        egui::Window::new("Recalibrate confidences")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.lazy_doc.is_some() {
+                    ui.label("Recalibrating needs every page loaded; reopen without lazy loading");
+                    return;
+                }
+                let linear = matches!(self.recalibration, Recalibration::Linear { .. });
+                if ui
+                    .radio(linear, "Rescale x_wconf linearly")
+                    .on_hover_text("Words checked by a person keep their 100")
+                    .clicked()
+                    && !linear
+                {
+                    self.recalibration = Recalibration::Linear {
+                        scale: 1.0,
+                        offset: 0.0,
+                    };
+                }
+                if let Recalibration::Linear { scale, offset } = &mut self.recalibration {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(scale)
+                                .speed(0.01)
+                                .clamp_range(0.0..=10.0)
+                                .prefix("× "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(offset)
+                                .clamp_range(-100.0..=100.0)
+                                .prefix("+ "),
+                        );
+                    });
+                }
+                if let Recalibration::Linear { .. } = self.recalibration {
+                    ui.label(format!(
+                        "50 → {}, {} → {}",
+                        self.recalibration.map(50),
+                        BAD_WCONF_THRESHOLD,
+                        self.recalibration.map(BAD_WCONF_THRESHOLD)
+                    ));
+                }
+                if ui
+                    .radio(
+                        !linear,
+                        format!(
+                            "Set the {} words resolved in the review queue to 100",
+                            checked
+                        ),
+                    )
+                    .clicked()
+                {
+                    self.recalibration = Recalibration::CheckedTo100;
+                }
+                ui.separator();
+                ui.label("What was done is noted in a <meta> in the document's head.");
+                if ui.button("Apply").clicked() {
+                    apply = true;
+                }
+            });
+        self.recalibrate_open = open;
+        if apply {
+            self.recalibrate();
+        }
+    }
+
+    fn recalibrate(&mut self) {
+        let checked = self.checked_words();
+        self.history.borrow_mut().touch();
+        let changed = calibrate::apply(
+            &mut self.internal_ocr_tree.borrow_mut(),
+            self.recalibration,
+            &checked,
+        );
+        // the note comes and goes with the step on undo and redo
+        if changed > 0 {
+            self.history
+                .borrow_mut()
+                .note(calibrate::note(&self.recalibration.describe(changed)));
+        }
+        println!("Recalibrated {} words", changed);
+    }
+
+    fn show_low_confidence(&mut self, ctx: &egui::Context) {
+        let mut open = self.low_conf_open;
+        egui::Window::new("Low confidence words")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.low_conf_threshold)
+                            .clamp_range(0..=100)
+                            .prefix("wconf below: "),
+                    );
+                    ui.checkbox(&mut self.low_conf_page_only, "Current page only");
+                    if ui.button("Select").clicked() {
+                        self.select_low_confidence();
+                    }
+                });
+                ui.label(format!("{} words", self.low_conf_words.len()));
+                ui.horizontal(|ui| {
+                    for action in BatchAction::variants() {
+                        if ui
+                            .add_enabled(
+                                !self.low_conf_words.is_empty(),
+                                egui::Button::new(action.to_user_str()),
+                            )
+                            .clicked()
+                        {
+                            self.apply_batch_action(*action);
+                        }
+                    }
+                });
+                if !self.reocr_queue.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} words queued for re-OCR",
+                            self.reocr_queue.len()
+                        ));
+                        if ui.button("Re-OCR now").clicked() {
+                            for id in std::mem::take(&mut self.reocr_queue) {
+                                self.start_reocr(id);
+                            }
+                        }
+                    });
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for id in &self.low_conf_words {
+                            let text = match self.internal_ocr_tree.borrow().get_node(id) {
+                                Some(node) => format!(
+                                    "\"{}\": {}",
+                                    node.ocr_text,
+                                    node.wconf().unwrap_or_default()
+                                ),
+                                None => continue,
+                            };
+                            ui.selectable_value(
+                                &mut *self.selected_id.borrow_mut(),
+                                Some(*id),
+                                text,
+                            );
+                        }
+                    });
+            });
+        self.low_conf_open = open;
+    }
+
+    // (re)read the reference wordlists if they changed in the preferences
+    fn load_wordlists(&mut self) {
+        let wordlists = self.project.wordlists(&self.settings.wordlists);
+        if self.loaded_wordlists == wordlists {
+            return;
+        }
+        self.reference_lists.clear();
+        for (lang, path) in &wordlists {
+            match FrequencyList::load(path) {
+                Ok(list) => {
+                    self.reference_lists.insert(lang.clone(), list);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+        self.loaded_wordlists = wordlists;
+    }
+
+    fn refresh_review_queue(&mut self) {
+        if self.lazy_doc.is_some() {
+            self.review_items.clear();
+            return;
+        }
+        self.load_wordlists();
+        self.review_items = review::collect(
+            &self.internal_ocr_tree.borrow(),
+            BAD_WCONF_THRESHOLD,
+            &self.reference_lists,
+            &self.review_resolved,
+        );
+        review::sort(&mut self.review_items, self.review_sort);
+    }
+
+    fn resolve_review_item(&mut self, index: usize) {
+        let item = self.review_items.remove(index);
+        if item.kind == ReviewKind::Flagged {
+            self.history.borrow_mut().touch();
+            if let Some(elt) = self.internal_ocr_tree.borrow_mut().get_mut_node(&item.id) {
+                elt.ocr_properties.remove("x_review");
+            }
+        } else {
+            self.review_resolved.insert((item.id, item.kind));
+        }
+    }
+
+    fn show_review_queue(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("Review queue").show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.heading("Review queue");
+            });
+            if self.lazy_doc.is_some() {
+                ui.label("The review queue needs every page loaded; reopen without lazy loading");
+                return;
+            }
+            ui.horizontal(|ui| {
+                let old_sort = self.review_sort;
+                egui::ComboBox::from_id_source("review sort")
+                    .selected_text(self.review_sort.to_user_str())
+                    .show_ui(ui, |ui| {
+                        for sort in ReviewSort::variants() {
+                            ui.selectable_value(&mut self.review_sort, *sort, sort.to_user_str());
+                        }
+                    });
+                if old_sort != self.review_sort {
+                    review::sort(&mut self.review_items, self.review_sort);
+                }
+                if ui.button("Refresh").clicked() {
+                    self.refresh_review_queue();
+                }
+            });
+            ui.label(format!("{} items", self.review_items.len()));
+            ui.separator();
+            let mut resolved = None;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (index, item) in self.review_items.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✔").on_hover_text("Resolve").clicked() {
+                            resolved = Some(index);
+                        }
+                        if item.kind == ReviewKind::SizeOutlier
+                            && ui
+                                .small_button("↕")
+                                .on_hover_text("Clamp to the line's height")
+                                .clicked()
+                        {
+                            self.history.borrow_mut().touch();
+                            cleanup::clamp_to_line(
+                                &mut self.internal_ocr_tree.borrow_mut(),
+                                &item.id,
+                            );
+                            resolved = Some(index);
+                        }
+                        if item.kind == ReviewKind::Ignored
+                            && ui
+                                .small_button("🗑")
+                                .on_hover_text("Delete the word")
+                                .clicked()
+                        {
+                            self.history.borrow_mut().touch();
+                            self.internal_ocr_tree.borrow_mut().delete_node(&item.id);
+                            if *self.selected_id.borrow() == Some(item.id) {
+                                *self.selected_id.borrow_mut() = None;
+                            }
+                            resolved = Some(index);
+                        }
+                        let text = match item.wconf {
+                            Some(wconf) => {
+                                format!("[{}] {} ({})", item.kind.to_user_str(), item.detail, wconf)
+                            }
+                            None => format!("[{}] {}", item.kind.to_user_str(), item.detail),
+                        };
+                        ui.selectable_value(
+                            &mut *self.selected_id.borrow_mut(),
+                            Some(item.id),
+                            text,
+                        );
+                    });
+                }
+            });
+            if let Some(index) = resolved {
+                self.resolve_review_item(index);
+            }
+        });
+    }
+
+    fn show_corrections(&mut self, ctx: &egui::Context) {
+        let mut open = self.corrections_open;
+        egui::Window::new("Batch corrections")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if self.lazy_doc.is_some() {
+                    ui.label(
+                        "Batch corrections need every page loaded; reopen without lazy loading",
+                    );
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Load CSV…")
+                        .on_hover_text("Two columns: the wrong word, then the right one")
+                        .clicked()
+                    {
+                        if let Some(path) =
+                            FileDialog::new().add_filter("CSV", &["csv"]).pick_file()
+                        {
+                            match CorrectionList::load(&path) {
+                                Ok(list) => self.correction_list = Some(list),
+                                Err(e) => println!("{}", e),
+                            }
+                        }
+                    }
+                    if let Some(list) = &self.correction_list {
+                        ui.label(format!("{} corrections", list.pairs.len()));
+                        if ui.button("Scan").clicked() {
+                            self.corrections = corrections::find_corrections(
+                                &self.internal_ocr_tree.borrow(),
+                                list,
+                            );
+                        }
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for correction in self.corrections.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut correction.checked, "");
+                                ui.selectable_value(
+                                    &mut *self.selected_id.borrow_mut(),
+                                    Some(correction.id),
+                                    format!("{} → {}", correction.old, correction.new),
+                                );
+                            });
+                        }
+                    });
+                let count = self.corrections.iter().filter(|c| c.checked).count();
+                if ui
+                    .add_enabled(count > 0, egui::Button::new(format!("Apply {}", count)))
+                    .clicked()
+                {
+                    self.history.borrow_mut().touch();
+                    let changed = corrections::apply(
+                        &mut self.internal_ocr_tree.borrow_mut(),
+                        &self.corrections,
+                    );
+                    println!("Corrected {} words", changed);
+                    self.corrections.retain(|c| !c.checked);
+                }
+            });
+        self.corrections_open = open;
+    }
+
+    fn show_split_words(&mut self, ctx: &egui::Context) {
+        let mut open = self.split_words_open;
+        egui::Window::new("Split words")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if ui.button("Scan").clicked() {
+                    self.split_words =
+                        hyphenation::find_split_words(&self.internal_ocr_tree.borrow());
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for split in self.split_words.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut split.checked, "");
+                                let tree = self.internal_ocr_tree.borrow();
+                                let text = |id| {
+                                    tree.get_node(id)
+                                        .map_or(String::new(), |node| node.ocr_text.clone())
+                                };
+                                ui.selectable_value(
+                                    &mut *self.selected_id.borrow_mut(),
+                                    Some(split.first),
+                                    format!("{} / {}", text(&split.first), text(&split.second)),
+                                );
+                                ui.label("→");
+                                ui.text_edit_singleline(&mut split.joined);
+                            });
+                        }
+                    });
+                let count = self.split_words.iter().filter(|s| s.checked).count();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Pair {} words", count)),
+                    )
+                    .clicked()
+                {
+                    self.history.borrow_mut().touch();
+                    let mut tree = self.internal_ocr_tree.borrow_mut();
+                    for split in self.split_words.iter().filter(|s| s.checked) {
+                        hyphenation::pair(&mut tree, split);
+                    }
+                    drop(tree);
+                    self.split_words.retain(|s| !s.checked);
+                }
+            });
+        self.split_words_open = open;
+    }
+
+    fn show_oversegmented(&mut self, ctx: &egui::Context) {
+        let mut open = self.oversegmented_open;
+        egui::Window::new("Join cut words")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.oversegmented_ratio, 0.05..=1.0)
+                            .text("of the usual gap"),
+                    )
+                    .on_hover_text("Words closer together than this are offered for joining");
+                    if ui.button("Scan").clicked() {
+                        self.oversegmented = cleanup::find_oversegmented(
+                            &self.internal_ocr_tree.borrow(),
+                            self.oversegmented_ratio,
+                        );
+                    }
+                });
+                ui.separator();
+                if self.oversegmented.is_empty() {
+                    ui.label("No words found");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for run in self.oversegmented.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut run.checked, "");
+                                let tree = self.internal_ocr_tree.borrow();
+                                let texts: Vec<String> = run
+                                    .words
+                                    .iter()
+                                    .map(|id| {
+                                        tree.get_node(id)
+                                            .map_or(String::new(), |node| node.ocr_text.clone())
+                                    })
+                                    .collect();
+                                ui.selectable_value(
+                                    &mut *self.selected_id.borrow_mut(),
+                                    Some(run.words[0]),
+                                    format!("{} → {}", texts.join(" | "), texts.concat()),
+                                )
+                                .on_hover_text(format!(
+                                    "{} px apart, {} px usual on this line",
+                                    run.gap as i32, run.line_gap as i32
+                                ));
+                            });
+                        }
+                    });
+                let count = self.oversegmented.iter().filter(|run| run.checked).count();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Join {} words", count)),
+                    )
+                    .clicked()
+                {
+                    self.history.borrow_mut().touch();
+                    let mut tree = self.internal_ocr_tree.borrow_mut();
+                    for run in self.oversegmented.iter().filter(|run| run.checked) {
+                        ocr_element::join_elements(&mut tree, &run.words, "");
+                    }
+                    drop(tree);
+                    self.oversegmented.retain(|run| !run.checked);
+                }
+            });
+        self.oversegmented_open = open;
+    }
+
+    // look for blank stretches inside words on the scans of this page or every page
+    // every page image has to be opened, so this runs as a job
+    fn scan_for_gaps(&mut self, all_pages: bool) {
+        if let Some(job) = self.gap_scan.take() {
+            job.cancel();
+        }
+        let tree = self.internal_ocr_tree.borrow().clone();
+        // the page, its image and whether the scan is stretched over it
+        let pages: Vec<(InternalID, PathBuf, bool)> = tree
+            .roots()
+            .copied()
+            .enumerate()
+            .filter(|(i, _)| all_pages || *i == self.current_page)
+            // nothing to look at on a page that hasn't been parsed yet
+            .filter(|(_, page)| self.lazy_doc.as_ref().is_none_or(|doc| doc.is_loaded(page)))
+            .filter_map(|(i, page)| {
+                let file = page_image_file(&tree, i, self.file_path.as_deref())?;
+                let stretched = self
+                    .session
+                    .image_mappings
+                    .get(&dpi::page_key(&tree, &page))
+                    == Some(&ImageMapping::ScaleImage);
+                Some((page, file, stretched))
+            })
+            .collect();
+        let ratio = self.gapped_ratio;
+        let name = if all_pages {
+            "Look for glued words".to_string()
+        } else {
+            format!("Look for glued words on page {}", self.current_page + 1)
+        };
+        self.gapped_words.clear();
+        self.gap_scan = Some(Job::spawn(name, pages.len(), move |progress| {
+            let mut found = Vec::new();
+            for (page, file, stretched) in pages {
+                progress.check()?;
+                let image = match image::open(&file) {
+                    Ok(image) => image.to_luma8(),
+                    Err(e) => {
+                        println!("Failed to open {}: {}", file.display(), e);
+                        progress.advance(1);
+                        continue;
+                    }
+                };
+                // boxes are in image pixels unless the scan is stretched over the page
+                let page_size = tree
+                    .get_node(&page)
+                    .and_then(|elt| elt.bbox())
+                    .map(|b| b.size());
+                let scale = match page_size {
+                    Some(size) if stretched && size.x > 0.0 && size.y > 0.0 => Vec2::new(
+                        image.width() as f32 / size.x,
+                        image.height() as f32 / size.y,
+                    ),
+                    _ => Vec2::splat(1.0),
+                };
+                found.extend(cleanup::find_gapped_words(
+                    &tree, &page, &image, scale, ratio,
+                ));
+                progress.advance(1);
+            }
+            Ok(found)
+        }));
+    }
+
+    fn show_edited(&mut self, ctx: &egui::Context) {
+        if !self.edited_open {
+            return;
+        }
+        let version = self.history.borrow().version();
+        if self.edited_version != Some(version) {
+            let (edited, deleted) = history::edited_elements(
+                self.history.borrow().opened(),
+                &self.internal_ocr_tree.borrow(),
+            );
+            self.edited = edited;
+            self.edited_deleted = deleted;
+            self.edited_version = Some(version);
+        }
+        let mut open = self.edited_open;
+        egui::Window::new("Edited this session")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.edited_filter);
+                });
+                let filter = self.edited_filter.to_lowercase();
+                let tree = self.internal_ocr_tree.borrow();
+                let rows: Vec<(InternalID, String, &String)> = self
+                    .edited
+                    .iter()
+                    .filter_map(|(id, what)| {
+                        let elt = tree.get_node(id)?;
+                        let text = format!("{:?} {}", elt.ocr_element_type, elt.ocr_text.trim());
+                        let shown = filter.is_empty()
+                            || text.to_lowercase().contains(&filter)
+                            || what.contains(&filter);
+                        shown.then_some((*id, text, what))
+                    })
+                    .collect();
+                ui.label(format!("{} of {} elements", rows.len(), self.edited.len()));
+                if self.edited_deleted > 0 {
+                    ui.label(format!("{} elements deleted", self.edited_deleted));
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (id, text, what) in rows {
+                            ui.selectable_value(
+                                &mut *self.selected_id.borrow_mut(),
+                                Some(id),
+                                text,
+                            )
+                            .on_hover_text(what);
+                        }
+                    });
+            });
+        self.edited_open = open;
+    }
+
+    fn show_gapped_words(&mut self, ctx: &egui::Context) {
+        let mut open = self.gapped_open;
+        egui::Window::new("Split glued words")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.gapped_ratio, 0.1..=1.0)
+                            .text("of the word's height"),
+                    )
+                    .on_hover_text(
+                        "Words with blank paper this wide inside are offered for splitting",
+                    );
+                    if ui.button("Scan page").clicked() {
+                        self.scan_for_gaps(false);
+                    }
+                    if ui.button("Scan all pages").clicked() {
+                        self.scan_for_gaps(true);
+                    }
+                });
+                ui.separator();
+                if let Some(job) = &self.gap_scan {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(format!(
+                            "Looking… {} of {} pages",
+                            job.progress.done(),
+                            job.progress.total()
+                        ));
+                    });
+                } else if self.gapped_words.is_empty() {
+                    ui.label("No words found");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for gapped in self.gapped_words.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut gapped.checked, "");
+                                let text = self
+                                    .internal_ocr_tree
+                                    .borrow()
+                                    .get_node(&gapped.word)
+                                    .map_or(String::new(), |node| node.ocr_text.clone());
+                                ui.selectable_value(
+                                    &mut *self.selected_id.borrow_mut(),
+                                    Some(gapped.word),
+                                    text,
+                                )
+                                .on_hover_text(format!(
+                                    "{} px of blank paper",
+                                    (gapped.gap.1 - gapped.gap.0) as i32
+                                ));
+                                ui.label("→");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut gapped.left)
+                                        .desired_width(80.0),
+                                );
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut gapped.right)
+                                        .desired_width(80.0),
+                                );
+                            });
+                        }
+                    });
+                let count = self.gapped_words.iter().filter(|g| g.checked).count();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Split {} words", count)),
+                    )
+                    .clicked()
+                {
+                    self.history.borrow_mut().touch();
+                    let mut tree = self.internal_ocr_tree.borrow_mut();
+                    for gapped in self.gapped_words.iter().filter(|g| g.checked) {
+                        cleanup::split_at_gap(&mut tree, gapped);
+                    }
+                    drop(tree);
+                    self.gapped_words.retain(|g| !g.checked);
+                }
+            });
+        self.gapped_open = open;
+    }
+
+    // the lines of the paragraph around the selection, each with the font it's shown in:
+    // its language's if it has one, the preview font if not
+    fn preview_lines(&self) -> Vec<Vec<(FontFamily, String)>> {
+        let selected = match *self.selected_id.borrow() {
+            Some(selected) => selected,
+            None => return Vec::new(),
+        };
+        let lines = {
+            let tree = self.internal_ocr_tree.borrow();
+            // show the whole paragraph around a selected line or word
+            let par = std::iter::once(selected)
+                .chain(tree.ancestors(&selected))
+                .find(|id| {
+                    tree.get_node(id)
+                        .is_some_and(|elt| elt.ocr_element_type == OCRClass::Par)
+                })
+                .unwrap_or(selected);
+            let options = export::ExportOptions {
+                dehyphenate: true,
+                ..Default::default()
+            };
+            export::text_lines(&tree, par, &options)
+        };
+        lines
+            .into_iter()
+            .map(|par| {
+                par.into_iter()
+                    .map(|(line, text)| {
+                        let family = match self.element_family(line) {
+                            FontFamily::Proportional => {
+                                FontFamily::Name(PREVIEW_FONT_FAMILY.into())
+                            }
+                            family => family,
+                        };
+                        (family, text)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // the corrected text of the selected paragraph, set in a font that's comfortable to read
+    fn show_text_preview(&mut self, ctx: &egui::Context) {
+        let mut open = self.text_preview_open;
+        let lines = self.preview_lines();
+        egui::Window::new("Text preview")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Choose font").clicked() {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("font", &["ttf", "otf"])
+                            .pick_file()
+                        {
+                            self.next_lang_fonts =
+                                Some(load_fonts(ctx, Some(&path), &self.settings.lang_fonts));
+                            self.settings.preview_font = Some(path);
+                        }
+                    }
+                    if self.settings.preview_font.is_some() && ui.button("Default font").clicked() {
+                        self.next_lang_fonts =
+                            Some(load_fonts(ctx, None, &self.settings.lang_fonts));
+                        self.settings.preview_font = None;
+                    }
+                    ui.add(
+                        egui::Slider::new(&mut self.settings.preview_font_size, 8.0..=48.0)
+                            .text("size"),
+                    );
+                });
+                ui.separator();
+                let color = ui.visuals().text_color();
+                let mut job = egui::text::LayoutJob::default();
+                for (i, par) in lines.into_iter().enumerate() {
+                    if i > 0 {
+                        job.append(
+                            "\n",
+                            0.0,
+                            egui::TextFormat::simple(
+                                egui::FontId::new(
+                                    self.settings.preview_font_size,
+                                    FontFamily::Name(PREVIEW_FONT_FAMILY.into()),
+                                ),
+                                color,
+                            ),
+                        );
+                    }
+                    for (family, text) in par {
+                        job.append(
+                            &format!("{}\n", text),
+                            0.0,
+                            egui::TextFormat::simple(
+                                egui::FontId::new(self.settings.preview_font_size, family),
+                                color,
+                            ),
+                        );
+                    }
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        ui.add(egui::Label::new(job).wrap(true));
+                    });
+            });
+        self.text_preview_open = open;
+    }
+
+    // select line n (1-based) of page index, as in "p. 12, l. 4"
+    fn go_to_line(&mut self, page_index: usize, n: usize) {
+        self.go_to_page(page_index);
+        let line = self.current_page_id().and_then(|page| {
+            search::page_lines(&self.internal_ocr_tree.borrow(), page)
+                .get(n.saturating_sub(1))
+                .copied()
+        });
+        match line {
+            Some(line) => *self.selected_id.borrow_mut() = Some(line),
+            None => println!("Page {} has no line {}", self.current_page + 1, n),
+        }
+    }
+
+    // the element a bug report or another tool refers to by id
+    fn go_to_element(&mut self) {
+        let found = search::find_by_id(
+            &self.internal_ocr_tree.borrow(),
+            &self.go_to_element_id,
+            &self.project.write_options(),
+        );
+        match found {
+            // follow_selection brings up its page
+            Some(id) => *self.selected_id.borrow_mut() = Some(id),
+            None => println!("No element has id {}", self.go_to_element_id.trim()),
+        }
+    }
+
+    // the innermost element under a point in image coords on the current page
+    fn go_to_point(&mut self) {
+        let pos = match search::parse_point(&self.go_to_point) {
+            Some(pos) => pos,
+            None => {
+                println!("{} isn't a point; write it as x,y", self.go_to_point);
+                return;
+            }
+        };
+        let found = self
+            .current_page_id()
+            .and_then(|page| search::element_at(&self.internal_ocr_tree.borrow(), page, pos));
+        match found {
+            Some(id) => *self.selected_id.borrow_mut() = Some(id),
+            None => println!(
+                "Nothing on page {} contains {},{}",
+                self.current_page + 1,
+                pos.x,
+                pos.y
+            ),
+        }
+    }
+
+    // "p. 3, l. 12" for the line the selection is in, the way proofreaders cite it
+    fn selected_line_position(&self) -> Option<String> {
+        let selected = (*self.selected_id.borrow())?;
+        let tree = self.internal_ocr_tree.borrow();
+        let page = search::page_of(&tree, selected);
+        let lines = search::page_lines(&tree, page);
+        let n = std::iter::once(selected)
+            .chain(tree.ancestors(&selected))
+            .find_map(|id| lines.iter().position(|line| *line == id))?;
+        let page_index = tree.roots().position(|root| *root == page)?;
+        Some(format!("p. {}, l. {}", page_index + 1, n + 1))
+    }
+
+    // deal with the pages whose scan doesn't match their bbox: just page, or every one of
+    // them nobody has decided about yet
+    fn apply_image_mapping(&mut self, page: usize, mapping: ImageMapping) {
+        let pages: Vec<(String, InternalID)> = {
+            let tree = self.internal_ocr_tree.borrow();
+            tree.roots()
+                .copied()
+                .enumerate()
+                .map(|(i, page_id)| (i, dpi::page_key(&tree, &page_id), page_id))
+                .filter(|(i, key, _)| {
+                    *i == page
+                        || (self.dpi_wizard_all && !self.session.image_mappings.contains_key(key))
+                })
+                .map(|(_, key, page_id)| (key, page_id))
+                .collect()
+        };
+        let mut mismatched = Vec::new();
+        for (key, page_id) in pages {
+            let page_size = self
+                .internal_ocr_tree
+                .borrow()
+                .get_node(&page_id)
+                .and_then(|elt| elt.bbox())
+                .map(|bbox| bbox.size());
+            let image_size = self
+                .element_image_file(page_id)
+                .and_then(|file| dpi::image_size(&file));
+            if let (Some(page_size), Some(image_size)) = (page_size, image_size) {
+                if dpi::mismatch(page_size, image_size) {
+                    mismatched.push((key, page_id, image_size));
+                }
+            }
+        }
+        if mapping == ImageMapping::ScaleCoordinates {
+            self.history.borrow_mut().touch();
+            let mut tree = self.internal_ocr_tree.borrow_mut();
+            for (_, page_id, image_size) in &mismatched {
+                // the boxes of a page that hasn't been parsed yet can't be scaled
+                if let Some(doc) = self.lazy_doc.as_mut().filter(|doc| !doc.is_loaded(page_id)) {
+                    tree.reserve_ids(self.history.borrow().next_id());
+                    doc.load_page(&mut tree, page_id);
+                    self.history.borrow_mut().page_loaded(&tree, page_id);
+                }
+                dpi::fit_page(&mut tree, page_id, *image_size);
+            }
+        } else {
+            for (key, _, _) in &mismatched {
+                self.session.image_mappings.insert(key.clone(), mapping);
+            }
+            if let Some(path) = &self.file_path {
+                if let Err(e) = self.session.save(path) {
+                    println!("Couldn't save the session: {}", e);
+                }
+            }
+        }
+        println!("{:?} on {} pages", mapping, mismatched.len());
+        self.dpi_wizard_page = None;
+    }
+
+    fn show_dpi_wizard(&mut self, ctx: &egui::Context) {
+        let page = match self.dpi_wizard_page {
+            Some(page) => page,
+            None => return,
+        };
+        let (page_size, image_size) = match (self.current_page_size(), self.image_size) {
+            (Some(page_size), Some(image_size)) if page == self.current_page => {
+                (page_size, image_size)
+            }
+            _ => {
+                self.dpi_wizard_page = None;
+                return;
+            }
+        };
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("Page and scan sizes differ")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Page {} is {:.0} × {:.0} but its scan is {:.0} × {:.0}: {}.",
+                    page + 1,
+                    page_size.x,
+                    page_size.y,
+                    image_size.x,
+                    image_size.y,
+                    dpi::describe(page_size, image_size)
+                ));
+                ui.label("Until one of them is scaled the boxes are drawn out of place.");
+                ui.checkbox(
+                    &mut self.dpi_wizard_all,
+                    "Do the same for every other page that doesn't fit",
+                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Scale coordinates")
+                        .on_hover_text("Resize every box to fit the scan; this edits the file")
+                        .clicked()
+                    {
+                        chosen = Some(ImageMapping::ScaleCoordinates);
+                    }
+                    if ui
+                        .button("Scale image")
+                        .on_hover_text("Stretch the scan to the page when drawing it")
+                        .clicked()
+                    {
+                        chosen = Some(ImageMapping::ScaleImage);
+                    }
+                    if ui.button("Ignore").clicked() {
+                        chosen = Some(ImageMapping::Ignore);
+                    }
+                });
+            });
+        if let Some(mapping) = chosen {
+            self.apply_image_mapping(page, mapping);
+        } else if !open {
+            // closed without choosing: ask again next time the page comes up
+            self.dpi_wizard_page = None;
+        }
+    }
+
+    fn show_go_to_line(&mut self, ctx: &egui::Context) {
+        let mut open = self.go_to_line_open;
+        let page_count = self.internal_ocr_tree.borrow().roots().len().max(1);
+        egui::Window::new("Go to")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.go_to_line_page)
+                            .clamp_range(1..=page_count)
+                            .prefix("page "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.go_to_line_number)
+                            .clamp_range(1..=usize::MAX)
+                            .prefix("line "),
+                    );
+                    if ui.button("Go").clicked() {
+                        self.go_to_line(self.go_to_line_page - 1, self.go_to_line_number);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.go_to_element_id)
+                            .hint_text("id, e.g. word_1_57")
+                            .desired_width(160.0),
+                    );
+                    let entered =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Go").clicked() || entered {
+                        self.go_to_element();
                     }
-                    // if we are editing, allow the bbox to be draggable
-                }
+                });
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.go_to_point)
+                            .hint_text("x,y on this page")
+                            .desired_width(160.0),
+                    );
+                    let entered =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Go").clicked() || entered {
+                        self.go_to_point();
+                    }
+                });
             });
+        self.go_to_line_open = open;
+    }
+
+    // each line's number just left of it on the current page
+    fn draw_line_numbers(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        let tree = self.internal_ocr_tree.borrow();
+        for (i, line) in search::page_lines(&tree, page).iter().enumerate() {
+            if let Some(bbox) = tree.get_node(line).and_then(|elt| elt.bbox()) {
+                let rect = to_screen.transform_rect(*bbox);
+                ui.painter().text(
+                    rect.left_center() - egui::vec2(4.0, 0.0),
+                    egui::Align2::RIGHT_CENTER,
+                    (i + 1).to_string(),
+                    egui::FontId::monospace(12.0),
+                    egui::Color32::from_rgb(0, 120, 255),
+                );
+            }
         }
     }
 
-    fn open_file(&mut self) {
-        self.file_path = FileDialog::new()
-            .add_filter("hocr", &["html", "xml", "hocr"])
-            .pick_file();
-        self.file_path_changed = true;
+    fn show_renumber_pages(&mut self, ctx: &egui::Context) {
+        let mut open = self.renumber_open;
+        egui::Window::new("Renumber pages")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let problems = ocr_element::ppageno_problems(&self.internal_ocr_tree.borrow());
+                if problems.is_empty() {
+                    ui.label("Page numbers are unique and increasing");
+                }
+                for (page, problem) in problems {
+                    ui.selectable_value(&mut *self.selected_id.borrow_mut(), Some(page), problem);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.renumber_start).prefix("start: "));
+                    ui.add(
+                        egui::DragValue::new(&mut self.renumber_step)
+                            .clamp_range(1..=1000)
+                            .prefix("step: "),
+                    );
+                    if ui.button("Renumber").clicked() {
+                        self.history.borrow_mut().touch();
+                        ocr_element::renumber_pages(
+                            &mut self.internal_ocr_tree.borrow_mut(),
+                            self.renumber_start,
+                            self.renumber_step,
+                        );
+                    }
+                });
+            });
+        self.renumber_open = open;
     }
 
-    fn save_file(&self) {
-        if let Some(path) = &self.file_path {
-            // let new_path = path.with_file_name("test.html");
-            let _ = std::fs::write(
-                // new_path,
-                path,
-                ocr_element::add_as_body(&self.internal_ocr_tree.borrow(), &self.html_write_head)
-                    .html(),
+    // shade what the redacted export will black out
+    fn draw_redactions(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        let tree = self.internal_ocr_tree.borrow();
+        for id in tree.descendants(&page) {
+            if let Some(elt) = tree.get_node(&id).filter(|elt| redact::is_redacted(elt)) {
+                if let Some(bbox) = elt.bbox() {
+                    ui.painter().rect_filled(
+                        to_screen.transform_rect(*bbox),
+                        egui::Rounding::ZERO,
+                        egui::Color32::from_black_alpha(180),
+                    );
+                }
+            }
+        }
+    }
+
+    // ignore regions of the current page, greyed out with their garbage words crossed through
+    fn draw_ignore_regions(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        let tree = self.internal_ocr_tree.borrow();
+        let regions = match tree.get_node(&page).map(ignore::ignore_regions) {
+            Some(regions) if !regions.is_empty() => regions,
+            _ => return,
+        };
+        let stroke = egui::Stroke::new(self.stroke_weight(), egui::Color32::GRAY);
+        for region in regions {
+            ui.painter().rect(
+                to_screen.transform_rect(region),
+                egui::Rounding::ZERO,
+                egui::Color32::from_gray(128).gamma_multiply(0.3),
+                stroke,
             );
         }
+        for id in tree.descendants(&page) {
+            if !ignore::is_ignored(&tree, id) {
+                continue;
+            }
+            if let Some(bbox) = tree.get_node(&id).and_then(|elt| elt.bbox()) {
+                let rect = to_screen.transform_rect(*bbox);
+                ui.painter()
+                    .line_segment([rect.left_bottom(), rect.right_top()], stroke);
+            }
+        }
     }
 
-    fn save_file_as(&self) {
-        if self.file_path.is_some() {
-            let path = FileDialog::new()
-                .add_filter("hocr", &["html", "xml", "hocr"])
-                .save_file();
-            if let Some(fp) = path {
-                let _ = std::fs::write(
-                    // new_path,
-                    fp,
-                    ocr_element::add_as_body(
-                        &self.internal_ocr_tree.borrow(),
-                        &self.html_write_head,
-                    )
-                    .html(),
+    fn add_ignore_region(&self, region: Rect) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        self.history.borrow_mut().touch();
+        if let Some(elt) = self.internal_ocr_tree.borrow_mut().get_mut_node(&page) {
+            let mut regions = ignore::ignore_regions(elt);
+            regions.push(region);
+            ignore::set_ignore_regions(elt, &regions);
+        }
+        let ignored = ignore::ignored_words(&self.internal_ocr_tree.borrow());
+        println!("{} words in ignore regions", ignored.len());
+    }
+
+    fn clear_ignore_regions(&self) {
+        let page = match self.current_page_id() {
+            Some(page) => page,
+            None => return,
+        };
+        self.history.borrow_mut().touch();
+        if let Some(elt) = self.internal_ocr_tree.borrow_mut().get_mut_node(&page) {
+            ignore::set_ignore_regions(elt, &[]);
+        }
+    }
+
+    fn delete_ignored_words(&mut self) {
+        let ignored = ignore::ignored_words(&self.internal_ocr_tree.borrow());
+        if ignored.is_empty() {
+            return;
+        }
+        self.history.borrow_mut().touch();
+        {
+            let mut tree = self.internal_ocr_tree.borrow_mut();
+            for id in &ignored {
+                tree.delete_node(id);
+            }
+        }
+        self.also_selected
+            .borrow_mut()
+            .retain(|id| !ignored.contains(id));
+        if self
+            .selected_id
+            .borrow()
+            .is_some_and(|id| ignored.contains(&id))
+        {
+            *self.selected_id.borrow_mut() = None;
+        }
+        self.review_items.retain(|item| !ignored.contains(&item.id));
+        println!("Deleted {} words in ignore regions", ignored.len());
+    }
+
+    // outline the words the noise window would delete
+    fn draw_noise_preview(&self, to_screen: &RectTransform, ui: &mut egui::Ui) {
+        let page = self.current_page_id();
+        let tree = self.internal_ocr_tree.borrow();
+        for candidate in self
+            .noise_candidates
+            .iter()
+            .filter(|c| c.checked && Some(search::page_of(&tree, c.id)) == page)
+        {
+            if let Some(bbox) = tree.get_node(&candidate.id).and_then(|node| node.bbox()) {
+                ui.painter().rect(
+                    to_screen.transform_rect(*bbox),
+                    egui::Rounding::ZERO,
+                    *BAD_FILL,
+                    egui::Stroke::new(self.stroke_weight(), BAD_STROKE.color),
                 );
             }
         }
     }
 
+    // the selection, minus anything inside another selected element since that comes along
+    fn copy_selected(&mut self) {
+        let selection = self.selection();
+        let tree = self.internal_ocr_tree.borrow();
+        let tops: Vec<InternalID> = selection
+            .iter()
+            .filter(|id| !tree.ancestors(id).iter().any(|a| selection.contains(a)))
+            .copied()
+            .collect();
+        if !tops.is_empty() {
+            self.clipboard = Some(tree.copy_subtrees(&tops));
+        }
+    }
+
+    // copy id, or the selection if it's part of one, each right after itself; the copies
+    // end up selected
+    fn duplicate(&mut self, id: InternalID) {
+        let selection = self.selection_or(id);
+        self.history.borrow_mut().touch();
+        let mut tree = self.internal_ocr_tree.borrow_mut();
+        let mut copies = Vec::new();
+        for original in &selection {
+            // anything inside another selected element is copied along with it
+            if tree
+                .ancestors(original)
+                .iter()
+                .any(|a| selection.contains(a))
+            {
+                continue;
+            }
+            let copy = tree.copy_subtrees(&[*original]);
+            match tree.paste(&copy, original, Some(&Position::After)) {
+                Ok(pasted) => copies.extend(pasted),
+                Err(e) => println!("Couldn't duplicate {}: {}", original, e),
+            }
+        }
+        let mut also = self.also_selected.borrow_mut();
+        also.clear();
+        also.extend(copies.iter().skip(1));
+        *self.selected_id.borrow_mut() = copies.first().copied();
+        self.last_selected.set(copies.first().copied());
+    }
+
+    fn paste(&mut self, target: InternalID, pos: Option<Position>) {
+        let clipboard = match &self.clipboard {
+            Some(clipboard) => clipboard,
+            None => return,
+        };
+        if !structure::can_paste(&self.internal_ocr_tree.borrow(), clipboard, &target, pos) {
+            println!("Can't paste there: what's on the clipboard doesn't go in that element");
+            return;
+        }
+        self.history.borrow_mut().touch();
+        let res = self
+            .internal_ocr_tree
+            .borrow_mut()
+            .paste(clipboard, &target, pos.as_ref());
+        match res {
+            Ok(pasted) => {
+                let mut also = self.also_selected.borrow_mut();
+                also.clear();
+                also.extend(pasted.iter().skip(1));
+                *self.selected_id.borrow_mut() = pasted.first().copied();
+                self.last_selected.set(pasted.first().copied());
+            }
+            Err(e) => println!("Couldn't paste: {}", e),
+        }
+    }
+
     fn delete_selected(&mut self) {
+        let selection = self.selection();
         let mut next_sib = None;
-        if let Some(elt) = *self.selected_id.borrow() {
-            next_sib = self.internal_ocr_tree.borrow().next_sibling(&elt);
-            self.internal_ocr_tree.borrow_mut().delete_node(&elt);
+        if let Some(last) = selection.last() {
+            next_sib = self
+                .internal_ocr_tree
+                .borrow()
+                .next_siblings(last)
+                .find(|sib| !selection.contains(sib))
+                .copied();
+            self.history.borrow_mut().touch();
+            let mut tree = self.internal_ocr_tree.borrow_mut();
+            for elt in &selection {
+                tree.delete_node(elt);
+            }
         }
+        self.also_selected.borrow_mut().clear();
         *self.selected_id.borrow_mut() = next_sib;
     }
 }
 
+// everything the properties panel can change, to tell whether it did
+fn edit_signature(elt: &OCRElement) -> String {
+    let mut props: Vec<String> = elt
+        .ocr_properties
+        .iter()
+        .map(|(name, prop)| format!("{} {}", name, prop.to_str()))
+        .collect();
+    props.sort();
+    format!(
+        "{:?}|{:?}|{}|{}",
+        elt.ocr_element_type,
+        elt.ocr_lang,
+        elt.ocr_text,
+        props.join(";")
+    )
+}
+
 fn render_property(prop: &mut OCRProperty, ui: &mut egui::Ui) {
     match prop {
         OCRProperty::BBox(Rect {
@@ -754,7 +6429,7 @@ fn render_property(prop: &mut OCRProperty, ui: &mut egui::Ui) {
                 });
             });
         }
-        OCRProperty::Image(path) => {
+        OCRProperty::Image(path) | OCRProperty::Text(path) => {
             ui.text_edit_singleline(path);
         }
         OCRProperty::Float(f) => {
@@ -788,11 +6463,46 @@ fn render_property(prop: &mut OCRProperty, ui: &mut egui::Ui) {
                 ui.add(egui::DragValue::new(dpi2).speed(0.1).prefix("also dpi?: "));
             });
         }
+        // one per character, only worth reading
+        OCRProperty::Floats(_) => {
+            ui.label(prop.to_str());
+        }
     };
 }
 
 impl eframe::App for HOCREditor {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // only known once frames are being drawn; a scan loaded before then may not fit
+        let max_texture_side = ctx.input(|i| i.max_texture_side);
+        if max_texture_side != self.max_texture_side {
+            self.max_texture_side = max_texture_side;
+            self.reload_images();
+        }
+        if let Some(lang_fonts) = self.next_lang_fonts.take() {
+            self.lang_fonts = lang_fonts;
+        }
+        // a key clicked on the on-screen keyboard is typed like any other; the text box gets
+        // its focus back first, and if it's gone by now the text goes nowhere
+        if let Some((target, text)) = self.keyboard_insert.take() {
+            ctx.memory_mut(|m| m.request_focus(target));
+            ctx.input_mut(|i| i.events.push(egui::Event::Text(text)));
+        }
+        // a plain click somewhere replaced the selection last frame
+        let selected = *self.selected_id.borrow();
+        if selected != self.last_selected.get() {
+            self.also_selected.borrow_mut().clear();
+            self.last_selected.set(selected);
+        }
+        {
+            let tree = self.internal_ocr_tree.borrow();
+            self.also_selected
+                .borrow_mut()
+                .retain(|id| tree.get_node(id).is_some());
+        }
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -800,7 +6510,32 @@ impl eframe::App for HOCREditor {
                         self.open_file();
                         ui.close_menu();
                     }
-                    if ui.button("Save").clicked() {
+                    if ui
+                        .add_enabled(
+                            self.import_job.is_none(),
+                            egui::Button::new("New from image…"),
+                        )
+                        .clicked()
+                    {
+                        self.new_from_image();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(self.import_job.is_none(), egui::Button::new("Open PDF…"))
+                        .on_hover_text(
+                            "Renders each page; a .hocr file next to the PDF is used for the text",
+                        )
+                        .clicked()
+                    {
+                        self.open_pdf();
+                        ui.close_menu();
+                    }
+                    let save = ui.button("Save");
+                    let save = match &self.save_path {
+                        Some(path) => save.on_hover_text(format!("Saves to {}", path.display())),
+                        None => save,
+                    };
+                    if save.clicked() {
                         self.save_file();
                         ui.close_menu();
                     }
@@ -808,10 +6543,394 @@ impl eframe::App for HOCREditor {
                         self.save_file_as();
                         ui.close_menu();
                     }
-                })
+                    ui.menu_button("Export", |ui| {
+                        for format in ExportFormat::variants() {
+                            if ui.button(format.to_user_str()).clicked() {
+                                self.export(*format);
+                                ui.close_menu();
+                            }
+                        }
+                        ui.menu_button("Text options", |ui| {
+                            ui.checkbox(&mut self.export_options.dehyphenate, "Dehyphenate");
+                            ui.label("Between pages:");
+                            for separator in PageSeparator::variants() {
+                                ui.radio_value(
+                                    &mut self.export_options.page_separator,
+                                    *separator,
+                                    separator.to_user_str(),
+                                );
+                            }
+                        });
+                        ui.menu_button("Unchecked words", |ui| {
+                            ui.label("Words not corrected by hand:");
+                            for unverified in Unverified::variants() {
+                                ui.radio_value(
+                                    &mut self.export_options.unverified,
+                                    *unverified,
+                                    unverified.to_user_str(),
+                                );
+                            }
+                            ui.checkbox(
+                                &mut self.export_options.list_unverified,
+                                "List low confidence ones",
+                            )
+                            .on_hover_text(
+                                "Also write NAME.unverified.tsv with the boxes of unchecked \
+                                 words below the confidence threshold",
+                            );
+                        });
+                        ui.separator();
+                        ui.menu_button("Word frequencies", |ui| {
+                            ui.checkbox(&mut self.frequency_options.ignore_case, "Ignore case");
+                            ui.checkbox(&mut self.frequency_options.normalize, "Normalize");
+                            ui.checkbox(
+                                &mut self.frequency_options.ignore_diacritics,
+                                "Ignore accents",
+                            );
+                            if ui.button("Export…").clicked() {
+                                self.export_word_frequencies();
+                                ui.close_menu();
+                            }
+                        });
+                        if ui.button("PAGE XML").clicked() {
+                            self.export_page_xml();
+                            ui.close_menu();
+                        }
+                        if ui.button("JSON tree").clicked() {
+                            self.export_json_tree();
+                            ui.close_menu();
+                        }
+                        if ui.button("Redacted copy").clicked() {
+                            self.export_redacted();
+                            ui.close_menu();
+                        }
+                    });
+                    ui.separator();
+                    if ui.button("Watch folder").clicked() {
+                        self.pick_watch_folder();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Preferences").clicked() {
+                        self.preferences_open = true;
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Edit", |ui| {
+                    let (can_undo, can_redo) = {
+                        let history = self.history.borrow();
+                        (history.can_undo(), history.can_redo())
+                    };
+                    if ui
+                        .add_enabled(can_undo, egui::Button::new("Undo").shortcut_text("Ctrl+Z"))
+                        .clicked()
+                    {
+                        self.undo();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            can_redo,
+                            egui::Button::new("Redo").shortcut_text("Ctrl+Shift+Z"),
+                        )
+                        .clicked()
+                    {
+                        self.redo();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .add(egui::Button::new("Go to…").shortcut_text("Ctrl+G"))
+                        .clicked()
+                    {
+                        self.go_to_line_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Edited this session…").clicked() {
+                        self.edited_open = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let selected = self.selection().len();
+                    ui.menu_button("Align selected", |ui| {
+                        for alignment in Alignment::ALL {
+                            if ui
+                                .add_enabled(
+                                    selected >= alignment.min_count(),
+                                    egui::Button::new(alignment.label()),
+                                )
+                                .on_disabled_hover_text(format!(
+                                    "Select at least {} boxes (ctrl-click or drag around them)",
+                                    alignment.min_count()
+                                ))
+                                .clicked()
+                            {
+                                self.align_selected(alignment);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.separator();
+                    if ui
+                        .button("Delete words in ignore regions")
+                        .on_hover_text("Every page's, see the Ignore tool")
+                        .clicked()
+                    {
+                        self.delete_ignored_words();
+                        ui.close_menu();
+                    }
+                    if ui.button("Clear this page's ignore regions").clicked() {
+                        self.clear_ignore_regions();
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.settings.zoom, MIN_ZOOM..=MAX_ZOOM)
+                            .logarithmic(true)
+                            .text("zoom"),
+                    );
+                    if ui.button("Actual size").clicked() {
+                        self.settings.zoom = 1.0;
+                    }
+                    ui.checkbox(&mut self.keyboard_open, "Keyboard");
+                    ui.checkbox(&mut self.stats_open, "Statistics");
+                    ui.checkbox(&mut self.search_open, "Search");
+                    ui.checkbox(&mut self.text_preview_open, "Text preview");
+                    ui.checkbox(&mut self.history_open, "Edit history");
+                    ui.checkbox(&mut self.jobs_open, "Jobs");
+                    ui.checkbox(&mut self.two_up, "Two-up text");
+                    ui.checkbox(&mut self.split.open, "Split view");
+                    ui.checkbox(&mut self.show_line_numbers, "Line numbers");
+                    ui.checkbox(&mut self.show_grid, "Pixel grid")
+                        .on_hover_text(format!("Shown from {}x zoom", GRID_MIN_ZOOM));
+                    ui.separator();
+                    if ui
+                        .checkbox(&mut self.settings.scan_grayscale, "Grayscale scan")
+                        .on_hover_text("Only how it's shown; the image file isn't changed")
+                        .changed()
+                    {
+                        self.reload_images();
+                    }
+                    let gamma = ui.add(
+                        egui::Slider::new(&mut self.settings.scan_gamma, 0.3..=3.0)
+                            .logarithmic(true)
+                            .text("scan gamma"),
+                    );
+                    // not while dragging: every change means converting the scan again
+                    if gamma.drag_released() || (gamma.changed() && !gamma.dragged()) {
+                        self.reload_images();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.scan_display().changes_pixels(),
+                            egui::Button::new("Show scan as it is"),
+                        )
+                        .clicked()
+                    {
+                        self.settings.scan_grayscale = false;
+                        self.settings.scan_gamma = 1.0;
+                        self.reload_images();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(!self.guides.is_empty(), egui::Button::new("Clear guides"))
+                        .clicked()
+                    {
+                        self.guides.clear();
+                    }
+                    if ui.checkbox(&mut self.review_open, "Review queue").changed()
+                        && self.review_open
+                    {
+                        self.refresh_review_queue();
+                    }
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Load transliteration table").clicked() {
+                        self.load_translit_table();
+                        ui.close_menu();
+                    }
+                    if ui.button("Low confidence words").clicked() {
+                        self.low_conf_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Recalibrate confidences…").clicked() {
+                        self.recalibrate_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Convert element type").clicked() {
+                        self.convert_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Duplicate words").clicked() {
+                        self.duplicates_open = true;
+                        self.scan_for_duplicates();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Restructure")
+                        .on_hover_text(
+                            "Lift nested pages out and add missing blocks, paragraphs and lines",
+                        )
+                        .clicked()
+                    {
+                        self.history.borrow_mut().touch();
+                        let changed =
+                            structure::restructure(&mut self.internal_ocr_tree.borrow_mut());
+                        println!("Restructured {} elements", changed);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Reading order (XY-cut)")
+                        .on_hover_text(
+                            "Reorder this page's blocks and paragraphs by cutting it into rows \
+                             and columns, so columns export one after the other",
+                        )
+                        .clicked()
+                    {
+                        if let Some(page) = self.current_page_id() {
+                            self.history.borrow_mut().touch();
+                            let changed = xycut::reorder_page(
+                                &mut self.internal_ocr_tree.borrow_mut(),
+                                &page,
+                            );
+                            println!("Reordered the children of {} elements", changed);
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.button("Snapshots").clicked() {
+                        self.snapshots_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Form template").clicked() {
+                        self.template_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Entities").clicked() {
+                        self.entities_open = true;
+                        self.find_entities();
+                        ui.close_menu();
+                    }
+                    if ui.button("Renumber pages").clicked() {
+                        self.renumber_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Batch corrections").clicked() {
+                        self.corrections_open = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Split words").clicked() {
+                        self.split_words_open = true;
+                        self.split_words =
+                            hyphenation::find_split_words(&self.internal_ocr_tree.borrow());
+                        ui.close_menu();
+                    }
+                    if ui.button("Join cut words").clicked() {
+                        self.oversegmented_open = true;
+                        self.oversegmented = cleanup::find_oversegmented(
+                            &self.internal_ocr_tree.borrow(),
+                            self.oversegmented_ratio,
+                        );
+                        ui.close_menu();
+                    }
+                    if ui.button("Split glued words").clicked() {
+                        self.gapped_open = true;
+                        self.scan_for_gaps(false);
+                        ui.close_menu();
+                    }
+                    if ui.button("Remove noise").clicked() {
+                        self.noise_window_open = true;
+                        self.scan_for_noise();
+                        ui.close_menu();
+                    }
+                });
+                ui.separator();
+                ui.selectable_value(&mut self.tool, Tool::Select, "Select");
+                ui.selectable_value(&mut self.tool, Tool::Draw, "Draw")
+                    .on_hover_text("Drag out a box to add an element inside whatever holds it (N)");
+                ui.selectable_value(&mut self.tool, Tool::Separator, "Separator");
+                ui.selectable_value(&mut self.tool, Tool::Photo, "Photo");
+                ui.selectable_value(&mut self.tool, Tool::PreviewOcr, "Preview OCR");
+                ui.selectable_value(&mut self.tool, Tool::Assign, "Assign to selected")
+                    .on_hover_text(
+                        "Select a container, then drag around the elements it should contain",
+                    );
+                ui.selectable_value(&mut self.tool, Tool::Redact, "Redact");
+                ui.selectable_value(&mut self.tool, Tool::Ignore, "Ignore")
+                    .on_hover_text(
+                        "Drag over a gutter or page edge; words inside it are left out of \
+                         exports and listed for deletion in the review queue",
+                    );
+                ui.selectable_value(&mut self.tool, Tool::Measure, "Measure");
+                ui.selectable_value(&mut self.tool, Tool::Guide, "Guide")
+                    .on_hover_text(
+                        "Drag across the page for a horizontal guide, down it for a vertical one",
+                    );
+                ui.checkbox(&mut self.word_boundaries, "Word boundaries")
+                    .on_hover_text(
+                        "Drag the handles between the words of the selected line to move where \
+                         one word ends and the next begins",
+                    );
+                ui.separator();
+                let page_count = self.internal_ocr_tree.borrow().roots().len();
+                if ui
+                    .add_enabled(self.current_page > 0, egui::Button::new("⬅"))
+                    .on_hover_text("Previous page (Page Up)")
+                    .clicked()
+                {
+                    self.flip_to_page(self.current_page - 1);
+                }
+                ui.label(format!(
+                    "page {}/{}",
+                    (self.current_page + 1).min(page_count),
+                    page_count
+                ));
+                if ui
+                    .add_enabled(self.current_page + 1 < page_count, egui::Button::new("➡"))
+                    .on_hover_text("Next page (Page Down)")
+                    .clicked()
+                {
+                    self.flip_to_page(self.current_page + 1);
+                }
+                ui.separator();
+                if self.playback.is_some() {
+                    if ui.button("⏹").on_hover_text("Stop playback").clicked() {
+                        self.playback = None;
+                    }
+                } else if ui
+                    .button("▶")
+                    .on_hover_text("Step the selection through the words in reading order")
+                    .clicked()
+                {
+                    self.start_playback();
+                }
+                ui.add(
+                    egui::Slider::new(&mut self.playback_speed, 0.5..=10.0)
+                        .logarithmic(true)
+                        .text("words/s"),
+                );
+                if Speech::available() {
+                    ui.separator();
+                    if ui
+                        .add_enabled(self.selected_id.borrow().is_some(), egui::Button::new("🔊"))
+                        .on_hover_text("Read the selected line or paragraph aloud")
+                        .clicked()
+                    {
+                        self.read_aloud();
+                    }
+                    if ui.button("🔇").on_hover_text("Stop reading").clicked() {
+                        self.speech.stop();
+                    }
+                }
             })
         });
+        self.poll_import_job(ctx);
+        self.step_playback(ctx);
+        self.follow_selection();
         if let Some(elt) = *self.selected_id.borrow() {
+            let lang_warning = ocr_element::lang_mismatch(&self.internal_ocr_tree.borrow(), &elt);
+            let strip = self.upright_strip(elt);
+            let word_family = self.element_family(elt);
             /*
             if self.mode == Mode::Select {
                 if let Some(node) = self.internal_ocr_tree.borrow().get_node(&elt) {
@@ -841,6 +6960,7 @@ impl eframe::App for HOCREditor {
             } else if self.mode == Mode::Edit {
              */
             if let Some(node) = self.internal_ocr_tree.borrow_mut().get_mut_node(&elt) {
+                let before = edit_signature(node);
                 egui::SidePanel::left("OCR Properties").show(ctx, |ui| {
                     egui::Grid::new("properties grid")
                         .num_columns(2)
@@ -860,19 +6980,204 @@ impl eframe::App for HOCREditor {
                                     }
                                 });
                             ui.end_row();
-                            for (name, prop) in node.ocr_properties.iter_mut() {
+                            // a page's lang is the default that new words inherit
+                            ui.label(if node.ocr_element_type == OCRClass::Page {
+                                "default lang"
+                            } else {
+                                "lang"
+                            });
+                            let mut lang = node.ocr_lang.clone().unwrap_or_default();
+                            if ui.text_edit_singleline(&mut lang).changed() {
+                                node.ocr_lang = if lang.trim().is_empty() {
+                                    None
+                                } else {
+                                    Some(lang.trim().to_string())
+                                };
+                            }
+                            ui.end_row();
+                            if let Some((word_lang, par_lang)) = &lang_warning {
+                                ui.label("");
+                                ui.colored_label(
+                                    egui::Color32::RED,
+                                    format!(
+                                        "word is {} but its paragraph is {}",
+                                        word_lang, par_lang
+                                    ),
+                                );
+                                ui.end_row();
+                            }
+                            for (name, prop) in node
+                                .ocr_properties
+                                .iter_mut()
+                                .filter(|(name, _)| !name.starts_with(USER_PROPERTY_PREFIX))
+                            {
                                 ui.label(name);
                                 render_property(prop, ui);
                                 ui.end_row();
                             }
+                            // user metadata, in a stable order
+                            let mut user_keys: Vec<String> = node
+                                .ocr_properties
+                                .keys()
+                                .filter(|name| name.starts_with(USER_PROPERTY_PREFIX))
+                                .cloned()
+                                .collect();
+                            user_keys.sort();
+                            let mut removed = None;
+                            for name in user_keys {
+                                ui.label(&name[USER_PROPERTY_PREFIX.len()..])
+                                    .on_hover_text(&name);
+                                ui.horizontal(|ui| {
+                                    if let Some(OCRProperty::Text(value)) =
+                                        node.ocr_properties.get_mut(&name)
+                                    {
+                                        ui.text_edit_singleline(value);
+                                    }
+                                    if ui.small_button("🗑").clicked() {
+                                        removed = Some(name.clone());
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                            if let Some(name) = removed {
+                                node.ocr_properties.remove(&name);
+                            }
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.new_user_key)
+                                    .hint_text("new metadata key")
+                                    .desired_width(100.0),
+                            );
+                            let key: String = self
+                                .new_user_key
+                                .trim()
+                                .chars()
+                                .map(|c| {
+                                    if c.is_whitespace() || c == ';' {
+                                        '_'
+                                    } else {
+                                        c
+                                    }
+                                })
+                                .collect();
+                            if ui
+                                .add_enabled(!key.is_empty(), egui::Button::new("Add"))
+                                .clicked()
+                            {
+                                node.ocr_properties
+                                    .entry(format!("{}{}", USER_PROPERTY_PREFIX, key))
+                                    .or_insert(OCRProperty::Text(String::new()));
+                                self.new_user_key.clear();
+                            }
+                            ui.end_row();
+                            // rotated text, turned so it can be read while typing it
+                            if let Some((image, turns, size)) = strip {
+                                ui.label("scan");
+                                let shown = if turns % 2 == 1 {
+                                    Vec2::new(size.y, size.x)
+                                } else {
+                                    size
+                                };
+                                let scale = (STRIP_WIDTH / shown.x).min(1.0);
+                                let (rect, _) =
+                                    ui.allocate_exact_size(shown * scale, Sense::hover());
+                                // egui turns clockwise, textangle counts counterclockwise
+                                image
+                                    .rotate(((turns * 90) as f32).to_radians(), Vec2::splat(0.5))
+                                    .paint_at(
+                                        ui,
+                                        Rect::from_center_size(rect.center(), size * scale),
+                                    );
+                                ui.end_row();
+                            }
                             if node.ocr_element_type == OCRClass::Word {
+                                let table = self
+                                    .translit_table
+                                    .and_then(|i| self.translit_tables.get(i));
                                 ui.label("text");
-                                let response = ui.text_edit_singleline(&mut node.ocr_text);
-                                if response.changed() {
-                                    node.ocr_properties
-                                        .insert(String::from("x_wconf"), OCRProperty::UInt(100));
+                                let text_id = egui::Id::new("word text");
+                                if let Some(at) = split_shortcut(ui, text_id) {
+                                    *self.context_action.borrow_mut() =
+                                        Some((elt, ContextAction::SplitWord(at)));
+                                }
+                                let confs = node.char_confidences().map(|confs| confs.to_vec());
+                                let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                                    confidence_layout(
+                                        ui,
+                                        text,
+                                        confs.as_deref().unwrap_or_default(),
+                                        &word_family,
+                                        wrap_width,
+                                    )
+                                };
+                                let text_before = node.ocr_text.clone();
+                                let output = egui::TextEdit::singleline(&mut node.ocr_text)
+                                    .id(text_id)
+                                    .layouter(&mut layouter)
+                                    .show(ui);
+                                output
+                                    .response
+                                    .clone()
+                                    .on_hover_text("Ctrl+Enter splits the word at the cursor");
+                                if output.response.changed() {
+                                    if let (true, Some(table)) = (self.translit_auto, table) {
+                                        let pending = match self.translit_pending {
+                                            Some((id, pending)) if id == elt => Some(pending),
+                                            _ => None,
+                                        };
+                                        let (text, cursor, pending) = table.apply_typed(
+                                            &text_before,
+                                            &node.ocr_text,
+                                            pending,
+                                        );
+                                        node.ocr_text = text;
+                                        self.translit_pending = pending.map(|p| (elt, p));
+                                        let mut state = output.state.clone();
+                                        state.set_ccursor_range(Some(CCursorRange::one(
+                                            CCursor::new(cursor),
+                                        )));
+                                        state.store(ui.ctx(), text_id);
+                                    }
+                                    node.mark_corrected();
                                 }
                                 ui.end_row();
+                                ui.label("transliterate");
+                                ui.horizontal(|ui| {
+                                    egui::ComboBox::from_id_source("transliteration table")
+                                        .selected_text(table.map_or("off", |t| t.name.as_str()))
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(
+                                                &mut self.translit_table,
+                                                None,
+                                                "off",
+                                            );
+                                            for (i, t) in self.translit_tables.iter().enumerate() {
+                                                ui.selectable_value(
+                                                    &mut self.translit_table,
+                                                    Some(i),
+                                                    t.name.as_str(),
+                                                );
+                                            }
+                                        });
+                                    if let Some(table) = table {
+                                        // only the selected part if there is a selection
+                                        if ui.button("Apply").clicked() {
+                                            node.ocr_text = match output.cursor_range {
+                                                Some(range) if range.primary != range.secondary => {
+                                                    let a = range.primary.ccursor.index;
+                                                    let b = range.secondary.ccursor.index;
+                                                    table.apply_range(
+                                                        &node.ocr_text,
+                                                        a.min(b),
+                                                        a.max(b),
+                                                    )
+                                                }
+                                                _ => table.apply(&node.ocr_text),
+                                            };
+                                        }
+                                        ui.checkbox(&mut self.translit_auto, "as you type");
+                                    }
+                                });
+                                ui.end_row();
                             }
                             // if editable, the numbers turn into drag values
                             // wconf is bounded by 0 and 100
@@ -880,27 +7185,106 @@ impl eframe::App for HOCREditor {
                             // the text is textedit box for words
                         })
                 });
+                if edit_signature(node) != before {
+                    self.history.borrow_mut().touch();
+                }
             }
             // }
         }
+        self.show_noise_window(ctx);
+        self.show_preferences(ctx);
+        self.show_inbox(ctx);
+        self.show_jobs(ctx);
+        self.show_stats(ctx);
+        self.show_search(ctx);
+        self.show_low_confidence(ctx);
+        self.show_ocr_preview(ctx);
+        self.show_split_words(ctx);
+        self.show_oversegmented(ctx);
+        self.show_gapped_words(ctx);
+        self.show_recalibrate(ctx);
+        self.show_edited(ctx);
+        self.show_corrections(ctx);
+        self.show_renumber_pages(ctx);
+        self.show_duplicates(ctx);
+        self.show_convert_class(ctx);
+        self.show_text_preview(ctx);
+        self.show_snapshots(ctx);
+        self.show_template(ctx);
+        self.show_entities(ctx);
+        self.show_go_to_line(ctx);
+        self.show_dpi_wizard(ctx);
+        self.show_confirm_overwrite(ctx);
+        if self.keyboard_open {
+            self.show_keyboard(ctx);
+        }
+        if self.review_open {
+            self.show_review_queue(ctx);
+        }
+        if self.history_open {
+            self.show_history(ctx);
+        }
+        egui::TopBottomPanel::bottom("status bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                match self.cursor_pos {
+                    Some(pos) => ui.label(format!("x {:.0}, y {:.0}", pos.x, pos.y)),
+                    None => ui.label("x -, y -"),
+                };
+                if let Some(position) = self.selected_line_position() {
+                    ui.separator();
+                    ui.label(position);
+                }
+                if let Some((a, b)) = self.measurement {
+                    ui.separator();
+                    ui.label(format!("measured {}", self.measurement_text(a, b)));
+                }
+                let jobs = self.job_count();
+                if jobs > 0 {
+                    ui.separator();
+                    ui.spinner();
+                    if ui.link(format!("{} jobs running", jobs)).clicked() {
+                        self.jobs_open = true;
+                    }
+                }
+                if let Some(job) = &self.import_job {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(format!("importing {}", job.source.display()));
+                }
+            });
+        });
         // TODO: you can also add a new property???
-        egui::SidePanel::right("HOCR Tree").show(ctx, |ui| {
+        let tree_panel = egui::SidePanel::right("HOCR Tree").show(ctx, |ui| {
             ui.vertical_centered(|ui| {
                 ui.heading("HOCR Tree");
             });
 
             self.render_tree(ui);
         });
+        if ctx.input(|i| i.pointer.any_pressed()) {
+            self.tree_focused = ctx
+                .pointer_interact_pos()
+                .is_some_and(|pos| tree_panel.response.rect.contains(pos));
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             // let's not re-parse the file every frame
             if self.file_path_changed {
                 self.reparse_file();
             }
+            // don't steal keys (or IME composition) from whatever text box has focus
+            let typing = ui.ctx().wants_keyboard_input();
             // move bboxes by using the arrow keys
             // left and right go to previous and next siblings (if they exist)
             // up and down go to parent and first child resp
-            if self.selected_id.borrow().is_some() {
+            if !typing && self.selected_id.borrow().is_some() {
                 let sel_id = self.selected_id.borrow().unwrap();
+                // alt moves the element itself instead of the selection
+                if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowUp)) {
+                    self.move_among_siblings(sel_id, Position::Before);
+                }
+                if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowDown)) {
+                    self.move_among_siblings(sel_id, Position::After);
+                }
                 if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowLeft)) {
                     *self.selected_id.borrow_mut() = Some(
                         self.internal_ocr_tree
@@ -936,20 +7320,119 @@ impl eframe::App for HOCREditor {
                     );
                 }
             }
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageUp))
+            {
+                self.flip_to_page(self.current_page.saturating_sub(1));
+            }
+            if !typing
+                && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageDown))
+            {
+                self.flip_to_page(self.current_page + 1);
+            }
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::G)) {
+                self.go_to_line_page = self.current_page + 1;
+                self.go_to_line_open = true;
+            }
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::N)) {
+                self.tool = Tool::Draw;
+            }
             // for now: you can edit the selected bbox by pressing "e"
-            if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::E)) {
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::E)) {
                 self.mode = Mode::SingleSelect;
             }
-            if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
-                self.mode = Mode::Select;
+            // escape leaves edit mode first, then walks up the hierarchy
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape))
+            {
+                let selected = *self.selected_id.borrow();
+                if self.mode != Mode::Select {
+                    self.mode = Mode::Select;
+                } else if let Some(sel_id) = selected {
+                    let parent = self.internal_ocr_tree.borrow().parent(&sel_id);
+                    if parent.is_some() {
+                        *self.selected_id.borrow_mut() = parent;
+                    }
+                }
+            }
+            // and if you've selected a word, you can edit the text by pressing enter
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+                self.inline_edit = true;
+                ui.memory_mut(|m| m.request_focus(egui::Id::new(INLINE_EDITOR_ID)));
             }
-            // and if you've selected a word, you can edit the text by...
-            self.draw_img_and_bboxes(ui);
-            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Backspace)) {
+            match (self.two_up, self.split.open) {
+                (true, true) => ui.columns(3, |columns| {
+                    self.draw_img_and_bboxes(&mut columns[0]);
+                    self.show_two_up_text(&mut columns[1]);
+                    self.show_split_page(&mut columns[2]);
+                }),
+                (true, false) => ui.columns(2, |columns| {
+                    self.draw_img_and_bboxes(&mut columns[0]);
+                    self.show_two_up_text(&mut columns[1]);
+                }),
+                (false, true) => ui.columns(2, |columns| {
+                    self.draw_img_and_bboxes(&mut columns[0]);
+                    self.show_split_page(&mut columns[1]);
+                }),
+                (false, false) => self.draw_img_and_bboxes(ui),
+            }
+            if !typing
+                && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Backspace))
+            {
                 self.delete_selected();
             }
+            // shift first: ctrl+z alone would also match ctrl+shift+z
+            if !typing
+                && ui.input_mut(|i| {
+                    i.consume_key(
+                        egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                        egui::Key::Z,
+                    )
+                })
+            {
+                self.redo();
+            }
+            if !typing && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+                self.undo();
+            }
+            // in the image ctrl+c and friends are left alone
+            if !typing && self.tree_focused {
+                let selected = *self.selected_id.borrow();
+                if let Some(selected) = selected {
+                    if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::X)) {
+                        self.copy_selected();
+                        self.delete_selected();
+                    }
+                    if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::C)) {
+                        self.copy_selected();
+                    }
+                    if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::V)) {
+                        // into the selection if it can hold what's pasted, after it if not
+                        let inside = self.clipboard.as_ref().is_some_and(|clipboard| {
+                            structure::can_paste(
+                                &self.internal_ocr_tree.borrow(),
+                                clipboard,
+                                &selected,
+                                None,
+                            )
+                        });
+                        self.paste(selected, if inside { None } else { Some(Position::After) });
+                    }
+                }
+            }
         });
         self.update_internal_tree();
+        self.handle_context_action();
+        // rows check for a drop on the frame the button is let go, so this waits until after
+        if !ctx.input(|i| i.pointer.any_down()) {
+            self.tree_drag.set(None);
+        }
+        self.poll_reocr_jobs(ctx);
+        self.poll_jobs(ctx);
+        self.load_selected_page();
+        self.track_keyboard_target(ctx);
+        // an edit is one undo step once the mouse is let go and no text box has focus
+        if !ctx.is_using_pointer() && !ctx.wants_keyboard_input() {
+            self.commit_history();
+        }
     }
 }
 
@@ -0,0 +1,79 @@
+use crate::jobs::CANCELLED;
+use std::time::{Duration, Instant};
+
+// how long a finished job's notice stays up
+const NOTICE_DURATION: Duration = Duration::from_secs(6);
+
+// one line about a job that finished, whichever window is open
+#[derive(Debug)]
+struct Notice {
+    text: String,
+    failed: bool,
+    shown_at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Notices {
+    notices: Vec<Notice>,
+}
+
+impl Notices {
+    pub fn push(&mut self, text: impl Into<String>, failed: bool) {
+        let text = text.into();
+        println!("{}", text);
+        self.notices.push(Notice {
+            text,
+            failed,
+            shown_at: Instant::now(),
+        });
+    }
+
+    // the result of a job that's finished: its own message if it worked, the error if not
+    pub fn finished<T>(
+        &mut self,
+        name: &str,
+        res: &Result<T, String>,
+        done: impl FnOnce(&T) -> String,
+    ) {
+        match res {
+            Ok(value) => self.push(done(value), false),
+            Err(e) if e == CANCELLED => self.push(format!("{}: cancelled", name), false),
+            Err(e) => self.push(format!("{} failed: {}", name, e), true),
+        }
+    }
+
+    // stacked in the bottom right corner until they time out or are clicked away
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.notices
+            .retain(|notice| notice.shown_at.elapsed() < NOTICE_DURATION);
+        if self.notices.is_empty() {
+            return;
+        }
+        let mut dismissed = None;
+        egui::Area::new("job notices")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .show(ctx, |ui| {
+                for (i, notice) in self.notices.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        let text = egui::RichText::new(&notice.text);
+                        let text = if notice.failed {
+                            text.color(ui.visuals().error_fg_color)
+                        } else {
+                            text
+                        };
+                        if ui
+                            .add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .on_hover_text("Click to dismiss")
+                            .clicked()
+                        {
+                            dismissed = Some(i);
+                        }
+                    });
+                }
+            });
+        if let Some(i) = dismissed {
+            self.notices.remove(i);
+        }
+        ctx.request_repaint_after(Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::jobs::Job;
+use crate::ocr_element::{self, OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::{Pos2, Rect};
+use image::DynamicImage;
+use scraper::Html;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// so two crops in flight never share a temp file
+static CROP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// a tesseract run in the background for the OCR preview
+#[derive(Debug)]
+pub struct OcrJob {
+    // image coords of what's being recognized
+    pub region: Rect,
+    pub job: Job<String>,
+    // kept once it's in, for as long as the preview shows it
+    result: Option<Result<String, String>>,
+}
+
+impl OcrJob {
+    pub fn finished(&mut self) -> Option<&Result<String, String>> {
+        if self.result.is_none() {
+            self.result = self.job.take_result();
+        }
+        self.result.as_ref()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.result.is_none()
+    }
+}
+
+// cut one rectangle (image coords) out of the scan
+pub fn crop_region(image_path: &Path, region: Rect) -> Result<DynamicImage, String> {
+    let image = image::open(image_path)
+        .map_err(|e| format!("Failed to open {}: {}", image_path.display(), e))?;
+    let x = region.min.x.max(0.0) as u32;
+    let y = region.min.y.max(0.0) as u32;
+    let width = (region.width() as u32).min(image.width().saturating_sub(x));
+    let height = (region.height() as u32).min(image.height().saturating_sub(y));
+    if width == 0 || height == 0 {
+        return Err("The region is outside the image".to_string());
+    }
+    Ok(image.crop_imm(x, y, width, height))
+}
+
+// turn a crop of text running at textangle so it reads left to right
+pub fn upright(crop: DynamicImage, angle: f32) -> DynamicImage {
+    match ocr_element::quarter_turns(angle) {
+        1 => crop.rotate90(),
+        2 => crop.rotate180(),
+        3 => crop.rotate270(),
+        _ => crop,
+    }
+}
+
+// where a rect in a crop turned upright by upright() lies in the crop as it was, which was
+// width by height
+fn from_upright(rect: Rect, width: f32, height: f32, angle: f32) -> Rect {
+    let unturn = |p: Pos2| match ocr_element::quarter_turns(angle) {
+        1 => Pos2::new(p.y, height - p.x),
+        2 => Pos2::new(width - p.x, height - p.y),
+        3 => Pos2::new(width - p.y, p.x),
+        _ => p,
+    };
+    Rect::from_two_pos(unturn(rect.min), unturn(rect.max))
+}
+
+// run tesseract on a crop of the scan and return its stdout
+// configs go after the options, e.g. "hocr" for hOCR instead of plain text
+fn run_tesseract(crop: &DynamicImage, configs: &[&str]) -> Result<String, String> {
+    let crop_path = std::env::temp_dir().join(format!(
+        "hocr_editor_crop_{}_{}.png",
+        std::process::id(),
+        CROP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    crop.save(&crop_path)
+        .map_err(|e| format!("Failed to write {}: {}", crop_path.display(), e))?;
+    // psm 6: treat the crop as a single block of text
+    let output = Command::new("tesseract")
+        .arg(&crop_path)
+        .arg("stdout")
+        .args(["--psm", "6"])
+        .args(configs)
+        .output();
+    let _ = std::fs::remove_file(&crop_path);
+    let output = output.map_err(|e| format!("Couldn't run tesseract: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// what tesseract reads in one rectangle of the scan
+pub fn recognize_region(image_path: &Path, region: Rect) -> Result<String, String> {
+    run_tesseract(&crop_region(image_path, region)?, &[]).map(|text| text.trim().to_string())
+}
+
+// tesseract's hOCR for one rectangle with text running at angle, with every bbox moved from
+// the upright crop to page coords
+pub fn recognize_region_hocr(
+    image_path: &Path,
+    region: Rect,
+    angle: f32,
+) -> Result<Tree<OCRElement>, String> {
+    let crop = crop_region(image_path, region)?;
+    let (width, height) = (crop.width() as f32, crop.height() as f32);
+    let hocr = run_tesseract(&upright(crop, angle), &["hocr"])?;
+    let mut tree = OCRElement::html_to_ocr_tree(Html::parse_document(&hocr));
+    for id in tree.preorder() {
+        if let Some(OCRProperty::BBox(bbox)) = tree
+            .get_mut_node(&id)
+            .and_then(|elt| elt.ocr_properties.get_mut("bbox"))
+        {
+            *bbox = from_upright(*bbox, width, height, angle).translate(region.min.to_vec2());
+        }
+    }
+    Ok(tree)
+}
+
+// a background re-OCR of one element
+#[derive(Debug)]
+pub struct ReocrJob {
+    pub target: InternalID,
+    // the element's bbox when the crop was taken; if it has moved since, the text is stale
+    pub region: Rect,
+    pub job: Job<Tree<OCRElement>>,
+}
+
+impl ReocrJob {
+    // the result once, when it's ready
+    pub fn take_result(&self) -> Option<Result<Tree<OCRElement>, String>> {
+        self.job.take_result()
+    }
+}
+
+pub fn spawn_reocr(image_path: PathBuf, target: InternalID, region: Rect, angle: f32) -> ReocrJob {
+    ReocrJob {
+        target,
+        region,
+        job: Job::spawn(format!("Re-OCR element {}", target), 0, move |_| {
+            recognize_region_hocr(&image_path, region, angle)
+        }),
+    }
+}
+
+pub fn spawn_recognize(image_path: PathBuf, region: Rect) -> OcrJob {
+    OcrJob {
+        region,
+        job: Job::spawn("OCR preview", 0, move |_| {
+            recognize_region(&image_path, region)
+        }),
+        result: None,
+    }
+}
+
+// a scan (or pdf) being turned into a new hOCR file in the background
+#[derive(Debug)]
+pub struct HocrJob {
+    pub source: PathBuf,
+    pub job: Job<PathBuf>,
+}
+
+impl HocrJob {
+    // the new hOCR file's path once it's written
+    pub fn take_result(&self) -> Option<Result<PathBuf, String>> {
+        self.job.take_result()
+    }
+}
+
+// run tesseract over a whole scan, writing <stem>.hocr next to it
+// (or <stem>_1.hocr, ... so an existing file is never overwritten)
+pub fn image_to_hocr(image_path: &Path) -> Result<PathBuf, String> {
+    let stem = image_path
+        .file_stem()
+        .ok_or(format!("{} isn't a file", image_path.display()))?
+        .to_string_lossy();
+    // the stem may have dots of its own (scan.001), so the extension is added, not swapped in
+    let name = (0..)
+        .map(|n| match n {
+            0 => stem.to_string(),
+            n => format!("{}_{}", stem, n),
+        })
+        .find(|name| !image_path.with_file_name(format!("{}.hocr", name)).exists())
+        .unwrap();
+    let base = image_path.with_file_name(&name);
+    // tesseract adds the extension to the output base itself
+    let output = Command::new("tesseract")
+        .arg(image_path)
+        .arg(&base)
+        .arg("hocr")
+        .output()
+        .map_err(|e| format!("Couldn't run tesseract: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(image_path.with_file_name(format!("{}.hocr", name)))
+}
+
+pub fn spawn_image_to_hocr(image: PathBuf) -> HocrJob {
+    let name = format!("OCR {}", image.display());
+    HocrJob {
+        source: image.clone(),
+        // tesseract can't be stopped halfway, but a cancelled import isn't opened
+        job: Job::spawn(name, 0, move |progress| {
+            let path = image_to_hocr(&image)?;
+            progress.check()?;
+            Ok(path)
+        }),
+    }
+}
@@ -1,6 +1,6 @@
-use crate::tree::Tree;
+use crate::tree::{Position, Tree};
+use crate::uri;
 use crate::InternalID;
-use eframe::egui;
 use egui::{Pos2, Rect};
 use html5ever::interface::tree_builder::TreeSink;
 use html5ever::interface::{AppendNode, AppendText};
@@ -10,12 +10,15 @@ use itertools::Itertools;
 
 use lazy_static::lazy_static;
 use scraper::{ElementRef, Selector};
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// free-form project metadata: `x_user_<key> "value"` in the title, never interpreted, always kept
+pub const USER_PROPERTY_PREFIX: &str = "x_user_";
 
 lazy_static! {
     pub static ref OCR_SELECTOR: Selector =
-        Selector::parse(".ocr_page, .ocr_carea, .ocr_line, .ocr_par, .ocrx_word, .ocr_caption, .ocr_separator, .ocr_photo").unwrap();
-    pub static ref OCR_WORD_SELECTOR: Selector = Selector::parse(".ocrx_word").unwrap();
+        Selector::parse(".ocr_page, .ocr_carea, .ocr_line, .ocr_par, .ocrx_word, .ocr_caption, .ocr_separator, .ocr_photo, .ocr_word, .ocrx_block, .ocrx_line").unwrap();
+    pub static ref OCR_WORD_SELECTOR: Selector = Selector::parse(".ocrx_word, .ocr_word").unwrap();
     pub static ref OCR_PAGE_SELECTOR: Selector = Selector::parse(".ocr_page").unwrap();
 }
 
@@ -89,6 +92,7 @@ fn rect_from_attr(s: &str) -> Result<Rect, String> {
         .map(|s| s.parse::<f32>())
         .collect();
     match coords {
+        Ok(v) if v.len() < 4 => Err(format!("Expected 4 coordinates in {s}")),
         Ok(v) => Ok(Rect {
             min: Pos2 { x: v[0], y: v[1] },
             max: Pos2 { x: v[2], y: v[3] },
@@ -97,7 +101,7 @@ fn rect_from_attr(s: &str) -> Result<Rect, String> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OCRProperty {
     // BBox(BBox),
     BBox(Rect),
@@ -108,13 +112,17 @@ pub enum OCRProperty {
     // Int(i32),
     Baseline(f32, f32),
     ScanRes(u32, u32),
+    // free text, written quoted like image
+    Text(String),
+    // one number per character, like x_confs
+    Floats(Vec<f32>),
 }
 
 impl OCRProperty {
     pub fn as_bbox(&self) -> Option<&Rect> {
         match self {
             OCRProperty::BBox(bbox) => Some(bbox),
-            _ => None
+            _ => None,
         }
     }
     pub fn to_str(&self) -> String {
@@ -134,6 +142,12 @@ impl OCRProperty {
             // OCRProperty::Int(u) => u.to_string(),
             OCRProperty::Baseline(f1, f2) => format!("{} {}", f1, f2),
             OCRProperty::ScanRes(f1, f2) => format!("{} {}", f1, f2),
+            OCRProperty::Text(text) => quote(text),
+            OCRProperty::Floats(values) => values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
         }
     }
 }
@@ -141,7 +155,7 @@ impl OCRProperty {
 // internal representation of a node in the HTML tree containing OCR data
 // TODO: transform the html tree into a tree of these
 // TODO: subclasses because page, word, line have different properties
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct OCRElement {
     pub html_element_type: String,
     pub ocr_element_type: OCRClass,
@@ -149,10 +163,87 @@ pub struct OCRElement {
     pub ocr_properties: HashMap<String, OCRProperty>,
     pub ocr_text: String,
     pub ocr_lang: Option<String>, // only ocr_par has lang I think
+    /// id and title as they were in the file, so a stable save can write them back untouched
+    pub source_id: Option<String>,
+    pub source_title: Option<String>,
+    /// hOCR 1.1 class name (ocr_word, ocrx_block, ocrx_line) if the file used one
+    pub legacy_class: Option<String>,
 }
 
 impl OCRElement {
-    fn add_children_to_ocr_tree(elt_ref: ElementRef, par_id: u32, tree: &mut Tree<OCRElement>) {
+    pub fn bbox(&self) -> Option<&Rect> {
+        self.ocr_properties
+            .get("bbox")
+            .and_then(|prop| prop.as_bbox())
+    }
+
+    pub fn wconf(&self) -> Option<u32> {
+        match self.ocr_properties.get("x_wconf") {
+            Some(OCRProperty::UInt(wconf)) => Some(*wconf),
+            _ => None,
+        }
+    }
+
+    /// horizontal scan resolution from scan_res, if the page has one
+    pub fn dpi(&self) -> Option<f32> {
+        match self.ocr_properties.get("scan_res") {
+            Some(OCRProperty::ScanRes(x, _)) if *x > 0 => Some(*x as f32),
+            _ => None,
+        }
+    }
+
+    /// the class to write: the legacy name it was read with unless its type has been changed since
+    pub fn class_name(&self) -> String {
+        match &self.legacy_class {
+            Some(legacy)
+                if legacy.parse::<OCRClass>().ok().as_ref() == Some(&self.ocr_element_type) =>
+            {
+                legacy.clone()
+            }
+            _ => self.ocr_element_type.to_string(),
+        }
+    }
+
+    /// the confidence of each character of the text, if the OCR engine gave them (x_confs)
+    /// and there's still one per character
+    pub fn char_confidences(&self) -> Option<&[f32]> {
+        match self.ocr_properties.get("x_confs") {
+            Some(OCRProperty::Floats(confs)) if confs.len() == self.ocr_text.chars().count() => {
+                Some(confs)
+            }
+            _ => None,
+        }
+    }
+
+    /// the text was typed in by a person, so the engine's confidences no longer apply
+    pub fn mark_corrected(&mut self) {
+        self.ocr_properties
+            .insert(String::from("x_wconf"), OCRProperty::UInt(100));
+        self.ocr_properties.remove("x_confs");
+    }
+
+    /// flagged by a person for a second look; stored as "x_review 1" in the title
+    pub fn needs_review(&self) -> bool {
+        matches!(
+            self.ocr_properties.get("x_review"),
+            Some(OCRProperty::UInt(1))
+        )
+    }
+
+    /// the angle this element's own textangle gives its text, counterclockwise in degrees
+    pub fn text_angle(&self) -> Option<f32> {
+        match self.ocr_properties.get("textangle") {
+            Some(OCRProperty::Float(angle)) => Some(*angle),
+            _ => None,
+        }
+    }
+
+    /// checked by a person: corrected (x_wconf 100) and not flagged for another look
+    pub fn is_verified(&self) -> bool {
+        self.wconf() == Some(100) && !self.needs_review()
+    }
+
+    pub fn add_children_to_ocr_tree(elt_ref: ElementRef, par_id: u32, tree: &mut Tree<OCRElement>) {
         for child in elt_ref.children() {
             if let Some(child_ref) = ElementRef::wrap(child) {
                 if OCR_SELECTOR.matches(&child_ref) {
@@ -163,6 +254,9 @@ impl OCRElement {
                     if res.is_err() {
                         println!("{}", res.err().unwrap());
                     }
+                } else {
+                    // some generators wrap ocr elements in plain divs; look through them
+                    Self::add_children_to_ocr_tree(child_ref, par_id, tree);
                 }
             }
         }
@@ -172,7 +266,7 @@ impl OCRElement {
         root.text().filter(|s| !s.trim().is_empty()).join("")
     }
 
-    fn html_elt_to_ocr_elt(elt: ElementRef) -> Result<OCRElement, String> {
+    pub fn html_elt_to_ocr_elt(elt: ElementRef) -> Result<OCRElement, String> {
         let mut ocr_class = "";
         // assumes this element matcehs the OCR selector
         for class in elt.value().classes() {
@@ -188,7 +282,7 @@ impl OCRElement {
             .parse()
             .map_err(|_| format!("Failed to parse {} into OCR class", ocr_class))?;
         let ocr_properties = if let Some(text) = elt.value().attr("title") {
-            OCRProperty::parse_properties(text).map_err(|x| x)?
+            OCRProperty::parse_properties(text)?
         } else {
             return Err(String::from("No content in title attribute"));
         };
@@ -201,14 +295,14 @@ impl OCRElement {
             } else {
                 String::new()
             },
-            ocr_lang: if let Some(lang) = elt.value().attr("lang") {
-                Some(lang.to_string())
-            } else {
-                None
-            },
+            ocr_lang: elt.value().attr("lang").map(|lang| lang.to_string()),
+            source_id: elt.value().attr("id").map(|id| id.to_string()),
+            source_title: elt.value().attr("title").map(|title| title.to_string()),
+            legacy_class: OCRClass::is_legacy(ocr_class).then(|| ocr_class.to_string()),
         })
     }
 
+    /// read every ocr_page of a parsed hOCR document, and everything in it, into a tree
     pub fn html_to_ocr_tree(html_tree: scraper::Html) -> Tree<OCRElement> {
         // recursively walk the html_tree starting from the root html node
         // look through all children
@@ -217,6 +311,14 @@ impl OCRElement {
         let mut tree: Tree<OCRElement> = Tree::new();
         // TODO: don't just grab ocr_pages
         for page_elt in html_tree.select(&OCR_PAGE_SELECTOR) {
+            // pages nested in another page are read as its children; restructuring lifts them out
+            let nested = page_elt
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| OCR_PAGE_SELECTOR.matches(&ancestor));
+            if nested {
+                continue;
+            }
             // if any html_elt_to_ocr_elt returns an error, we do nothing, which is fine
             let _ = Self::html_elt_to_ocr_elt(page_elt)
                 .map(|elt| tree.add_root(elt))
@@ -242,6 +344,35 @@ pub enum OCRClass {
 }
 
 impl OCRClass {
+    /// how deep this class normally sits under a page, so we know what can contain what
+    pub fn level(&self) -> u8 {
+        match self {
+            Self::Page => 0,
+            Self::CArea | Self::Separator | Self::Photo => 1,
+            Self::Par => 2,
+            Self::Line | Self::Caption => 3,
+            Self::Word => 4,
+        }
+    }
+
+    /// what goes directly inside an element of this class: blocks in pages, paragraphs in
+    /// blocks and so on; None for words, photos and separators
+    pub fn child_class(&self) -> Option<Self> {
+        match self {
+            Self::Page => Some(Self::CArea),
+            Self::CArea => Some(Self::Par),
+            Self::Par => Some(Self::Line),
+            Self::Line | Self::Caption => Some(Self::Word),
+            Self::Word | Self::Separator | Self::Photo => None,
+        }
+    }
+
+    /// whether an element of class child can go directly inside one of this class
+    pub fn can_contain(&self, child: &Self) -> bool {
+        self.child_class()
+            .is_some_and(|class| class.level() == child.level())
+    }
+
     pub fn variants() -> std::slice::Iter<'static, Self> {
         [
             Self::Page,
@@ -276,6 +407,13 @@ impl OCRClass {
             Self::Word => "word".to_string(),
         }
     }
+    pub fn is_legacy(class: &str) -> bool {
+        matches!(class, "ocr_word" | "ocrx_block" | "ocrx_line")
+    }
+    /// separators and photos are layout-only regions: they never carry text
+    pub fn is_textual(&self) -> bool {
+        !matches!(self, Self::Separator | Self::Photo)
+    }
 }
 
 pub struct ParseOCRError;
@@ -293,89 +431,227 @@ impl FromStr for OCRClass {
             "ocr_photo" => Ok(Self::Photo),
             "ocr_separator" => Ok(Self::Separator),
             "ocr_caption" => Ok(Self::Caption),
+            // hOCR 1.1 names
+            "ocr_word" => Ok(Self::Word),
+            "ocrx_block" => Ok(Self::CArea),
+            "ocrx_line" => Ok(Self::Line),
             _ => Err(ParseOCRError),
         }
     }
 }
 
-impl ToString for OCRClass {
-    fn to_string(&self) -> String {
-        match self {
-            Self::CArea => "ocr_carea".to_string(),
-            Self::Page => "ocr_page".to_string(),
-            Self::Line => "ocr_line".to_string(),
-            Self::Par => "ocr_par".to_string(),
-            Self::Word => "ocrx_word".to_string(),
-            Self::Photo => "ocr_photo".to_string(),
-            Self::Separator => "ocr_separator".to_string(),
-            Self::Caption => "ocr_caption".to_string(),
-        }
+impl fmt::Display for OCRClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let class = match self {
+            Self::CArea => "ocr_carea",
+            Self::Page => "ocr_page",
+            Self::Line => "ocr_line",
+            Self::Par => "ocr_par",
+            Self::Word => "ocrx_word",
+            Self::Photo => "ocr_photo",
+            Self::Separator => "ocr_separator",
+            Self::Caption => "ocr_caption",
+        };
+        f.write_str(class)
     }
 }
 
 impl OCRProperty {
-    // Return an error if we don't have a bbox (it is required for every OCR element)
+    /// one property from its name and the rest of its title entry; None for unknown names or
+    /// values that don't parse
+    pub fn parse_property(name: &str, suffix: &str) -> Option<OCRProperty> {
+        match name {
+            "image" => Some(OCRProperty::Image(String::from(suffix.trim_matches('"')))),
+            name if is_text_property(name) => Some(OCRProperty::Text(unquote(suffix))),
+            "bbox" => match rect_from_attr(suffix) {
+                Ok(rect) => Some(OCRProperty::BBox(rect)),
+                Err(_) => None,
+            },
+            "baseline" => {
+                let parts: Result<Vec<f32>, _> =
+                    suffix.splitn(2, " ").map(|x| x.parse::<f32>()).collect();
+                match parts {
+                    Ok(v) if v.len() == 2 => Some(OCRProperty::Baseline(v[0], v[1])),
+                    _ => None,
+                }
+            }
+            "ppageno" | "x_wconf" | "x_review" | "x_continued" => match suffix.parse::<u32>() {
+                Ok(v) => Some(OCRProperty::UInt(v)),
+                Err(_) => None,
+            },
+            "scan_res" => {
+                let parts: Result<Vec<u32>, _> =
+                    suffix.splitn(2, " ").map(|x| x.parse::<u32>()).collect();
+                match parts {
+                    Ok(v) if v.len() == 2 => Some(OCRProperty::ScanRes(v[0], v[1])),
+                    _ => None,
+                }
+            }
+            "x_size" | "x_descenders" | "x_ascenders" | "textangle" => {
+                match suffix.parse::<f32>() {
+                    Ok(v) => Some(OCRProperty::Float(v)),
+                    Err(_) => None,
+                }
+            }
+            "x_confs" | "x_ignore" => suffix
+                .split_whitespace()
+                .map(|x| x.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .ok()
+                .map(OCRProperty::Floats),
+            _ => None,
+        }
+    }
+
+    /// Return an error if we don't have a bbox (it is required for every OCR element)
     pub fn parse_properties(title_content: &str) -> Result<HashMap<String, OCRProperty>, String> {
         let mut property_dict = HashMap::new();
-        for pattern in title_content.split_terminator("; ") {
+        for pattern in split_title(title_content) {
             // println!("{}", pattern);
             if let Some((prefix, suffix)) = pattern.split_once(" ") {
                 let trimmed = prefix.trim();
-                let ocr_prop = match trimmed {
-                    "image" => Some(OCRProperty::Image(String::from(suffix.trim_matches('"')))),
-                    "bbox" => match rect_from_attr(suffix) {
-                        Ok(rect) => Some(OCRProperty::BBox(rect)),
-                        Err(_) => None,
-                    },
-                    "baseline" => {
-                        let parts: Result<Vec<f32>, _> =
-                            suffix.splitn(2, " ").map(|x| x.parse::<f32>()).collect();
-                        match parts {
-                            Ok(v) => Some(OCRProperty::Baseline(v[0], v[1])),
-                            Err(_) => None,
-                        }
-                    }
-                    "ppageno" | "x_wconf" => match suffix.parse::<u32>() {
-                        Ok(v) => Some(OCRProperty::UInt(v)),
-                        Err(_) => None,
-                    },
-                    "scan_res" => {
-                        let parts: Result<Vec<u32>, _> =
-                            suffix.splitn(2, " ").map(|x| x.parse::<u32>()).collect();
-                        match parts {
-                            Ok(v) => Some(OCRProperty::ScanRes(v[0], v[1])),
-                            Err(_) => None,
-                        }
-                    }
-                    "x_size" | "x_descenders" | "x_ascenders" => match suffix.parse::<f32>() {
-                        Ok(v) => Some(OCRProperty::Float(v)),
-                        Err(_) => None,
-                    },
-                    _ => None,
-                };
-                if !ocr_prop.is_none() {
-                    property_dict.insert(trimmed.to_string(), ocr_prop.unwrap());
+                if let Some(ocr_prop) = Self::parse_property(trimmed, suffix) {
+                    property_dict.insert(trimmed.to_string(), ocr_prop);
                 }
             }
         }
-        if property_dict.get("bbox").is_none() {
+        if !property_dict.contains_key("bbox") {
             return Err(String::from("Couldn't find bbox in properties!"));
         }
         Ok(property_dict)
     }
 }
 
+/// whether the property called name is free text, written quoted
+pub fn is_text_property(name: &str) -> bool {
+    name == "x_joined" || name.starts_with(USER_PROPERTY_PREFIX)
+}
+
+/// text as a quoted title value; quotes and backslashes in it are escaped with a backslash
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// the text of a quoted title value, or the value itself if it isn't quoted
+pub fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let inner = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return value.to_string(),
+    };
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// the entries of a title, split at semicolons outside quoted values
+pub fn split_title(title: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in title.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                entries.push(title[start..i].trim_start());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    entries.push(title[start..].trim_start());
+    entries.retain(|entry| !entry.is_empty());
+    entries
+}
+
+/// write every element under its modern class name from now on
+pub fn normalize_legacy_classes(tree: &mut Tree<OCRElement>) {
+    for id in tree.preorder() {
+        if let Some(elt) = tree.get_mut_node(&id) {
+            elt.legacy_class = None;
+        }
+    }
+}
+
+/// how ids are made up when writing: PerPage gives word_1_5 (5th word overall, on page 1),
+/// Global leaves the page out: word_5
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum IdScheme {
+    #[default]
+    PerPage,
+    Global,
+}
+
+impl IdScheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "per-page" => Some(IdScheme::PerPage),
+            "global" => Some(IdScheme::Global),
+            _ => None,
+        }
+    }
+}
+
+/// what the hOCR writer is allowed to vary; a project file can set these
+#[derive(Debug, Default, Clone)]
+pub struct WriteOptions {
+    pub id_scheme: IdScheme,
+    /// only these properties get written (and bbox, always); None writes everything
+    pub properties: Option<Vec<String>>,
+}
+
+impl WriteOptions {
+    /// whether the property called name goes in the written title; without its bbox an
+    /// element couldn't be read back
+    pub fn writes(&self, name: &str) -> bool {
+        name == "bbox"
+            || self
+                .properties
+                .as_ref()
+                .is_none_or(|allowed| allowed.iter().any(|p| p == name))
+    }
+}
+
+/// image properties written as file:// uris (as some older tools did) become plain paths,
+/// which is what hOCR expects; returns how many pages were changed
+pub fn migrate_image_uris(tree: &mut Tree<OCRElement>) -> usize {
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    let mut migrated = 0;
+    for page in pages {
+        if let Some(elt) = tree.get_mut_node(&page) {
+            if let Some(OCRProperty::Image(image)) = elt.ocr_properties.get_mut("image") {
+                if image.starts_with("file://") {
+                    *image = uri::uri_to_path(image).to_string_lossy().to_string();
+                    migrated += 1;
+                }
+            }
+        }
+    }
+    migrated
+}
+
+/// write the tree out as the body of a copy of html_head (a document without a body), with
+/// fresh ids
 pub fn add_as_body(tree: &Tree<OCRElement>, html_head: &scraper::Html) -> scraper::Html {
+    add_as_body_with(tree, html_head, &WriteOptions::default())
+}
+
+/// add_as_body with other ids or fewer properties
+pub fn add_as_body_with(
+    tree: &Tree<OCRElement>,
+    html_head: &scraper::Html,
+    options: &WriteOptions,
+) -> scraper::Html {
     let mut html_final = html_head.clone();
-    // debug
     // TODO: this guy doesn't have the doctype
-    println!("head of cloned: {}", html_final.html());
-    let mut ids = HashMap::<String, u32>::new();
-    ids.insert("page".to_string(), 1);
-    ids.insert("block".to_string(), 1);
-    ids.insert("par".to_string(), 1);
-    ids.insert("line".to_string(), 1);
-    ids.insert("word".to_string(), 1);
+    let mut ids = fresh_counters(1);
     // add body element to html
     let html_id = html_final.root_element().id();
     let body_id = html_final.create_element(
@@ -386,30 +662,87 @@ pub fn add_as_body(tree: &Tree<OCRElement>, html_head: &scraper::Html) -> scrape
     html_final.append(&html_id, AppendNode(body_id));
     // now add the roots
     for root in tree.roots() {
-        add_ocr_tree(&tree, root, &mut ids, &mut html_final, &body_id);
+        add_ocr_tree(tree, root, &mut ids, options, &mut html_final, &body_id);
     }
     html_final
 }
 
+/// serialize one page subtree on its own, with ids numbered as page page_no
+pub fn page_to_html(tree: &Tree<OCRElement>, page: &InternalID, page_no: u32) -> String {
+    page_to_html_with(tree, page, page_no, &WriteOptions::default())
+}
+
+/// page_to_html with other ids or fewer properties
+pub fn page_to_html_with(
+    tree: &Tree<OCRElement>,
+    page: &InternalID,
+    page_no: u32,
+    options: &WriteOptions,
+) -> String {
+    // new_fragment has no <html> element to hang the page from; parsing an empty one does
+    let mut html = scraper::Html::parse_fragment("");
+    let mut ids = fresh_counters(page_no);
+    let root_id = html.root_element().id();
+    add_ocr_tree(tree, page, &mut ids, options, &mut html, &root_id);
+    html.root_element().inner_html()
+}
+
+// the numbers the next id of each kind gets, starting on page page_no
+fn fresh_counters(page_no: u32) -> HashMap<String, u32> {
+    let mut ids = HashMap::<String, u32>::new();
+    ids.insert("page".to_string(), page_no);
+    ids.insert("block".to_string(), 1);
+    ids.insert("par".to_string(), 1);
+    ids.insert("line".to_string(), 1);
+    ids.insert("word".to_string(), 1);
+    ids
+}
+
+fn next_html_id(
+    class: &OCRClass,
+    ids: &mut HashMap<String, u32>,
+    options: &WriteOptions,
+) -> String {
+    let type_id = class.to_id_str();
+    let curr_no = *ids.get(&type_id).unwrap();
+    ids.insert(type_id.clone(), curr_no + 1);
+    if type_id == "page" {
+        format! {"page_{}", curr_no}
+    } else if options.id_scheme == IdScheme::Global {
+        format!("{}_{}", type_id, curr_no)
+    } else {
+        format!("{}_{}_{}", type_id, *ids.get("page").unwrap() - 1, curr_no)
+    }
+}
+
+/// the id add_as_body_with would give each element if the tree were saved now
+pub fn written_ids(tree: &Tree<OCRElement>, options: &WriteOptions) -> HashMap<String, InternalID> {
+    let mut ids = fresh_counters(1);
+    tree.preorder()
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            Some((next_html_id(&elt.ocr_element_type, &mut ids, options), id))
+        })
+        .collect()
+}
+
 // add node as a child of parent in html
 fn add_ocr_tree(
     tree: &Tree<OCRElement>,
     node: &InternalID,
     ids: &mut HashMap<String, u32>,
+    options: &WriteOptions,
     html: &mut scraper::Html,
     parent_id: &ego_tree::NodeId,
 ) {
     if let Some(n) = tree.get_node(node) {
-        let type_id = n.ocr_element_type.to_id_str();
-        let curr_no = *ids.get(&type_id).unwrap();
-        ids.insert(type_id.clone(), curr_no + 1);
-        let html_id = if type_id == "page" {
-            format! {"page_{}", curr_no}
-        } else {
-            format!("{}_{}_{}", type_id, *ids.get("page").unwrap() - 1, curr_no)
-        };
+        let html_id = next_html_id(&n.ocr_element_type, ids, options);
         let mut props = Vec::new();
         for (name, prop) in n.ocr_properties.iter() {
+            if !options.writes(name) {
+                continue;
+            }
             props.push(format!("{} {}", name, prop.to_str()));
         }
         let mut attrs: Vec<Attribute> = Vec::new();
@@ -423,7 +756,7 @@ fn add_ocr_tree(
         });
         attrs.push(Attribute {
             name: QualName::new(None, ns!(), local_name!("class")),
-            value: n.ocr_element_type.to_string().into(),
+            value: n.class_name().into(),
         });
         if let Some(lang) = &n.ocr_lang {
             attrs.push(Attribute {
@@ -450,7 +783,7 @@ fn add_ocr_tree(
         // s.push_str(&n.to_html_elt_with_id(html_id));
         // then serialize my chlidren
         for child in tree.children(node) {
-            add_ocr_tree(tree, child, ids, html, &child_id);
+            add_ocr_tree(tree, child, ids, options, html, &child_id);
             // s.push_str(&serialize_me_and_children(tree, child, ids));
         }
     }
@@ -458,6 +791,9 @@ fn add_ocr_tree(
 
 fn build_text(tree: &Tree<OCRElement>, id: InternalID, count: &mut u32, s: &mut String) {
     if let Some(node) = tree.get_node(&id) {
+        if !node.ocr_element_type.is_textual() {
+            return;
+        }
         if !node.ocr_text.trim().is_empty() {
             s.push_str(node.ocr_text.as_str());
             *count += 1;
@@ -474,9 +810,329 @@ fn build_text(tree: &Tree<OCRElement>, id: InternalID, count: &mut u32, s: &mut
     }
 }
 
-pub(crate) fn get_root_preview_text(tree: &Tree<OCRElement>, root: InternalID) -> String {
+pub fn get_root_preview_text(tree: &Tree<OCRElement>, root: InternalID) -> String {
     let mut s = String::new();
     let mut count = 0;
     build_text(tree, root, &mut count, &mut s);
     s
 }
+
+/// give every page a fresh ppageno: start, start + step, ...
+pub fn renumber_pages(tree: &mut Tree<OCRElement>, start: u32, step: u32) {
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    for (index, page) in pages.iter().enumerate() {
+        if let Some(elt) = tree.get_mut_node(page) {
+            elt.ocr_properties.insert(
+                "ppageno".to_string(),
+                OCRProperty::UInt(start + step * index as u32),
+            );
+        }
+    }
+}
+
+/// pages whose ppageno repeats an earlier one or goes backwards
+pub fn ppageno_problems(tree: &Tree<OCRElement>) -> Vec<(InternalID, String)> {
+    let mut problems = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut last: Option<u32> = None;
+    for (index, page) in tree.roots().enumerate() {
+        let ppageno = match tree
+            .get_node(page)
+            .and_then(|elt| elt.ocr_properties.get("ppageno"))
+        {
+            Some(OCRProperty::UInt(ppageno)) => *ppageno,
+            _ => continue,
+        };
+        if !seen.insert(ppageno) {
+            problems.push((
+                *page,
+                format!("page {} repeats ppageno {}", index + 1, ppageno),
+            ));
+        } else if last.is_some_and(|last| ppageno < last) {
+            problems.push((
+                *page,
+                format!(
+                    "page {} has ppageno {} after {}",
+                    index + 1,
+                    ppageno,
+                    last.unwrap()
+                ),
+            ));
+        }
+        last = Some(ppageno);
+    }
+    problems
+}
+
+/// the smallest child of id whose bbox contains pos (in image coords)
+pub fn child_at(tree: &Tree<OCRElement>, id: &InternalID, pos: Pos2) -> Option<InternalID> {
+    tree.children(id)
+        .filter_map(|child| {
+            let bbox = tree.get_node(child)?.bbox()?;
+            bbox.contains(pos).then(|| (*child, bbox.area()))
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(child, _)| child)
+}
+
+/// the innermost element under id (id itself included) whose bbox holds all of rect and
+/// that can have children
+pub fn container_of(tree: &Tree<OCRElement>, id: &InternalID, rect: Rect) -> InternalID {
+    let mut found = *id;
+    while let Some(child) = tree
+        .children(&found)
+        .filter_map(|child| {
+            let node = tree.get_node(child)?;
+            let bbox = node.bbox()?;
+            (node.ocr_element_type.child_class().is_some() && bbox.contains_rect(rect))
+                .then(|| (*child, bbox.area()))
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(child, _)| child)
+    {
+        found = child;
+    }
+    found
+}
+
+/// whether elements can be joined into one: they're all of the same class and siblings of
+/// each other, so nothing of one is lost and no bbox spans pages
+pub fn can_join(tree: &Tree<OCRElement>, ids: &[InternalID]) -> bool {
+    let (first, rest) = match ids.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let (class, parent) = match (tree.get_node(first), tree.parent(first)) {
+        (Some(elt), Some(parent)) => (&elt.ocr_element_type, parent),
+        _ => return false,
+    };
+    rest.iter().all(|id| {
+        tree.parent(id) == Some(parent)
+            && tree
+                .get_node(id)
+                .is_some_and(|elt| elt.ocr_element_type == *class)
+    })
+}
+
+/// merge sibling elements of one class into the first of them in document order: the others'
+/// children move over after its own, their bboxes are added to its bbox and words' text is
+/// joined with separator in between. Returns the element that's left, or None when they
+/// can't be joined.
+pub fn join_elements(
+    tree: &mut Tree<OCRElement>,
+    ids: &[InternalID],
+    separator: &str,
+) -> Option<InternalID> {
+    if !can_join(tree, ids) {
+        return None;
+    }
+    let order: HashMap<InternalID, usize> = tree
+        .preorder()
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| (id, i))
+        .collect();
+    let mut ids = ids.to_vec();
+    ids.sort_by_key(|id| order[id]);
+    let (kept, rest) = ids.split_first()?;
+    for id in rest {
+        let children: Vec<InternalID> = tree.children(id).copied().collect();
+        for child in children {
+            let _ = tree.move_node(&child, kept);
+        }
+        let (bbox, text, wconf) = match tree.get_node(id) {
+            Some(elt) => (elt.bbox().copied(), elt.ocr_text.clone(), elt.wconf()),
+            None => continue,
+        };
+        if let Some(elt) = tree.get_mut_node(kept) {
+            if let (Some(bbox), Some(OCRProperty::BBox(kept_bbox))) =
+                (bbox, elt.ocr_properties.get_mut("bbox"))
+            {
+                *kept_bbox = kept_bbox.union(bbox);
+            }
+            if elt.ocr_element_type == OCRClass::Word {
+                elt.ocr_text.push_str(separator);
+                elt.ocr_text.push_str(&text);
+                // the joined word is only as sure as its least sure part
+                if let (Some(wconf), Some(kept_wconf)) = (wconf, elt.wconf()) {
+                    elt.ocr_properties.insert(
+                        String::from("x_wconf"),
+                        OCRProperty::UInt(wconf.min(kept_wconf)),
+                    );
+                }
+                elt.ocr_properties.remove("x_confs");
+            }
+        }
+        tree.delete_node(id);
+    }
+    Some(*kept)
+}
+
+/// merge id with the sibling before or after it, the way join_elements does, returning the
+/// one of the two that's left
+pub fn merge_with_sibling(
+    tree: &mut Tree<OCRElement>,
+    id: &InternalID,
+    pos: &Position,
+    separator: &str,
+) -> Option<InternalID> {
+    let sibling = match pos {
+        Position::After => tree.next_sibling(id),
+        Position::Before => tree.prev_sibling(id),
+    }?;
+    join_elements(tree, &[*id, sibling], separator)
+}
+
+/// reorder id's children top to bottom, and left to right within a row. A child whose
+/// vertical middle falls inside the first box of the row above joins that row; children
+/// without a bbox go last. Returns whether the order changed.
+pub fn sort_children_by_position(tree: &mut Tree<OCRElement>, id: &InternalID) -> bool {
+    let before: Vec<InternalID> = tree.children(id).copied().collect();
+    let mut boxed: Vec<(InternalID, Rect)> = before
+        .iter()
+        .filter_map(|child| Some((*child, *tree.get_node(child)?.bbox()?)))
+        .collect();
+    boxed.sort_by(|a, b| a.1.top().total_cmp(&b.1.top()));
+    let mut rows: Vec<(Rect, Vec<(InternalID, Rect)>)> = Vec::new();
+    for (child, bbox) in boxed {
+        match rows.last_mut() {
+            Some((first, row)) if (first.top()..=first.bottom()).contains(&bbox.center().y) => {
+                row.push((child, bbox))
+            }
+            _ => rows.push((bbox, vec![(child, bbox)])),
+        }
+    }
+    let mut rank: HashMap<InternalID, usize> = HashMap::new();
+    for (_, mut row) in rows {
+        row.sort_by(|a, b| a.1.left().total_cmp(&b.1.left()));
+        for (child, _) in row {
+            rank.insert(child, rank.len());
+        }
+    }
+    let _ = tree.sort_children_by_key(id, |child| rank.get(child).copied().unwrap_or(usize::MAX));
+    !tree.children(id).eq(before.iter())
+}
+
+/// the lang a new node under id should get: the closest ancestor's lang, so the page lang acts as a default
+pub fn inherited_lang(tree: &Tree<OCRElement>, id: &InternalID) -> Option<String> {
+    std::iter::once(*id)
+        .chain(tree.ancestors(id))
+        .filter_map(|anc_id| tree.get_node(&anc_id))
+        .find_map(|node| node.ocr_lang.clone())
+}
+
+/// the direction the text of id runs in, counterclockwise in degrees: its own textangle or the
+/// closest ancestor's, so words in a vertical caption read the same way as the caption
+pub fn inherited_text_angle(tree: &Tree<OCRElement>, id: &InternalID) -> f32 {
+    std::iter::once(*id)
+        .chain(tree.ancestors(id))
+        .filter_map(|anc_id| tree.get_node(&anc_id))
+        .find_map(|node| node.text_angle())
+        .unwrap_or(0.0)
+}
+
+/// how many quarter turns counterclockwise an angle is closest to, 0 to 3
+pub fn quarter_turns(angle: f32) -> u32 {
+    ((angle / 90.0).round() as i32).rem_euclid(4) as u32
+}
+
+/// (word lang, par lang) if a word is marked with a different lang than its paragraph
+pub fn lang_mismatch(tree: &Tree<OCRElement>, id: &InternalID) -> Option<(String, String)> {
+    let node = tree.get_node(id)?;
+    if node.ocr_element_type != OCRClass::Word {
+        return None;
+    }
+    let word_lang = node.ocr_lang.clone()?;
+    let par_lang = tree
+        .ancestors(id)
+        .iter()
+        .filter_map(|anc_id| tree.get_node(anc_id))
+        .find(|anc| anc.ocr_element_type == OCRClass::Par)?
+        .ocr_lang
+        .clone()?;
+    if word_lang != par_lang {
+        Some((word_lang, par_lang))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_PAGES: &str = r#"<html><body>
+<div class="ocr_page" id="page_1" title="bbox 0 0 100 100; image &quot;a.png&quot;">
+ <span class="ocr_line" title="bbox 0 0 100 10">
+  <span class="ocrx_word" title="bbox 0 0 40 10; x_wconf 90">one</span>
+  <span class="ocrx_word" title="bbox 50 0 100 10; x_wconf 80">two</span>
+ </span>
+</div>
+<div class="ocr_page" id="page_2" title="bbox 0 0 100 100">
+ <span class="ocr_line" title="bbox 0 0 100 10">
+  <span class="ocrx_word" title="bbox 0 0 40 10">three</span>
+ </span>
+</div>
+</body></html>"#;
+
+    #[test]
+    fn quote_and_unquote_round_trip() {
+        for text in ["plain", r#"a "quoted" word"#, r"back\slash", "semi;colon"] {
+            assert_eq!(unquote(&quote(text)), text);
+        }
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        // unquoted values come back as they are
+        assert_eq!(unquote(" 12 34 "), "12 34");
+    }
+
+    #[test]
+    fn split_title_skips_quoted_semicolons() {
+        let title = r#"bbox 0 0 10 10; image "a;b.png"; x_wconf 90;"#;
+        assert_eq!(
+            split_title(title),
+            ["bbox 0 0 10 10", r#"image "a;b.png""#, "x_wconf 90"]
+        );
+        let escaped = r#"x_source "say \"a;b\""; ppageno 3"#;
+        assert_eq!(split_title(escaped).len(), 2);
+    }
+
+    #[test]
+    fn parses_pages_lines_and_words() {
+        let tree = OCRElement::html_to_ocr_tree(scraper::Html::parse_document(TWO_PAGES));
+        let classes: Vec<OCRClass> = tree
+            .preorder()
+            .iter()
+            .map(|id| tree.get_node(id).unwrap().ocr_element_type.clone())
+            .collect();
+        assert_eq!(tree.roots().count(), 2);
+        assert_eq!(
+            classes,
+            [
+                OCRClass::Page,
+                OCRClass::Line,
+                OCRClass::Word,
+                OCRClass::Word,
+                OCRClass::Page,
+                OCRClass::Line,
+                OCRClass::Word,
+            ]
+        );
+        let first_word = tree.get_node(&tree.preorder()[2]).unwrap();
+        assert_eq!(first_word.ocr_text, "one");
+        assert_eq!(first_word.wconf(), Some(90));
+    }
+
+    #[test]
+    fn written_ids_follow_the_scheme() {
+        let tree = OCRElement::html_to_ocr_tree(scraper::Html::parse_document(TWO_PAGES));
+        let last_word = *tree.preorder().last().unwrap();
+        let per_page = written_ids(&tree, &WriteOptions::default());
+        assert_eq!(per_page.get("word_2_3"), Some(&last_word));
+        assert_eq!(per_page.len(), tree.preorder().len());
+        let global = WriteOptions {
+            id_scheme: IdScheme::Global,
+            ..Default::default()
+        };
+        assert_eq!(written_ids(&tree, &global).get("word_3"), Some(&last_word));
+    }
+}
@@ -0,0 +1,539 @@
+use crate::atomic;
+use crate::ignore;
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::Pos2;
+use egui::Rect;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// PAGE XML (what Transkribus and eScriptorium read) has one file per scan, with text
+// regions holding lines holding words, each outlined by a polygon. hOCR paragraphs become
+// regions since PAGE has no paragraph level, and our rectangles become 4-point polygons.
+
+const NAMESPACE: &str = "http://schema.primaresearch.org/PAGE/gts/pagecontent/2019-07-15";
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// clockwise from the top left, the way PAGE wants its points
+fn points(bbox: &Rect) -> String {
+    let (x0, y0, x1, y1) = (
+        bbox.min.x.round() as i32,
+        bbox.min.y.round() as i32,
+        bbox.max.x.round() as i32,
+        bbox.max.y.round() as i32,
+    );
+    format!("{},{} {},{} {},{} {},{}", x0, y0, x1, y0, x1, y1, x0, y1)
+}
+
+// hOCR's baseline is a slope and an offset from the bottom left of the bbox
+fn baseline_points(elt: &OCRElement) -> Option<String> {
+    let bbox = elt.bbox()?;
+    match elt.ocr_properties.get("baseline") {
+        Some(OCRProperty::Baseline(slope, offset)) => {
+            let y = |x: f32| bbox.max.y + offset + slope * (x - bbox.min.x);
+            Some(format!(
+                "{},{} {},{}",
+                bbox.min.x.round() as i32,
+                y(bbox.min.x).round() as i32,
+                bbox.max.x.round() as i32,
+                y(bbox.max.x).round() as i32
+            ))
+        }
+        _ => None,
+    }
+}
+
+// 2024-05-01T12:00:00Z without pulling in a date crate
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs()) as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn descendants_of(
+    tree: &Tree<OCRElement>,
+    id: &InternalID,
+    classes: &[OCRClass],
+) -> Vec<InternalID> {
+    tree.descendants(id)
+        .into_iter()
+        .filter(|child| {
+            tree.get_node(child)
+                .is_some_and(|elt| classes.contains(&elt.ocr_element_type))
+        })
+        .collect()
+}
+
+fn text_equiv(s: &mut String, indent: &str, text: &str, conf: Option<u32>) {
+    let conf = conf.map_or(String::new(), |c| {
+        format!(" conf=\"{:.2}\"", c as f32 / 100.0)
+    });
+    let _ = writeln!(
+        s,
+        "{}<TextEquiv{}><Unicode>{}</Unicode></TextEquiv>",
+        indent,
+        conf,
+        escape(text)
+    );
+}
+
+fn write_line(s: &mut String, tree: &Tree<OCRElement>, line: &InternalID, id: &str) -> String {
+    let elt = match tree.get_node(line) {
+        Some(elt) => elt,
+        None => return String::new(),
+    };
+    let _ = writeln!(s, "      <TextLine id=\"{}\">", id);
+    if let Some(bbox) = elt.bbox() {
+        let _ = writeln!(s, "        <Coords points=\"{}\"/>", points(bbox));
+    }
+    if let Some(baseline) = baseline_points(elt) {
+        let _ = writeln!(s, "        <Baseline points=\"{}\"/>", baseline);
+    }
+    let mut words = Vec::new();
+    for (i, word) in descendants_of(tree, line, &[OCRClass::Word])
+        .iter()
+        .enumerate()
+    {
+        let word = match tree.get_node(word) {
+            Some(word) => word,
+            None => continue,
+        };
+        let _ = writeln!(s, "        <Word id=\"{}_w{}\">", id, i + 1);
+        if let Some(bbox) = word.bbox() {
+            let _ = writeln!(s, "          <Coords points=\"{}\"/>", points(bbox));
+        }
+        text_equiv(s, "          ", word.ocr_text.trim(), word.wconf());
+        let _ = writeln!(s, "        </Word>");
+        if !word.ocr_text.trim().is_empty() {
+            words.push(word.ocr_text.trim());
+        }
+    }
+    let text = words.join(" ");
+    text_equiv(s, "        ", &text, None);
+    let _ = writeln!(s, "      </TextLine>");
+    text
+}
+
+// hOCR paragraphs, and blocks without any, are the text regions
+fn text_regions(tree: &Tree<OCRElement>, page: &InternalID) -> Vec<InternalID> {
+    tree.descendants(page)
+        .into_iter()
+        .filter(
+            |id| match tree.get_node(id).map(|elt| &elt.ocr_element_type) {
+                Some(OCRClass::Par) => true,
+                Some(OCRClass::CArea) => descendants_of(tree, id, &[OCRClass::Par]).is_empty(),
+                _ => false,
+            },
+        )
+        .collect()
+}
+
+pub fn page_to_xml(tree: &Tree<OCRElement>, page: &InternalID) -> String {
+    let mut s = String::new();
+    let page_elt = match tree.get_node(page) {
+        Some(elt) => elt,
+        None => return s,
+    };
+    let image = match page_elt.ocr_properties.get("image") {
+        Some(OCRProperty::Image(path)) => path.clone(),
+        _ => String::new(),
+    };
+    let (width, height) = page_elt
+        .bbox()
+        .map_or((0, 0), |bbox| (bbox.max.x as i32, bbox.max.y as i32));
+    let now = timestamp();
+    let _ = writeln!(s, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    let _ = writeln!(s, "<PcGts xmlns=\"{}\">", NAMESPACE);
+    let _ = writeln!(s, "  <Metadata>");
+    let _ = writeln!(s, "    <Creator>hocr_editor</Creator>");
+    let _ = writeln!(s, "    <Created>{}</Created>", now);
+    let _ = writeln!(s, "    <LastChange>{}</LastChange>", now);
+    let _ = writeln!(s, "  </Metadata>");
+    let _ = writeln!(
+        s,
+        "  <Page imageFilename=\"{}\" imageWidth=\"{}\" imageHeight=\"{}\">",
+        escape(&image),
+        width,
+        height
+    );
+    for (r, region) in text_regions(tree, page).iter().enumerate() {
+        let id = format!("r{}", r + 1);
+        let _ = writeln!(s, "    <TextRegion id=\"{}\" type=\"paragraph\">", id);
+        if let Some(bbox) = tree.get_node(region).and_then(|elt| elt.bbox()) {
+            let _ = writeln!(s, "      <Coords points=\"{}\"/>", points(bbox));
+        }
+        let mut lines = Vec::new();
+        for (l, line) in descendants_of(tree, region, &[OCRClass::Line, OCRClass::Caption])
+            .iter()
+            .enumerate()
+        {
+            lines.push(write_line(
+                &mut s,
+                tree,
+                line,
+                &format!("{}_l{}", id, l + 1),
+            ));
+        }
+        text_equiv(&mut s, "      ", &lines.join("\n"), None);
+        let _ = writeln!(s, "    </TextRegion>");
+    }
+    // layout-only regions keep their outline so the page layout survives the round trip
+    let others = descendants_of(tree, page, &[OCRClass::Separator, OCRClass::Photo]);
+    for (i, id) in others.iter().enumerate() {
+        let elt = match tree.get_node(id) {
+            Some(elt) => elt,
+            None => continue,
+        };
+        let tag = match elt.ocr_element_type {
+            OCRClass::Separator => "SeparatorRegion",
+            _ => "ImageRegion",
+        };
+        let _ = writeln!(s, "    <{} id=\"o{}\">", tag, i + 1);
+        if let Some(bbox) = elt.bbox() {
+            let _ = writeln!(s, "      <Coords points=\"{}\"/>", points(bbox));
+        }
+        let _ = writeln!(s, "    </{}>", tag);
+    }
+    let _ = writeln!(s, "  </Page>");
+    let _ = writeln!(s, "</PcGts>");
+    s
+}
+
+// where write_pages puts each page, in order: out_dir/<the page's image>.xml, or
+// out_dir/stem_0001.xml if it has none
+pub fn page_paths(tree: &Tree<OCRElement>, stem: &str, out_dir: &Path) -> Vec<PathBuf> {
+    tree.roots()
+        .enumerate()
+        .map(|(i, page)| {
+            let image_stem = match tree
+                .get_node(page)
+                .and_then(|elt| elt.ocr_properties.get("image"))
+            {
+                Some(OCRProperty::Image(path)) => Path::new(path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string()),
+                _ => None,
+            };
+            let name = image_stem.unwrap_or_else(|| format!("{}_{:04}", stem, i + 1));
+            // with_extension would cut scan.001 down to scan
+            out_dir.join(format!("{}.xml", name))
+        })
+        .collect()
+}
+
+// one xml file per page in out_dir, named as page_paths says; returns the files written
+pub fn write_pages(
+    tree: &Tree<OCRElement>,
+    stem: &str,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let stripped = ignore::without_ignored(tree);
+    let tree = &*stripped;
+    let mut written = Vec::new();
+    for (page, path) in tree.roots().zip(page_paths(tree, stem, out_dir)) {
+        atomic::write_atomic(&path, page_to_xml(tree, page))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// what a PAGE import is saved with: PAGE has nothing to carry over into an hOCR head
+pub const HOCR_SKELETON: &str = "<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\">
+<head>
+<title></title>
+<meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/>
+<meta name=\"ocr-system\" content=\"hocr_editor\"/>
+<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word\"/>
+</head>
+<body></body>
+</html>";
+
+pub fn is_page_xml(source: &str) -> bool {
+    source.contains("<PcGts")
+}
+
+// looks at the start of a file only, so huge hOCR files opened lazily aren't read twice
+pub fn file_is_page_xml(path: &Path) -> bool {
+    let mut start = [0; 4096];
+    let read = std::fs::File::open(path).and_then(|mut file| {
+        use std::io::Read;
+        file.read(&mut start)
+    });
+    read.is_ok_and(|n| is_page_xml(&String::from_utf8_lossy(&start[..n])))
+}
+
+// "x,y x,y ..." -> the rectangle around the points
+fn parse_points(points: &str) -> Option<Rect> {
+    let pts: Vec<Pos2> = points
+        .split_whitespace()
+        .filter_map(|pair| {
+            let (x, y) = pair.split_once(',')?;
+            Some(Pos2::new(x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect();
+    if pts.is_empty() {
+        return None;
+    }
+    Some(Rect::from_points(&pts))
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn coords(node: roxmltree::Node) -> Option<Rect> {
+    child(node, "Coords")
+        .and_then(|c| c.attribute("points"))
+        .and_then(parse_points)
+}
+
+// the first TextEquiv's text and confidence (0-1 in PAGE, 0-100 in hOCR)
+fn text_equiv_of(node: roxmltree::Node) -> Option<(String, Option<u32>)> {
+    let equiv = child(node, "TextEquiv")?;
+    let text = child(equiv, "Unicode")
+        .and_then(|u| u.text())
+        .unwrap_or_default()
+        .to_string();
+    let conf = equiv
+        .attribute("conf")
+        .and_then(|c| c.parse::<f32>().ok())
+        .map(|c| (c * 100.0).round().clamp(0.0, 100.0) as u32);
+    Some((text, conf))
+}
+
+// back from polyline points to hOCR's slope and offset from the bottom left of bbox
+fn baseline_of(node: roxmltree::Node, bbox: &Rect) -> Option<OCRProperty> {
+    let points = child(node, "Baseline")?.attribute("points")?;
+    let pts: Vec<(f32, f32)> = points
+        .split_whitespace()
+        .filter_map(|pair| {
+            let (x, y) = pair.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect();
+    let (first, last) = (pts.first()?, pts.last()?);
+    let slope = if last.0 != first.0 {
+        (last.1 - first.1) / (last.0 - first.0)
+    } else {
+        0.0
+    };
+    let offset = first.1 + slope * (bbox.min.x - first.0) - bbox.max.y;
+    Some(OCRProperty::Baseline(slope, offset))
+}
+
+fn new_element(class: OCRClass, html_type: &str, bbox: Option<Rect>) -> OCRElement {
+    let mut properties = HashMap::new();
+    if let Some(bbox) = bbox {
+        properties.insert("bbox".to_string(), OCRProperty::BBox(bbox));
+    }
+    OCRElement {
+        html_element_type: html_type.to_string(),
+        ocr_element_type: class,
+        ocr_properties: properties,
+        ..Default::default()
+    }
+}
+
+fn word_element(bbox: Option<Rect>, text: String, conf: Option<u32>) -> OCRElement {
+    let mut word = new_element(OCRClass::Word, "span", bbox);
+    word.ocr_text = text;
+    if let Some(conf) = conf {
+        word.ocr_properties
+            .insert("x_wconf".to_string(), OCRProperty::UInt(conf));
+    }
+    word
+}
+
+fn import_line(tree: &mut Tree<OCRElement>, par: InternalID, line: roxmltree::Node) {
+    let bbox = coords(line);
+    let mut elt = new_element(OCRClass::Line, "span", bbox);
+    if let Some(baseline) = bbox.and_then(|bbox| baseline_of(line, &bbox)) {
+        elt.ocr_properties.insert("baseline".to_string(), baseline);
+    }
+    let line_id = match tree.push_child(&par, elt) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    let words: Vec<roxmltree::Node> = line.children().filter(|n| n.has_tag_name("Word")).collect();
+    if !words.is_empty() {
+        for word in words {
+            let (text, conf) = text_equiv_of(word).unwrap_or_default();
+            let _ = tree.push_child(&line_id, word_element(coords(word), text, conf));
+        }
+        return;
+    }
+    // line-level ground truth: split the text into words spread over the line by length
+    let text = text_equiv_of(line)
+        .map(|(text, _)| text)
+        .unwrap_or_default();
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let total: usize = tokens.iter().map(|t| t.chars().count() + 1).sum();
+    let mut x = bbox.map_or(0.0, |bbox| bbox.min.x);
+    for token in tokens {
+        let word_bbox = bbox.map(|bbox| {
+            let width = bbox.width() * (token.chars().count() + 1) as f32 / total as f32;
+            let word_bbox =
+                Rect::from_min_max(Pos2::new(x, bbox.min.y), Pos2::new(x + width, bbox.max.y));
+            x += width;
+            word_bbox
+        });
+        let _ = tree.push_child(&line_id, word_element(word_bbox, token.to_string(), None));
+    }
+}
+
+// region ids in reading order, from the first ReadingOrder group
+fn reading_order(page: roxmltree::Node) -> Vec<String> {
+    let group =
+        match child(page, "ReadingOrder").and_then(|ro| ro.children().find(|n| n.is_element())) {
+            Some(group) => group,
+            None => return Vec::new(),
+        };
+    let mut refs: Vec<(i64, String)> = group
+        .descendants()
+        .filter(|n| n.has_tag_name("RegionRefIndexed") || n.has_tag_name("RegionRef"))
+        .enumerate()
+        .filter_map(|(i, n)| {
+            let index = n
+                .attribute("index")
+                .and_then(|index| index.parse().ok())
+                .unwrap_or(i as i64);
+            Some((index, n.attribute("regionRef")?.to_string()))
+        })
+        .collect();
+    refs.sort_by_key(|(index, _)| *index);
+    refs.into_iter().map(|(_, id)| id).collect()
+}
+
+// a PAGE document as an hOCR tree: each TextRegion becomes a block with one paragraph,
+// in the file's reading order when it has one
+pub fn parse(source: &str) -> Result<Tree<OCRElement>, String> {
+    let doc = roxmltree::Document::parse(source).map_err(|e| e.to_string())?;
+    let mut tree = Tree::new();
+    for page in doc.descendants().filter(|n| n.has_tag_name("Page")) {
+        let width: f32 = page
+            .attribute("imageWidth")
+            .and_then(|w| w.parse().ok())
+            .unwrap_or(0.0);
+        let height: f32 = page
+            .attribute("imageHeight")
+            .and_then(|h| h.parse().ok())
+            .unwrap_or(0.0);
+        let mut page_elt = new_element(
+            OCRClass::Page,
+            "div",
+            Some(Rect::from_min_max(Pos2::ZERO, Pos2::new(width, height))),
+        );
+        if let Some(image) = page.attribute("imageFilename") {
+            page_elt
+                .ocr_properties
+                .insert("image".to_string(), OCRProperty::Image(image.to_string()));
+        }
+        let page_id = tree.add_root(page_elt);
+        let mut regions: Vec<roxmltree::Node> = page
+            .descendants()
+            .filter(|n| n.is_element() && n.tag_name().name().ends_with("Region") && n != &page)
+            .collect();
+        let order = reading_order(page);
+        // ordered regions first, the rest after them as they appear in the file
+        regions.sort_by_key(|region| {
+            region
+                .attribute("id")
+                .and_then(|id| order.iter().position(|o| o == id))
+                .unwrap_or(order.len())
+        });
+        for region in regions {
+            let bbox = coords(region);
+            let (class, html_type) = match region.tag_name().name() {
+                "TextRegion" => (OCRClass::CArea, "div"),
+                "SeparatorRegion" => (OCRClass::Separator, "div"),
+                "ImageRegion" | "GraphicRegion" => (OCRClass::Photo, "div"),
+                _ => continue,
+            };
+            let block = tree.push_child(&page_id, new_element(class.clone(), html_type, bbox))?;
+            if class != OCRClass::CArea {
+                continue;
+            }
+            let par = tree.push_child(&block, new_element(OCRClass::Par, "p", bbox))?;
+            for line in region.children().filter(|n| n.has_tag_name("TextLine")) {
+                import_line(&mut tree, par, line);
+            }
+        }
+    }
+    if tree.roots().len() == 0 {
+        return Err("No Page element found".to_string());
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_words_and_boxes() {
+        let tree = OCRElement::html_to_ocr_tree(scraper::Html::parse_document(
+            r#"<div class="ocr_page" title="bbox 0 0 200 100; image &quot;scan 1.png&quot;">
+<div class="ocr_carea" title="bbox 10 10 190 40"><p class="ocr_par" title="bbox 10 10 190 40">
+<span class="ocr_line" title="bbox 10 10 190 30">
+<span class="ocrx_word" title="bbox 10 10 90 30; x_wconf 95">Grüße</span>
+<span class="ocrx_word" title="bbox 100 10 190 30; x_wconf 40">&lt;und&gt;</span>
+</span></p></div></div>"#,
+        ));
+        let page = *tree.roots().next().unwrap();
+        let parsed = parse(&page_to_xml(&tree, &page)).unwrap();
+        let words = |tree: &Tree<OCRElement>| -> Vec<(String, Option<Rect>, Option<u32>)> {
+            tree.preorder()
+                .iter()
+                .filter_map(|id| tree.get_node(id))
+                .filter(|elt| elt.ocr_element_type == OCRClass::Word)
+                .map(|elt| (elt.ocr_text.clone(), elt.bbox().copied(), elt.wconf()))
+                .collect()
+        };
+        assert_eq!(words(&parsed), words(&tree));
+        let new_page = parsed.get_node(parsed.roots().next().unwrap()).unwrap();
+        assert_eq!(new_page.bbox(), tree.get_node(&page).unwrap().bbox());
+        assert!(matches!(
+            new_page.ocr_properties.get("image"),
+            Some(OCRProperty::Image(image)) if image == "scan 1.png"
+        ));
+    }
+
+    #[test]
+    fn a_file_without_pages_is_an_error() {
+        assert!(parse("<PcGts><Metadata/></PcGts>").is_err());
+        assert!(!is_page_xml("<html><body></body></html>"));
+    }
+}
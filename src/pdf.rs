@@ -0,0 +1,188 @@
+use crate::jobs::{Job, Progress};
+use crate::ocr::HocrJob;
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::page_xml;
+use crate::tree::Tree;
+use crate::{atomic, ocr_element};
+use egui::{Pos2, Rect};
+use pdfium_render::prelude::*;
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+// Opening a PDF renders its pages with pdfium into <stem>_pages/ next to it and writes
+// <stem>_pages/<stem>.hocr with one ocr_page per rendered page. If the PDF has a
+// <stem>.hocr sidecar its pages are used (scaled to the rendered size), otherwise the pages
+// start out empty, ready for New child or Re-OCR. An earlier import is opened instead of
+// being redone, and a folder that's already there is never written into: the pages go to
+// <stem>_pages_1/, <stem>_pages_2/, ... The pdfium library is looked for next to the
+// executable, then wherever the system keeps its libraries.
+
+pub const DEFAULT_DPI: u32 = 300;
+
+fn bind_pdfium() -> Result<Pdfium, String> {
+    let beside_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+        .unwrap_or_default();
+    Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&beside_exe))
+        .or_else(|_| Pdfium::bind_to_system_library())
+        .map(Pdfium::new)
+        .map_err(|e| format!("Couldn't load the pdfium library: {:?}", e))
+}
+
+fn pdf_stem(pdf: &Path) -> Result<String, String> {
+    Ok(pdf
+        .file_stem()
+        .ok_or(format!("{} isn't a file", pdf.display()))?
+        .to_string_lossy()
+        .to_string())
+}
+
+// the hOCR an earlier import of pdf wrote, if it's still there
+pub fn earlier_import(pdf: &Path) -> Option<PathBuf> {
+    let stem = pdf_stem(pdf).ok()?;
+    let path = pdf
+        .with_file_name(format!("{}_pages", stem))
+        .join(format!("{}.hocr", stem));
+    path.exists().then_some(path)
+}
+
+// <stem>_pages, or <stem>_pages_N if that's taken; whatever is in a taken folder may belong
+// to a transcription, so it's left alone
+fn free_pages_dir(pdf: &Path, stem: &str) -> PathBuf {
+    (0..)
+        .map(|n| match n {
+            0 => pdf.with_file_name(format!("{}_pages", stem)),
+            n => pdf.with_file_name(format!("{}_pages_{}", stem, n)),
+        })
+        .find(|dir| !dir.exists())
+        .unwrap()
+}
+
+// page-0001.png, page-0002.png, ... in out_dir, in page order
+fn render_pages(
+    pdf: &Path,
+    out_dir: &Path,
+    dpi: u32,
+    progress: &Progress,
+) -> Result<Vec<PathBuf>, String> {
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+    let pdfium = bind_pdfium()?;
+    let document = pdfium
+        .load_pdf_from_file(pdf, None)
+        .map_err(|e| format!("Failed to open {}: {:?}", pdf.display(), e))?;
+    // PDF sizes are in points, 72 to the inch
+    let config = PdfRenderConfig::new().scale_page_by_factor(dpi as f32 / 72.0);
+    let mut pages = Vec::new();
+    progress.set_total(document.pages().len() as usize);
+    for (i, page) in document.pages().iter().enumerate() {
+        progress.check()?;
+        let path = out_dir.join(format!("page-{:04}.png", i + 1));
+        page.render_with_config(&config)
+            .map_err(|e| format!("Failed to render page {}: {:?}", i + 1, e))?
+            .as_image()
+            .save(&path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        pages.push(path);
+        progress.advance(1);
+    }
+    if pages.is_empty() {
+        return Err(format!("{} has no pages", pdf.display()));
+    }
+    Ok(pages)
+}
+
+fn empty_page(image: &Path, size: egui::Vec2) -> OCRElement {
+    OCRElement {
+        html_element_type: "div".to_string(),
+        ocr_element_type: OCRClass::Page,
+        ocr_properties: HashMap::from([
+            (
+                "bbox".to_string(),
+                OCRProperty::BBox(Rect::from_min_size(Pos2::ZERO, size)),
+            ),
+            (
+                "image".to_string(),
+                OCRProperty::Image(image.display().to_string()),
+            ),
+        ]),
+        ..Default::default()
+    }
+}
+
+// add_as_body wants a document with just a head
+pub fn without_body(mut html: Html) -> Html {
+    let body = Selector::parse("body").unwrap();
+    let ids: Vec<_> = html.select(&body).map(|elt| elt.id()).collect();
+    for id in ids {
+        if let Some(mut node) = html.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+    html
+}
+
+// render the pdf and write the hOCR for it; returns the hOCR path
+pub fn import(pdf: &Path, dpi: u32, progress: &Progress) -> Result<PathBuf, String> {
+    let stem = pdf_stem(pdf)?;
+    let out_dir = free_pages_dir(pdf, &stem);
+    let images = render_pages(pdf, &out_dir, dpi, progress)?;
+    let sidecar = pdf.with_extension("hocr");
+    let (mut tree, head) = match read_to_string(&sidecar) {
+        Ok(source) => {
+            let html = Html::parse_document(&source);
+            (
+                OCRElement::html_to_ocr_tree(html.clone()),
+                without_body(html),
+            )
+        }
+        Err(_) => (
+            Tree::new(),
+            without_body(Html::parse_document(page_xml::HOCR_SKELETON)),
+        ),
+    };
+    let pages: Vec<crate::InternalID> = tree.roots().copied().collect();
+    if !pages.is_empty() && pages.len() != images.len() {
+        println!(
+            "{} has {} pages but the PDF has {}; matching them up in order",
+            sidecar.display(),
+            pages.len(),
+            images.len()
+        );
+    }
+    for (i, image) in images.iter().enumerate() {
+        let (width, height) = image::image_dimensions(image)
+            .map_err(|e| format!("Failed to read {}: {}", image.display(), e))?;
+        let size = egui::vec2(width as f32, height as f32);
+        // relative to the hOCR file, which sits in the same folder
+        let name = Path::new(image.file_name().unwrap_or_default());
+        match pages.get(i) {
+            Some(page) => {
+                crate::dpi::fit_page(&mut tree, page, size);
+                if let Some(elt) = tree.get_mut_node(page) {
+                    elt.ocr_properties.insert(
+                        "image".to_string(),
+                        OCRProperty::Image(name.display().to_string()),
+                    );
+                }
+            }
+            None => {
+                tree.add_root(empty_page(name, size));
+            }
+        }
+    }
+    let hocr_path = out_dir.join(format!("{}.hocr", stem));
+    atomic::write_atomic(&hocr_path, ocr_element::add_as_body(&tree, &head).html())?;
+    Ok(hocr_path)
+}
+
+pub fn spawn_import(pdf: PathBuf, dpi: u32) -> HocrJob {
+    let name = format!("Import {}", pdf.display());
+    HocrJob {
+        source: pdf.clone(),
+        job: Job::spawn(name, 0, move |progress| import(&pdf, dpi, progress)),
+    }
+}
@@ -0,0 +1,222 @@
+use crate::export::{ExportOptions, PageSeparator, ReadingOrder, Unverified};
+use crate::ocr_element::{IdScheme, WriteOptions};
+use serde::Deserialize;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+// a team's shared settings, kept next to the documents and checked in with them:
+//
+//     language = "deu"
+//     id_scheme = "global"
+//     properties = ["bbox", "x_wconf", "baseline"]
+//
+//     [[dictionaries]]
+//     lang = "deu"
+//     path = "wordlists/deu.txt"
+//
+//     [export]
+//     reading_order = "geometric"
+//     dehyphenate = true
+//     page_separator = "numbered"
+//     unverified = "flag"
+//     list_unverified = true
+pub const PROJECT_FILE: &str = ".hocr-editor.toml";
+
+// a misspelled value would otherwise quietly leave the setting at its default
+fn check_name<T>(
+    path: &Path,
+    key: &str,
+    name: &Option<String>,
+    from_name: fn(&str) -> Option<T>,
+    choices: &str,
+) -> Result<(), String> {
+    match name {
+        Some(name) if from_name(name).is_none() => Err(format!(
+            "{}: unknown {} {}, use {}",
+            path.display(),
+            key,
+            name,
+            choices
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dictionary {
+    pub lang: String,
+    // relative to the folder the project file is in
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportDefaults {
+    pub reading_order: Option<String>,
+    pub dehyphenate: Option<bool>,
+    pub page_separator: Option<String>,
+    // "keep", "blank" or "flag"
+    pub unverified: Option<String>,
+    pub list_unverified: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    // lang for words that don't say, both for new words and for rare word checks
+    pub language: Option<String>,
+    pub dictionaries: Vec<Dictionary>,
+    // "per-page" (word_1_5) or "global" (word_5)
+    pub id_scheme: Option<String>,
+    // the only properties written on save, besides bbox; lazy saving only applies it to
+    // edited pages
+    pub properties: Option<Vec<String>>,
+    pub export: ExportDefaults,
+    // the file this was read from
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl ProjectSettings {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut project: ProjectSettings =
+            toml::from_str(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        check_name(
+            path,
+            "id_scheme",
+            &project.id_scheme,
+            IdScheme::from_name,
+            "per-page or global",
+        )?;
+        let export = &project.export;
+        check_name(
+            path,
+            "reading_order",
+            &export.reading_order,
+            ReadingOrder::from_name,
+            "document or geometric",
+        )?;
+        check_name(
+            path,
+            "page_separator",
+            &export.page_separator,
+            PageSeparator::from_name,
+            "blank, formfeed or numbered",
+        )?;
+        check_name(
+            path,
+            "unverified",
+            &export.unverified,
+            Unverified::from_name,
+            "keep, blank or flag",
+        )?;
+        let folder = path.parent().unwrap_or(Path::new("."));
+        for dictionary in project.dictionaries.iter_mut() {
+            dictionary.path = folder.join(&dictionary.path);
+        }
+        project.path = Some(path.to_path_buf());
+        Ok(project)
+    }
+
+    // the nearest project file in the document's folder or above it, if any
+    pub fn find(document: &Path) -> Option<PathBuf> {
+        let document = document.canonicalize().ok()?;
+        document
+            .ancestors()
+            .skip(1)
+            .map(|folder| folder.join(PROJECT_FILE))
+            .find(|path| path.is_file())
+    }
+
+    // the project for a document, or the defaults if there's none (or it can't be read)
+    pub fn for_document(document: &Path) -> Self {
+        match Self::find(document).map(|path| Self::load(&path)) {
+            Some(Ok(project)) => {
+                if let Some(path) = &project.path {
+                    println!("Using project settings from {}", path.display());
+                }
+                project
+            }
+            Some(Err(e)) => {
+                println!("{}", e);
+                Self::default()
+            }
+            None => Self::default(),
+        }
+    }
+
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            id_scheme: self
+                .id_scheme
+                .as_deref()
+                .and_then(IdScheme::from_name)
+                .unwrap_or_default(),
+            properties: self.properties.clone(),
+        }
+    }
+
+    // options with whatever the project sets for export replaced
+    pub fn export_options(&self, options: &ExportOptions) -> ExportOptions {
+        let mut options = options.clone();
+        if let Some(order) = self
+            .export
+            .reading_order
+            .as_deref()
+            .and_then(ReadingOrder::from_name)
+        {
+            options.reading_order = order;
+        }
+        if let Some(dehyphenate) = self.export.dehyphenate {
+            options.dehyphenate = dehyphenate;
+        }
+        if let Some(separator) = self
+            .export
+            .page_separator
+            .as_deref()
+            .and_then(PageSeparator::from_name)
+        {
+            options.page_separator = separator;
+        }
+        if let Some(unverified) = self
+            .export
+            .unverified
+            .as_deref()
+            .and_then(Unverified::from_name)
+        {
+            options.unverified = unverified;
+        }
+        if let Some(list_unverified) = self.export.list_unverified {
+            options.list_unverified = list_unverified;
+        }
+        options
+    }
+
+    // the user's wordlists with the project's on top. Words without a lang are checked
+    // against the project language's list unless there's a list for "" already.
+    pub fn wordlists(&self, user: &[(String, PathBuf)]) -> Vec<(String, PathBuf)> {
+        let mut wordlists: Vec<(String, PathBuf)> = user
+            .iter()
+            .filter(|(lang, _)| !self.dictionaries.iter().any(|d| &d.lang == lang))
+            .cloned()
+            .collect();
+        wordlists.extend(
+            self.dictionaries
+                .iter()
+                .map(|d| (d.lang.clone(), d.path.clone())),
+        );
+        if let Some(language) = &self.language {
+            let fallback = wordlists
+                .iter()
+                .find(|(lang, _)| lang == language)
+                .map(|(_, path)| path.clone());
+            if let Some(path) = fallback {
+                if !wordlists.iter().any(|(lang, _)| lang.is_empty()) {
+                    wordlists.push((String::new(), path));
+                }
+            }
+        }
+        wordlists
+    }
+}
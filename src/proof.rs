@@ -0,0 +1,149 @@
+use crate::atomic;
+use crate::ignore;
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::redact::resolve_image;
+use crate::tree::Tree;
+use crate::{InternalID, DEFAULT_FONT};
+use printpdf::{
+    Color, Image, ImageTransform, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point,
+    Rgb,
+};
+use std::path::Path;
+
+// used when a page has no scan_res
+const DEFAULT_DPI: f32 = 300.0;
+const MM_PER_INCH: f32 = 25.4;
+
+// green when fine, yellow when below the threshold, red when well below, grey without a wconf
+fn confidence_color(wconf: Option<u32>, threshold: u32) -> Color {
+    let (r, g, b) = match wconf {
+        None => (0.5, 0.5, 0.5),
+        Some(wconf) if wconf >= threshold => (0.0, 0.6, 0.0),
+        Some(wconf) if wconf >= threshold / 2 => (0.9, 0.6, 0.0),
+        Some(_) => (0.9, 0.0, 0.0),
+    };
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+fn page_dpi(page: &OCRElement) -> f32 {
+    page.dpi().unwrap_or(DEFAULT_DPI)
+}
+
+// draw one page: the scan if we can find it, then a box and the recognized text per word
+fn draw_page(
+    tree: &Tree<OCRElement>,
+    page: &InternalID,
+    hocr_path: &Path,
+    layer: &PdfLayerReference,
+    font: &IndirectFontRef,
+    threshold: u32,
+) {
+    let elt = match tree.get_node(page) {
+        Some(elt) => elt,
+        None => return,
+    };
+    let page_bbox = elt.bbox().copied().unwrap_or(egui::Rect::ZERO);
+    let dpi = page_dpi(elt);
+    let to_mm = |px: f32| Mm(px / dpi * MM_PER_INCH);
+    // pdf y goes up from the bottom of the page
+    let to_point = |x: f32, y: f32| Point::new(to_mm(x), to_mm(page_bbox.max.y - y));
+    if let Some(OCRProperty::Image(image)) = elt.ocr_properties.get("image") {
+        let path = resolve_image(image, Some(hocr_path));
+        match image::open(&path) {
+            Ok(img) => Image::from_dynamic_image(&img).add_to_layer(
+                layer.clone(),
+                ImageTransform {
+                    dpi: Some(dpi),
+                    ..Default::default()
+                },
+            ),
+            Err(e) => println!("warning: couldn't open {}: {}", path.display(), e),
+        }
+    }
+    layer.set_outline_thickness(0.5);
+    for id in tree.descendants(page) {
+        let word = match tree.get_node(&id) {
+            Some(word) if word.ocr_element_type == OCRClass::Word => word,
+            _ => continue,
+        };
+        let bbox = match word.bbox() {
+            Some(bbox) => bbox,
+            None => continue,
+        };
+        let color = confidence_color(word.wconf(), threshold);
+        layer.set_outline_color(color.clone());
+        layer.add_line(Line {
+            points: vec![
+                (to_point(bbox.min.x, bbox.min.y), false),
+                (to_point(bbox.max.x, bbox.min.y), false),
+                (to_point(bbox.max.x, bbox.max.y), false),
+                (to_point(bbox.min.x, bbox.max.y), false),
+            ],
+            is_closed: true,
+        });
+        // font size in points, roughly the height of the word
+        let size = bbox.height() / dpi * 72.0 * 0.7;
+        layer.set_fill_color(color);
+        layer.use_text(
+            word.ocr_text.trim(),
+            size,
+            to_mm(bbox.min.x),
+            to_mm(page_bbox.max.y - bbox.max.y),
+            font,
+        );
+    }
+}
+
+// one pdf page per ocr_page, each the size of its scan, with the text in font (a ttf or otf
+// file) or the bundled one. Ignored words are left out.
+pub fn write_proof(
+    tree: &Tree<OCRElement>,
+    hocr_path: &Path,
+    out_path: &Path,
+    threshold: u32,
+    font: Option<&Path>,
+) -> Result<(), String> {
+    let stripped = ignore::without_ignored(tree);
+    let tree = &*stripped;
+    let title = hocr_path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_string());
+    let page_size = |page: &InternalID| {
+        let elt = tree.get_node(page);
+        let bbox = elt
+            .and_then(|elt| elt.bbox())
+            .copied()
+            .unwrap_or(egui::Rect::ZERO);
+        let dpi = elt.map_or(DEFAULT_DPI, page_dpi);
+        (
+            Mm(bbox.max.x / dpi * MM_PER_INCH),
+            Mm(bbox.max.y / dpi * MM_PER_INCH),
+        )
+    };
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    let first = pages.first().ok_or("no pages to render")?;
+    let (width, height) = page_size(first);
+    let (doc, first_page, first_layer) = PdfDocument::new(&title, width, height, "proof");
+    let font_bytes = match font {
+        Some(path) => std::fs::read(path)
+            .map_err(|e| format!("Failed to read font {}: {}", path.display(), e))?,
+        None => DEFAULT_FONT.to_vec(),
+    };
+    let font = doc
+        .add_external_font(font_bytes.as_slice())
+        .map_err(|e| format!("Couldn't embed the font: {}", e))?;
+    for (i, page) in pages.iter().enumerate() {
+        let layer = if i == 0 {
+            doc.get_page(first_page).get_layer(first_layer)
+        } else {
+            let (width, height) = page_size(page);
+            let (pdf_page, pdf_layer) = doc.add_page(width, height, "proof");
+            doc.get_page(pdf_page).get_layer(pdf_layer)
+        };
+        draw_page(tree, page, hocr_path, &layer, &font, threshold);
+    }
+    let bytes = doc
+        .save_to_bytes()
+        .map_err(|e| format!("Failed to render {}: {}", out_path.display(), e))?;
+    atomic::write_atomic(out_path, bytes)
+}
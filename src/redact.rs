@@ -0,0 +1,136 @@
+use crate::atomic;
+use crate::hyphenation;
+use crate::ignore;
+use crate::ocr_element::{self, OCRElement, OCRProperty, USER_PROPERTY_PREFIX};
+use crate::tree::Tree;
+use crate::uri;
+use crate::InternalID;
+use egui::Rect;
+use image::Rgba;
+use std::path::{Path, PathBuf};
+
+// "x_redacted 1" in the title: blacked out in the image and stripped of text on export
+pub const REDACTED_PROPERTY: &str = "x_redacted";
+
+pub fn is_redacted(elt: &OCRElement) -> bool {
+    matches!(
+        elt.ocr_properties.get(REDACTED_PROPERTY),
+        Some(OCRProperty::UInt(1))
+    )
+}
+
+pub fn set_redacted(elt: &mut OCRElement, redacted: bool) {
+    if redacted {
+        elt.ocr_properties
+            .insert(REDACTED_PROPERTY.to_string(), OCRProperty::UInt(1));
+    } else {
+        elt.ocr_properties.remove(REDACTED_PROPERTY);
+    }
+}
+
+// image paths in hOCR are usually relative to the hOCR file
+pub fn resolve_image(image: &str, hocr_path: Option<&Path>) -> PathBuf {
+    let path = uri::uri_to_path(image);
+    match hocr_path.and_then(|file| file.parent()) {
+        Some(dir) if path.is_relative() && !path.exists() => dir.join(path),
+        _ => path,
+    }
+}
+
+// boxes to black out on one page
+fn redacted_rects(tree: &Tree<OCRElement>, page: &InternalID) -> Vec<Rect> {
+    tree.descendants(page)
+        .iter()
+        .filter_map(|id| tree.get_node(id))
+        .filter(|elt| is_redacted(elt))
+        .filter_map(|elt| elt.bbox().copied())
+        .collect()
+}
+
+// drop everything on elt that spells out its text
+fn strip_text(elt: &mut OCRElement) {
+    elt.ocr_text.clear();
+    elt.ocr_properties.retain(|name, _| {
+        name != hyphenation::JOINED_PROPERTY && !name.starts_with(USER_PROPERTY_PREFIX)
+    });
+}
+
+// a copy of the tree with the text under every redacted element removed, including the
+// joined form and text of the other half of a word broken over a line end
+fn strip_redacted_text(tree: &Tree<OCRElement>) -> Tree<OCRElement> {
+    let mut stripped = tree.clone();
+    for id in tree.preorder() {
+        if tree.get_node(&id).is_some_and(is_redacted) {
+            for desc in tree.descendants(&id) {
+                let partner = hyphenation::partner(tree, &desc);
+                for word in std::iter::once(desc).chain(partner) {
+                    if let Some(elt) = stripped.get_mut_node(&word) {
+                        strip_text(elt);
+                    }
+                }
+            }
+        }
+    }
+    stripped
+}
+
+// write <name>_redacted.hocr plus a blacked out copy of every page image into out_dir,
+// leaving out the ignored words like the other exports
+pub fn export(
+    tree: &Tree<OCRElement>,
+    html_head: &scraper::Html,
+    hocr_path: &Path,
+    out_dir: &Path,
+) -> Result<PathBuf, String> {
+    let kept = ignore::without_ignored(tree);
+    let tree = &*kept;
+    let mut stripped = strip_redacted_text(tree);
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    for page in pages {
+        let image = match tree
+            .get_node(&page)
+            .and_then(|elt| elt.ocr_properties.get("image"))
+        {
+            Some(OCRProperty::Image(image)) => image.clone(),
+            _ => continue,
+        };
+        let source = resolve_image(&image, Some(hocr_path));
+        let mut img = image::open(&source)
+            .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?
+            .into_rgba8();
+        for rect in redacted_rects(tree, &page) {
+            let x0 = rect.min.x.max(0.0) as u32;
+            let y0 = rect.min.y.max(0.0) as u32;
+            let x1 = (rect.max.x.max(0.0) as u32).min(img.width());
+            let y1 = (rect.max.y.max(0.0) as u32).min(img.height());
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    img.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        let stem = source
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("page_{}", page));
+        let file_name = format!("{}_redacted.png", stem);
+        let target = out_dir.join(&file_name);
+        img.save(&target)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        // the new hOCR points at the new image, next to it
+        if let Some(elt) = stripped.get_mut_node(&page) {
+            elt.ocr_properties
+                .insert("image".to_string(), OCRProperty::Image(file_name));
+        }
+    }
+    let stem = hocr_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let hocr_target = out_dir.join(format!("{}_redacted.hocr", stem));
+    atomic::write_atomic(
+        &hocr_target,
+        ocr_element::add_as_body(&stripped, html_head).html(),
+    )?;
+    Ok(hocr_target)
+}
@@ -0,0 +1,194 @@
+use crate::cleanup;
+use crate::ignore;
+use crate::ocr_element::{OCRClass, OCRElement};
+use crate::stats;
+use crate::suspicious::{self, FrequencyList};
+use crate::tree::Tree;
+use crate::InternalID;
+use std::collections::{HashMap, HashSet};
+
+// how many times taller (or shorter) than its line's median a word must be to get flagged
+const OUTLIER_RATIO: f32 = 2.0;
+
+// why something ended up in the review queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReviewKind {
+    // someone marked it with "x_review"
+    Flagged,
+    Warning,
+    // a word much taller or shorter than the rest of its line
+    SizeOutlier,
+    // rare or unknown in the reference wordlist for its language
+    Suspicious,
+    LowConfidence,
+    // inside one of its page's ignore regions, most likely garbage to delete
+    Ignored,
+}
+
+impl ReviewKind {
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::Flagged => "flagged",
+            Self::Warning => "warning",
+            Self::SizeOutlier => "size",
+            Self::Suspicious => "rare word",
+            Self::LowConfidence => "low confidence",
+            Self::Ignored => "ignored region",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewItem {
+    pub id: InternalID,
+    pub kind: ReviewKind,
+    pub detail: String,
+    pub wconf: Option<u32>,
+    // position in the document, so sorting can get back to reading order
+    pub order: usize,
+    // how likely an OCR error this is, for items ranked by something other than confidence
+    pub score: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ReviewSort {
+    #[default]
+    Document,
+    Kind,
+    Confidence,
+    // most suspicious rare words first
+    Suspicion,
+}
+
+impl ReviewSort {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [
+            Self::Document,
+            Self::Kind,
+            Self::Confidence,
+            Self::Suspicion,
+        ]
+        .iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::Document => "Document order",
+            Self::Kind => "Kind",
+            Self::Confidence => "Confidence",
+            Self::Suspicion => "Suspicion",
+        }
+    }
+}
+
+// everything that needs a look, skipping items already resolved
+// (flagged items are resolved by clearing the flag, so they never end up in resolved)
+pub fn collect(
+    tree: &Tree<OCRElement>,
+    low_conf_threshold: u32,
+    references: &HashMap<String, FrequencyList>,
+    resolved: &HashSet<(InternalID, ReviewKind)>,
+) -> Vec<ReviewItem> {
+    let order: Vec<InternalID> = tree.preorder();
+    let position: HashMap<InternalID, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (*id, index))
+        .collect();
+    let mut items = Vec::new();
+    for (index, id) in order.iter().enumerate() {
+        let elt = match tree.get_node(id) {
+            Some(elt) => elt,
+            None => continue,
+        };
+        if elt.needs_review() {
+            items.push(ReviewItem {
+                id: *id,
+                kind: ReviewKind::Flagged,
+                detail: elt.ocr_text.clone(),
+                wconf: elt.wconf(),
+                order: index,
+                score: 0.0,
+            });
+        }
+        if elt.ocr_element_type == OCRClass::Word {
+            if let Some(wconf) = elt.wconf().filter(|wconf| *wconf < low_conf_threshold) {
+                items.push(ReviewItem {
+                    id: *id,
+                    kind: ReviewKind::LowConfidence,
+                    detail: elt.ocr_text.clone(),
+                    wconf: Some(wconf),
+                    order: index,
+                    score: 0.0,
+                });
+            }
+        }
+    }
+    for (id, warning) in stats::validate(tree) {
+        items.push(ReviewItem {
+            id,
+            kind: ReviewKind::Warning,
+            detail: warning,
+            wconf: tree.get_node(&id).and_then(|elt| elt.wconf()),
+            order: position.get(&id).copied().unwrap_or(0),
+            score: 0.0,
+        });
+    }
+    for (id, detail) in cleanup::find_height_outliers(tree, OUTLIER_RATIO) {
+        items.push(ReviewItem {
+            id,
+            kind: ReviewKind::SizeOutlier,
+            detail,
+            wconf: tree.get_node(&id).and_then(|elt| elt.wconf()),
+            order: position.get(&id).copied().unwrap_or(0),
+            score: 0.0,
+        });
+    }
+    for id in ignore::ignored_words(tree) {
+        let elt = tree.get_node(&id);
+        items.push(ReviewItem {
+            id,
+            kind: ReviewKind::Ignored,
+            detail: elt.map_or(String::new(), |elt| elt.ocr_text.clone()),
+            wconf: elt.and_then(|elt| elt.wconf()),
+            order: position.get(&id).copied().unwrap_or(0),
+            score: 0.0,
+        });
+    }
+    // one item per token, at its first occurrence
+    for token in suspicious::find_suspicious(tree, references) {
+        let id = token.ids[0];
+        items.push(ReviewItem {
+            id,
+            kind: ReviewKind::Suspicious,
+            detail: format!(
+                "{} ×{} ({} in wordlist)",
+                token.token,
+                token.ids.len(),
+                token.reference_count
+            ),
+            wconf: tree.get_node(&id).and_then(|elt| elt.wconf()),
+            order: position.get(&id).copied().unwrap_or(0),
+            score: token.score,
+        });
+    }
+    items.retain(|item| !resolved.contains(&(item.id, item.kind)));
+    items
+}
+
+pub fn sort(items: &mut [ReviewItem], by: ReviewSort) {
+    match by {
+        ReviewSort::Document => items.sort_by_key(|item| (item.order, item.kind)),
+        ReviewSort::Kind => items.sort_by_key(|item| (item.kind, item.order)),
+        // items without a confidence go last
+        ReviewSort::Confidence => {
+            items.sort_by_key(|item| (item.wconf.unwrap_or(u32::MAX), item.order))
+        }
+        ReviewSort::Suspicion => items.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.order.cmp(&b.order))
+        }),
+    }
+}
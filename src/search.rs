@@ -0,0 +1,193 @@
+use crate::ocr_element::{self, OCRClass, OCRElement, WriteOptions};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::Pos2;
+use std::collections::HashMap;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// how loosely the query has to match a word
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    // "Wort" finds "wort"
+    pub ignore_case: bool,
+    // compatibility forms match their plain equivalents, e.g. "ﬁ" finds "fi" and "ſ" finds "s"
+    pub normalize: bool,
+    // "e" finds "é", "è" and "ë"
+    pub ignore_diacritics: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            ignore_case: true,
+            normalize: true,
+            ignore_diacritics: false,
+        }
+    }
+}
+
+// bring text into the form we compare in, applying the same folding to query and words
+pub fn fold(text: &str, options: &SearchOptions) -> String {
+    let mut folded: String = if options.normalize {
+        text.nfkc().collect()
+    } else {
+        text.to_string()
+    };
+    if options.ignore_diacritics {
+        folded = folded
+            .nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .nfc()
+            .collect();
+    }
+    if options.ignore_case {
+        folded = folded.to_lowercase();
+    }
+    folded
+}
+
+// which part of the document to look in
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum SearchScope {
+    #[default]
+    Document,
+    // the page the selection is on
+    Page,
+    // the selected element and everything under it
+    Selection,
+}
+
+impl SearchScope {
+    pub fn variants() -> std::slice::Iter<'static, Self> {
+        [Self::Document, Self::Page, Self::Selection].iter()
+    }
+
+    pub fn to_user_str(&self) -> &'static str {
+        match self {
+            Self::Document => "Whole document",
+            Self::Page => "Current page",
+            Self::Selection => "Selected element",
+        }
+    }
+
+    // the subtrees to search; empty if the scope needs a selection and there isn't one
+    pub fn roots(&self, tree: &Tree<OCRElement>, selected: Option<InternalID>) -> Vec<InternalID> {
+        match (self, selected) {
+            (Self::Document, _) => tree.roots().copied().collect(),
+            (Self::Page, Some(id)) => vec![page_of(tree, id)],
+            (Self::Selection, Some(id)) => vec![id],
+            (_, None) => Vec::new(),
+        }
+    }
+}
+
+pub fn page_of(tree: &Tree<OCRElement>, id: InternalID) -> InternalID {
+    tree.ancestors(&id).last().copied().unwrap_or(id)
+}
+
+// the lines (and captions) of a page in document order; proofreaders count them from 1
+pub fn page_lines(tree: &Tree<OCRElement>, page: InternalID) -> Vec<InternalID> {
+    tree.descendants(&page)
+        .into_iter()
+        .filter(|id| {
+            tree.get_node(id).is_some_and(|elt| {
+                matches!(elt.ocr_element_type, OCRClass::Line | OCRClass::Caption)
+            })
+        })
+        .collect()
+}
+
+// the line number on its page of every line in the document
+pub fn line_numbers(tree: &Tree<OCRElement>) -> HashMap<InternalID, usize> {
+    tree.roots()
+        .flat_map(|page| {
+            page_lines(tree, *page)
+                .into_iter()
+                .enumerate()
+                .map(|(i, line)| (line, i + 1))
+        })
+        .collect()
+}
+
+// number of matches on each page that has any, in page order
+pub fn count_per_page(tree: &Tree<OCRElement>, matches: &[InternalID]) -> Vec<(usize, usize)> {
+    let pages: Vec<InternalID> = tree.roots().copied().collect();
+    let mut counts: Vec<(usize, usize)> = Vec::new();
+    for id in matches {
+        let page = page_of(tree, *id);
+        let page_no = pages.iter().position(|p| *p == page).unwrap_or(0);
+        match counts.last_mut() {
+            Some((last_page, count)) if *last_page == page_no => *count += 1,
+            _ => counts.push((page_no, 1)),
+        }
+    }
+    counts
+}
+
+// every word under roots containing the query, in document order
+pub fn find_matches(
+    tree: &Tree<OCRElement>,
+    roots: &[InternalID],
+    query: &str,
+    options: &SearchOptions,
+) -> Vec<InternalID> {
+    let query = fold(query.trim(), options);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    roots
+        .iter()
+        .flat_map(|root| tree.descendants(root))
+        .filter(|id| {
+            tree.get_node(id).is_some_and(|node| {
+                node.ocr_element_type == OCRClass::Word
+                    && fold(&node.ocr_text, options).contains(&query)
+            })
+        })
+        .collect()
+}
+
+// the element a downstream tool calls id_str: the id it had in the file, or failing that
+// the one it would get if the document were saved now
+pub fn find_by_id(
+    tree: &Tree<OCRElement>,
+    id_str: &str,
+    options: &WriteOptions,
+) -> Option<InternalID> {
+    let id_str = id_str.trim().trim_start_matches('#');
+    tree.preorder()
+        .into_iter()
+        .find(|id| tree.get_node(id).and_then(|elt| elt.source_id.as_deref()) == Some(id_str))
+        .or_else(|| ocr_element::written_ids(tree, options).get(id_str).copied())
+}
+
+// "1200,800", "1200 800" or "(1200, 800)"
+pub fn parse_point(s: &str) -> Option<Pos2> {
+    let numbers: Vec<f32> = s
+        .split(|c: char| c == ',' || c.is_whitespace() || c == '(' || c == ')')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    match numbers[..] {
+        [x, y] => Some(Pos2::new(x, y)),
+        _ => None,
+    }
+}
+
+// the innermost element on page whose bbox contains pos
+pub fn element_at(tree: &Tree<OCRElement>, page: InternalID, pos: Pos2) -> Option<InternalID> {
+    let contains = |id: &InternalID| {
+        tree.get_node(id)
+            .and_then(|elt| elt.bbox())
+            .is_some_and(|bbox| bbox.contains(pos))
+    };
+    if !contains(&page) {
+        return None;
+    }
+    let mut found = page;
+    while let Some(child) = ocr_element::child_at(tree, &found, pos) {
+        found = child;
+    }
+    Some(found)
+}
@@ -0,0 +1,74 @@
+use crate::highlight::{self, Highlighter};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const DEFAULT_KEYBOARD_KEYS: &str =
+    "ā ē ī ō ū ä ö ü ß é è ê ç ñ ſ æ œ þ ð « » „ “ ” — 々 〆 ヶ ゝ ゞ ヽ ヾ 〃 nbsp=\\u{a0}";
+
+// screen pixels at 100% zoom
+pub const DEFAULT_STROKE_WEIGHT: f32 = 4.0;
+pub const DEFAULT_HANDLE_SIZE: f32 = 16.0;
+
+// editor state that is stored with eframe between runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub zoom: f32,
+    pub keyboard_keys: String,
+    // shell command run after every successful save
+    pub post_save_command: String,
+    // folder to watch for fresh OCR output, if any
+    pub watch_folder: Option<PathBuf>,
+    // only parse a page's subtree when it's first selected, for huge books
+    pub lazy_pages: bool,
+    // bbox outline and drag handle sizes at 100% zoom; both scale with zoom
+    pub stroke_weight: f32,
+    pub handle_size: f32,
+    // font file for the text preview, if not the ui font
+    pub preview_font: Option<PathBuf>,
+    pub preview_font_size: f32,
+    // font files by lang ("jpn", "ja", ...) for text in that language, so mixed documents
+    // show each script in a font made for it
+    pub lang_fonts: Vec<(String, PathBuf)>,
+    // keep ids, property order and line layout stable between saves
+    pub stable_save: bool,
+    // write ocr_word/ocrx_block/ocrx_line from old files as their modern names
+    pub normalize_legacy_classes: bool,
+    // regexes for the entities (dates, amounts, ...) the Entities window tints
+    pub highlighters: Vec<Highlighter>,
+    // reference frequency lists by lang ("" for words of any language) for spotting rare words
+    pub wordlists: Vec<(String, PathBuf)>,
+    // scans bigger than this on either side are shown scaled down; 0 for as big as the
+    // graphics card can take
+    pub max_texture_size: u32,
+    // how scans are shown: in gray, and with their midtones lightened or darkened
+    pub scan_grayscale: bool,
+    pub scan_gamma: f32,
+    // put between the text of two words when one is merged into the other
+    pub merge_separator: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            zoom: 1.0,
+            keyboard_keys: DEFAULT_KEYBOARD_KEYS.to_string(),
+            post_save_command: String::new(),
+            watch_folder: None,
+            lazy_pages: false,
+            stroke_weight: DEFAULT_STROKE_WEIGHT,
+            handle_size: DEFAULT_HANDLE_SIZE,
+            preview_font: None,
+            preview_font_size: 18.0,
+            lang_fonts: Vec::new(),
+            stable_save: false,
+            normalize_legacy_classes: true,
+            highlighters: highlight::default_highlighters(),
+            wordlists: Vec::new(),
+            max_texture_size: 0,
+            scan_grayscale: false,
+            scan_gamma: 1.0,
+            merge_separator: String::new(),
+        }
+    }
+}
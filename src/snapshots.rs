@@ -0,0 +1,131 @@
+use crate::atomic;
+use crate::dpi::ImageMapping;
+use crate::ocr_element::{self, OCRClass, OCRElement};
+use crate::tree::Tree;
+use crate::InternalID;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// a named copy of the whole document, e.g. "after pass 1"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    // seconds since the epoch
+    pub created: u64,
+    pub html: String,
+}
+
+// per-document state kept next to the file as <file>.session.json
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub snapshots: Vec<Snapshot>,
+    // what to do about pages whose scan doesn't match their bbox, by dpi::page_key
+    pub image_mappings: HashMap<String, ImageMapping>,
+}
+
+pub fn session_path(doc_path: &Path) -> PathBuf {
+    let name = doc_path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    doc_path.with_file_name(format!("{}.session.json", name))
+}
+
+impl Session {
+    // a missing or unreadable session just means there's nothing saved yet
+    pub fn load(doc_path: &Path) -> Self {
+        read_to_string(session_path(doc_path))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, doc_path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        atomic::write_atomic(&session_path(doc_path), json)
+    }
+}
+
+pub fn take(name: &str, tree: &Tree<OCRElement>, html_head: &Html) -> Snapshot {
+    Snapshot {
+        name: name.to_string(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        html: ocr_element::add_as_body(tree, html_head).html(),
+    }
+}
+
+pub fn restore(snapshot: &Snapshot) -> Tree<OCRElement> {
+    OCRElement::html_to_ocr_tree(Html::parse_document(&snapshot.html))
+}
+
+// one difference between a snapshot and the current document
+#[derive(Debug, Clone)]
+pub struct Change {
+    // the word in the current document, if it still exists
+    pub id: Option<InternalID>,
+    pub description: String,
+}
+
+type BBoxKey = (i32, i32, i32, i32);
+
+// words in document order, keyed by where they are since ids don't survive a round trip through html
+fn words_by_position(tree: &Tree<OCRElement>) -> Vec<(BBoxKey, InternalID, String)> {
+    tree.preorder()
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            let bbox = elt.bbox()?;
+            let key = (
+                bbox.min.x.round() as i32,
+                bbox.min.y.round() as i32,
+                bbox.max.x.round() as i32,
+                bbox.max.y.round() as i32,
+            );
+            Some((key, id, elt.ocr_text.clone()))
+        })
+        .collect()
+}
+
+// word level changes from old to current, in current document order with removals last
+pub fn diff(old: &Tree<OCRElement>, current: &Tree<OCRElement>) -> Vec<Change> {
+    let old_words: HashMap<BBoxKey, String> = words_by_position(old)
+        .into_iter()
+        .map(|(key, _, text)| (key, text))
+        .collect();
+    let current_words = words_by_position(current);
+    let mut changes = Vec::new();
+    for (key, id, text) in &current_words {
+        let id = *id;
+        match old_words.get(key) {
+            Some(old_text) if old_text != text => changes.push(Change {
+                id: Some(id),
+                description: format!("\"{}\" → \"{}\"", old_text, text),
+            }),
+            Some(_) => (),
+            None => changes.push(Change {
+                id: Some(id),
+                description: format!("added \"{}\"", text),
+            }),
+        }
+    }
+    let current_keys: HashSet<&BBoxKey> = current_words.iter().map(|(key, _, _)| key).collect();
+    for (key, old_text) in &old_words {
+        if !current_keys.contains(key) {
+            changes.push(Change {
+                id: None,
+                description: format!("removed \"{}\"", old_text),
+            });
+        }
+    }
+    changes
+}
@@ -0,0 +1,44 @@
+// reading text aloud with the platform's voices, so the eyes can stay on the scan
+// needs the "tts" feature; without it there's just nothing to click
+#[derive(Default)]
+pub struct Speech {
+    // started on first use, since some platforms are slow to bring a voice up
+    #[cfg(feature = "tts")]
+    tts: Option<tts::Tts>,
+}
+
+impl std::fmt::Debug for Speech {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Speech").finish()
+    }
+}
+
+impl Speech {
+    pub fn available() -> bool {
+        cfg!(feature = "tts")
+    }
+
+    #[cfg(feature = "tts")]
+    pub fn speak(&mut self, text: &str) -> Result<(), String> {
+        if self.tts.is_none() {
+            self.tts = Some(tts::Tts::default().map_err(|e| e.to_string())?);
+        }
+        let tts = self.tts.as_mut().expect("started above");
+        tts.speak(text, true).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "tts"))]
+    pub fn speak(&mut self, _text: &str) -> Result<(), String> {
+        Err("built without text-to-speech (the tts feature)".to_string())
+    }
+
+    #[cfg(feature = "tts")]
+    pub fn stop(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            let _ = tts.stop();
+        }
+    }
+
+    #[cfg(not(feature = "tts"))]
+    pub fn stop(&mut self) {}
+}
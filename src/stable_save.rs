@@ -0,0 +1,170 @@
+use crate::ocr_element::{self, IdScheme, OCRClass, OCRElement, OCRProperty, WriteOptions};
+use crate::tree::Tree;
+use crate::InternalID;
+use scraper::Html;
+use std::collections::{HashMap, HashSet};
+
+// Saving for files kept in git: one element per line, ids and titles from the file kept
+// as they were, and new ids only handed out to new elements. The first stable save of a
+// file still reflows it, but after that saving only touches the lines that were edited.
+// A project's id scheme applies to the new ids, its property list to every title.
+
+fn escape_attr(s: &str) -> String {
+    s.replace('&', "&amp;").replace('\'', "&#39;")
+}
+
+fn escape_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn property_names(title: &str) -> Vec<&str> {
+    ocr_element::split_title(title)
+        .into_iter()
+        .filter_map(|part| part.split_whitespace().next())
+        .collect()
+}
+
+// the title from the file if nothing in it changed, otherwise the properties in the
+// order the file had them with any new ones sorted after
+fn title(elt: &OCRElement, options: &WriteOptions) -> String {
+    let current: HashMap<&str, String> = elt
+        .ocr_properties
+        .iter()
+        .filter(|(name, _)| options.writes(name))
+        .map(|(name, prop)| (name.as_str(), prop.to_str()))
+        .collect();
+    let source = elt.source_title.as_deref().unwrap_or("");
+    let unchanged = OCRProperty::parse_properties(source).is_ok_and(|parsed| {
+        parsed.len() == current.len()
+            && parsed
+                .iter()
+                .all(|(name, prop)| current.get(name.as_str()) == Some(&prop.to_str()))
+    });
+    if unchanged {
+        return source.to_string();
+    }
+    let mut names: Vec<&str> = property_names(source)
+        .into_iter()
+        .filter(|name| current.contains_key(name))
+        .collect();
+    let mut new_names: Vec<&str> = current
+        .keys()
+        .copied()
+        .filter(|name| !names.contains(name))
+        .collect();
+    new_names.sort();
+    names.extend(new_names);
+    names.dedup();
+    names
+        .iter()
+        .map(|name| format!("{} {}", name, current[name]))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+struct IdAllocator {
+    used: HashSet<String>,
+    next: HashMap<String, u32>,
+    // ids from the file that were written already
+    written: HashSet<String>,
+    scheme: IdScheme,
+}
+
+impl IdAllocator {
+    // every id from the file is reserved, so new ids never collide with old ones
+    fn new(tree: &Tree<OCRElement>, scheme: IdScheme) -> Self {
+        let used = tree
+            .preorder()
+            .iter()
+            .filter_map(|id| tree.get_node(id)?.source_id.clone())
+            .collect();
+        IdAllocator {
+            used,
+            next: HashMap::new(),
+            written: HashSet::new(),
+            scheme,
+        }
+    }
+
+    fn fresh(&mut self, class: &OCRClass, page_no: usize) -> String {
+        let type_id = class.to_id_str();
+        let prefix = if *class == OCRClass::Page {
+            "page".to_string()
+        } else if self.scheme == IdScheme::Global {
+            type_id
+        } else {
+            format!("{}_{}", type_id, page_no)
+        };
+        let n = self.next.entry(prefix.clone()).or_insert(1);
+        loop {
+            let candidate = format!("{}_{}", prefix, n);
+            *n += 1;
+            if self.used.insert(candidate.clone()) {
+                return candidate;
+            }
+        }
+    }
+
+    // the element's id from the file, or a fresh one for new elements; a copied element
+    // would otherwise write its original's id a second time
+    fn id_for(&mut self, elt: &OCRElement, page_no: usize) -> String {
+        match &elt.source_id {
+            Some(source_id) if self.written.insert(source_id.clone()) => source_id.clone(),
+            _ => self.fresh(&elt.ocr_element_type, page_no),
+        }
+    }
+}
+
+fn write_element(
+    tree: &Tree<OCRElement>,
+    id: &InternalID,
+    depth: usize,
+    page_no: usize,
+    ids: &mut IdAllocator,
+    options: &WriteOptions,
+    out: &mut String,
+) {
+    let elt = match tree.get_node(id) {
+        Some(elt) => elt,
+        None => return,
+    };
+    let html_id = ids.id_for(elt, page_no);
+    let indent = "  ".repeat(depth);
+    let tag = &elt.html_element_type;
+    out.push_str(&format!(
+        "{}<{} class='{}' id='{}' title='{}'",
+        indent,
+        tag,
+        elt.class_name(),
+        escape_attr(&html_id),
+        escape_attr(&title(elt, options)),
+    ));
+    if let Some(lang) = &elt.ocr_lang {
+        out.push_str(&format!(" lang='{}'", escape_attr(lang)));
+    }
+    out.push('>');
+    if tree.children(id).next().is_none() {
+        out.push_str(&escape_text(&elt.ocr_text));
+        out.push_str(&format!("</{}>\n", tag));
+        return;
+    }
+    out.push('\n');
+    for child in tree.children(id) {
+        write_element(tree, child, depth + 1, page_no, ids, options, out);
+    }
+    out.push_str(&format!("{}</{}>\n", indent, tag));
+}
+
+pub fn to_html(tree: &Tree<OCRElement>, html_head: &Html, options: &WriteOptions) -> String {
+    let head = html_head.html();
+    let head = head.strip_suffix("</html>").unwrap_or(&head);
+    let mut out = format!("{}\n<body>\n", head);
+    let mut ids = IdAllocator::new(tree, options.id_scheme);
+    for (i, page) in tree.roots().enumerate() {
+        write_element(tree, page, 1, i + 1, &mut ids, options, &mut out);
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
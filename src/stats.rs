@@ -0,0 +1,200 @@
+use crate::ignore;
+use crate::ocr_element::{OCRClass, OCRElement};
+use crate::search::{fold, SearchOptions};
+use crate::tree::Tree;
+use crate::InternalID;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+// counts and quality numbers for the whole document
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DocumentStats {
+    pub pages: usize,
+    pub blocks: usize,
+    pub pars: usize,
+    pub lines: usize,
+    pub words: usize,
+    pub mean_wconf: Option<f32>,
+    pub low_conf_words: usize,
+    // word counts by confidence: 0-9, 10-19, ..., 90-100
+    pub wconf_histogram: [usize; 10],
+    pub memory_bytes: usize,
+}
+
+// rough heap + inline size of one node, including its property map
+fn estimated_node_size(elt: &OCRElement, child_count: usize) -> usize {
+    let strings = elt.html_element_type.capacity()
+        + elt.ocr_text.capacity()
+        + elt.ocr_lang.as_ref().map_or(0, |lang| lang.capacity());
+    let properties: usize = elt
+        .ocr_properties
+        .iter()
+        .map(|(name, prop)| {
+            name.capacity()
+                + size_of::<String>()
+                + size_of::<crate::ocr_element::OCRProperty>()
+                + match prop {
+                    crate::ocr_element::OCRProperty::Image(path)
+                    | crate::ocr_element::OCRProperty::Text(path) => path.capacity(),
+                    _ => 0,
+                }
+        })
+        .sum();
+    size_of::<crate::tree::Node<OCRElement>>()
+        + size_of::<crate::InternalID>() // key in the node map
+        + strings
+        + properties
+        + child_count * size_of::<crate::InternalID>()
+}
+
+// rough size of one copy of the tree, as estimated for memory_bytes
+pub fn tree_bytes(tree: &Tree<OCRElement>) -> usize {
+    tree.roots().map(|root| subtree_bytes(tree, root)).sum()
+}
+
+// the same for id and everything under it
+pub fn subtree_bytes(tree: &Tree<OCRElement>, id: &InternalID) -> usize {
+    tree.descendants(id)
+        .iter()
+        .filter_map(|id| {
+            Some(estimated_node_size(
+                tree.get_node(id)?,
+                tree.children(id).len(),
+            ))
+        })
+        .sum()
+}
+
+pub fn compute(tree: &Tree<OCRElement>, low_conf_threshold: u32) -> DocumentStats {
+    let mut stats = DocumentStats::default();
+    let mut wconf_sum: u64 = 0;
+    let mut wconf_count: u64 = 0;
+    for id in tree.preorder() {
+        if let Some(node) = tree.get_node(&id) {
+            stats.memory_bytes += estimated_node_size(node, tree.children(&id).len());
+            match node.ocr_element_type {
+                OCRClass::Page => stats.pages += 1,
+                OCRClass::CArea => stats.blocks += 1,
+                OCRClass::Par => stats.pars += 1,
+                OCRClass::Line | OCRClass::Caption => stats.lines += 1,
+                OCRClass::Word => {
+                    stats.words += 1;
+                    if let Some(wconf) = node.wconf() {
+                        wconf_sum += wconf as u64;
+                        wconf_count += 1;
+                        if wconf < low_conf_threshold {
+                            stats.low_conf_words += 1;
+                        }
+                        stats.wconf_histogram[(wconf as usize / 10).min(9)] += 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    if wconf_count > 0 {
+        stats.mean_wconf = Some(wconf_sum as f32 / wconf_count as f32);
+    }
+    stats
+}
+
+// problems worth a look before the file goes anywhere else, with the element they're about
+pub fn validate(tree: &Tree<OCRElement>) -> Vec<(InternalID, String)> {
+    let mut warnings = crate::ocr_element::ppageno_problems(tree);
+    warnings.extend(crate::structure::structure_problems(tree));
+    for id in tree.preorder() {
+        let node = match tree.get_node(&id) {
+            Some(node) => node,
+            None => continue,
+        };
+        let name = format!("{:?} {}", node.ocr_element_type, id);
+        match node.bbox() {
+            None => warnings.push((id, format!("{} has no bbox", name))),
+            Some(bbox) => {
+                let parent_bbox = tree
+                    .parent(&id)
+                    .and_then(|par_id| tree.get_node(&par_id))
+                    .and_then(|parent| parent.bbox());
+                if let Some(parent_bbox) = parent_bbox {
+                    if !parent_bbox.expand(1.0).contains_rect(*bbox) {
+                        warnings.push((id, format!("{} sticks out of its parent", name)));
+                    }
+                }
+            }
+        }
+        if node.ocr_element_type == OCRClass::Word && node.ocr_text.trim().is_empty() {
+            warnings.push((id, format!("{} has no text", name)));
+        }
+        if let Some((word_lang, par_lang)) = crate::ocr_element::lang_mismatch(tree, &id) {
+            warnings.push((
+                id,
+                format!(
+                    "{} is marked {} inside a {} paragraph",
+                    name, word_lang, par_lang
+                ),
+            ));
+        }
+    }
+    warnings
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1 << 30 {
+        format!("{:.1} GiB", bytes as f32 / (1 << 30) as f32)
+    } else if bytes >= 1 << 20 {
+        format!("{:.1} MiB", bytes as f32 / (1 << 20) as f32)
+    } else if bytes >= 1 << 10 {
+        format!("{:.1} KiB", bytes as f32 / (1 << 10) as f32)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+// a word as it's counted: folded, without surrounding punctuation; None for pure punctuation
+pub fn token(text: &str, options: &SearchOptions) -> Option<String> {
+    let folded = fold(text.trim(), options);
+    let word = folded.trim_matches(|c: char| !c.is_alphanumeric());
+    if word.is_empty() {
+        None
+    } else {
+        Some(word.to_string())
+    }
+}
+
+// count every word under tree into counts, folded the way search folds words and with
+// surrounding punctuation stripped, so "The," and "the" count together when ignoring case.
+// Ignored words don't count.
+pub fn count_words(
+    tree: &Tree<OCRElement>,
+    options: &SearchOptions,
+    counts: &mut HashMap<String, usize>,
+) {
+    let stripped = ignore::without_ignored(tree);
+    let tree = &*stripped;
+    for id in tree.preorder() {
+        if let Some(node) = tree.get_node(&id) {
+            if node.ocr_element_type != OCRClass::Word {
+                continue;
+            }
+            if let Some(word) = token(&node.ocr_text, options) {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+    }
+}
+
+// most frequent first, ties in alphabetical order
+pub fn sorted_frequencies(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut frequencies: Vec<(String, usize)> = counts.into_iter().collect();
+    frequencies.sort_by(|(a_word, a), (b_word, b)| b.cmp(a).then_with(|| a_word.cmp(b_word)));
+    frequencies
+}
+
+// word<TAB>count lines, which spreadsheets and dictionary tools both read
+pub fn frequencies_to_tsv(frequencies: &[(String, usize)]) -> String {
+    frequencies
+        .iter()
+        .map(|(word, count)| format!("{}\t{}\n", word, count))
+        .collect()
+}
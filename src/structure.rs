@@ -0,0 +1,232 @@
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::tree::{Position, Tree};
+use crate::InternalID;
+
+// hOCR nests page > carea > par > line > word. Some generators skip levels (words right
+// under a carea) or nest pages in pages; we read those as they are and can fill in the rest.
+
+// a new element to stand in for a missing level under a parent at level parent_level
+fn filler(parent_level: u8) -> Option<(OCRClass, &'static str)> {
+    match parent_level {
+        0 => Some((OCRClass::CArea, "div")),
+        1 => Some((OCRClass::Par, "p")),
+        2 => Some((OCRClass::Line, "span")),
+        _ => None,
+    }
+}
+
+fn class_of(tree: &Tree<OCRElement>, id: &InternalID) -> Option<OCRClass> {
+    tree.get_node(id).map(|elt| elt.ocr_element_type.clone())
+}
+
+pub fn structure_problems(tree: &Tree<OCRElement>) -> Vec<(InternalID, String)> {
+    let mut problems = Vec::new();
+    for id in tree.preorder() {
+        let (class, parent_class) = match (
+            class_of(tree, &id),
+            tree.parent(&id).and_then(|par_id| class_of(tree, &par_id)),
+        ) {
+            (Some(class), Some(parent_class)) => (class, parent_class),
+            (Some(class), None) if class != OCRClass::Page => {
+                problems.push((id, format!("{:?} {} isn't inside a page", class, id)));
+                continue;
+            }
+            _ => continue,
+        };
+        if class.level() <= parent_class.level() || class.level() > parent_class.level() + 1 {
+            problems.push((
+                id,
+                format!("{:?} {} is directly inside a {:?}", class, id, parent_class),
+            ));
+        }
+    }
+    problems
+}
+
+fn union_bbox(tree: &Tree<OCRElement>, ids: &[InternalID]) -> Option<egui::Rect> {
+    ids.iter()
+        .filter_map(|id| tree.get_node(id).and_then(|elt| elt.bbox()).copied())
+        .reduce(|a, b| a.union(b))
+}
+
+// wrap runs of children that skip levels in the missing elements, one level per pass
+fn fill_in_one_level(tree: &mut Tree<OCRElement>) -> usize {
+    let mut added = 0;
+    for parent in tree.preorder() {
+        let parent_level = match class_of(tree, &parent) {
+            Some(class) => class.level(),
+            None => continue,
+        };
+        let (class, html_type) = match filler(parent_level) {
+            Some(filler) => filler,
+            None => continue,
+        };
+        // consecutive children that are too deep for this parent
+        let mut runs: Vec<Vec<InternalID>> = Vec::new();
+        let mut in_run = false;
+        for child in tree.children(&parent) {
+            let too_deep = class_of(tree, child).is_some_and(|c| c.level() > parent_level + 1);
+            if too_deep {
+                if !in_run {
+                    runs.push(Vec::new());
+                }
+                runs.last_mut().unwrap().push(*child);
+            }
+            in_run = too_deep;
+        }
+        for run in runs {
+            let mut properties = std::collections::HashMap::new();
+            if let Some(bbox) = union_bbox(tree, &run) {
+                properties.insert("bbox".to_string(), OCRProperty::BBox(bbox));
+            }
+            let wrapper = OCRElement {
+                html_element_type: html_type.to_string(),
+                ocr_element_type: class.clone(),
+                ocr_properties: properties,
+                ..Default::default()
+            };
+            match tree.wrap(&run, wrapper) {
+                Ok(_) => added += 1,
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+    added
+}
+
+// the containers ids could be wrapped in, the one that fits right above them first: a
+// paragraph for lines, a block for paragraphs
+pub fn wrapper_classes(tree: &Tree<OCRElement>, ids: &[InternalID]) -> Vec<OCRClass> {
+    let level = match ids
+        .iter()
+        .filter_map(|id| class_of(tree, id))
+        .map(|c| c.level())
+        .min()
+    {
+        Some(level) => level,
+        None => return Vec::new(),
+    };
+    let mut classes: Vec<OCRClass> = [
+        OCRClass::Line,
+        OCRClass::Caption,
+        OCRClass::Par,
+        OCRClass::CArea,
+    ]
+    .into_iter()
+    .filter(|class| class.level() < level)
+    .collect();
+    classes.sort_by_key(|class| std::cmp::Reverse(class.level()));
+    classes
+}
+
+// put a new element of class where the siblings ids are, holding them, with a bbox around
+// all of them
+pub fn wrap_in(
+    tree: &mut Tree<OCRElement>,
+    ids: &[InternalID],
+    class: OCRClass,
+) -> Result<InternalID, String> {
+    let mut properties = std::collections::HashMap::new();
+    let bbox = union_bbox(tree, ids).ok_or("nothing to wrap has a bbox")?;
+    properties.insert("bbox".to_string(), OCRProperty::BBox(bbox));
+    let html_type = match filler(class.level().saturating_sub(1)) {
+        Some((_, html_type)) => html_type,
+        None => "span",
+    };
+    tree.wrap(
+        ids,
+        OCRElement {
+            html_element_type: html_type.to_string(),
+            ocr_element_type: class,
+            ocr_properties: properties,
+            ..Default::default()
+        },
+    )
+}
+
+// whether id can be moved into target (pos None) or next to it without breaking the nesting
+pub fn can_move(
+    tree: &Tree<OCRElement>,
+    id: &InternalID,
+    target: &InternalID,
+    pos: Option<Position>,
+) -> bool {
+    if tree.descendants(id).contains(target) {
+        return false;
+    }
+    let class = match class_of(tree, id) {
+        Some(class) => class,
+        None => return false,
+    };
+    let parent = match pos {
+        None => Some(*target),
+        Some(_) => tree.parent(target),
+    };
+    match parent.and_then(|parent| class_of(tree, &parent)) {
+        Some(parent_class) => parent_class.can_contain(&class),
+        // pages are the only thing at the top
+        None => class == OCRClass::Page && tree.get_node(target).is_some(),
+    }
+}
+
+// whether clipboard's roots can be pasted into target (pos None) or next to it
+pub fn can_paste(
+    tree: &Tree<OCRElement>,
+    clipboard: &Tree<OCRElement>,
+    target: &InternalID,
+    pos: Option<Position>,
+) -> bool {
+    let parent = match pos {
+        None => Some(*target),
+        Some(_) => tree.parent(target),
+    };
+    let parent_class = parent.and_then(|parent| class_of(tree, &parent));
+    tree.get_node(target).is_some()
+        && clipboard.roots().next().is_some()
+        && clipboard
+            .roots()
+            .filter_map(|root| class_of(clipboard, root))
+            .all(|class| match &parent_class {
+                Some(parent_class) => parent_class.can_contain(&class),
+                None => class == OCRClass::Page,
+            })
+}
+
+// move id into target as its last child (pos None), or right before or after it
+pub fn move_to(
+    tree: &mut Tree<OCRElement>,
+    id: &InternalID,
+    target: &InternalID,
+    pos: Option<Position>,
+) -> Result<(), String> {
+    if !can_move(tree, id, target, pos) {
+        return Err(format!("{} can't go there", id));
+    }
+    match pos {
+        None => tree.move_node(id, target),
+        Some(Position::Before) => tree.move_before(id, target),
+        Some(Position::After) => tree.move_after(id, target),
+    }
+}
+
+// lift nested pages out and fill in skipped levels; returns how many elements changed
+pub fn restructure(tree: &mut Tree<OCRElement>) -> usize {
+    let nested_pages: Vec<InternalID> = tree
+        .preorder()
+        .into_iter()
+        .filter(|id| tree.parent(id).is_some() && class_of(tree, id) == Some(OCRClass::Page))
+        .collect();
+    // backwards, so they end up after their outer page in document order
+    for page in nested_pages.iter().rev() {
+        tree.move_to_root(page);
+    }
+    let mut changed = nested_pages.len();
+    loop {
+        let added = fill_in_one_level(tree);
+        if added == 0 {
+            break;
+        }
+        changed += added;
+    }
+    changed
+}
@@ -0,0 +1,115 @@
+use crate::ocr_element::{self, OCRClass, OCRElement};
+use crate::search::SearchOptions;
+use crate::stats;
+use crate::tree::Tree;
+use crate::InternalID;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+// how many of the most suspicious tokens go into the review queue
+pub const MAX_SUSPICIOUS: usize = 200;
+// tokens seen in the reference fewer times than this count as rare
+const RARE_COUNT: u64 = 3;
+
+// a reference corpus: how often each word occurs in text we trust
+#[derive(Debug, Default, Clone)]
+pub struct FrequencyList {
+    counts: HashMap<String, u64>,
+    total: u64,
+}
+
+impl FrequencyList {
+    // "word<TAB>count" or "word count" per line (what the frequency export writes);
+    // a line with just a word counts once, so a plain dictionary works too
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let options = SearchOptions::default();
+        let mut list = FrequencyList::default();
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            let word = match parts.next().and_then(|word| stats::token(word, &options)) {
+                Some(word) => word,
+                None => continue,
+            };
+            let count = parts.next().and_then(|c| c.parse().ok()).unwrap_or(1);
+            *list.counts.entry(word).or_default() += count;
+            list.total += count;
+        }
+        Ok(list)
+    }
+
+    fn count(&self, word: &str) -> u64 {
+        self.counts.get(word).copied().unwrap_or(0)
+    }
+
+    // -log10 of how likely the word is in the reference, smoothed so unseen words are the
+    // rarest but still finite
+    fn rarity(&self, word: &str) -> f32 {
+        let p = (self.count(word) + 1) as f64 / (self.total + self.counts.len() as u64 + 1) as f64;
+        -p.log10() as f32
+    }
+}
+
+// a token that's rare in the reference for its language
+#[derive(Debug, Clone)]
+pub struct SuspiciousToken {
+    pub token: String,
+    // every word in the document that reads as this token
+    pub ids: Vec<InternalID>,
+    // how often the reference has it (0 if never)
+    pub reference_count: u64,
+    // occurrences × rarity: a strange token repeated many times is most likely a
+    // systematic misrecognition
+    pub score: f32,
+}
+
+// tokens ranked by suspicion, most suspicious first. Each word is checked against the list
+// for its lang (the closest lang attribute), falling back to the "" list for any language;
+// words with no list to check against are skipped.
+pub fn find_suspicious(
+    tree: &Tree<OCRElement>,
+    references: &HashMap<String, FrequencyList>,
+) -> Vec<SuspiciousToken> {
+    let options = SearchOptions::default();
+    // (lang, token) -> ids
+    let mut occurrences: HashMap<(String, String), Vec<InternalID>> = HashMap::new();
+    for id in tree.preorder() {
+        let elt = match tree.get_node(&id) {
+            Some(elt) if elt.ocr_element_type == OCRClass::Word => elt,
+            _ => continue,
+        };
+        let token = match stats::token(&elt.ocr_text, &options) {
+            // numbers aren't in wordlists and aren't misspellings
+            Some(token) if !token.chars().all(|c| c.is_numeric()) => token,
+            _ => continue,
+        };
+        let lang = ocr_element::inherited_lang(tree, &id)
+            .filter(|lang| references.contains_key(lang))
+            .unwrap_or_default();
+        if references.contains_key(&lang) {
+            occurrences.entry((lang, token)).or_default().push(id);
+        }
+    }
+    let mut tokens: Vec<SuspiciousToken> = occurrences
+        .into_iter()
+        .map(|((lang, token), ids)| {
+            let reference = &references[&lang];
+            SuspiciousToken {
+                score: ids.len() as f32 * reference.rarity(&token),
+                reference_count: reference.count(&token),
+                token,
+                ids,
+            }
+        })
+        .filter(|t| t.reference_count < RARE_COUNT)
+        .collect();
+    tokens.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    tokens.truncate(MAX_SUSPICIOUS);
+    tokens
+}
@@ -0,0 +1,292 @@
+use crate::atomic;
+use crate::ocr_element::{OCRClass, OCRElement, OCRProperty};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::{Pos2, Rect};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::read_to_string;
+use std::path::Path;
+
+// the block a template region becomes is labelled with this user property
+pub const FIELD_PROPERTY: &str = "x_user_field";
+
+// one named box on a form, in page coords
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRegion {
+    pub label: String,
+    // x0, y0, x1, y1 like an hOCR bbox
+    pub bbox: [f32; 4],
+}
+
+impl TemplateRegion {
+    pub fn new(label: &str, rect: Rect) -> Self {
+        TemplateRegion {
+            label: label.to_string(),
+            bbox: [rect.min.x, rect.min.y, rect.max.x, rect.max.y],
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        let [x0, y0, x1, y1] = self.bbox;
+        Rect::from_min_max(Pos2::new(x0, y0), Pos2::new(x1, y1))
+    }
+}
+
+// the layout shared by a batch of similar forms, kept as a json file so it can be
+// reused across documents
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Template {
+    pub regions: Vec<TemplateRegion>,
+}
+
+impl Template {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        atomic::write_atomic(path, json)
+    }
+
+    // the labelled blocks of a page, so a form laid out by hand can become the template
+    pub fn from_page(tree: &Tree<OCRElement>, page: &InternalID) -> Self {
+        let regions = tree
+            .descendants(page)
+            .iter()
+            .filter_map(|id| tree.get_node(id))
+            .filter_map(|elt| match (field_label(elt), elt.bbox()) {
+                (Some(label), Some(bbox)) => Some(TemplateRegion::new(label, *bbox)),
+                _ => None,
+            })
+            .collect();
+        Template { regions }
+    }
+}
+
+pub fn field_label(elt: &OCRElement) -> Option<&str> {
+    match elt.ocr_properties.get(FIELD_PROPERTY) {
+        Some(OCRProperty::Text(label)) => Some(label),
+        _ => None,
+    }
+}
+
+fn element(class: OCRClass, html_type: &str, bbox: Rect) -> OCRElement {
+    OCRElement {
+        html_element_type: html_type.to_string(),
+        ocr_element_type: class,
+        ocr_properties: HashMap::from([("bbox".to_string(), OCRProperty::BBox(bbox))]),
+        ..Default::default()
+    }
+}
+
+// give a page the template's blocks: each region becomes a labelled carea > par holding
+// the words whose centers fall inside it, one line per line they came from. A region
+// nothing was read in gets one empty word to type into. Regions the page already has a
+// block for are skipped, so applying twice is harmless. Returns how many blocks were made.
+pub fn apply(tree: &mut Tree<OCRElement>, page: &InternalID, template: &Template) -> usize {
+    let existing: HashSet<String> = tree
+        .descendants(page)
+        .iter()
+        .filter_map(|id| tree.get_node(id).and_then(field_label))
+        .map(|label| label.to_string())
+        .collect();
+    let words: Vec<(InternalID, Pos2)> = tree
+        .descendants(page)
+        .into_iter()
+        .filter_map(|id| {
+            let elt = tree.get_node(&id)?;
+            if elt.ocr_element_type != OCRClass::Word {
+                return None;
+            }
+            Some((id, elt.bbox()?.center()))
+        })
+        .collect();
+    let mut taken: HashSet<InternalID> = HashSet::new();
+    let mut emptied: Vec<InternalID> = Vec::new();
+    let mut created = 0;
+    for region in &template.regions {
+        if existing.contains(&region.label) {
+            continue;
+        }
+        let rect = region.rect();
+        let mut block = element(OCRClass::CArea, "div", rect);
+        block.ocr_properties.insert(
+            FIELD_PROPERTY.to_string(),
+            OCRProperty::Text(region.label.clone()),
+        );
+        let block = match tree.push_child(page, block) {
+            Ok(id) => id,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        created += 1;
+        let par = tree
+            .push_child(&block, element(OCRClass::Par, "p", rect))
+            .expect("block was just added");
+        // group the region's words by the line they were on
+        let mut lines: Vec<(Option<InternalID>, Vec<InternalID>)> = Vec::new();
+        for (id, center) in &words {
+            if taken.contains(id) || !rect.contains(*center) {
+                continue;
+            }
+            taken.insert(*id);
+            let old_line = tree.parent(id);
+            match lines.iter_mut().find(|(line, _)| *line == old_line) {
+                Some((_, ids)) => ids.push(*id),
+                None => lines.push((old_line, vec![*id])),
+            }
+        }
+        if lines.is_empty() {
+            let line = tree
+                .push_child(&par, element(OCRClass::Line, "span", rect))
+                .expect("par was just added");
+            tree.push_child(&line, element(OCRClass::Word, "span", rect))
+                .expect("line was just added");
+            continue;
+        }
+        for (old_line, ids) in lines {
+            let bbox = ids
+                .iter()
+                .filter_map(|id| tree.get_node(id).and_then(|elt| elt.bbox()).copied())
+                .reduce(|a, b| a.union(b))
+                .unwrap_or(rect);
+            let line = tree
+                .push_child(&par, element(OCRClass::Line, "span", bbox))
+                .expect("par was just added");
+            for id in ids {
+                if let Some(old_parent) = old_line {
+                    emptied.extend(tree.ancestors(&old_parent));
+                    emptied.push(old_parent);
+                }
+                if let Err(e) = tree.move_node(&id, &line) {
+                    println!("{}", e);
+                }
+            }
+        }
+    }
+    // drop the lines, pars and blocks the words were taken out of if nothing is left in them,
+    // innermost first
+    let mut seen = HashSet::new();
+    emptied.retain(|id| id != page && seen.insert(*id));
+    emptied.sort_by_key(|id| std::cmp::Reverse(tree.ancestors(id).len()));
+    for id in emptied {
+        if tree.get_node(&id).is_some() && !tree.has_children(&id) {
+            tree.delete_node(&id);
+        }
+    }
+    created
+}
+
+// the text of the words whose centers fall inside rect, in document order
+fn text_in(tree: &Tree<OCRElement>, page: &InternalID, rect: Rect) -> String {
+    tree.descendants(page)
+        .iter()
+        .filter_map(|id| tree.get_node(id))
+        .filter(|elt| elt.ocr_element_type == OCRClass::Word)
+        .filter(|elt| elt.bbox().is_some_and(|bbox| rect.contains(bbox.center())))
+        .map(|elt| elt.ocr_text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// one page of a filled-in form: field label -> text, in template order
+#[derive(Debug, Clone)]
+pub struct FieldRecord {
+    // the file the page came from
+    pub source: String,
+    // 1-based, like the page numbers in the UI
+    pub page: usize,
+    pub fields: Vec<(String, String)>,
+}
+
+pub fn extract_records(
+    tree: &Tree<OCRElement>,
+    source: &str,
+    template: &Template,
+) -> Vec<FieldRecord> {
+    tree.roots()
+        .enumerate()
+        .map(|(i, page)| FieldRecord {
+            source: source.to_string(),
+            page: i + 1,
+            fields: template
+                .regions
+                .iter()
+                .map(|region| (region.label.clone(), text_in(tree, page, region.rect())))
+                .collect(),
+        })
+        .collect()
+}
+
+pub fn records_to_json(records: &[FieldRecord]) -> String {
+    let records: Vec<serde_json::Value> = records
+        .iter()
+        .map(|record| {
+            let fields: serde_json::Map<String, serde_json::Value> = record
+                .fields
+                .iter()
+                .map(|(label, text)| (label.clone(), serde_json::Value::from(text.as_str())))
+                .collect();
+            serde_json::json!({
+                "source": record.source,
+                "page": record.page,
+                "fields": fields,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&records).unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// one row per page, one column per template region
+pub fn records_to_csv(records: &[FieldRecord], template: &Template) -> String {
+    let mut header = vec!["source".to_string(), "page".to_string()];
+    header.extend(
+        template
+            .regions
+            .iter()
+            .map(|region| csv_field(&region.label)),
+    );
+    let mut s = header.join(",");
+    s.push('\n');
+    for record in records {
+        let mut row = vec![csv_field(&record.source), record.page.to_string()];
+        row.extend(record.fields.iter().map(|(_, text)| csv_field(text)));
+        s.push_str(&row.join(","));
+        s.push('\n');
+    }
+    s
+}
+
+// csv or json depending on the extension of path
+pub fn write_records(
+    records: &[FieldRecord],
+    template: &Template,
+    path: &Path,
+) -> Result<(), String> {
+    let contents = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => records_to_csv(records, template),
+        Some("json") => records_to_json(records),
+        _ => {
+            return Err(format!(
+                "{}: export fields to .csv or .json",
+                path.display()
+            ))
+        }
+    };
+    atomic::write_atomic(path, contents)
+}
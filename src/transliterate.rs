@@ -0,0 +1,217 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+// a list of (from, to) replacements applied longest match first
+#[derive(Debug, Clone)]
+pub struct MappingTable {
+    pub name: String,
+    pub pairs: Vec<(String, String)>,
+    // written for the first of a doubled consonant ("kk", and "tch"), like romaji's っ
+    pub geminate: Option<String>,
+}
+
+// chars start..end (char indices) of a word that were typed but not transliterated yet,
+// because they could still become part of a longer match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pending {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl MappingTable {
+    fn from_pairs(name: &str, pairs: &[(&str, &str)]) -> Self {
+        MappingTable {
+            name: name.to_string(),
+            pairs: pairs
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            geminate: None,
+        }
+    }
+
+    // one mapping per line, from and to separated by a tab; # starts a comment
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let pairs: Vec<(String, String)> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(from, to)| (from.to_string(), to.trim_end().to_string()))
+            .collect();
+        if pairs.is_empty() {
+            return Err(format!("No mappings found in {}", path.display()));
+        }
+        Ok(MappingTable {
+            name: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "custom".to_string()),
+            pairs,
+            geminate: None,
+        })
+    }
+
+    // a doubled consonant starts rest
+    fn is_geminate(rest: &str) -> bool {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some('t'), Some('c')) => rest.starts_with("tch"),
+            (Some(a), Some(b)) => a == b && a.is_ascii_lowercase() && !"aeioun".contains(a),
+            _ => false,
+        }
+    }
+
+    // transliterate text; with hold_back, whatever at its end could still grow into a longer
+    // match ("z" before "zh") is left as it is. Returns the text and how many chars at its
+    // end were held back.
+    fn convert(&self, text: &str, hold_back: bool) -> (String, usize) {
+        let mut sorted: Vec<&(String, String)> = self.pairs.iter().collect();
+        sorted.sort_by_key(|pair| std::cmp::Reverse(pair.0.len()));
+        let mut out = String::new();
+        let mut rest = text;
+        'outer: while !rest.is_empty() {
+            let grows_into = |from: &str| from.len() > rest.len() && from.starts_with(rest);
+            let could_grow = sorted.iter().any(|(from, _)| grows_into(from))
+                || (self.geminate.is_some() && grows_into("tch"));
+            if hold_back && could_grow {
+                out.push_str(rest);
+                return (out, rest.chars().count());
+            }
+            if let (Some(mark), true) = (&self.geminate, Self::is_geminate(rest)) {
+                out.push_str(mark);
+                rest = &rest[1..];
+                continue;
+            }
+            for (from, to) in &sorted {
+                if !from.is_empty() && rest.starts_with(from.as_str()) {
+                    out.push_str(to);
+                    rest = &rest[from.len()..];
+                    continue 'outer;
+                }
+            }
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        (out, 0)
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.convert(text, false).0
+    }
+
+    // as you type: after an edit turned before into after, transliterate only what was just
+    // typed, along with what was still pending right in front of it. Returns the new text,
+    // where the cursor goes (a char index) and what's pending now.
+    pub fn apply_typed(
+        &self,
+        before: &str,
+        after: &str,
+        pending: Option<Pending>,
+    ) -> (String, usize, Option<Pending>) {
+        let old: Vec<char> = before.chars().collect();
+        let new: Vec<char> = after.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let end = new.len() - suffix;
+        // only deleted: nothing to transliterate, and what was pending is broken up
+        if end == prefix {
+            return (after.to_string(), prefix, None);
+        }
+        let start = match pending {
+            Some(pending) if pending.end == prefix => pending.start,
+            _ => prefix,
+        };
+        let typed: String = new[start..end].iter().collect();
+        let (converted, held_back) = self.convert(&typed, true);
+        let cursor = start + converted.chars().count();
+        let text = format!(
+            "{}{}{}",
+            new[..start].iter().collect::<String>(),
+            converted,
+            new[end..].iter().collect::<String>()
+        );
+        let pending = (held_back > 0).then(|| Pending {
+            start: cursor - held_back,
+            end: cursor,
+        });
+        (text, cursor, pending)
+    }
+
+    // only transliterate chars start..end (char indices, not bytes)
+    pub fn apply_range(&self, text: &str, start: usize, end: usize) -> String {
+        let byte_index = |i: usize| text.char_indices().nth(i).map_or(text.len(), |(b, _)| b);
+        let (start, end) = (byte_index(start), byte_index(end));
+        format!(
+            "{}{}{}",
+            &text[..start],
+            self.apply(&text[start..end]),
+            &text[end..]
+        )
+    }
+}
+
+pub fn builtin_tables() -> Vec<MappingTable> {
+    vec![
+        MappingTable {
+            geminate: Some("っ".to_string()),
+            ..MappingTable::from_pairs("romaji → kana", ROMAJI_TO_HIRAGANA)
+        },
+        MappingTable::from_pairs("Latin → Cyrillic", LATIN_TO_CYRILLIC),
+    ]
+}
+
+#[rustfmt::skip]
+const ROMAJI_TO_HIRAGANA: &[(&str, &str)] = &[
+    ("kya", "きゃ"), ("kyu", "きゅ"), ("kyo", "きょ"),
+    ("sha", "しゃ"), ("shu", "しゅ"), ("sho", "しょ"), ("shi", "し"),
+    ("cha", "ちゃ"), ("chu", "ちゅ"), ("cho", "ちょ"), ("chi", "ち"), ("tsu", "つ"),
+    ("nya", "にゃ"), ("nyu", "にゅ"), ("nyo", "にょ"),
+    ("hya", "ひゃ"), ("hyu", "ひゅ"), ("hyo", "ひょ"),
+    ("mya", "みゃ"), ("myu", "みゅ"), ("myo", "みょ"),
+    ("rya", "りゃ"), ("ryu", "りゅ"), ("ryo", "りょ"),
+    ("gya", "ぎゃ"), ("gyu", "ぎゅ"), ("gyo", "ぎょ"),
+    ("bya", "びゃ"), ("byu", "びゅ"), ("byo", "びょ"),
+    ("pya", "ぴゃ"), ("pyu", "ぴゅ"), ("pyo", "ぴょ"),
+    ("ja", "じゃ"), ("ju", "じゅ"), ("jo", "じょ"), ("ji", "じ"),
+    ("xtsu", "っ"), ("ltu", "っ"),
+    ("ka", "か"), ("ki", "き"), ("ku", "く"), ("ke", "け"), ("ko", "こ"),
+    ("sa", "さ"), ("su", "す"), ("se", "せ"), ("so", "そ"),
+    ("ta", "た"), ("te", "て"), ("to", "と"),
+    ("na", "な"), ("ni", "に"), ("nu", "ぬ"), ("ne", "ね"), ("no", "の"),
+    ("ha", "は"), ("hi", "ひ"), ("fu", "ふ"), ("he", "へ"), ("ho", "ほ"),
+    ("ma", "ま"), ("mi", "み"), ("mu", "む"), ("me", "め"), ("mo", "も"),
+    ("ya", "や"), ("yu", "ゆ"), ("yo", "よ"),
+    ("ra", "ら"), ("ri", "り"), ("ru", "る"), ("re", "れ"), ("ro", "ろ"),
+    ("wa", "わ"), ("wo", "を"), ("nn", "ん"), ("n'", "ん"),
+    ("ga", "が"), ("gi", "ぎ"), ("gu", "ぐ"), ("ge", "げ"), ("go", "ご"),
+    ("za", "ざ"), ("zu", "ず"), ("ze", "ぜ"), ("zo", "ぞ"),
+    ("da", "だ"), ("de", "で"), ("do", "ど"),
+    ("ba", "ば"), ("bi", "び"), ("bu", "ぶ"), ("be", "べ"), ("bo", "ぼ"),
+    ("pa", "ぱ"), ("pi", "ぴ"), ("pu", "ぷ"), ("pe", "ぺ"), ("po", "ぽ"),
+    ("a", "あ"), ("i", "い"), ("u", "う"), ("e", "え"), ("o", "お"),
+    ("-", "ー"),
+];
+
+#[rustfmt::skip]
+const LATIN_TO_CYRILLIC: &[(&str, &str)] = &[
+    ("shch", "щ"), ("zh", "ж"), ("kh", "х"), ("ts", "ц"), ("ch", "ч"), ("sh", "ш"),
+    ("yu", "ю"), ("ya", "я"), ("yo", "ё"),
+    ("Shch", "Щ"), ("Zh", "Ж"), ("Kh", "Х"), ("Ts", "Ц"), ("Ch", "Ч"), ("Sh", "Ш"),
+    ("Yu", "Ю"), ("Ya", "Я"), ("Yo", "Ё"),
+    ("a", "а"), ("b", "б"), ("v", "в"), ("g", "г"), ("d", "д"), ("e", "е"),
+    ("z", "з"), ("i", "и"), ("j", "й"), ("k", "к"), ("l", "л"), ("m", "м"),
+    ("n", "н"), ("o", "о"), ("p", "п"), ("r", "р"), ("s", "с"), ("t", "т"),
+    ("u", "у"), ("f", "ф"), ("y", "ы"), ("'", "ь"),
+    ("A", "А"), ("B", "Б"), ("V", "В"), ("G", "Г"), ("D", "Д"), ("E", "Е"),
+    ("Z", "З"), ("I", "И"), ("J", "Й"), ("K", "К"), ("L", "Л"), ("M", "М"),
+    ("N", "Н"), ("O", "О"), ("P", "П"), ("R", "Р"), ("S", "С"), ("T", "Т"),
+    ("U", "У"), ("F", "Ф"), ("Y", "Ы"),
+];
@@ -3,14 +3,14 @@ use std::collections::HashMap;
 use std::slice::Iter;
 
 // the "tree" is a dictionary of IDs to nodes
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Tree<D> {
     nodes: HashMap<InternalID, Node<D>>,
     roots: Vec<InternalID>,
     curr_id: InternalID,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 // a node has a value, a parent (an ID), and children (a vector of IDs)
 // yes, removing and inserting are O(n), but whatever, I need order to be preserved
 pub struct Node<D> {
@@ -20,7 +20,7 @@ pub struct Node<D> {
     pub id: InternalID,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Position {
     Before,
     After,
@@ -45,7 +45,7 @@ impl<D> Tree<D> {
                 value: root,
                 parent: None,
                 children: Vec::new(),
-                id: id,
+                id,
             },
         );
         self.roots.push(id);
@@ -88,10 +88,8 @@ impl<D> Tree<D> {
         // insert sibling into the hash map
         // insert sibling's ID into the parent's child vector before id
         if let Some(node) = self.nodes.get(id) {
-            return if let Some(par_id) = node.parent {
+            if let Some(par_id) = node.parent {
                 let new_id = self.curr_id;
-                println!("add_sibling: sib has id {}", new_id);
-                println!("add_sibling: I have id {}", id);
                 self.nodes.insert(
                     new_id,
                     Node {
@@ -103,9 +101,12 @@ impl<D> Tree<D> {
                 );
                 self.curr_id += 1;
                 // this error is fatal because it means our internal representation of the tree is wrong
-                let par_child_index = self.children(&par_id).position(|&x| x == *id).expect(
-                    format!("Couldn't find {} among parent {}'s children", id, par_id).as_str(),
-                );
+                let par_child_index = self
+                    .children(&par_id)
+                    .position(|&x| x == *id)
+                    .unwrap_or_else(|| {
+                        panic!("Couldn't find {} among parent {}'s children", id, par_id)
+                    });
                 let insert_index = par_child_index
                     + match pos {
                         Position::After => 1,
@@ -113,13 +114,13 @@ impl<D> Tree<D> {
                     };
                 self.nodes
                     .get_mut(&par_id)
-                    .expect(format!("parent {} of {} doesn't exist", par_id, id).as_str())
+                    .unwrap_or_else(|| panic!("parent {} of {} doesn't exist", par_id, id))
                     .children
                     .insert(insert_index, new_id);
                 Ok(new_id)
             } else {
                 Ok(self.add_root(sibling))
-            };
+            }
         } else {
             Err(format!("add_sibling: node {} doesn't exist!", id))
         }
@@ -142,6 +143,17 @@ impl<D> Tree<D> {
         self.nodes.get(id).map(|node| node.parent).unwrap_or(None)
     }
 
+    // parent first, root last
+    pub fn ancestors(&self, id: &InternalID) -> Vec<InternalID> {
+        let mut ancestors = Vec::new();
+        let mut curr = self.parent(id);
+        while let Some(par_id) = curr {
+            ancestors.push(par_id);
+            curr = self.parent(&par_id);
+        }
+        ancestors
+    }
+
     // if the node doesn't exist, it has no siblings, but we can run into errors in the tree
     fn siblings(&self, id: &InternalID) -> Option<&Vec<InternalID>> {
         self.nodes.get(id).map(|node| {
@@ -149,7 +161,7 @@ impl<D> Tree<D> {
                 let par_node = self
                     .nodes
                     .get(&par_id)
-                    .expect(format!("node {}'s parent {} doesn't exist", id, par_id).as_str());
+                    .unwrap_or_else(|| panic!("node {}'s parent {} doesn't exist", id, par_id));
                 &par_node.children
             } else {
                 &self.roots
@@ -162,10 +174,9 @@ impl<D> Tree<D> {
     pub fn prev_siblings(&self, id: &InternalID) -> Iter<'_, InternalID> {
         self.siblings(id)
             .map(|siblings| {
-                let my_index = siblings
-                    .iter()
-                    .position(|&x| x == *id)
-                    .expect(format!("couldn't find {} among siblings {:?}", id, siblings).as_str());
+                let my_index = siblings.iter().position(|&x| x == *id).unwrap_or_else(|| {
+                    panic!("couldn't find {} among siblings {:?}", id, siblings)
+                });
                 siblings[..my_index].iter()
             })
             .unwrap_or_default()
@@ -177,7 +188,6 @@ impl<D> Tree<D> {
             Position::After => self.next_sibling(id),
             Position::Before => self.prev_sibling(id),
         };
-        println!("Merging {} with {:?}", id, sib_id);
         if sib_id.is_none() {
             return;
         }
@@ -186,7 +196,6 @@ impl<D> Tree<D> {
         // reparent each sib_child
         for child_id in &sib_children {
             if let Some(node) = self.nodes.get_mut(child_id) {
-                println!("merge sibling: reparented {} to {}", child_id, id);
                 node.parent = Some(*id);
             }
         }
@@ -199,12 +208,11 @@ impl<D> Tree<D> {
                     node.children = sib_children;
                 }
             }
-            println!("merge_sibling: new children {:?}", node.children);
         }
 
         self.nodes
             .get_mut(&sibling_id)
-            .expect(format!("sibling {} of {} didn't exist", sibling_id, id).as_str())
+            .unwrap_or_else(|| panic!("sibling {} of {} didn't exist", sibling_id, id))
             .children = Vec::new();
         self.delete_node(&sibling_id);
     }
@@ -220,7 +228,7 @@ impl<D> Tree<D> {
                     &self
                         .nodes
                         .get(&par_id)
-                        .expect(format!("parent {} of {} doesn't exist", par_id, id).as_str())
+                        .unwrap_or_else(|| panic!("parent {} of {} doesn't exist", par_id, id))
                         .children
                 }
                 None => &self.roots,
@@ -228,7 +236,7 @@ impl<D> Tree<D> {
             let my_index = siblings
                 .iter()
                 .position(|&x| x == *id)
-                .expect(format!("couldn't find {} among siblings {:?}", id, siblings).as_str());
+                .unwrap_or_else(|| panic!("couldn't find {} among siblings {:?}", id, siblings));
             if my_index > 0 {
                 Some(siblings[my_index - 1])
             } else {
@@ -242,10 +250,9 @@ impl<D> Tree<D> {
     pub fn next_siblings(&self, id: &InternalID) -> Iter<'_, InternalID> {
         self.siblings(id)
             .map(|siblings| {
-                let my_index =
-                    siblings.iter().position(|&x| x == *id).expect(
-                        format!("couldn't find {} among siblings {:?}", id, siblings).as_str(),
-                    ) + 1;
+                let my_index = siblings.iter().position(|&x| x == *id).unwrap_or_else(|| {
+                    panic!("couldn't find {} among siblings {:?}", id, siblings)
+                }) + 1;
                 siblings[my_index..].iter()
             })
             .unwrap_or_default()
@@ -253,7 +260,7 @@ impl<D> Tree<D> {
 
     pub fn has_children(&self, id: &InternalID) -> bool {
         match self.nodes.get(id) {
-            Some(node) => node.children.len() > 0,
+            Some(node) => !node.children.is_empty(),
             None => false,
         }
     }
@@ -262,6 +269,302 @@ impl<D> Tree<D> {
         self.roots.iter()
     }
 
+    // every node under id (including id) in document order
+    pub fn descendants(&self, id: &InternalID) -> Vec<InternalID> {
+        let mut ids = Vec::new();
+        if self.nodes.contains_key(id) {
+            ids.push(*id);
+            for child in self.children(id) {
+                ids.extend(self.descendants(child));
+            }
+        }
+        ids
+    }
+
+    // detach id (and its subtree) and append it to new_parent's children
+    pub fn move_node(&mut self, id: &InternalID, new_parent: &InternalID) -> Result<(), String> {
+        if !self.nodes.contains_key(id) || !self.nodes.contains_key(new_parent) {
+            return Err(format!(
+                "move_node: {} or {} doesn't exist!",
+                id, new_parent
+            ));
+        }
+        if self.descendants(id).contains(new_parent) {
+            return Err(format!("move_node: {} is inside {}", new_parent, id));
+        }
+        match self.parent(id) {
+            Some(par_id) => self.delete_child_from_parent(&par_id, id),
+            None => self.roots.retain(|root| root != id),
+        }
+        self.nodes
+            .get_mut(new_parent)
+            .expect("checked above")
+            .children
+            .push(*id);
+        self.nodes.get_mut(id).expect("checked above").parent = Some(*new_parent);
+        Ok(())
+    }
+
+    // move id (and its subtree) to right before target, under target's parent
+    pub fn move_before(&mut self, id: &InternalID, target: &InternalID) -> Result<(), String> {
+        self.move_beside(id, target, &Position::Before)
+    }
+
+    // move id (and its subtree) to right after target, under target's parent
+    pub fn move_after(&mut self, id: &InternalID, target: &InternalID) -> Result<(), String> {
+        self.move_beside(id, target, &Position::After)
+    }
+
+    fn move_beside(
+        &mut self,
+        id: &InternalID,
+        target: &InternalID,
+        pos: &Position,
+    ) -> Result<(), String> {
+        if !self.nodes.contains_key(id) || !self.nodes.contains_key(target) {
+            return Err(format!("move_beside: {} or {} doesn't exist!", id, target));
+        }
+        if self.descendants(id).contains(target) {
+            return Err(format!("move_beside: {} is {} or inside it", target, id));
+        }
+        match self.parent(id) {
+            Some(par_id) => self.delete_child_from_parent(&par_id, id),
+            None => self.roots.retain(|root| root != id),
+        }
+        let new_parent = self.parent(target);
+        let siblings = match new_parent {
+            Some(par_id) => &mut self.nodes.get_mut(&par_id).expect("checked above").children,
+            None => &mut self.roots,
+        };
+        let index = siblings
+            .iter()
+            .position(|x| x == target)
+            .expect("a node is among its parent's children");
+        match pos {
+            Position::Before => siblings.insert(index, *id),
+            Position::After => siblings.insert(index + 1, *id),
+        }
+        self.nodes.get_mut(id).expect("checked above").parent = new_parent;
+        Ok(())
+    }
+
+    // put a new node where the siblings ids were, with them (in order) as its children
+    pub fn wrap(&mut self, ids: &[InternalID], value: D) -> Result<InternalID, String> {
+        let first = ids.first().ok_or("wrap: nothing to wrap")?;
+        if ids.iter().any(|id| !self.nodes.contains_key(id)) {
+            return Err(format!("wrap: one of {:?} doesn't exist!", ids));
+        }
+        let parent = self.parent(first);
+        if ids.iter().any(|id| self.parent(id) != parent) {
+            return Err(format!("wrap: {:?} aren't siblings", ids));
+        }
+        let new_id = self.curr_id;
+        self.curr_id += 1;
+        let siblings = match parent {
+            Some(par_id) => &mut self.nodes.get_mut(&par_id).expect("checked above").children,
+            None => &mut self.roots,
+        };
+        let index = siblings
+            .iter()
+            .position(|x| x == first)
+            .expect("checked above");
+        siblings.retain(|x| !ids.contains(x));
+        siblings.insert(index, new_id);
+        for id in ids {
+            self.nodes.get_mut(id).expect("checked above").parent = Some(new_id);
+        }
+        self.nodes.insert(
+            new_id,
+            Node {
+                value,
+                parent,
+                children: ids.to_vec(),
+                id: new_id,
+            },
+        );
+        Ok(new_id)
+    }
+
+    // the opposite of wrap: put id's children where id was, in order, and remove id.
+    // Returns the children
+    pub fn unwrap(&mut self, id: &InternalID) -> Result<Vec<InternalID>, String> {
+        let node = self
+            .nodes
+            .remove(id)
+            .ok_or(format!("unwrap: {} doesn't exist!", id))?;
+        let siblings = match node.parent {
+            Some(par_id) => &mut self.nodes.get_mut(&par_id).expect("parent exists").children,
+            None => &mut self.roots,
+        };
+        let index = siblings
+            .iter()
+            .position(|x| x == id)
+            .expect("a node is among its parent's children");
+        siblings.splice(index..=index, node.children.iter().copied());
+        for child in &node.children {
+            self.nodes.get_mut(child).expect("children exist").parent = node.parent;
+        }
+        Ok(node.children)
+    }
+
+    // reorder id's children by a key computed from each child's id; children with equal keys
+    // keep their order
+    pub fn sort_children_by_key<K, F>(&mut self, id: &InternalID, key: F) -> Result<(), String>
+    where
+        K: Ord,
+        F: FnMut(&InternalID) -> K,
+    {
+        let node = self
+            .nodes
+            .get_mut(id)
+            .ok_or(format!("sort_children_by_key: {} doesn't exist!", id))?;
+        node.children.sort_by_key(key);
+        Ok(())
+    }
+
+    // detach id (and its subtree) and make it a root right after the root it was under
+    pub fn move_to_root(&mut self, id: &InternalID) {
+        let top = match self.ancestors(id).last() {
+            Some(top) => *top,
+            None => return,
+        };
+        if let Some(par_id) = self.parent(id) {
+            self.delete_child_from_parent(&par_id, id);
+        }
+        let index = self
+            .roots
+            .iter()
+            .position(|x| *x == top)
+            .map_or(0, |i| i + 1);
+        self.roots.insert(index, *id);
+        if let Some(node) = self.nodes.get_mut(id) {
+            node.parent = None;
+        }
+    }
+
+    // copy node (and its subtree) from other onto the end of parent's children
+    pub fn graft(
+        &mut self,
+        parent: &InternalID,
+        other: &Tree<D>,
+        node: &InternalID,
+    ) -> Result<InternalID, String>
+    where
+        D: Clone,
+    {
+        let value = other
+            .get_node(node)
+            .ok_or(format!("graft: node {} doesn't exist!", node))?
+            .clone();
+        let new_id = self.push_child(parent, value)?;
+        for child in other.children(node) {
+            self.graft(&new_id, other, child)?;
+        }
+        Ok(new_id)
+    }
+
+    // give id copies of everything under id in other, with the same ids, e.g. a subtree that
+    // was filled in after this tree was copied from other
+    pub fn copy_children_from(&mut self, id: &InternalID, other: &Tree<D>) -> Result<(), String>
+    where
+        D: Clone,
+    {
+        if !self.nodes.contains_key(id) {
+            return Err(format!("copy_children_from: {} doesn't exist!", id));
+        }
+        let below: Vec<InternalID> = other.descendants(id).into_iter().skip(1).collect();
+        if let Some(taken) = below.iter().find(|x| self.nodes.contains_key(x)) {
+            return Err(format!("copy_children_from: {} is already taken", taken));
+        }
+        for child_id in &below {
+            self.nodes.insert(*child_id, other.nodes[child_id].clone());
+            self.curr_id = self.curr_id.max(child_id + 1);
+        }
+        let children: Vec<InternalID> = other.children(id).copied().collect();
+        self.nodes
+            .get_mut(id)
+            .expect("checked above")
+            .children
+            .extend(children);
+        Ok(())
+    }
+
+    // the id the next node added gets
+    pub fn next_id(&self) -> InternalID {
+        self.curr_id
+    }
+
+    // never hand out ids below next, e.g. ones that copies of this tree have used
+    pub fn reserve_ids(&mut self, next: InternalID) {
+        self.curr_id = self.curr_id.max(next);
+    }
+
+    // a tree of its own with copies of ids (and everything under them) as its roots, in
+    // order; ids that don't exist are skipped
+    pub fn copy_subtrees(&self, ids: &[InternalID]) -> Tree<D>
+    where
+        D: Clone,
+    {
+        let mut copy = Tree::new();
+        for id in ids {
+            if let Some(value) = self.get_node(id) {
+                let root = copy.add_root(value.clone());
+                for child in self.children(id) {
+                    copy.graft(&root, self, child).expect("root was just added");
+                }
+            }
+        }
+        copy
+    }
+
+    // copies (with new ids) of other's roots and everything under them, put into target
+    // after its children (pos None) or next to it. Returns the copies of the roots
+    pub fn paste(
+        &mut self,
+        other: &Tree<D>,
+        target: &InternalID,
+        pos: Option<&Position>,
+    ) -> Result<Vec<InternalID>, String>
+    where
+        D: Clone,
+    {
+        if !self.nodes.contains_key(target) {
+            return Err(format!("paste: {} doesn't exist!", target));
+        }
+        let mut pasted: Vec<InternalID> = Vec::new();
+        for root in other.roots() {
+            let new_id = match (pos, self.parent(target)) {
+                (None, _) => self.graft(target, other, root)?,
+                (Some(_), Some(parent)) => self.graft(&parent, other, root)?,
+                (Some(_), None) => {
+                    let value = other.get_node(root).expect("roots exist").clone();
+                    let new_root = self.add_root(value);
+                    for child in other.children(root) {
+                        self.graft(&new_root, other, child)?;
+                    }
+                    new_root
+                }
+            };
+            // in other's order: each after the last one pasted
+            match (pos, pasted.last()) {
+                (None, _) => {}
+                (Some(_), Some(last)) => self.move_after(&new_id, last)?,
+                (Some(Position::Before), None) => self.move_before(&new_id, target)?,
+                (Some(Position::After), None) => self.move_after(&new_id, target)?,
+            }
+            pasted.push(new_id);
+        }
+        Ok(pasted)
+    }
+
+    // every node in the tree in document order
+    pub fn preorder(&self) -> Vec<InternalID> {
+        self.roots
+            .iter()
+            .flat_map(|root| self.descendants(root))
+            .collect()
+    }
+
     // mutable ref to node val by ID -- used when we need to modify bbox or text
     pub fn get_mut_node(&mut self, id: &InternalID) -> Option<&mut D> {
         match self.nodes.get_mut(id) {
@@ -276,7 +579,7 @@ impl<D> Tree<D> {
         let par = self
             .nodes
             .get_mut(par_id)
-            .expect(format!("child {}'s parent {} doesn't exist", child_id, par_id).as_str());
+            .unwrap_or_else(|| panic!("child {}'s parent {} doesn't exist", child_id, par_id));
         if let Some(id) = index {
             par.children.remove(id);
         }
@@ -294,7 +597,7 @@ impl<D> Tree<D> {
             }
             return (true, node.parent);
         }
-        return (false, None);
+        (false, None)
     }
 
     // delete a node from the tree. This ALSO DELETES ITS CHILDREN!
@@ -315,3 +618,85 @@ impl<D> Tree<D> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a(b, c(d)), e
+    fn sample() -> (Tree<&'static str>, [InternalID; 5]) {
+        let mut tree = Tree::new();
+        let a = tree.add_root("a");
+        let b = tree.push_child(&a, "b").unwrap();
+        let c = tree.push_child(&a, "c").unwrap();
+        let d = tree.push_child(&c, "d").unwrap();
+        let e = tree.add_root("e");
+        (tree, [a, b, c, d, e])
+    }
+
+    fn values(tree: &Tree<&'static str>) -> Vec<&'static str> {
+        tree.preorder()
+            .iter()
+            .map(|id| *tree.get_node(id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn move_node_appends_to_new_parent() {
+        let (mut tree, [a, b, _, d, e]) = sample();
+        tree.move_node(&b, &e).unwrap();
+        assert_eq!(values(&tree), ["a", "c", "d", "e", "b"]);
+        assert_eq!(tree.parent(&b), Some(e));
+        // not into its own subtree
+        assert!(tree.move_node(&a, &d).is_err());
+        tree.move_before(&e, &a).unwrap();
+        assert_eq!(tree.roots().copied().collect::<Vec<_>>(), [e, a]);
+    }
+
+    #[test]
+    fn wrap_and_unwrap_round_trip() {
+        let (mut tree, [a, b, c, _, _]) = sample();
+        let wrapper = tree.wrap(&[b, c], "w").unwrap();
+        assert_eq!(values(&tree), ["a", "w", "b", "c", "d", "e"]);
+        assert_eq!(tree.parent(&b), Some(wrapper));
+        assert_eq!(tree.unwrap(&wrapper).unwrap(), [b, c]);
+        assert_eq!(values(&tree), ["a", "b", "c", "d", "e"]);
+        assert_eq!(tree.parent(&c), Some(a));
+        // only siblings can be wrapped together
+        let d = tree.children(&c).next().copied().unwrap();
+        assert!(tree.wrap(&[b, d], "w").is_err());
+    }
+
+    #[test]
+    fn graft_copies_a_subtree_with_new_ids() {
+        let (mut tree, [_, _, c, _, e]) = sample();
+        let other = tree.clone();
+        let copy = tree.graft(&e, &other, &c).unwrap();
+        assert_ne!(copy, c);
+        assert_eq!(values(&tree), ["a", "b", "c", "d", "e", "c", "d"]);
+    }
+
+    #[test]
+    fn copy_subtrees_and_paste_keep_order() {
+        let (mut tree, [a, b, c, _, e]) = sample();
+        let clipboard = tree.copy_subtrees(&[c, b]);
+        assert_eq!(values(&clipboard), ["c", "d", "b"]);
+        let pasted = tree.paste(&clipboard, &a, Some(&Position::After)).unwrap();
+        assert_eq!(pasted.len(), 2);
+        assert_eq!(values(&tree), ["a", "b", "c", "d", "c", "d", "b", "e"]);
+        tree.paste(&clipboard, &e, None).unwrap();
+        assert_eq!(tree.children(&e).count(), 2);
+    }
+
+    #[test]
+    fn copy_children_from_keeps_ids() {
+        let (tree, [a, b, _, _, _]) = sample();
+        let mut bare = Tree::new();
+        bare.add_root("a");
+        bare.copy_children_from(&a, &tree).unwrap();
+        assert_eq!(bare.preorder(), tree.preorder()[..4]);
+        assert!(bare.next_id() > b);
+        // the children are there now
+        assert!(bare.copy_children_from(&a, &tree).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+// bytes that can go into a file uri as they are
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/' | b':')
+}
+
+/// file:///home/me/My%20Scans/%E6%9C%AC.png; spaces and anything outside ASCII are
+/// percent-encoded, windows separators become slashes
+pub fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    // C:/scans -> file:///C:/scans
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if is_unreserved(byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// undo percent-encoding; anything that isn't a valid escape is left as it is
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// the path a file uri points at; plain paths come back unchanged
+pub fn uri_to_path(uri: &str) -> PathBuf {
+    match uri.strip_prefix("file://") {
+        Some(rest) => {
+            let path = percent_decode(rest);
+            // file:///C:/scans -> C:/scans
+            let is_drive = path.len() > 2 && path.starts_with('/') && path.as_bytes()[2] == b':';
+            PathBuf::from(if is_drive { &path[1..] } else { &path })
+        }
+        None => PathBuf::from(uri),
+    }
+}
@@ -0,0 +1,85 @@
+use crate::ocr_element::{OCRClass, OCRElement};
+use crate::tree::Tree;
+use crate::InternalID;
+use egui::Rect;
+use std::collections::HashMap;
+
+// the widest blank stretch across boxes along one axis: (where the later part starts in
+// the sorted boxes, how wide the gap is)
+fn widest_gap(
+    boxes: &mut [(InternalID, Rect)],
+    start: fn(&Rect) -> f32,
+    end: fn(&Rect) -> f32,
+) -> Option<(usize, f32)> {
+    boxes.sort_by(|a, b| start(&a.1).total_cmp(&start(&b.1)));
+    let mut reach = end(&boxes.first()?.1);
+    let mut widest: Option<(usize, f32)> = None;
+    for (i, (_, bbox)) in boxes.iter().enumerate().skip(1) {
+        let gap = start(bbox) - reach;
+        if gap > 0.0 && widest.is_none_or(|(_, width)| gap > width) {
+            widest = Some((i, gap));
+        }
+        reach = reach.max(end(bbox));
+    }
+    widest
+}
+
+// recursive XY-cut: split the boxes at the widest horizontal or vertical blank band, read
+// the top part before the bottom and the left part before the right, and go on in each part
+fn cut(mut boxes: Vec<(InternalID, Rect)>, order: &mut Vec<InternalID>) {
+    if boxes.len() <= 1 {
+        order.extend(boxes.iter().map(|(id, _)| *id));
+        return;
+    }
+    let rows = widest_gap(&mut boxes, Rect::top, Rect::bottom);
+    let mut by_column = boxes.clone();
+    let columns = widest_gap(&mut by_column, Rect::left, Rect::right);
+    let (mut first, at) = match (rows, columns) {
+        (Some((at, row_gap)), Some((_, column_gap))) if row_gap >= column_gap => (boxes, at),
+        (_, Some((at, _))) => (by_column, at),
+        (Some((at, _)), None) => (boxes, at),
+        // overlapping every way: top to bottom, then left to right
+        (None, None) => {
+            boxes.sort_by(|a, b| {
+                (a.1.top(), a.1.left())
+                    .partial_cmp(&(b.1.top(), b.1.left()))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            order.extend(boxes.iter().map(|(id, _)| *id));
+            return;
+        }
+    };
+    let rest = first.split_off(at);
+    cut(first, order);
+    cut(rest, order);
+}
+
+// put id's children in XY-cut reading order; ones without a bbox go last. Returns whether
+// the order changed.
+fn reorder_children(tree: &mut Tree<OCRElement>, id: &InternalID) -> bool {
+    let before: Vec<InternalID> = tree.children(id).copied().collect();
+    let boxes: Vec<(InternalID, Rect)> = before
+        .iter()
+        .filter_map(|child| Some((*child, *tree.get_node(child)?.bbox()?)))
+        .collect();
+    let mut order = Vec::new();
+    cut(boxes, &mut order);
+    let rank: HashMap<InternalID, usize> =
+        order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let _ = tree.sort_children_by_key(id, |child| rank.get(child).copied().unwrap_or(usize::MAX));
+    !tree.children(id).eq(before.iter())
+}
+
+// recompute the order of a page's blocks and of the paragraphs in each block, so columns
+// are read one after the other. Returns how many elements had their children reordered.
+pub fn reorder_page(tree: &mut Tree<OCRElement>, page: &InternalID) -> usize {
+    let mut containers = vec![*page];
+    containers.extend(tree.children(page).copied().filter(|child| {
+        tree.get_node(child)
+            .is_some_and(|elt| elt.ocr_element_type == OCRClass::CArea)
+    }));
+    containers
+        .iter()
+        .filter(|id| reorder_children(tree, id))
+        .count()
+}